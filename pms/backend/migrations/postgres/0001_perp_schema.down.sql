@@ -0,0 +1,11 @@
+DROP TABLE IF EXISTS perp.intents;
+DROP TABLE IF EXISTS perp.margin_alerts;
+DROP TABLE IF EXISTS perp.auto_top_up_settings;
+DROP TABLE IF EXISTS perp.user_risk_metrics;
+DROP TABLE IF EXISTS perp.user_daily_stats;
+DROP TABLE IF EXISTS perp.pnl_snapshots;
+DROP TABLE IF EXISTS perp.position_modifications;
+DROP TABLE IF EXISTS perp.positions;
+DROP TABLE IF EXISTS perp.users;
+DROP TABLE IF EXISTS perp.markets;
+DROP SCHEMA IF EXISTS perp;
@@ -0,0 +1,153 @@
+CREATE SCHEMA IF NOT EXISTS perp;
+
+CREATE TABLE IF NOT EXISTS perp.markets (
+    symbol          TEXT PRIMARY KEY,
+    quote_mint      BYTEA NOT NULL,
+    base_decimals   SMALLINT NOT NULL DEFAULT 6,
+    quote_decimals  SMALLINT NOT NULL DEFAULT 6,
+    price_scale     BIGINT NOT NULL DEFAULT 1000000,
+    im_rate_ppm     BIGINT NOT NULL DEFAULT 50000,
+    mm_rate_ppm     BIGINT NOT NULL DEFAULT 25000,
+    max_leverage    INTEGER NOT NULL DEFAULT 1000,
+    is_active       BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at      TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS perp.users (
+    owner               BYTEA NOT NULL,
+    sub_id              INTEGER NOT NULL DEFAULT 0 CHECK (sub_id BETWEEN 0 AND 65535),
+    margin_mode         TEXT NOT NULL DEFAULT 'isolated' CHECK (margin_mode IN ('isolated', 'cross')),
+    total_collateral    BIGINT NOT NULL DEFAULT 0,
+    locked_collateral   BIGINT NOT NULL DEFAULT 0,
+    total_realized_pnl  BIGINT NOT NULL DEFAULT 0,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (owner, sub_id)
+);
+
+CREATE TABLE IF NOT EXISTS perp.positions (
+    pda                 BYTEA PRIMARY KEY,
+    owner               BYTEA NOT NULL,
+    sub_id              INTEGER NOT NULL DEFAULT 0,
+    symbol              TEXT NOT NULL,
+    side                TEXT NOT NULL CHECK (side IN ('long', 'short')),
+    size_base           BIGINT NOT NULL DEFAULT 0,
+    entry_price         BIGINT NOT NULL,
+    margin              BIGINT NOT NULL DEFAULT 0,
+    leverage            INTEGER NOT NULL,
+    unrealized_pnl      BIGINT NOT NULL DEFAULT 0,
+    realized_pnl        BIGINT NOT NULL DEFAULT 0,
+    liquidation_price   BIGINT NOT NULL DEFAULT 0,
+    state               TEXT NOT NULL DEFAULT 'opening'
+                        CHECK (state IN ('opening', 'open', 'modifying', 'closing', 'closed', 'liquidating')),
+    opened_at           TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+    closed_at           TIMESTAMPTZ,
+    last_slot           BIGINT,
+    last_signature      TEXT,
+    FOREIGN KEY (owner, sub_id) REFERENCES perp.users (owner, sub_id)
+);
+
+CREATE INDEX IF NOT EXISTS positions_owner_idx ON perp.positions (owner, sub_id);
+CREATE INDEX IF NOT EXISTS positions_live_idx ON perp.positions (state) WHERE state <> 'closed';
+
+CREATE TABLE IF NOT EXISTS perp.position_modifications (
+    id                  BIGSERIAL PRIMARY KEY,
+    ts                  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    slot                BIGINT NOT NULL,
+    signature           TEXT NOT NULL,
+    event_index         INTEGER NOT NULL,
+    position_pda        BYTEA NOT NULL,
+    kind                TEXT NOT NULL
+                        CHECK (kind IN ('open', 'increase', 'decrease', 'add_margin', 'remove_margin', 'close', 'liquidate', 'transfer_in', 'transfer_out', 'top_up')),
+    base_delta          BIGINT NOT NULL DEFAULT 0,
+    margin_delta        BIGINT NOT NULL DEFAULT 0,
+    price               BIGINT,
+    fee_paid            BIGINT NOT NULL DEFAULT 0,
+    funding_paid        BIGINT NOT NULL DEFAULT 0,
+    realized_pnl_delta  BIGINT NOT NULL DEFAULT 0,
+    UNIQUE (signature, event_index)
+);
+
+CREATE INDEX IF NOT EXISTS position_modifications_pda_idx ON perp.position_modifications (position_pda, ts);
+
+CREATE TABLE IF NOT EXISTS perp.pnl_snapshots (
+    id                  BIGSERIAL PRIMARY KEY,
+    bucket_start        TIMESTAMPTZ NOT NULL,
+    granularity         TEXT NOT NULL CHECK (granularity IN ('1m', '1h', '1d')),
+    owner               BYTEA NOT NULL,
+    symbol              TEXT NOT NULL,
+    position_pda        BYTEA NOT NULL,
+    mark_price          BIGINT NOT NULL,
+    unrealized_pnl      BIGINT NOT NULL,
+    realized_pnl_cum    BIGINT NOT NULL DEFAULT 0,
+    funding_cum         BIGINT NOT NULL DEFAULT 0,
+    equity              BIGINT NOT NULL,
+    margin_ratio        DOUBLE PRECISION NOT NULL,
+    UNIQUE (position_pda, granularity, bucket_start)
+);
+
+CREATE INDEX IF NOT EXISTS pnl_snapshots_owner_idx ON perp.pnl_snapshots (owner, granularity, bucket_start);
+
+CREATE TABLE IF NOT EXISTS perp.user_daily_stats (
+    owner               BYTEA NOT NULL,
+    day                 DATE NOT NULL,
+    trades_count        INTEGER NOT NULL DEFAULT 0,
+    gross_volume_quote  BIGINT NOT NULL DEFAULT 0,
+    fees_paid_quote     BIGINT NOT NULL DEFAULT 0,
+    realized_pnl_quote  BIGINT NOT NULL DEFAULT 0,
+    funding_paid_quote  BIGINT NOT NULL DEFAULT 0,
+    liquidations_count  INTEGER NOT NULL DEFAULT 0,
+    max_leverage_used   INTEGER NOT NULL DEFAULT 0,
+    win_trades          INTEGER NOT NULL DEFAULT 0,
+    loss_trades         INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (owner, day)
+);
+
+CREATE TABLE IF NOT EXISTS perp.user_risk_metrics (
+    owner               BYTEA NOT NULL,
+    computed_at         TIMESTAMPTZ NOT NULL,
+    total_notional      BIGINT NOT NULL,
+    total_equity        BIGINT NOT NULL,
+    im_req              BIGINT NOT NULL,
+    mm_req              BIGINT NOT NULL,
+    margin_ratio        DOUBLE PRECISION NOT NULL,
+    positions_at_risk   INTEGER NOT NULL DEFAULT 0,
+    worst_liq_distance  DOUBLE PRECISION,
+    risk_score          DOUBLE PRECISION,
+    PRIMARY KEY (owner, computed_at)
+);
+
+CREATE TABLE IF NOT EXISTS perp.auto_top_up_settings (
+    position_pda        BYTEA PRIMARY KEY REFERENCES perp.positions (pda) ON DELETE CASCADE,
+    target_mr           BIGINT NOT NULL,
+    max_total           BIGINT NOT NULL,
+    used                BIGINT NOT NULL DEFAULT 0,
+    updated_at          TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS perp.margin_alerts (
+    id                  BIGSERIAL PRIMARY KEY,
+    ts                  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    position_pda        BYTEA NOT NULL,
+    owner               BYTEA NOT NULL,
+    symbol              TEXT NOT NULL,
+    margin_ratio        DOUBLE PRECISION NOT NULL,
+    mark_price          BIGINT NOT NULL,
+    liquidation_price   BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS margin_alerts_pda_idx ON perp.margin_alerts (position_pda, ts);
+
+CREATE TABLE IF NOT EXISTS perp.intents (
+    id                  BIGSERIAL PRIMARY KEY,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+    kind                TEXT NOT NULL,
+    owner               BYTEA NOT NULL,
+    target              BYTEA NOT NULL,
+    payload             JSONB NOT NULL,
+    status              TEXT NOT NULL DEFAULT 'pending'
+);
+
+CREATE INDEX IF NOT EXISTS intents_pending_idx ON perp.intents (created_at) WHERE status = 'pending';
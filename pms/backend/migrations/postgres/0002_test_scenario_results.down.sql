@@ -0,0 +1 @@
+DROP TABLE IF EXISTS perp.test_scenario_results;
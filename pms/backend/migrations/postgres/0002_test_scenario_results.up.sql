@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS perp.test_scenario_results (
+    id                  UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    scenario_name       TEXT NOT NULL,
+    symbol              TEXT NOT NULL,
+    trades_executed     INTEGER NOT NULL,
+    pnl                 BIGINT NOT NULL,
+    max_drawdown        DOUBLE PRECISION NOT NULL,
+    success             BOOLEAN NOT NULL,
+    details             TEXT,
+    test_data           JSONB,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS perp.indexer_cursors;
+ALTER TABLE perp.position_modifications DROP COLUMN IF EXISTS instruction_index;
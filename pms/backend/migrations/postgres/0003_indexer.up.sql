@@ -0,0 +1,8 @@
+ALTER TABLE perp.position_modifications ADD COLUMN IF NOT EXISTS instruction_index INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS perp.indexer_cursors (
+    name                TEXT PRIMARY KEY,
+    slot                BIGINT NOT NULL,
+    signature           TEXT NOT NULL,
+    updated_at          TIMESTAMPTZ NOT NULL DEFAULT now()
+);
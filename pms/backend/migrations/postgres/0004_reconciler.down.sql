@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS perp.reconcile_drifts;
+DROP TABLE IF EXISTS perp.reconcile_runs;
@@ -0,0 +1,25 @@
+CREATE TABLE IF NOT EXISTS perp.reconcile_runs (
+    id                  BIGSERIAL PRIMARY KEY,
+    ran_at              TIMESTAMPTZ NOT NULL,
+    slot                BIGINT NOT NULL,
+    duration_ms         BIGINT NOT NULL,
+    positions_checked   INTEGER NOT NULL,
+    users_checked       INTEGER NOT NULL,
+    drifted             INTEGER NOT NULL,
+    repaired            INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS reconcile_runs_ran_at_idx ON perp.reconcile_runs (ran_at);
+
+CREATE TABLE IF NOT EXISTS perp.reconcile_drifts (
+    id                  BIGSERIAL PRIMARY KEY,
+    run_id              BIGINT NOT NULL REFERENCES perp.reconcile_runs (id) ON DELETE CASCADE,
+    account             TEXT NOT NULL CHECK (account IN ('position', 'user')),
+    address             BYTEA NOT NULL,
+    field               TEXT NOT NULL,
+    chain_value         TEXT NOT NULL,
+    db_value            TEXT NOT NULL,
+    repaired            BOOLEAN NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS reconcile_drifts_run_idx ON perp.reconcile_drifts (run_id);
@@ -0,0 +1,13 @@
+DROP INDEX IF EXISTS perp.operations_in_flight_idx;
+ALTER TABLE perp.operations DROP CONSTRAINT IF EXISTS operations_status_check;
+UPDATE perp.operations SET status = 'pending' WHERE status IN ('intent', 'submitted');
+ALTER TABLE perp.operations ALTER COLUMN status SET DEFAULT 'pending';
+
+ALTER TABLE perp.operations
+    DROP COLUMN IF EXISTS signature,
+    DROP COLUMN IF EXISTS last_valid_block_height,
+    DROP COLUMN IF EXISTS error,
+    DROP COLUMN IF EXISTS updated_at;
+
+ALTER TABLE perp.operations RENAME TO intents;
+CREATE INDEX IF NOT EXISTS intents_pending_idx ON perp.intents (created_at) WHERE status = 'pending';
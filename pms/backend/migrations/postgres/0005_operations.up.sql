@@ -0,0 +1,19 @@
+-- intents becomes the operation log: each row moves intent → submitted (signature of the
+-- transaction in flight) → confirmed | failed
+ALTER TABLE perp.intents RENAME TO operations;
+DROP INDEX IF EXISTS perp.intents_pending_idx;
+
+ALTER TABLE perp.operations
+    ADD COLUMN IF NOT EXISTS signature TEXT,
+    ADD COLUMN IF NOT EXISTS last_valid_block_height BIGINT,
+    ADD COLUMN IF NOT EXISTS error TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+-- Earlier rows were never tracked past recording; the recovery worker expires them
+UPDATE perp.operations SET status = 'intent' WHERE status = 'pending';
+ALTER TABLE perp.operations ALTER COLUMN status SET DEFAULT 'intent';
+ALTER TABLE perp.operations ADD CONSTRAINT operations_status_check
+    CHECK (status IN ('intent', 'submitted', 'confirmed', 'failed'));
+
+CREATE INDEX IF NOT EXISTS operations_in_flight_idx ON perp.operations (created_at)
+    WHERE status IN ('intent', 'submitted');
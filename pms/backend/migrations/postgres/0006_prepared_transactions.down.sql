@@ -0,0 +1 @@
+ALTER TABLE perp.operations DROP COLUMN IF EXISTS message;
@@ -0,0 +1,3 @@
+-- The unsigned message handed to a wallet (SIGNING_MODE=wallet); /tx/submit only relays a
+-- transaction carrying exactly this message. last_valid_block_height is set with it.
+ALTER TABLE perp.operations ADD COLUMN IF NOT EXISTS message BYTEA;
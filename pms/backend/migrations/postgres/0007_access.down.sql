@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS perp.api_keys;
+DROP TABLE IF EXISTS perp.delegates;
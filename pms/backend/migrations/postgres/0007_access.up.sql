@@ -0,0 +1,21 @@
+-- Wallets an owner lets act on their positions (api/auth.rs)
+CREATE TABLE IF NOT EXISTS perp.delegates (
+    owner               BYTEA NOT NULL,
+    delegate            BYTEA NOT NULL,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (owner, delegate)
+);
+
+-- API keys for bots. Only the SHA-256 of a key is stored; scopes is a comma-separated list
+-- (read, trade, transfer). Revoked keys are kept with revoked_at set.
+CREATE TABLE IF NOT EXISTS perp.api_keys (
+    id                  BIGSERIAL PRIMARY KEY,
+    owner               BYTEA NOT NULL,
+    key_hash            BYTEA NOT NULL UNIQUE,
+    scopes              TEXT NOT NULL,
+    label               TEXT,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+    revoked_at          TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS api_keys_owner_idx ON perp.api_keys (owner);
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS perp.demo_orders;
+DROP TABLE IF EXISTS perp.demo_price_updates;
+DROP TABLE IF EXISTS perp.demo_positions;
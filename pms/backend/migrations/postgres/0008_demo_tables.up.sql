@@ -0,0 +1,34 @@
+-- Tables of the terminal demo (src/bin/interactive_demo_with_db.rs); prices and sizes in cents
+CREATE TABLE IF NOT EXISTS perp.demo_positions (
+    id                  UUID PRIMARY KEY,
+    username            TEXT NOT NULL,
+    symbol              TEXT NOT NULL,
+    side                TEXT NOT NULL,
+    size                BIGINT NOT NULL,
+    entry_price         BIGINT NOT NULL,
+    exit_price          BIGINT,
+    leverage            INTEGER NOT NULL,
+    take_profit         BIGINT,
+    stop_loss           BIGINT,
+    status              TEXT NOT NULL DEFAULT 'OPEN',
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS perp.demo_price_updates (
+    id                  BIGSERIAL PRIMARY KEY,
+    position_id         UUID NOT NULL REFERENCES perp.demo_positions(id) ON DELETE CASCADE,
+    price               BIGINT NOT NULL,
+    pnl                 BIGINT NOT NULL,
+    timestamp           TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Take-profit and stop-loss triggers; status ACTIVE until the price crosses trigger_price
+CREATE TABLE IF NOT EXISTS perp.demo_orders (
+    id                  UUID PRIMARY KEY,
+    position_id         UUID NOT NULL REFERENCES perp.demo_positions(id) ON DELETE CASCADE,
+    order_type          TEXT NOT NULL,
+    trigger_price       BIGINT NOT NULL,
+    status              TEXT NOT NULL DEFAULT 'ACTIVE',
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT now(),
+    triggered_at        TIMESTAMPTZ
+);
@@ -0,0 +1,10 @@
+DROP TABLE IF EXISTS intents;
+DROP TABLE IF EXISTS margin_alerts;
+DROP TABLE IF EXISTS auto_top_up_settings;
+DROP TABLE IF EXISTS user_risk_metrics;
+DROP TABLE IF EXISTS user_daily_stats;
+DROP TABLE IF EXISTS pnl_snapshots;
+DROP TABLE IF EXISTS position_modifications;
+DROP TABLE IF EXISTS positions;
+DROP TABLE IF EXISTS users;
+DROP TABLE IF EXISTS markets;
@@ -0,0 +1,151 @@
+CREATE TABLE IF NOT EXISTS markets (
+    symbol          TEXT PRIMARY KEY,
+    quote_mint      BLOB NOT NULL,
+    base_decimals   INTEGER NOT NULL DEFAULT 6,
+    quote_decimals  INTEGER NOT NULL DEFAULT 6,
+    price_scale     INTEGER NOT NULL DEFAULT 1000000,
+    im_rate_ppm     INTEGER NOT NULL DEFAULT 50000,
+    mm_rate_ppm     INTEGER NOT NULL DEFAULT 25000,
+    max_leverage    INTEGER NOT NULL DEFAULT 1000,
+    is_active       INTEGER NOT NULL DEFAULT 1,
+    created_at      TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at      TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+CREATE TABLE IF NOT EXISTS users (
+    owner               BLOB NOT NULL,
+    sub_id              INTEGER NOT NULL DEFAULT 0 CHECK (sub_id BETWEEN 0 AND 65535),
+    margin_mode         TEXT NOT NULL DEFAULT 'isolated' CHECK (margin_mode IN ('isolated', 'cross')),
+    total_collateral    INTEGER NOT NULL DEFAULT 0,
+    locked_collateral   INTEGER NOT NULL DEFAULT 0,
+    total_realized_pnl  INTEGER NOT NULL DEFAULT 0,
+    created_at          TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at          TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    PRIMARY KEY (owner, sub_id)
+);
+
+CREATE TABLE IF NOT EXISTS positions (
+    pda                 BLOB PRIMARY KEY,
+    owner               BLOB NOT NULL,
+    sub_id              INTEGER NOT NULL DEFAULT 0,
+    symbol              TEXT NOT NULL,
+    side                TEXT NOT NULL CHECK (side IN ('long', 'short')),
+    size_base           INTEGER NOT NULL DEFAULT 0,
+    entry_price         INTEGER NOT NULL,
+    margin              INTEGER NOT NULL DEFAULT 0,
+    leverage            INTEGER NOT NULL,
+    unrealized_pnl      INTEGER NOT NULL DEFAULT 0,
+    realized_pnl        INTEGER NOT NULL DEFAULT 0,
+    liquidation_price   INTEGER NOT NULL DEFAULT 0,
+    state               TEXT NOT NULL DEFAULT 'opening'
+                        CHECK (state IN ('opening', 'open', 'modifying', 'closing', 'closed', 'liquidating')),
+    opened_at           TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at          TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    closed_at           TEXT,
+    last_slot           INTEGER,
+    last_signature      TEXT,
+    FOREIGN KEY (owner, sub_id) REFERENCES users (owner, sub_id)
+);
+
+CREATE INDEX IF NOT EXISTS positions_owner_idx ON positions (owner, sub_id);
+CREATE INDEX IF NOT EXISTS positions_live_idx ON positions (state) WHERE state <> 'closed';
+
+CREATE TABLE IF NOT EXISTS position_modifications (
+    id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+    ts                  TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    slot                INTEGER NOT NULL,
+    signature           TEXT NOT NULL,
+    event_index         INTEGER NOT NULL,
+    position_pda        BLOB NOT NULL,
+    kind                TEXT NOT NULL
+                        CHECK (kind IN ('open', 'increase', 'decrease', 'add_margin', 'remove_margin', 'close', 'liquidate', 'transfer_in', 'transfer_out', 'top_up')),
+    base_delta          INTEGER NOT NULL DEFAULT 0,
+    margin_delta        INTEGER NOT NULL DEFAULT 0,
+    price               INTEGER,
+    fee_paid            INTEGER NOT NULL DEFAULT 0,
+    funding_paid        INTEGER NOT NULL DEFAULT 0,
+    realized_pnl_delta  INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (signature, event_index)
+);
+
+CREATE INDEX IF NOT EXISTS position_modifications_pda_idx ON position_modifications (position_pda, ts);
+
+CREATE TABLE IF NOT EXISTS pnl_snapshots (
+    id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+    bucket_start        TEXT NOT NULL,
+    granularity         TEXT NOT NULL CHECK (granularity IN ('1m', '1h', '1d')),
+    owner               BLOB NOT NULL,
+    symbol              TEXT NOT NULL,
+    position_pda        BLOB NOT NULL,
+    mark_price          INTEGER NOT NULL,
+    unrealized_pnl      INTEGER NOT NULL,
+    realized_pnl_cum    INTEGER NOT NULL DEFAULT 0,
+    funding_cum         INTEGER NOT NULL DEFAULT 0,
+    equity              INTEGER NOT NULL,
+    margin_ratio        REAL NOT NULL,
+    UNIQUE (position_pda, granularity, bucket_start)
+);
+
+CREATE INDEX IF NOT EXISTS pnl_snapshots_owner_idx ON pnl_snapshots (owner, granularity, bucket_start);
+
+CREATE TABLE IF NOT EXISTS user_daily_stats (
+    owner               BLOB NOT NULL,
+    day                 TEXT NOT NULL,
+    trades_count        INTEGER NOT NULL DEFAULT 0,
+    gross_volume_quote  INTEGER NOT NULL DEFAULT 0,
+    fees_paid_quote     INTEGER NOT NULL DEFAULT 0,
+    realized_pnl_quote  INTEGER NOT NULL DEFAULT 0,
+    funding_paid_quote  INTEGER NOT NULL DEFAULT 0,
+    liquidations_count  INTEGER NOT NULL DEFAULT 0,
+    max_leverage_used   INTEGER NOT NULL DEFAULT 0,
+    win_trades          INTEGER NOT NULL DEFAULT 0,
+    loss_trades         INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (owner, day)
+);
+
+CREATE TABLE IF NOT EXISTS user_risk_metrics (
+    owner               BLOB NOT NULL,
+    computed_at         TEXT NOT NULL,
+    total_notional      INTEGER NOT NULL,
+    total_equity        INTEGER NOT NULL,
+    im_req              INTEGER NOT NULL,
+    mm_req              INTEGER NOT NULL,
+    margin_ratio        REAL NOT NULL,
+    positions_at_risk   INTEGER NOT NULL DEFAULT 0,
+    worst_liq_distance  REAL,
+    risk_score          REAL,
+    PRIMARY KEY (owner, computed_at)
+);
+
+CREATE TABLE IF NOT EXISTS auto_top_up_settings (
+    position_pda        BLOB PRIMARY KEY REFERENCES positions (pda) ON DELETE CASCADE,
+    target_mr           INTEGER NOT NULL,
+    max_total           INTEGER NOT NULL,
+    used                INTEGER NOT NULL DEFAULT 0,
+    updated_at          TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+CREATE TABLE IF NOT EXISTS margin_alerts (
+    id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+    ts                  TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    position_pda        BLOB NOT NULL,
+    owner               BLOB NOT NULL,
+    symbol              TEXT NOT NULL,
+    margin_ratio        REAL NOT NULL,
+    mark_price          INTEGER NOT NULL,
+    liquidation_price   INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS margin_alerts_pda_idx ON margin_alerts (position_pda, ts);
+
+CREATE TABLE IF NOT EXISTS intents (
+    id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at          TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    kind                TEXT NOT NULL,
+    owner               BLOB NOT NULL,
+    target              BLOB NOT NULL,
+    payload             TEXT NOT NULL,
+    status              TEXT NOT NULL DEFAULT 'pending'
+);
+
+CREATE INDEX IF NOT EXISTS intents_pending_idx ON intents (created_at) WHERE status = 'pending';
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS test_scenario_results (
+    id                  TEXT PRIMARY KEY DEFAULT (lower(hex(randomblob(16)))),
+    scenario_name       TEXT NOT NULL,
+    symbol              TEXT NOT NULL,
+    trades_executed     INTEGER NOT NULL,
+    pnl                 INTEGER NOT NULL,
+    max_drawdown        REAL NOT NULL,
+    success             INTEGER NOT NULL,
+    details             TEXT,
+    test_data           TEXT,
+    created_at          TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
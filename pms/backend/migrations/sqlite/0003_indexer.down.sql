@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS indexer_cursors;
+ALTER TABLE position_modifications DROP COLUMN instruction_index;
@@ -0,0 +1,8 @@
+ALTER TABLE position_modifications ADD COLUMN instruction_index INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS indexer_cursors (
+    name                TEXT PRIMARY KEY,
+    slot                INTEGER NOT NULL,
+    signature           TEXT NOT NULL,
+    updated_at          TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS reconcile_drifts;
+DROP TABLE IF EXISTS reconcile_runs;
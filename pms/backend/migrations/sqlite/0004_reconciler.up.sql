@@ -0,0 +1,25 @@
+CREATE TABLE IF NOT EXISTS reconcile_runs (
+    id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+    ran_at              TEXT NOT NULL,
+    slot                INTEGER NOT NULL,
+    duration_ms         INTEGER NOT NULL,
+    positions_checked   INTEGER NOT NULL,
+    users_checked       INTEGER NOT NULL,
+    drifted             INTEGER NOT NULL,
+    repaired            INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS reconcile_runs_ran_at_idx ON reconcile_runs (ran_at);
+
+CREATE TABLE IF NOT EXISTS reconcile_drifts (
+    id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_id              INTEGER NOT NULL REFERENCES reconcile_runs (id) ON DELETE CASCADE,
+    account             TEXT NOT NULL CHECK (account IN ('position', 'user')),
+    address             BLOB NOT NULL,
+    field               TEXT NOT NULL,
+    chain_value         TEXT NOT NULL,
+    db_value            TEXT NOT NULL,
+    repaired            INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS reconcile_drifts_run_idx ON reconcile_drifts (run_id);
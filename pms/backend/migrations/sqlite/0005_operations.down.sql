@@ -0,0 +1,10 @@
+DROP INDEX IF EXISTS operations_in_flight_idx;
+UPDATE operations SET status = 'pending' WHERE status IN ('intent', 'submitted');
+
+ALTER TABLE operations DROP COLUMN signature;
+ALTER TABLE operations DROP COLUMN last_valid_block_height;
+ALTER TABLE operations DROP COLUMN error;
+ALTER TABLE operations DROP COLUMN updated_at;
+
+ALTER TABLE operations RENAME TO intents;
+CREATE INDEX IF NOT EXISTS intents_pending_idx ON intents (created_at) WHERE status = 'pending';
@@ -0,0 +1,17 @@
+-- intents becomes the operation log: each row moves intent → submitted (signature of the
+-- transaction in flight) → confirmed | failed. SQLite can't change a column's default or add
+-- a CHECK in place, so status keeps its old default; every insert sets it.
+ALTER TABLE intents RENAME TO operations;
+DROP INDEX IF EXISTS intents_pending_idx;
+
+ALTER TABLE operations ADD COLUMN signature TEXT;
+ALTER TABLE operations ADD COLUMN last_valid_block_height INTEGER;
+ALTER TABLE operations ADD COLUMN error TEXT;
+ALTER TABLE operations ADD COLUMN updated_at TEXT;
+UPDATE operations SET updated_at = created_at;
+
+-- Earlier rows were never tracked past recording; the recovery worker expires them
+UPDATE operations SET status = 'intent' WHERE status = 'pending';
+
+CREATE INDEX IF NOT EXISTS operations_in_flight_idx ON operations (created_at)
+    WHERE status IN ('intent', 'submitted');
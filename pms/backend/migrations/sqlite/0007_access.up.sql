@@ -0,0 +1,21 @@
+-- Wallets an owner lets act on their positions (api/auth.rs)
+CREATE TABLE IF NOT EXISTS delegates (
+    owner               BLOB NOT NULL,
+    delegate            BLOB NOT NULL,
+    created_at          TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    PRIMARY KEY (owner, delegate)
+);
+
+-- API keys for bots. Only the SHA-256 of a key is stored; scopes is a comma-separated list
+-- (read, trade, transfer). Revoked keys are kept with revoked_at set.
+CREATE TABLE IF NOT EXISTS api_keys (
+    id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+    owner               BLOB NOT NULL,
+    key_hash            BLOB NOT NULL UNIQUE,
+    scopes              TEXT NOT NULL,
+    label               TEXT,
+    created_at          TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    revoked_at          TEXT
+);
+
+CREATE INDEX IF NOT EXISTS api_keys_owner_idx ON api_keys (owner);
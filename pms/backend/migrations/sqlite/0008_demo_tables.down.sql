@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS demo_orders;
+DROP TABLE IF EXISTS demo_price_updates;
+DROP TABLE IF EXISTS demo_positions;
@@ -0,0 +1,34 @@
+-- Tables of the terminal demo (src/bin/interactive_demo_with_db.rs); prices and sizes in cents
+CREATE TABLE IF NOT EXISTS demo_positions (
+    id                  TEXT PRIMARY KEY,
+    username            TEXT NOT NULL,
+    symbol              TEXT NOT NULL,
+    side                TEXT NOT NULL,
+    size                INTEGER NOT NULL,
+    entry_price         INTEGER NOT NULL,
+    exit_price          INTEGER,
+    leverage            INTEGER NOT NULL,
+    take_profit         INTEGER,
+    stop_loss           INTEGER,
+    status              TEXT NOT NULL DEFAULT 'OPEN',
+    created_at          TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+CREATE TABLE IF NOT EXISTS demo_price_updates (
+    id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+    position_id         TEXT NOT NULL REFERENCES demo_positions(id) ON DELETE CASCADE,
+    price               INTEGER NOT NULL,
+    pnl                 INTEGER NOT NULL,
+    timestamp           TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+-- Take-profit and stop-loss triggers; status ACTIVE until the price crosses trigger_price
+CREATE TABLE IF NOT EXISTS demo_orders (
+    id                  TEXT PRIMARY KEY,
+    position_id         TEXT NOT NULL REFERENCES demo_positions(id) ON DELETE CASCADE,
+    order_type          TEXT NOT NULL,
+    trigger_price       INTEGER NOT NULL,
+    status              TEXT NOT NULL DEFAULT 'ACTIVE',
+    created_at          TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    triggered_at        TEXT
+);
@@ -0,0 +1,298 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, State},
+    http::{header::AUTHORIZATION, request::Parts, HeaderMap},
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use solana_sdk::{hash::hash, pubkey::Pubkey, signature::Signature};
+
+use super::{
+    error::{parse_pubkey, ApiError, Json, Path},
+    http::AppState,
+};
+use crate::{
+    db::{scopes_str, SharedRepo},
+    models::{ApiKey, ApiKeyId, Scope},
+};
+
+// How long a nonce can be signed for
+const NONCE_TTL: Duration = Duration::from_secs(300);
+// Unused nonces kept per wallet; asking for another drops the wallet's oldest
+const MAX_NONCES_PER_PUBKEY: usize = 4;
+// Unused nonces kept in all; beyond this /auth/nonce refuses until some expire
+const MAX_NONCES: usize = 10_000;
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+// Who may act for an owner.
+//
+// A wallet proves its key by signing a nonce the server handed out (POST /auth/nonce, then
+// POST /auth/session with the signature) and gets a session token for `session_ttl`, sent as
+// `Authorization: Bearer <token>`. Bots send `X-Api-Key: <key>` instead; a key acts for the
+// wallet that created it, limited to its scopes. Either way the caller must be the owner or
+// one of the owner's delegates. Nonces and sessions are kept in memory, so a restart signs
+// everyone out; delegates and keys are in the database.
+#[derive(Clone)]
+pub struct Auth {
+    repo: SharedRepo,
+    // AUTH=off: requests without credentials are let through (local development)
+    required: bool,
+    session_ttl: Duration,
+    nonces: Arc<Mutex<HashMap<String, Challenge>>>,
+    sessions: Arc<Mutex<HashMap<String, Session>>>,
+}
+
+struct Challenge {
+    pubkey: Pubkey,
+    message: String,
+    expires_at: DateTime<Utc>,
+}
+
+struct Session {
+    pubkey: Pubkey,
+    expires_at: DateTime<Utc>,
+}
+
+// The credentials a request came with
+#[derive(Debug, Clone)]
+pub enum Caller {
+    // No credentials, with AUTH=off
+    Anyone,
+    Wallet(Pubkey),
+    Key(ApiKey),
+}
+
+impl Auth {
+    pub fn new(repo: SharedRepo, required: bool, session_ttl: Duration) -> Self {
+        Self {
+            repo,
+            required,
+            session_ttl,
+            nonces: Arc::new(Mutex::new(HashMap::new())),
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // A nonce for `pubkey`, and the text the wallet signs (as UTF-8 bytes) to prove it holds it
+    fn challenge(&self, pubkey: Pubkey) -> Result<(String, String, DateTime<Utc>), ApiError> {
+        let nonce = random_token();
+        let expires_at = Utc::now() + chrono::Duration::from_std(NONCE_TTL).unwrap();
+        let message = format!("Sign in to the position service\n\nWallet: {pubkey}\nNonce: {nonce}\nExpires: {}", expires_at.to_rfc3339());
+        let mut nonces = self.nonces.lock().unwrap();
+        let now = Utc::now();
+        nonces.retain(|_, c| c.expires_at > now);
+        let mut mine: Vec<_> = nonces.iter().filter(|(_, c)| c.pubkey == pubkey).map(|(n, c)| (c.expires_at, n.clone())).collect();
+        if mine.len() >= MAX_NONCES_PER_PUBKEY {
+            mine.sort();
+            for (_, oldest) in &mine[..=mine.len() - MAX_NONCES_PER_PUBKEY] {
+                nonces.remove(oldest);
+            }
+        } else if nonces.len() >= MAX_NONCES {
+            return Err(ApiError::TooMany("too many sign-ins in progress, try again in a few minutes".into()));
+        }
+        nonces.insert(nonce.clone(), Challenge { pubkey, message: message.clone(), expires_at });
+        Ok((nonce, message, expires_at))
+    }
+
+    // Trades a signed nonce for a session token. Each nonce can be used once.
+    fn sign_in(&self, pubkey: &Pubkey, nonce: &str, signature: &Signature) -> Result<(String, DateTime<Utc>), ApiError> {
+        let challenge = self.nonces.lock().unwrap().remove(nonce);
+        let challenge = match challenge {
+            Some(c) if c.expires_at > Utc::now() => c,
+            _ => return Err(ApiError::Unauthorized("unknown or expired nonce".into())),
+        };
+        if challenge.pubkey != *pubkey || !signature.verify(pubkey.as_ref(), challenge.message.as_bytes()) {
+            return Err(ApiError::Unauthorized("signature doesn't match the nonce's wallet".into()));
+        }
+        let token = random_token();
+        let expires_at = Utc::now() + chrono::Duration::from_std(self.session_ttl).unwrap();
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Utc::now();
+        sessions.retain(|_, s| s.expires_at > now);
+        sessions.insert(token.clone(), Session { pubkey: *pubkey, expires_at });
+        Ok((token, expires_at))
+    }
+
+    fn sign_out(&self, token: &str) -> bool {
+        self.sessions.lock().unwrap().remove(token).is_some()
+    }
+
+    // The wallet signed in with session `token`
+    pub(crate) fn session(&self, token: &str) -> Result<Caller, ApiError> {
+        match self.sessions.lock().unwrap().get(token) {
+            Some(s) if s.expires_at > Utc::now() => Ok(Caller::Wallet(s.pubkey)),
+            _ => Err(ApiError::Unauthorized("unknown or expired session".into())),
+        }
+    }
+
+    pub(crate) async fn caller(&self, headers: &HeaderMap) -> Result<Caller, ApiError> {
+        if let Some(token) = bearer(headers) {
+            return self.session(token);
+        }
+        if let Some(key) = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+            return match self.repo.fetch_api_key(&key_hash(key)).await? {
+                Some(key) => Ok(Caller::Key(key)),
+                None => Err(ApiError::Unauthorized("unknown or revoked API key".into())),
+            };
+        }
+        if self.required {
+            return Err(ApiError::Unauthorized("sign in (Authorization: Bearer <session>) or send X-Api-Key".into()));
+        }
+        Ok(Caller::Anyone)
+    }
+}
+
+impl Caller {
+    // Allows the request if the caller is `owner` or one of its delegates, and an API key
+    // carries `scope`
+    pub async fn authorize(&self, auth: &Auth, owner: &Pubkey, scope: Scope) -> Result<(), ApiError> {
+        let pubkey = match self {
+            Caller::Anyone => return Ok(()),
+            Caller::Wallet(pubkey) => pubkey,
+            Caller::Key(key) => {
+                if !key.scopes.contains(&scope) {
+                    return Err(ApiError::Forbidden(format!("API key {} lacks the {} scope", key.id, scopes_str(&[scope]))));
+                }
+                &key.owner
+            }
+        };
+        if pubkey == owner || auth.repo.is_delegate(owner, pubkey).await? {
+            return Ok(());
+        }
+        Err(ApiError::Forbidden(format!("{pubkey} is neither {owner} nor one of its delegates")))
+    }
+
+    // Operators: an API key with the admin scope, whoever it was made for
+    pub fn admin(&self) -> Result<(), ApiError> {
+        match self {
+            Caller::Anyone => Ok(()),
+            Caller::Key(key) if key.scopes.contains(&Scope::Admin) => Ok(()),
+            Caller::Key(key) => Err(ApiError::Forbidden(format!("API key {} lacks the admin scope", key.id))),
+            Caller::Wallet(pubkey) => Err(ApiError::Forbidden(format!("{pubkey} is not an admin; use an API key with the admin scope"))),
+        }
+    }
+
+    // Delegates and API keys are managed by the wallet itself, never through a key
+    fn wallet(&self) -> Result<Pubkey, ApiError> {
+        match self {
+            Caller::Wallet(pubkey) => Ok(*pubkey),
+            _ => Err(ApiError::Unauthorized("sign in with the wallet to manage delegates and API keys".into())),
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Caller {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, ApiError> {
+        state.auth.caller(&parts.headers).await
+    }
+}
+
+fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers.get(AUTHORIZATION)?.to_str().ok()?.strip_prefix("Bearer ")
+}
+
+fn random_token() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+// API keys are stored as their SHA-256
+pub fn key_hash(key: &str) -> Vec<u8> {
+    hash(key.as_bytes()).to_bytes().to_vec()
+}
+
+// A new API key for `owner`; the returned key is not stored and can't be shown again
+pub async fn create_api_key(repo: &SharedRepo, owner: &Pubkey, scopes: &[Scope], label: Option<&str>) -> anyhow::Result<(ApiKeyId, String)> {
+    let key = format!("pk_{}", random_token());
+    let id = repo.insert_api_key(owner, &key_hash(&key), scopes, label).await?;
+    Ok((id, key))
+}
+
+// ---------- /auth endpoints ----------
+
+type ApiResult = Result<Json<serde_json::Value>, ApiError>;
+
+#[derive(Deserialize)]
+pub struct NonceReq { pubkey: String }
+
+pub async fn nonce(State(st): State<AppState>, Json(req): Json<NonceReq>) -> ApiResult {
+    let pubkey = parse_pubkey(&req.pubkey, "pubkey")?;
+    let (nonce, message, expires_at) = st.auth.challenge(pubkey)?;
+    Ok(Json(serde_json::json!({ "nonce": nonce, "message": message, "expires_at": expires_at })))
+}
+
+#[derive(Deserialize)]
+pub struct SessionReq { pubkey: String, nonce: String, signature: String }
+
+pub async fn session(State(st): State<AppState>, Json(req): Json<SessionReq>) -> ApiResult {
+    let pubkey = parse_pubkey(&req.pubkey, "pubkey")?;
+    let signature = req.signature.parse::<Signature>().map_err(|_| ApiError::Invalid("signature is not a base58 ed25519 signature".into()))?;
+    let (token, expires_at) = st.auth.sign_in(&pubkey, &req.nonce, &signature)?;
+    Ok(Json(serde_json::json!({ "token": token, "expires_at": expires_at })))
+}
+
+pub async fn end_session(State(st): State<AppState>, headers: HeaderMap) -> ApiResult {
+    let token = bearer(&headers).ok_or_else(|| ApiError::Unauthorized("no session".into()))?;
+    Ok(Json(serde_json::json!({ "ok": st.auth.sign_out(token) })))
+}
+
+pub async fn list_delegates(State(st): State<AppState>, caller: Caller) -> ApiResult {
+    let delegates = st.repo.fetch_delegates(&caller.wallet()?).await?;
+    let delegates: Vec<String> = delegates.iter().map(|d| d.to_string()).collect();
+    Ok(Json(serde_json::json!({ "delegates": delegates })))
+}
+
+#[derive(Deserialize)]
+pub struct DelegateReq { delegate: String }
+
+pub async fn add_delegate(State(st): State<AppState>, caller: Caller, Json(req): Json<DelegateReq>) -> ApiResult {
+    let owner = caller.wallet()?;
+    let delegate = parse_pubkey(&req.delegate, "delegate")?;
+    if delegate == owner {
+        return Err(ApiError::Invalid("a wallet can't delegate to itself".into()));
+    }
+    let added = st.repo.add_delegate(&owner, &delegate).await?;
+    Ok(Json(serde_json::json!({ "ok": true, "added": added })))
+}
+
+pub async fn remove_delegate(State(st): State<AppState>, caller: Caller, Path(delegate): Path<String>) -> ApiResult {
+    let delegate = parse_pubkey(&delegate, "delegate")?;
+    if !st.repo.remove_delegate(&caller.wallet()?, &delegate).await? {
+        return Err(ApiError::NotFound(format!("delegate {delegate}")));
+    }
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+pub async fn list_api_keys(State(st): State<AppState>, caller: Caller) -> ApiResult {
+    let keys = st.repo.fetch_api_keys(&caller.wallet()?).await?;
+    Ok(Json(serde_json::json!({ "api_keys": keys })))
+}
+
+#[derive(Deserialize)]
+pub struct ApiKeyReq { scopes: Vec<Scope>, label: Option<String> }
+
+pub async fn create_key(State(st): State<AppState>, caller: Caller, Json(req): Json<ApiKeyReq>) -> ApiResult {
+    let owner = caller.wallet()?;
+    if req.scopes.is_empty() {
+        return Err(ApiError::Invalid("an API key needs at least one scope".into()));
+    }
+    if req.scopes.contains(&Scope::Admin) {
+        return Err(ApiError::Forbidden("admin keys are only made with the api_key tool".into()));
+    }
+    let (id, key) = create_api_key(&st.repo, &owner, &req.scopes, req.label.as_deref()).await?;
+    Ok(Json(serde_json::json!({ "id": id, "key": key, "scopes": req.scopes, "label": req.label })))
+}
+
+pub async fn revoke_key(State(st): State<AppState>, caller: Caller, Path(id): Path<ApiKeyId>) -> ApiResult {
+    if !st.repo.revoke_api_key(&caller.wallet()?, id).await? {
+        return Err(ApiError::NotFound(format!("API key {id}")));
+    }
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
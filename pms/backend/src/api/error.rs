@@ -0,0 +1,205 @@
+use axum::{
+    extract::{
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        FromRequest, FromRequestParts,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use perp_sdk::PerpError;
+use serde::Serialize;
+use solana_sdk::{pubkey::Pubkey, transaction::TransactionError};
+
+use crate::errors::{SvcError, TxError};
+
+// What a handler fails with. Responses are
+//   { "error": { "code", "message", "program_error"?: { "name", "code" } } }
+// where code is one of the stable strings in ApiError::code.
+#[derive(Debug)]
+pub enum ApiError {
+    // 400: malformed or out-of-range input
+    Invalid(String),
+    // 401: no valid session or API key
+    Unauthorized(String),
+    // 403: authenticated, but not the owner or a delegate, or an API key without the scope
+    Forbidden(String),
+    // 404
+    NotFound(String),
+    // 409: not possible in the current state
+    Conflict(String),
+    // 429: too many outstanding requests of this kind
+    TooMany(String),
+    // 422: the program rejected the request (in simulation or on-chain)
+    Program(PerpError),
+    // 422: the transaction failed for a reason other than a program error
+    Failed(TransactionError),
+    // 502: the RPC node or the signer failed
+    Rpc(String),
+    Signer(String),
+    // 504: the transaction didn't land before its blockhash expired
+    Expired(String),
+    // 500: details are logged, not returned
+    Internal(anyhow::Error),
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(Serialize)]
+struct ErrorDetail {
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    program_error: Option<ProgramErrorDetail>,
+}
+
+#[derive(Serialize)]
+struct ProgramErrorDetail {
+    name: String,
+    code: u32,
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::Invalid(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::TooMany(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Program(_) | ApiError::Failed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Rpc(_) | ApiError::Signer(_) => StatusCode::BAD_GATEWAY,
+            ApiError::Expired(_) => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // Stable identifiers for clients to match on; messages may change
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::Invalid(_) => "invalid_input",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::TooMany(_) => "rate_limited",
+            ApiError::Program(_) => "program_error",
+            ApiError::Failed(_) => "transaction_failed",
+            ApiError::Rpc(_) => "rpc_error",
+            ApiError::Signer(_) => "signer_error",
+            ApiError::Expired(_) => "not_confirmed",
+            ApiError::Internal(_) => "internal",
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            ApiError::Invalid(m) | ApiError::Unauthorized(m) | ApiError::Forbidden(m) | ApiError::Conflict(m) | ApiError::TooMany(m) | ApiError::Expired(m) => m.clone(),
+            ApiError::NotFound(what) => format!("{what} not found"),
+            ApiError::Program(e) => e.message().to_string(),
+            ApiError::Failed(e) => format!("transaction failed: {e}"),
+            ApiError::Rpc(m) => format!("RPC error: {m}"),
+            ApiError::Signer(m) => format!("signer error: {m}"),
+            ApiError::Internal(_) => "internal error".to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        match &self {
+            ApiError::Internal(e) => tracing::error!("request failed: {e:?}"),
+            ApiError::Rpc(_) | ApiError::Signer(_) | ApiError::Expired(_) => tracing::warn!("request failed: {}", self.message()),
+            _ => {}
+        }
+        let program_error = match &self {
+            ApiError::Program(e) => Some(ProgramErrorDetail { name: format!("{e:?}"), code: e.code() }),
+            _ => None,
+        };
+        let body = ErrorBody { error: ErrorDetail { code: self.code(), message: self.message(), program_error } };
+        (self.status(), axum::Json(body)).into_response()
+    }
+}
+
+impl From<TxError> for ApiError {
+    fn from(err: TxError) -> Self {
+        match err {
+            TxError::Program(e) => ApiError::Program(e),
+            TxError::Failed(e) => ApiError::Failed(e),
+            TxError::Expired(_) => ApiError::Expired(err.to_string()),
+            TxError::Rpc(e) => ApiError::Rpc(e),
+            TxError::Signer(e) => ApiError::Signer(e),
+            TxError::Unrecorded(_) => ApiError::Internal(err.into()),
+        }
+    }
+}
+
+impl From<SvcError> for ApiError {
+    fn from(err: SvcError) -> Self {
+        match err {
+            SvcError::Invalid(m) => ApiError::Invalid(m),
+            SvcError::NotFound(what) => ApiError::NotFound(what),
+            SvcError::Conflict(m) => ApiError::Conflict(m),
+            SvcError::Rpc(m) => ApiError::Rpc(m),
+            SvcError::Db(_) | SvcError::Serde(_) => ApiError::Internal(err.into()),
+        }
+    }
+}
+
+// The services return anyhow errors; the typed ones inside are mapped, anything else is a 500
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        let err = match err.downcast::<SvcError>() {
+            Ok(e) => return e.into(),
+            Err(err) => err,
+        };
+        match err.downcast::<TxError>() {
+            Ok(e) => e.into(),
+            Err(err) => ApiError::Internal(err),
+        }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::Invalid(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::Invalid(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::Invalid(rejection.body_text())
+    }
+}
+
+// axum's extractors, rejecting with an ApiError body instead of plain text
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct Path<T>(pub T);
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct Query<T>(pub T);
+
+pub fn parse_pubkey(value: &str, what: &str) -> Result<Pubkey, ApiError> {
+    value.parse().map_err(|_| ApiError::Invalid(format!("{what} is not a valid pubkey: {value}")))
+}
@@ -1,37 +1,97 @@
-use axum::{routing::{get, post, put, delete}, Router, extract::{State, Path}, Json};
+use axum::{routing::{get, post, put, delete}, Router, extract::State};
 use std::{net::SocketAddr};
 use serde::Deserialize;
 use anyhow::Result;
-use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::Transaction;
+use base64::{engine::general_purpose::STANDARD, Engine};
 
-use crate::{services::manager::PositionManager, db::repo::PgRepo, models::{OpenPositionInput, ModifyAction, PositionView}};
+use crate::{config::SigningMode, services::{manager::PositionManager, reconciler::Reconciler}, db::SharedRepo, models::{OperationId, OpenPositionInput, Scope, ModifyAction, PositionState, PositionView, TransferCollateralInput, TransferPositionInput, AutoTopUpInput, CloseAllInput}};
+use super::{auth::{self, Auth, Caller}, error::{parse_pubkey, ApiError, Json, Path, Query}, ws::{ws_handler, WsHub}};
+
+type ApiResult = Result<Json<serde_json::Value>, ApiError>;
 
 #[derive(Clone)]
 pub struct AppState {
     pub manager: PositionManager,
-    pub repo: PgRepo,
+    pub repo: SharedRepo,
+    pub reconciler: Reconciler,
+    // Wallet: open/modify/close return unsigned transactions for the owner, sent back via /tx/submit
+    pub signing: SigningMode,
+    pub auth: Auth,
+    // Fed by the monitor and the indexer, served at /ws
+    pub hub: WsHub,
 }
 
-pub async fn start_http_server(addr: String, manager: PositionManager, repo: PgRepo) -> Result<()> {
-    let state = AppState { manager, repo };
-    let app = Router::new()
+pub async fn start_http_server(addr: String, state: AppState) -> Result<()> {
+    let app = router(state);
+    let addr: SocketAddr = addr.parse()?;
+    tracing::info!("HTTP listening on {}", addr);
+    axum::Server::bind(&addr).serve(app.into_make_service()).await?;
+    Ok(())
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
         .route("/health", get(|| async { "ok" }))
         .route("/positions/open", post(open_position))
         .route("/positions/:id/modify", put(modify_position))
         .route("/positions/:id/close", delete(close_position))
+        .route("/positions/:id/transfer", post(transfer_position))
+        .route("/positions/:id/auto-top-up", put(set_auto_top_up).delete(disable_auto_top_up))
         .route("/positions/:id", get(get_position))
         .route("/users/:owner/positions", get(list_positions))
-        .with_state(state);
+        .route("/users/:owner/positions/close-all", post(close_all_positions))
+        .route("/users/:owner/sub_accounts", get(list_sub_accounts))
+        .route("/users/:owner/sub_accounts/transfer", post(transfer_collateral))
+        .route("/users/:owner/sub_accounts/:sub_id/positions", get(list_sub_account_positions))
+        .route("/tx/submit", post(submit_transaction))
+        .route("/auth/nonce", post(auth::nonce))
+        .route("/auth/session", post(auth::session).delete(auth::end_session))
+        .route("/auth/delegates", get(auth::list_delegates).post(auth::add_delegate))
+        .route("/auth/delegates/:delegate", delete(auth::remove_delegate))
+        .route("/auth/api-keys", get(auth::list_api_keys).post(auth::create_key))
+        .route("/auth/api-keys/:id", delete(auth::revoke_key))
+        .route("/admin/reconcile", get(latest_reconcile).post(run_reconcile))
+        .route("/ws", get(ws_handler))
+        .with_state(state)
+}
 
-    let addr: SocketAddr = addr.parse()?;
-    tracing::info!("HTTP listening on {}", addr);
-    axum::Server::bind(&addr).serve(app.into_make_service()).await?;
-    Ok(())
+#[derive(Deserialize)]
+struct OpenReq {
+    // Required in wallet mode: the wallet that will sign
+    owner: Option<String>,
+    #[serde(flatten)]
+    input: OpenPositionInput,
+}
+
+async fn open_position(State(st): State<AppState>, caller: Caller, Json(req): Json<OpenReq>) -> ApiResult {
+    if st.signing == SigningMode::Wallet {
+        let owner = req.owner.ok_or_else(|| ApiError::Invalid("owner is required in wallet mode".into()))?;
+        let owner = parse_pubkey(&owner, "owner")?;
+        caller.authorize(&st.auth, &owner, Scope::Trade).await?;
+        let prepared = st.manager.prepare_open(owner, req.input).await?;
+        return Ok(Json(serde_json::json!(prepared)));
+    }
+    caller.authorize(&st.auth, &st.manager.signer(), Scope::Trade).await?;
+    let receipt = st.manager.open_position(req.input).await?;
+    Ok(Json(serde_json::json!(receipt)))
+}
+
+// The position at `id`; 404 if the database has no such position
+async fn position_at(st: &AppState, id: &str) -> Result<PositionView, ApiError> {
+    let pda = parse_pubkey(id, "position id")?;
+    st.repo.fetch_position_view(&pda).await?.ok_or_else(|| ApiError::NotFound(format!("position {id}")))
 }
 
-async fn open_position(State(st): State<AppState>, Json(input): Json<OpenPositionInput>) -> Json<serde_json::Value> {
-    let pda = st.manager.open_position(input).await.unwrap();
-    Json(serde_json::json!({ "position_pda": pda.to_string(), "signature": null }))
+// The position at `id`, which `caller` has to be allowed to act on with `scope`, and which has
+// to be open to start another operation on it (409 while one is in flight, or once it's closed)
+async fn open_position_at(st: &AppState, caller: &Caller, id: &str, scope: Scope) -> Result<PositionView, ApiError> {
+    let pos = position_at(st, id).await?;
+    caller.authorize(&st.auth, &pos.owner, scope).await?;
+    if pos.state != PositionState::Open {
+        return Err(ApiError::Conflict(format!("position {id} is {}", format!("{:?}", pos.state).to_lowercase())));
+    }
+    Ok(pos)
 }
 
 #[derive(Deserialize)]
@@ -43,10 +103,9 @@ enum ModifyReq {
     RemoveMargin { amount: u64, price: u64 },
 }
 
-async fn modify_position(State(st): State<AppState>, Path(id): Path<String>, Json(req): Json<ModifyReq>) -> Json<serde_json::Value> {
+async fn modify_position(State(st): State<AppState>, caller: Caller, Path(id): Path<String>, Json(req): Json<ModifyReq>) -> ApiResult {
     // Look up owner+symbol from DB using PDA, then call manager.modify_position
-    let pda = id.parse::<Pubkey>().unwrap();
-    let pos = st.repo.fetch_position_view(&pda).await.unwrap().expect("position not found");
+    let pos = open_position_at(&st, &caller, &id, Scope::Trade).await?;
     let owner = pos.owner;
     let symbol = pos.symbol.clone();
 
@@ -57,28 +116,113 @@ async fn modify_position(State(st): State<AppState>, Path(id): Path<String>, Jso
         ModifyReq::RemoveMargin{ amount, price } => ModifyAction::RemoveMargin{ amount, price },
     };
 
-    st.manager.modify_position(owner, &symbol, action).await.unwrap();
-    Json(serde_json::json!({ "ok": true, "signature": null }))
+    if st.signing == SigningMode::Wallet {
+        let prepared = st.manager.prepare_modify(owner, pos.sub_id, &symbol, action).await?;
+        return Ok(Json(serde_json::json!(prepared)));
+    }
+    let receipt = st.manager.modify_position(owner, pos.sub_id, &symbol, action).await?;
+    Ok(Json(serde_json::json!({ "ok": true, "signature": receipt.signature, "slot": receipt.slot, "position": receipt.position })))
+}
+
+#[derive(Deserialize)]
+struct CloseReq { exit_price: u64 }
+
+async fn close_position(State(st): State<AppState>, caller: Caller, Path(id): Path<String>, Json(req): Json<CloseReq>) -> ApiResult {
+    let pos = open_position_at(&st, &caller, &id, Scope::Trade).await?;
+    if st.signing == SigningMode::Wallet {
+        let prepared = st.manager.prepare_close(pos.owner, pos.sub_id, &pos.symbol, req.exit_price).await?;
+        return Ok(Json(serde_json::json!(prepared)));
+    }
+    let receipt = st.manager.close_position(pos.owner, pos.sub_id, &pos.symbol, req.exit_price).await?;
+    Ok(Json(serde_json::json!({ "ok": true, "signature": receipt.signature, "slot": receipt.slot, "closed": receipt.closed })))
 }
 
 #[derive(Deserialize)]
-struct CloseReq { exit_price: u64, funding_payment: i64 }
+struct SubmitReq { operation_id: OperationId, transaction: String }
 
-async fn close_position(State(st): State<AppState>, Path(id): Path<String>, Json(req): Json<CloseReq>) -> Json<serde_json::Value> {
-    let pda = id.parse::<Pubkey>().unwrap();
-    let pos = st.repo.fetch_position_view(&pda).await.unwrap().expect("position not found");
-    st.manager.close_position(pos.owner, &pos.symbol, req.exit_price, req.funding_payment).await.unwrap();
-    Json(serde_json::json!({ "ok": true, "signature": null, "payout": null }))
+// A transaction prepared by open/modify/close in wallet mode, signed by the owner's wallet
+// (base64 of the serialized transaction, as handed out)
+async fn submit_transaction(State(st): State<AppState>, caller: Caller, Json(req): Json<SubmitReq>) -> ApiResult {
+    let op = st.repo.fetch_operation(req.operation_id).await?.ok_or_else(|| ApiError::NotFound(format!("operation {}", req.operation_id)))?;
+    caller.authorize(&st.auth, &op.owner, Scope::Trade).await?;
+    let bytes = STANDARD.decode(&req.transaction).map_err(|e| ApiError::Invalid(format!("transaction is not base64: {e}")))?;
+    let tx: Transaction = bincode::deserialize(&bytes).map_err(|e| ApiError::Invalid(format!("transaction doesn't decode: {e}")))?;
+    let receipt = st.manager.submit_signed(req.operation_id, &tx).await?;
+    Ok(Json(serde_json::json!(receipt)))
 }
 
-async fn get_position(State(st): State<AppState>, Path(id): Path<String>) -> Json<serde_json::Value> {
-    let pda = id.parse::<Pubkey>().unwrap();
-    let pos: Option<PositionView> = st.repo.fetch_position_view(&pda).await.unwrap();
-    Json(serde_json::json!({ "position": pos }))
+async fn transfer_position(State(st): State<AppState>, caller: Caller, Path(id): Path<String>, Json(req): Json<TransferPositionInput>) -> ApiResult {
+    let pos = open_position_at(&st, &caller, &id, Scope::Transfer).await?;
+    let receipt = st.manager.transfer_position(pos.owner, pos.sub_id, &pos.symbol, req).await?;
+    Ok(Json(serde_json::json!({ "ok": true, "position_pda": receipt.position_pda.to_string(), "signature": receipt.signature })))
 }
 
-async fn list_positions(State(st): State<AppState>, Path(owner): Path<String>) -> Json<serde_json::Value> {
-    let owner = owner.parse::<Pubkey>().unwrap();
-    let res = st.manager.list_positions_by_user(owner).await.unwrap_or_default();
-    Json(serde_json::json!({ "positions": res }))
-}
\ No newline at end of file
+async fn set_auto_top_up(State(st): State<AppState>, caller: Caller, Path(id): Path<String>, Json(req): Json<AutoTopUpInput>) -> ApiResult {
+    let pos = open_position_at(&st, &caller, &id, Scope::Trade).await?;
+    let confirmed = st.manager.set_auto_top_up(pos.owner, pos.sub_id, &pos.symbol, req).await?;
+    Ok(Json(serde_json::json!({ "ok": true, "signature": confirmed.signature.to_string() })))
+}
+
+async fn disable_auto_top_up(State(st): State<AppState>, caller: Caller, Path(id): Path<String>) -> ApiResult {
+    let pos = open_position_at(&st, &caller, &id, Scope::Trade).await?;
+    let confirmed = st.manager.disable_auto_top_up(pos.owner, pos.sub_id, &pos.symbol).await?;
+    Ok(Json(serde_json::json!({ "ok": true, "signature": confirmed.signature.to_string() })))
+}
+
+async fn close_all_positions(State(st): State<AppState>, caller: Caller, Path(owner): Path<String>, Json(req): Json<CloseAllInput>) -> ApiResult {
+    let owner = parse_pubkey(&owner, "owner")?;
+    caller.authorize(&st.auth, &owner, Scope::Trade).await?;
+    let receipt = st.manager.close_all_positions(owner, req).await?;
+    let positions: Vec<String> = receipt.positions.iter().map(|p| p.to_string()).collect();
+    Ok(Json(serde_json::json!({ "ok": true, "positions": positions, "signature": receipt.signature })))
+}
+
+// 200 with null for an unknown position, as before
+async fn get_position(State(st): State<AppState>, Path(id): Path<String>) -> ApiResult {
+    let pda = parse_pubkey(&id, "position id")?;
+    let pos: Option<PositionView> = st.repo.fetch_position_view(&pda).await?;
+    Ok(Json(serde_json::json!({ "position": pos })))
+}
+
+#[derive(Deserialize)]
+struct SubAccountQuery { sub_id: Option<u16> }
+
+async fn list_positions(State(st): State<AppState>, caller: Caller, Path(owner): Path<String>, Query(q): Query<SubAccountQuery>) -> ApiResult {
+    let owner = parse_pubkey(&owner, "owner")?;
+    caller.authorize(&st.auth, &owner, Scope::Read).await?;
+    let res = st.manager.list_positions_by_user(owner, q.sub_id).await?;
+    Ok(Json(serde_json::json!({ "positions": res })))
+}
+
+async fn list_sub_account_positions(State(st): State<AppState>, caller: Caller, Path((owner, sub_id)): Path<(String, u16)>) -> ApiResult {
+    let owner = parse_pubkey(&owner, "owner")?;
+    caller.authorize(&st.auth, &owner, Scope::Read).await?;
+    let res = st.manager.list_positions_by_user(owner, Some(sub_id)).await?;
+    Ok(Json(serde_json::json!({ "sub_id": sub_id, "positions": res })))
+}
+
+async fn list_sub_accounts(State(st): State<AppState>, caller: Caller, Path(owner): Path<String>) -> ApiResult {
+    let owner = parse_pubkey(&owner, "owner")?;
+    caller.authorize(&st.auth, &owner, Scope::Read).await?;
+    let res = st.manager.list_sub_accounts(owner).await?;
+    Ok(Json(serde_json::json!({ "sub_accounts": res })))
+}
+
+async fn transfer_collateral(State(st): State<AppState>, caller: Caller, Path(owner): Path<String>, Json(req): Json<TransferCollateralInput>) -> ApiResult {
+    let owner = parse_pubkey(&owner, "owner")?;
+    caller.authorize(&st.auth, &owner, Scope::Transfer).await?;
+    let confirmed = st.manager.transfer_collateral(owner, req).await?;
+    Ok(Json(serde_json::json!({ "ok": true, "signature": confirmed.signature.to_string() })))
+}
+
+// The last reconciler run: every drift found, whether it was repaired, and the run's counts
+async fn latest_reconcile(State(st): State<AppState>, caller: Caller) -> ApiResult {
+    caller.admin()?;
+    Ok(Json(serde_json::json!({ "report": st.reconciler.latest().await })))
+}
+
+async fn run_reconcile(State(st): State<AppState>, caller: Caller) -> ApiResult {
+    caller.admin()?;
+    let report = st.reconciler.reconcile_once().await?;
+    Ok(Json(serde_json::json!({ "report": report })))
+}
@@ -1,2 +1,4 @@
+pub mod auth;
+pub mod error;
 pub mod http;
 pub mod ws;
@@ -1,23 +1,144 @@
-use axum::{extract::{Query, State, WebSocketUpgrade}, response::IntoResponse};
-use futures::{StreamExt};
-use serde::Deserialize;
-use tokio::sync::broadcast;
+use std::{sync::Arc, time::Duration};
+use axum::{
+    extract::{ws::{CloseFrame, Message, WebSocket}, State, WebSocketUpgrade},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, info};
 
+use super::{
+    auth::Caller,
+    error::{parse_pubkey, ApiError, Query},
+    http::AppState,
+};
+use crate::models::{ChainChange, PositionView, Scope};
+
+// Messages a connection may fall behind by before it is dropped as a slow consumer (default)
+const HUB_CAPACITY: usize = 1024;
+// A client that hasn't answered (or sent anything) for two pings is gone
+const PING_INTERVAL: Duration = Duration::from_secs(20);
+// How long one send may wait on the client's socket
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+// Close code for slow consumers (RFC 6455 1013, "try again later")
+const CLOSE_TOO_SLOW: u16 = 1013;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Stream {
+    // Position rows as the indexer left them
+    #[serde(rename = "positions.update")]
+    Positions,
+    // Mark price, uPnL and margin ratio from each monitor scan
+    #[serde(rename = "pnl.update")]
+    Pnl,
+    // Margin ratio below RISK_ALERT_THRESHOLD
+    #[serde(rename = "alerts.margin")]
+    Alerts,
+    // A program event applied by the indexer
+    #[serde(rename = "position.event")]
+    Events,
+}
+
+impl Stream {
+    const ALL: [Stream; 4] = [Stream::Positions, Stream::Pnl, Stream::Alerts, Stream::Events];
+
+    // Its name in ?streams=
+    fn name(self) -> &'static str {
+        match self {
+            Stream::Positions => "positions",
+            Stream::Pnl => "pnl",
+            Stream::Alerts => "alerts",
+            Stream::Events => "events",
+        }
+    }
+}
+
+// What /ws sends, one JSON text message each: { type, owner, position, ts, data }
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Serialize)]
+pub struct Envelope {
+    #[serde(rename = "type")]
+    pub stream: Stream,
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    pub owner: Pubkey,
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    pub position: Pubkey,
+    pub ts: DateTime<Utc>,
+    pub data: serde_json::Value,
+}
+
+// Fan-out from the monitor and the indexer to /ws connections. Publishing never waits: a
+// connection that falls HUB_CAPACITY messages behind is disconnected instead.
 #[derive(Clone)]
 pub struct WsHub {
-    pub positions_tx: broadcast::Sender<serde_json::Value>,
-    pub pnl_tx: broadcast::Sender<serde_json::Value>,
-    pub alerts_tx: broadcast::Sender<serde_json::Value>,
-    pub events_tx: broadcast::Sender<serde_json::Value>,
+    tx: broadcast::Sender<Arc<Envelope>>,
+}
+
+impl Default for WsHub {
+    fn default() -> Self {
+        Self::new()
+    }
 }
+
 impl WsHub {
     pub fn new() -> Self {
-        let (a,_)=broadcast::channel(1024);
-        let (b,_)=broadcast::channel(1024);
-        let (c,_)=broadcast::channel(1024);
-        let (d,_)=broadcast::channel(1024);
-        Self { positions_tx:a, pnl_tx:b, alerts_tx:c, events_tx:d }
+        Self::with_capacity(HUB_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { tx: broadcast::channel(capacity).0 }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Envelope>> {
+        self.tx.subscribe()
+    }
+
+    pub fn publish(&self, stream: Stream, p: &PositionView, data: serde_json::Value) {
+        let envelope = Envelope { stream, owner: p.owner, position: p.pda, ts: Utc::now(), data };
+        // An error only means nobody is connected
+        let _ = self.tx.send(Arc::new(envelope));
     }
+
+    pub fn position_update(&self, p: &PositionView) {
+        self.publish(Stream::Positions, p, serde_json::json!(p));
+    }
+
+    pub fn pnl_update(&self, p: &PositionView, mark_price: u64, unrealized_pnl: i64, margin_ratio: f64) {
+        self.publish(Stream::Pnl, p, serde_json::json!({
+            "symbol": p.symbol,
+            "mark_price": mark_price,
+            "unrealized_pnl": unrealized_pnl,
+            "margin_ratio": finite(margin_ratio),
+        }));
+    }
+
+    pub fn margin_alert(&self, p: &PositionView, mark_price: u64, margin_ratio: f64) {
+        self.publish(Stream::Alerts, p, serde_json::json!({
+            "symbol": p.symbol,
+            "mark_price": mark_price,
+            "margin_ratio": finite(margin_ratio),
+            "liquidation_price": p.liquidation_price,
+        }));
+    }
+
+    // `p` is the position the change applies to, as it is after it
+    pub fn position_event(&self, p: &PositionView, slot: u64, signature: &str, change: &ChainChange) {
+        self.publish(Stream::Events, p, serde_json::json!({
+            "slot": slot,
+            "signature": signature,
+            "event_index": change.event_index,
+            "event": change.update,
+        }));
+    }
+}
+
+// JSON has no infinity (a position without notional); send null
+fn finite(ratio: f64) -> Option<f64> {
+    ratio.is_finite().then_some(ratio)
 }
 
 #[derive(Deserialize)]
@@ -25,69 +146,111 @@ pub struct WsParams {
     owner: Option<String>,
     position: Option<String>,
     streams: Option<String>,
+    // A session token, for browsers, which can't set headers on a WebSocket
+    token: Option<String>,
+}
+
+// Which envelopes a connection gets
+struct Filter {
+    streams: Vec<Stream>,
+    owner: Option<Pubkey>,
+    position: Option<Pubkey>,
+}
+
+impl Filter {
+    fn matches(&self, e: &Envelope) -> bool {
+        self.streams.contains(&e.stream)
+            && self.owner.is_none_or(|owner| owner == e.owner)
+            && self.position.is_none_or(|position| position == e.position)
+    }
 }
 
+// GET /ws?owner=&position=&streams=positions,pnl,alerts,events. With AUTH required the caller
+// must name an owner (or a position) it may read, like GET /users/:owner/positions.
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
-    State(hub): State<WsHub>,
+    State(st): State<AppState>,
+    headers: HeaderMap,
     Query(params): Query<WsParams>,
-) -> impl IntoResponse {
-    ws.on_upgrade(move |mut socket| async move {
-        let streams = params.streams.clone().unwrap_or_else(|| "positions,pnl,alerts,events".into());
-        let subs: Vec<&str> = streams.split(',').map(|s| s.trim()).collect();
-
-        let mut pos_rx = if subs.contains(&"positions") { Some(hub.positions_tx.subscribe()) } else { None };
-        let mut pnl_rx = if subs.contains(&"pnl") { Some(hub.pnl_tx.subscribe()) } else { None };
-        let mut alerts_rx = if subs.contains(&"alerts") { Some(hub.alerts_tx.subscribe()) } else { None };
-        let mut events_rx = if subs.contains(&"events") { Some(hub.events_tx.subscribe()) } else { None };
-
-        loop {
-            tokio::select! {
-                biased;
-
-                // You can filter by owner/position on publish or here on receive by inspecting message.data
-                msg = async {
-                    match pos_rx.as_mut() {
-                        Some(rx) => Some(rx.recv().await),
-                        None => None,
-                    }
-                }, if pos_rx.is_some() => {
-                    if let Some(Ok(msg)) = msg {
-                        let _ = socket.send(axum::extract::ws::Message::Text(msg.to_string())).await;
-                    }
-                },
-                msg = async {
-                    match pnl_rx.as_mut() {
-                        Some(rx) => Some(rx.recv().await),
-                        None => None,
-                    }
-                }, if pnl_rx.is_some() => {
-                    if let Some(Ok(msg)) = msg {
-                        let _ = socket.send(axum::extract::ws::Message::Text(msg.to_string())).await;
-                    }
-                },
-                msg = async {
-                    match alerts_rx.as_mut() {
-                        Some(rx) => Some(rx.recv().await),
-                        None => None,
-                    }
-                }, if alerts_rx.is_some() => {
-                    if let Some(Ok(msg)) = msg {
-                        let _ = socket.send(axum::extract::ws::Message::Text(msg.to_string())).await;
-                    }
-                },
-                msg = async {
-                    match events_rx.as_mut() {
-                        Some(rx) => Some(rx.recv().await),
-                        None => None,
-                    }
-                }, if events_rx.is_some() => {
-                    if let Some(Ok(msg)) = msg {
-                        let _ = socket.send(axum::extract::ws::Message::Text(msg.to_string())).await;
-                    }
+) -> Result<Response, ApiError> {
+    let streams = match &params.streams {
+        None => Stream::ALL.to_vec(),
+        Some(names) => names
+            .split(',')
+            .map(str::trim)
+            .map(|name| Stream::ALL.into_iter().find(|s| s.name() == name).ok_or_else(|| ApiError::Invalid(format!("unknown stream {name}"))))
+            .collect::<Result<_, _>>()?,
+    };
+    let owner = params.owner.as_deref().map(|o| parse_pubkey(o, "owner")).transpose()?;
+    let position = params.position.as_deref().map(|p| parse_pubkey(p, "position")).transpose()?;
+
+    let caller = match &params.token {
+        Some(token) => st.auth.session(token)?,
+        None => st.auth.caller(&headers).await?,
+    };
+    // Whose messages these are: the owner named, else the position's
+    let owner_of = match (owner, position) {
+        (Some(owner), _) => Some(owner),
+        (None, Some(position)) => st.repo.fetch_position_view(&position).await?.map(|p| p.owner),
+        (None, None) => None,
+    };
+    match (&caller, owner_of) {
+        (Caller::Anyone, _) => {}
+        (_, Some(owner)) => caller.authorize(&st.auth, &owner, Scope::Read).await?,
+        (_, None) => return Err(ApiError::Forbidden("subscribe with ?owner= or ?position=".into())),
+    }
+
+    let filter = Filter { streams, owner, position };
+    let rx = st.hub.subscribe();
+    Ok(ws.on_upgrade(move |socket| serve(socket, rx, filter)).into_response())
+}
+
+async fn serve(socket: WebSocket, mut rx: broadcast::Receiver<Arc<Envelope>>, filter: Filter) {
+    let (mut sink, mut incoming) = socket.split();
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+    // Pings sent since the client was last heard from
+    let mut unanswered = 0;
+
+    let reason = loop {
+        let message = tokio::select! {
+            received = rx.recv() => match received {
+                Ok(envelope) if filter.matches(&envelope) => match serde_json::to_string(&*envelope) {
+                    Ok(text) => Message::Text(text),
+                    Err(e) => break format!("encoding failed: {e}"),
                 },
-                else => break,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    let close = CloseFrame { code: CLOSE_TOO_SLOW, reason: format!("too slow, {missed} messages dropped").into() };
+                    let _ = tokio::time::timeout(SEND_TIMEOUT, sink.send(Message::Close(Some(close)))).await;
+                    break format!("slow consumer, {missed} messages behind");
+                }
+                Err(RecvError::Closed) => break "hub closed".to_string(),
+            },
+            _ = ping.tick() => {
+                if unanswered >= 2 {
+                    break "no pong".to_string();
+                }
+                unanswered += 1;
+                Message::Ping(vec![])
             }
+            frame = incoming.next() => match frame {
+                Some(Ok(Message::Close(_))) | None => break "closed by client".to_string(),
+                Some(Ok(_)) => {
+                    unanswered = 0;
+                    continue;
+                }
+                Some(Err(e)) => break format!("read failed: {e}"),
+            },
+        };
+        match tokio::time::timeout(SEND_TIMEOUT, sink.send(message)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => break format!("send failed: {e}"),
+            Err(_) => break "slow consumer, send timed out".to_string(),
         }
-    })
-}
\ No newline at end of file
+    };
+    if reason.starts_with("slow consumer") {
+        info!("ws disconnected: {reason}");
+    } else {
+        debug!("ws disconnected: {reason}");
+    }
+}
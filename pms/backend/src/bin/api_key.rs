@@ -0,0 +1,36 @@
+use anyhow::{bail, Result};
+use position_service::{api::auth::create_api_key, db, models::Scope};
+use solana_sdk::pubkey::Pubkey;
+
+// Creates an API key for OWNER straight in DATABASE_URL, for owners that can't sign in with a
+// wallet to do it (the service's own key, when it sits behind a remote signer). Prints the key,
+// which isn't stored and can't be shown again.
+//
+//   api_key <owner> <scope,scope,...> [label]    scopes: read, trade, transfer, admin
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (owner, scopes, label) = match args.as_slice() {
+        [owner, scopes] => (owner, scopes, None),
+        [owner, scopes, label] => (owner, scopes, Some(label.as_str())),
+        _ => bail!("usage: api_key <owner> <scope,scope,...> [label]"),
+    };
+    let owner = owner.parse::<Pubkey>()?;
+    let scopes = scopes
+        .split(',')
+        .map(|s| serde_json::from_value::<Scope>(serde_json::Value::String(s.trim().to_string())))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| anyhow::anyhow!("scopes are read, trade, transfer and admin, comma separated"))?;
+    let program_id = match std::env::var("PROGRAM_ID") {
+        Ok(id) => id.parse::<Pubkey>()?,
+        Err(_) => perp_sdk::PROGRAM_ID,
+    };
+
+    let repo = db::connect(&std::env::var("DATABASE_URL")?, program_id).await?;
+    repo.migrate().await?;
+    let (id, key) = create_api_key(&repo, &owner, &scopes, label).await?;
+    println!("API key {id} for {owner}: {key}");
+    Ok(())
+}
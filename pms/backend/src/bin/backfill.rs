@@ -0,0 +1,51 @@
+use std::sync::Arc;
+use anyhow::{bail, Result};
+use position_service::db;
+use position_service::services::{
+    backfill::{Backfill, BackfillStart},
+    indexer::{ChainSource, RecordedSource, RpcSource},
+};
+use solana_sdk::pubkey::Pubkey;
+
+// Replays the program's history into DATABASE_URL, from a slot or transaction signature up to
+// where the live indexer took over (or the newest transaction, on an empty database). Safe to
+// run again or while the service runs: the live indexer waits for the first run to reach
+// where it took over, and changes already applied are skipped. With nothing to replay, start
+// after the newest slot to let the live indexer go.
+//
+//   backfill <slot | signature>                   read transactions from RPC_URL
+//   backfill <slot | signature> --recorded <dir>  read getTransaction results saved as *.json
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    tracing_subscriber::fmt().with_env_filter(tracing_subscriber::EnvFilter::from_default_env()).init();
+
+    let database_url = std::env::var("DATABASE_URL").map_err(|_| anyhow::anyhow!("set DATABASE_URL"))?;
+    let program_id = match std::env::var("PROGRAM_ID") {
+        Ok(id) => id.parse::<Pubkey>()?,
+        Err(_) => perp_sdk::PROGRAM_ID,
+    };
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let (start, source): (BackfillStart, Arc<dyn ChainSource>) =
+        match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+            [start] => {
+                let rpc_url = std::env::var("RPC_URL")?;
+                (start.parse()?, Arc::new(RpcSource::new(&rpc_url, program_id)))
+            }
+            [start, "--recorded", dir] => (start.parse()?, Arc::new(RecordedSource::open(dir, program_id)?)),
+            _ => bail!("usage: backfill <slot | signature> [--recorded <dir>]"),
+        };
+
+    let repo = db::connect(&database_url, program_id).await?;
+    repo.migrate().await?;
+
+    let report = Backfill::new(repo, source, program_id).run(&start).await?;
+    println!("✅ Replayed {} transactions, {} position changes", report.transactions, report.changes);
+    match report.end {
+        Some(end) => println!("📋 Live indexer continues after {} (slot {})", end.signature, end.slot),
+        None => println!("📋 Program has no transactions"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use std::net::SocketAddr;
+use anyhow::{anyhow, Result};
+use position_service::signing_service::{signing_service, SigningPolicy, SERVICE_INSTRUCTIONS};
+use position_service::solana::signer::keypair_from_base58;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Signer},
+};
+
+// Reference signing service for SIGNER=remote: holds the key so the API host doesn't, and signs
+// only what its allow-list permits. Run it on a separate host (or at least as a separate user).
+//
+//   SIGNER_KEYPAIR=<keypair file> or SIGNER_SECRET=<base58 secret key>
+//   SIGNER_ADDR (default 127.0.0.1:8091)
+//   SIGNER_TOKEN: bearer token callers must send (recommended anywhere but localhost)
+//   SIGNER_ALLOW: "PROGRAM:instruction,...;PROGRAM:..." (default: PROGRAM_ID with the
+//                 instructions the position service sends, except transfers)
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    tracing_subscriber::fmt().with_env_filter(tracing_subscriber::EnvFilter::from_default_env()).init();
+
+    let keypair = match (std::env::var("SIGNER_KEYPAIR"), std::env::var("SIGNER_SECRET")) {
+        (Ok(path), _) => read_keypair_file(&path).map_err(|e| anyhow!("failed to read keypair {path}: {e}"))?,
+        (_, Ok(secret)) => keypair_from_base58(&secret)?,
+        _ => return Err(anyhow!("set SIGNER_KEYPAIR or SIGNER_SECRET")),
+    };
+    let policy = match std::env::var("SIGNER_ALLOW") {
+        Ok(spec) => SigningPolicy::parse(&spec)?,
+        Err(_) => SigningPolicy::default().allow(std::env::var("PROGRAM_ID")?.parse::<Pubkey>()?, SERVICE_INSTRUCTIONS),
+    };
+    let token = std::env::var("SIGNER_TOKEN").ok();
+    if token.is_none() {
+        tracing::warn!("SIGNER_TOKEN not set: anyone who can reach the signer can ask it to sign");
+    }
+
+    let addr: SocketAddr = std::env::var("SIGNER_ADDR").unwrap_or_else(|_| "127.0.0.1:8091".to_string()).parse()?;
+    tracing::info!("signing as {} on {addr}", keypair.pubkey());
+    axum::Server::bind(&addr).serve(signing_service(keypair, policy, token).into_make_service()).await?;
+    Ok(())
+}
@@ -1,54 +1,22 @@
 use anyhow::Result;
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use position_service::db::{PositionRepo, SqliteRepo};
 
+// Creates (or upgrades) a local SQLite database with the service schema.
+// Run the service against it with DATABASE_URL=sqlite:rust_trading.db
 #[tokio::main]
 async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    let url = std::env::args().nth(1).unwrap_or_else(|| "sqlite:rust_trading.db".to_string());
+
     println!("🔧 Setting up SQLite database...");
-    
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect("sqlite:rust_trading.db")
-        .await?;
-    
-    println!("✅ SQLite database created: rust_trading.db");
-    
-    // Create basic schema
-    sqlx::query(r#"
-        CREATE TABLE IF NOT EXISTS positions (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            owner TEXT NOT NULL,
-            symbol TEXT NOT NULL,
-            side TEXT NOT NULL,
-            size INTEGER NOT NULL,
-            entry_price INTEGER NOT NULL,
-            margin INTEGER NOT NULL,
-            leverage INTEGER NOT NULL,
-            state TEXT NOT NULL DEFAULT 'open',
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-        )
-    "#).execute(&pool).await?;
-    
+    let repo = SqliteRepo::connect(&url).await?;
+    repo.migrate().await?;
     println!("✅ Schema created successfully!");
-    
-    // Test insert
-    sqlx::query("INSERT INTO positions (owner, symbol, side, size, entry_price, margin, leverage) VALUES (?, ?, ?, ?, ?, ?, ?)")
-        .bind("test_owner")
-        .bind("BTC/USD")
-        .bind("long")
-        .bind(100)
-        .bind(50000)
-        .bind(1000)
-        .bind(10)
-        .execute(&pool).await?;
-    
-    // Test query
-    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM positions")
-        .fetch_one(&pool).await?;
-    
-    println!("✅ Test record inserted! Total positions: {}", count);
-    
-    pool.close().await;
-    println!("✅ SQLite database is ready to use!");
-    
+
+    let tables: Vec<String> = sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")
+        .fetch_all(repo.pool())
+        .await?;
+    println!("✅ SQLite database is ready to use: {} ({})", url, tables.join(", "));
+
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,334 @@
+pub mod repo;
+mod rows;
+pub mod sqlite;
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::models::{
+    ApiKey, ApiKeyId, AutoTopUpInput, BatchCloseLeg, ChainCursor, ChainTx, DriftAccount, MarginMode, ModifyAction,
+    OpenPositionInput, Operation, OperationId, OperationStatus, PositionState, PositionUpdate, PositionView,
+    ReconcileReport, Scope, Side, StoredPosition, SubAccountView, TransferCollateralInput, TransferPositionInput,
+};
+
+pub use repo::PgRepo;
+pub use sqlite::SqliteRepo;
+
+// Storage used by the manager, monitor and HTTP API. PgRepo is the production store;
+// SqliteRepo runs the same schema in a local file (or in memory) for development and CI.
+#[async_trait]
+pub trait PositionRepo: Send + Sync {
+    // Applies pending migrations (migrations/<store>/NNNN_*.up.sql), recording each version
+    // and checksum; fails if an applied migration was edited afterwards
+    async fn migrate(&self) -> Result<()>;
+    // Reverts applied migrations newer than `version`; 0 drops the whole schema
+    async fn rollback(&self, version: i64) -> Result<()>;
+    // Latest applied migration, None for a database that was never migrated
+    async fn schema_version(&self) -> Result<Option<i64>>;
+
+    // Each records an operation (status intent) together with the state it puts the position
+    // in, before anything is sent, and returns the operation's id
+    async fn insert_position_open_intent(&self, owner: &Pubkey, position_pda: &Pubkey, input: &OpenPositionInput) -> Result<OperationId>;
+    async fn insert_position_modify_intent(&self, owner: &Pubkey, position_pda: &Pubkey, action: &ModifyAction) -> Result<OperationId>;
+    async fn insert_position_close_intent(&self, owner: &Pubkey, position_pda: &Pubkey, exit_price: u64) -> Result<OperationId>;
+    async fn insert_collateral_transfer_intent(&self, owner: &Pubkey, from_user: &Pubkey, to_user: &Pubkey, input: &TransferCollateralInput) -> Result<OperationId>;
+    async fn insert_position_transfer_intent(&self, owner: &Pubkey, from_pda: &Pubkey, to_pda: &Pubkey, input: &TransferPositionInput) -> Result<OperationId>;
+    async fn upsert_auto_top_up_intent(&self, owner: &Pubkey, position_pda: &Pubkey, input: Option<&AutoTopUpInput>) -> Result<OperationId>;
+    // None, recording nothing, while an earlier auto_top_up of the position is still in flight
+    async fn insert_auto_top_up_intent(&self, keeper: &Pubkey, position_pda: &Pubkey, mark_price: u64) -> Result<Option<OperationId>>;
+    async fn insert_batch_close_intent(&self, owner: &Pubkey, user_pda: &Pubkey, reduce_bps: u16, legs: &[BatchCloseLeg]) -> Result<OperationId>;
+
+    // Records the transaction about to be sent for an operation that hasn't finished; a
+    // re-signed transaction replaces the previous (expired) signature
+    async fn mark_submitted(&self, id: OperationId, signature: &str, last_valid_block_height: u64) -> Result<()>;
+    // Stores the unsigned message of an operation handed to the owner's wallet to sign
+    async fn record_prepared(&self, id: OperationId, message: &[u8], last_valid_block_height: u64) -> Result<()>;
+    // Moves an operation to confirmed or failed and, in the same transaction, its positions
+    // out of their in-flight state (see operation_transitions). Returns false, changing
+    // nothing, if the operation had already finished.
+    async fn finish_operation(&self, id: OperationId, status: OperationStatus, error: Option<&str>) -> Result<bool>;
+    async fn fetch_operation(&self, id: OperationId) -> Result<Option<Operation>>;
+    // Operations still in intent or submitted that were created before `before`, oldest first
+    async fn fetch_inflight_operations(&self, before: DateTime<Utc>) -> Result<Vec<Operation>>;
+
+    async fn fetch_position_view(&self, position_pda: &Pubkey) -> Result<Option<PositionView>>;
+    async fn fetch_positions_by_owner(&self, owner: &Pubkey, sub_id: Option<u16>) -> Result<Vec<PositionView>>;
+    async fn fetch_sub_accounts(&self, owner: &Pubkey) -> Result<Vec<SubAccountView>>;
+    async fn fetch_open_positions(&self) -> Result<Vec<PositionView>>;
+
+    async fn insert_liq_alert(&self, p: &PositionView, margin_ratio: f64, price: f64) -> Result<()>;
+    async fn upsert_position_snapshot(&self, p: &PositionView, price: f64, unrealized_pnl: i64, margin_ratio: f64) -> Result<()>;
+
+    // Applies a confirmed transaction's changes and moves `cursor` to it, atomically. Returns
+    // how many changes were new; replaying a transaction applies nothing.
+    async fn apply_chain_tx(&self, cursor: &str, tx: &ChainTx) -> Result<usize>;
+    async fn fetch_cursor(&self, cursor: &str) -> Result<Option<ChainCursor>>;
+
+    // Every positions row and every users row, for comparison with the program's accounts
+    async fn fetch_stored_positions(&self) -> Result<Vec<StoredPosition>>;
+    async fn fetch_all_sub_accounts(&self) -> Result<Vec<SubAccountView>>;
+    // Writes an on-chain position's values as of `slot` (inserting the row if missing, state
+    // open) unless the row was updated from a later slot or an operation on it is in flight
+    // (opening, modifying, closing, liquidating); identity columns are never changed.
+    // Returns whether the row was written.
+    async fn repair_position(&self, chain: &PositionView, slot: u64) -> Result<bool>;
+    async fn repair_sub_account(&self, chain: &SubAccountView) -> Result<()>;
+    // Stores a run's counts in reconcile_runs and each drift in reconcile_drifts
+    async fn record_reconcile(&self, report: &ReconcileReport) -> Result<()>;
+
+    // Delegates act for an owner (api/auth.rs). add/remove return whether anything changed.
+    async fn add_delegate(&self, owner: &Pubkey, delegate: &Pubkey) -> Result<bool>;
+    async fn remove_delegate(&self, owner: &Pubkey, delegate: &Pubkey) -> Result<bool>;
+    async fn is_delegate(&self, owner: &Pubkey, delegate: &Pubkey) -> Result<bool>;
+    async fn fetch_delegates(&self, owner: &Pubkey) -> Result<Vec<Pubkey>>;
+    // API keys are looked up by the SHA-256 of the key; revoked keys are never returned
+    async fn insert_api_key(&self, owner: &Pubkey, key_hash: &[u8], scopes: &[Scope], label: Option<&str>) -> Result<ApiKeyId>;
+    async fn fetch_api_key(&self, key_hash: &[u8]) -> Result<Option<ApiKey>>;
+    async fn fetch_api_keys(&self, owner: &Pubkey) -> Result<Vec<ApiKey>>;
+    async fn revoke_api_key(&self, owner: &Pubkey, id: ApiKeyId) -> Result<bool>;
+}
+
+pub type SharedRepo = Arc<dyn PositionRepo>;
+
+// DATABASE_URL picks the store: sqlite:perp.db (or sqlite::memory:) for SQLite,
+// anything else is handed to Postgres.
+pub async fn connect(url: &str, program_id: Pubkey) -> Result<SharedRepo> {
+    if url.starts_with("sqlite:") {
+        Ok(Arc::new(SqliteRepo::connect(url).await?.with_program_id(program_id)))
+    } else {
+        Ok(Arc::new(PgRepo::connect(url).await?.with_program_id(program_id)))
+    }
+}
+
+// ---------- column encodings shared by both stores ----------
+
+// Amounts are u64 on-chain and BIGINT/INTEGER in the database
+pub(crate) fn to_db(v: u64) -> Result<i64> {
+    Ok(i64::try_from(v)?)
+}
+
+pub(crate) fn from_db(v: i64) -> Result<u64> {
+    Ok(u64::try_from(v)?)
+}
+
+pub(crate) fn key(pubkey: &Pubkey) -> Vec<u8> {
+    pubkey.to_bytes().to_vec()
+}
+
+pub(crate) fn pubkey(bytes: Vec<u8>) -> Result<Pubkey> {
+    Pubkey::try_from(bytes.as_slice()).map_err(|_| anyhow!("invalid pubkey ({} bytes)", bytes.len()))
+}
+
+pub(crate) fn side_str(side: Side) -> &'static str {
+    match side {
+        Side::Long => "long",
+        Side::Short => "short",
+    }
+}
+
+pub(crate) fn parse_side(s: &str) -> Result<Side> {
+    match s {
+        "long" => Ok(Side::Long),
+        "short" => Ok(Side::Short),
+        other => Err(anyhow!("unknown side {other}")),
+    }
+}
+
+pub(crate) fn state_str(state: PositionState) -> &'static str {
+    match state {
+        PositionState::Opening => "opening",
+        PositionState::Open => "open",
+        PositionState::Modifying => "modifying",
+        PositionState::Closing => "closing",
+        PositionState::Closed => "closed",
+        PositionState::Liquidating => "liquidating",
+    }
+}
+
+pub(crate) fn parse_state(s: &str) -> Result<PositionState> {
+    match s {
+        "opening" => Ok(PositionState::Opening),
+        "open" => Ok(PositionState::Open),
+        "modifying" => Ok(PositionState::Modifying),
+        "closing" => Ok(PositionState::Closing),
+        "closed" => Ok(PositionState::Closed),
+        "liquidating" => Ok(PositionState::Liquidating),
+        other => Err(anyhow!("unknown position state {other}")),
+    }
+}
+
+pub(crate) fn margin_mode_str(mode: MarginMode) -> &'static str {
+    match mode {
+        MarginMode::Isolated => "isolated",
+        MarginMode::Cross => "cross",
+    }
+}
+
+pub(crate) fn parse_margin_mode(s: &str) -> Result<MarginMode> {
+    match s {
+        "isolated" => Ok(MarginMode::Isolated),
+        "cross" => Ok(MarginMode::Cross),
+        other => Err(anyhow!("unknown margin mode {other}")),
+    }
+}
+
+pub(crate) fn drift_account_str(account: DriftAccount) -> &'static str {
+    match account {
+        DriftAccount::Position => "position",
+        DriftAccount::User => "user",
+    }
+}
+
+pub(crate) fn operation_status_str(status: OperationStatus) -> &'static str {
+    match status {
+        OperationStatus::Intent => "intent",
+        OperationStatus::Submitted => "submitted",
+        OperationStatus::Confirmed => "confirmed",
+        OperationStatus::Failed => "failed",
+    }
+}
+
+pub(crate) fn parse_operation_status(s: &str) -> Result<OperationStatus> {
+    match s {
+        "intent" => Ok(OperationStatus::Intent),
+        "submitted" => Ok(OperationStatus::Submitted),
+        "confirmed" => Ok(OperationStatus::Confirmed),
+        "failed" => Ok(OperationStatus::Failed),
+        other => Err(anyhow!("unknown operation status {other}")),
+    }
+}
+
+// api_keys.scopes: "read,trade"
+pub(crate) fn scopes_str(scopes: &[Scope]) -> String {
+    let names: Vec<&str> = scopes
+        .iter()
+        .map(|scope| match scope {
+            Scope::Read => "read",
+            Scope::Trade => "trade",
+            Scope::Transfer => "transfer",
+            Scope::Admin => "admin",
+        })
+        .collect();
+    names.join(",")
+}
+
+pub(crate) fn parse_scopes(s: &str) -> Result<Vec<Scope>> {
+    s.split(',')
+        .filter(|name| !name.is_empty())
+        .map(|name| match name {
+            "read" => Ok(Scope::Read),
+            "trade" => Ok(Scope::Trade),
+            "transfer" => Ok(Scope::Transfer),
+            "admin" => Ok(Scope::Admin),
+            other => Err(anyhow!("unknown scope {other}")),
+        })
+        .collect()
+}
+
+// A position an operation put in an in-flight state, and where it goes when the operation
+// finishes. Applied only while the position is still `in_flight`, so a state the indexer (or a
+// later operation) has written since wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Transition {
+    pub pda: Pubkey,
+    pub in_flight: PositionState,
+    pub confirmed: PositionState,
+    pub failed: PositionState,
+}
+
+impl Transition {
+    pub fn to(&self, status: OperationStatus) -> PositionState {
+        if status == OperationStatus::Confirmed { self.confirmed } else { self.failed }
+    }
+}
+
+// The transitions for an operation row, mirroring the states the insert_*_intent methods set.
+// Kinds that leave positions alone (collateral transfers, auto top-up) have none.
+pub(crate) fn operation_transitions(kind: &str, target: &Pubkey, payload: &serde_json::Value) -> Result<Vec<Transition>> {
+    use PositionState::{Closed, Closing, Modifying, Open, Opening};
+    let single = |in_flight, confirmed, failed| vec![Transition { pda: *target, in_flight, confirmed, failed }];
+    Ok(match kind {
+        "open" => single(Opening, Open, Closed),
+        "modify" => single(Modifying, Open, Open),
+        "close" => single(Closing, Closed, Open),
+        "close_all" => {
+            let reduce_bps: u16 = serde_json::from_value(payload["reduce_bps"].clone())?;
+            let legs: Vec<BatchCloseLeg> = serde_json::from_value(payload["legs"].clone())?;
+            let (in_flight, confirmed) = if reduce_bps >= 10_000 { (Closing, Closed) } else { (Modifying, Open) };
+            legs.iter().map(|l| Transition { pda: l.position_pda, in_flight, confirmed, failed: Open }).collect()
+        }
+        _ => vec![],
+    })
+}
+
+// Initial margin recorded with an open intent, before the program reports the real one
+pub(crate) fn intended_margin(input: &OpenPositionInput) -> Result<i64> {
+    let notional = perp_math::notional(input.size, input.entry_price)?;
+    Ok(i64::try_from(perp_math::initial_margin(notional, input.leverage)?)?)
+}
+
+pub(crate) fn equity(p: &PositionView, unrealized_pnl: i64) -> Result<i64> {
+    to_db(p.margin)?.checked_add(unrealized_pnl).ok_or_else(|| anyhow!("equity overflow"))
+}
+
+// A position_modifications row
+pub(crate) struct Modification {
+    pub kind: &'static str,
+    pub base_delta: i64,
+    pub margin_delta: i64,
+    pub price: Option<i64>,
+    pub realized_pnl_delta: i64,
+}
+
+// `before` is (size_base, margin) of the row the change applies to (the source position for
+// a transfer). Only an open can be recorded without one; anything else about a position
+// this database never saw is skipped and left to the reconciler.
+pub(crate) fn modification(update: &PositionUpdate, before: Option<(i64, i64)>) -> Result<Option<Modification>> {
+    let m = match (update, before) {
+        (PositionUpdate::Opened { size, entry_price, margin, .. }, _) => Modification {
+            kind: "open",
+            base_delta: to_db(*size)?,
+            margin_delta: to_db(*margin)?,
+            price: Some(to_db(*entry_price)?),
+            realized_pnl_delta: 0,
+        },
+        (PositionUpdate::Modified { size, margin, price, .. }, Some((size_before, margin_before))) => {
+            let base_delta = to_db(*size)? - size_before;
+            let margin_delta = to_db(*margin)? - margin_before;
+            let kind = match (base_delta.signum(), margin_delta.signum()) {
+                (1, _) => "increase",
+                (-1, _) => "decrease",
+                (_, -1) => "remove_margin",
+                _ => "add_margin",
+            };
+            Modification { kind, base_delta, margin_delta, price: Some(to_db(*price)?), realized_pnl_delta: 0 }
+        }
+        (PositionUpdate::Closed { size_closed, exit_price, realized_pnl }, Some((_, margin_before))) => Modification {
+            kind: "close",
+            base_delta: -to_db(*size_closed)?,
+            margin_delta: -margin_before,
+            price: Some(to_db(*exit_price)?),
+            realized_pnl_delta: *realized_pnl,
+        },
+        (PositionUpdate::ToppedUp { amount, mark_price, .. }, Some(_)) => Modification {
+            kind: "top_up",
+            base_delta: 0,
+            margin_delta: to_db(*amount)?,
+            price: Some(to_db(*mark_price)?),
+            realized_pnl_delta: 0,
+        },
+        (PositionUpdate::Transferred { size, margin, .. }, Some(_)) => Modification {
+            kind: "transfer_in",
+            base_delta: to_db(*size)?,
+            margin_delta: to_db(*margin)?,
+            price: None,
+            realized_pnl_delta: 0,
+        },
+        (_, None) => return Ok(None),
+    };
+    Ok(Some(m))
+}
@@ -0,0 +1,150 @@
+// Row decoding and SELECT lists shared by PgRepo and SqliteRepo. Both stores return the same
+// columns for these queries; only the table prefix (perp. on Postgres) differs.
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+use sqlx::{postgres::PgRow, sqlite::SqliteRow, Decode, Postgres, Row, Sqlite, Type};
+
+use super::{from_db, parse_margin_mode, parse_operation_status, parse_scopes, parse_side, parse_state, pubkey};
+use crate::models::{ApiKey, AutoTopUpView, Operation, PositionView, SubAccountView};
+
+// States the monitor keeps watching: confirmed on-chain and not yet closed
+pub(crate) const LIVE_STATES: &str = "('open', 'modifying', 'closing', 'liquidating')";
+
+// `$schema` is the table prefix: "perp." on Postgres, "" on SQLite
+macro_rules! position_select {
+    ($schema:literal) => {
+        concat!(
+            "SELECT p.pda, p.owner, p.sub_id, p.symbol, p.side, p.size_base, p.entry_price, p.margin, p.leverage, ",
+            "p.unrealized_pnl, p.realized_pnl, p.liquidation_price, p.state, p.updated_at, p.last_slot, ",
+            "t.target_mr, t.max_total, t.used ",
+            "FROM ", $schema, "positions p ",
+            "LEFT JOIN ", $schema, "auto_top_up_settings t ON t.position_pda = p.pda "
+        )
+    };
+}
+
+macro_rules! sub_account_select {
+    ($schema:literal) => {
+        concat!(
+            "SELECT u.owner, u.sub_id, u.margin_mode, u.total_collateral, u.locked_collateral, u.total_realized_pnl, ",
+            "COUNT(p.pda) FILTER (WHERE p.state <> 'closed') AS position_count ",
+            "FROM ", $schema, "users u ",
+            "LEFT JOIN ", $schema, "positions p ON p.owner = u.owner AND p.sub_id = u.sub_id "
+        )
+    };
+}
+
+macro_rules! operation_select {
+    ($schema:literal) => {
+        concat!(
+            "SELECT id, kind, owner, target, status, signature, last_valid_block_height, error, created_at, message ",
+            "FROM ", $schema, "operations "
+        )
+    };
+}
+
+macro_rules! api_key_select {
+    ($schema:literal) => {
+        concat!("SELECT id, owner, scopes, label, created_at FROM ", $schema, "api_keys ")
+    };
+}
+
+pub(crate) use {api_key_select, operation_select, position_select, sub_account_select};
+
+// A column type both stores decode
+pub(crate) trait Value: for<'r> Decode<'r, Postgres> + Type<Postgres> + for<'r> Decode<'r, Sqlite> + Type<Sqlite> {}
+
+impl<T> Value for T where T: for<'r> Decode<'r, Postgres> + Type<Postgres> + for<'r> Decode<'r, Sqlite> + Type<Sqlite> {}
+
+// A result row of either store, read by column name
+pub(crate) trait Columns {
+    fn get<T: Value>(&self, column: &str) -> Result<T>;
+
+    fn get_u64(&self, column: &str) -> Result<u64> {
+        from_db(self.get(column)?)
+    }
+}
+
+impl Columns for PgRow {
+    fn get<T: Value>(&self, column: &str) -> Result<T> {
+        Ok(self.try_get(column)?)
+    }
+}
+
+impl Columns for SqliteRow {
+    fn get<T: Value>(&self, column: &str) -> Result<T> {
+        Ok(self.try_get(column)?)
+    }
+}
+
+// Reads an api_key_select! row
+pub(crate) fn api_key(row: &impl Columns) -> Result<ApiKey> {
+    Ok(ApiKey {
+        id: row.get("id")?,
+        owner: pubkey(row.get("owner")?)?,
+        scopes: parse_scopes(&row.get::<String>("scopes")?)?,
+        label: row.get("label")?,
+        created_at: row.get("created_at")?,
+    })
+}
+
+// Reads an operation_select! row
+pub(crate) fn operation(row: &impl Columns) -> Result<Operation> {
+    Ok(Operation {
+        id: row.get("id")?,
+        kind: row.get("kind")?,
+        owner: pubkey(row.get("owner")?)?,
+        target: pubkey(row.get("target")?)?,
+        status: parse_operation_status(&row.get::<String>("status")?)?,
+        signature: row.get("signature")?,
+        last_valid_block_height: row.get::<Option<i64>>("last_valid_block_height")?.map(from_db).transpose()?,
+        error: row.get("error")?,
+        created_at: row.get("created_at")?,
+        message: row.get("message")?,
+    })
+}
+
+// Reads a position_select! row
+pub(crate) fn position_view(row: &impl Columns) -> Result<PositionView> {
+    let auto_top_up = match row.get::<Option<i64>>("target_mr")? {
+        Some(target_mr) => Some(AutoTopUpView {
+            target_mr: from_db(target_mr)?,
+            max_total: row.get_u64("max_total")?,
+            used: row.get_u64("used")?,
+        }),
+        None => None,
+    };
+    Ok(PositionView {
+        owner: pubkey(row.get("owner")?)?,
+        sub_id: u16::try_from(row.get::<i32>("sub_id")?)?,
+        symbol: row.get("symbol")?,
+        side: parse_side(&row.get::<String>("side")?)?,
+        size: row.get_u64("size_base")?,
+        entry_price: row.get_u64("entry_price")?,
+        margin: row.get_u64("margin")?,
+        leverage: u16::try_from(row.get::<i32>("leverage")?)?,
+        unrealized_pnl: row.get("unrealized_pnl")?,
+        realized_pnl: row.get("realized_pnl")?,
+        liquidation_price: row.get_u64("liquidation_price")?,
+        last_update: row.get("updated_at")?,
+        state: parse_state(&row.get::<String>("state")?)?,
+        pda: pubkey(row.get("pda")?)?,
+        auto_top_up,
+    })
+}
+
+// Reads a sub_account_select! row; the PDA is derived under `program_id`
+pub(crate) fn sub_account_view(row: &impl Columns, program_id: &Pubkey) -> Result<SubAccountView> {
+    let owner = pubkey(row.get("owner")?)?;
+    let sub_id = u16::try_from(row.get::<i32>("sub_id")?)?;
+    Ok(SubAccountView {
+        owner,
+        sub_id,
+        margin_mode: parse_margin_mode(&row.get::<String>("margin_mode")?)?,
+        total_collateral: row.get_u64("total_collateral")?,
+        locked_collateral: row.get_u64("locked_collateral")?,
+        total_pnl: row.get("total_realized_pnl")?,
+        position_count: u32::try_from(row.get::<i64>("position_count")?)?,
+        pda: perp_sdk::pda::user_pda(program_id, &owner, sub_id).0,
+    })
+}
@@ -0,0 +1,842 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use solana_sdk::pubkey::Pubkey;
+use sqlx::{
+    migrate::Migrator,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+    Row, Sqlite, SqlitePool, Transaction,
+};
+
+use super::rows::{
+    api_key, api_key_select, operation, operation_select, position_select, position_view, sub_account_select,
+    sub_account_view, LIVE_STATES,
+};
+use super::{
+    drift_account_str, equity, from_db, intended_margin, key, margin_mode_str, modification, operation_status_str,
+    operation_transitions, parse_operation_status, pubkey, scopes_str, side_str, state_str, to_db, PositionRepo,
+};
+use crate::models::{
+    ApiKey, ApiKeyId, AutoTopUpInput, BatchCloseLeg, ChainChange, ChainCursor, ChainTx, ModifyAction, OpenPositionInput,
+    Operation, OperationId, OperationStatus, PositionState, PositionUpdate, PositionView, ReconcileReport,
+    Scope, StoredPosition, SubAccountView, TransferCollateralInput, TransferPositionInput,
+};
+
+// migrations/sqlite mirrors migrations/postgres version for version, without the perp
+// schema (BLOB keys, TEXT timestamps)
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+const POSITION_SELECT: &str = position_select!("");
+const SUB_ACCOUNT_SELECT: &str = sub_account_select!("");
+const OPERATION_SELECT: &str = operation_select!("");
+const API_KEY_SELECT: &str = api_key_select!("");
+
+#[derive(Clone)]
+pub struct SqliteRepo {
+    pool: SqlitePool,
+    program_id: Pubkey,
+}
+
+impl SqliteRepo {
+    // sqlite:perp.db creates the file if needed. Every connection to sqlite::memory: is a
+    // separate database, so that one keeps a single connection for the pool's lifetime.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(url)?
+            .create_if_missing(true)
+            .foreign_keys(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(Duration::from_secs(5));
+        let pool = if url.contains(":memory:") {
+            SqlitePoolOptions::new().max_connections(1).idle_timeout(None).max_lifetime(None)
+        } else {
+            SqlitePoolOptions::new().max_connections(5)
+        }
+        .connect_with(options)
+        .await?;
+        Ok(Self { pool, program_id: perp_sdk::PROGRAM_ID })
+    }
+
+    // Program the sub-account PDAs in fetch_sub_accounts are derived under
+    pub fn with_program_id(mut self, program_id: Pubkey) -> Self {
+        self.program_id = program_id;
+        self
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl PositionRepo for SqliteRepo {
+    async fn migrate(&self) -> Result<()> {
+        MIGRATOR.run(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn rollback(&self, version: i64) -> Result<()> {
+        MIGRATOR.undo(&self.pool, version).await?;
+        Ok(())
+    }
+
+    async fn schema_version(&self) -> Result<Option<i64>> {
+        let tracked: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')")
+                .fetch_one(&self.pool)
+                .await?;
+        if !tracked {
+            return Ok(None);
+        }
+        Ok(sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success").fetch_one(&self.pool).await?)
+    }
+
+    // ---------- operations (recorded before the transaction is sent) ----------
+
+    async fn insert_position_open_intent(&self, owner: &Pubkey, position_pda: &Pubkey, input: &OpenPositionInput) -> Result<OperationId> {
+        let margin = intended_margin(input)?;
+
+        let mut tx = self.pool.begin().await?;
+        ensure_user(&mut tx, owner, input.sub_id).await?;
+        sqlx::query("INSERT INTO markets (symbol, quote_mint) VALUES (?1, ?2) ON CONFLICT (symbol) DO NOTHING")
+            .bind(&input.symbol)
+            .bind(key(&input.quote_mint))
+            .execute(&mut tx)
+            .await?;
+        // A closed row under the same PDA is reused; a live one is left for the indexer to reconcile
+        sqlx::query(
+            r#"
+            INSERT INTO positions (pda, owner, sub_id, symbol, side, size_base, entry_price, margin, leverage, state)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 'opening')
+            ON CONFLICT (pda) DO UPDATE SET
+                side = EXCLUDED.side, size_base = EXCLUDED.size_base, entry_price = EXCLUDED.entry_price,
+                margin = EXCLUDED.margin, leverage = EXCLUDED.leverage, unrealized_pnl = 0, realized_pnl = 0,
+                liquidation_price = 0, state = 'opening', closed_at = NULL,
+                opened_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+            WHERE positions.state = 'closed'
+            "#,
+        )
+        .bind(key(position_pda))
+        .bind(key(owner))
+        .bind(i32::from(input.sub_id))
+        .bind(&input.symbol)
+        .bind(side_str(input.side))
+        .bind(to_db(input.size)?)
+        .bind(to_db(input.entry_price)?)
+        .bind(margin)
+        .bind(i32::from(input.leverage))
+        .execute(&mut tx)
+        .await?;
+        let id = record_intent(&mut tx, "open", owner, position_pda, json!(input)).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn insert_position_modify_intent(&self, owner: &Pubkey, position_pda: &Pubkey, action: &ModifyAction) -> Result<OperationId> {
+        let mut tx = self.pool.begin().await?;
+        set_state(&mut tx, position_pda, PositionState::Modifying, "('open')").await?;
+        let id = record_intent(&mut tx, "modify", owner, position_pda, json!(action)).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn insert_position_close_intent(&self, owner: &Pubkey, position_pda: &Pubkey, exit_price: u64) -> Result<OperationId> {
+        let mut tx = self.pool.begin().await?;
+        set_state(&mut tx, position_pda, PositionState::Closing, "('open', 'modifying')").await?;
+        let payload = json!({ "exit_price": exit_price });
+        let id = record_intent(&mut tx, "close", owner, position_pda, payload).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn insert_collateral_transfer_intent(&self, owner: &Pubkey, from_user: &Pubkey, to_user: &Pubkey, input: &TransferCollateralInput) -> Result<OperationId> {
+        let mut tx = self.pool.begin().await?;
+        ensure_user(&mut tx, owner, input.to_sub_id).await?;
+        let payload = json!({ "to_user": to_user.to_string(), "input": input });
+        let id = record_intent(&mut tx, "transfer_collateral", owner, from_user, payload).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn insert_position_transfer_intent(&self, owner: &Pubkey, from_pda: &Pubkey, to_pda: &Pubkey, input: &TransferPositionInput) -> Result<OperationId> {
+        let mut tx = self.pool.begin().await?;
+        let payload = json!({ "to_position": to_pda.to_string(), "input": input });
+        let id = record_intent(&mut tx, "transfer_position", owner, from_pda, payload).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    // Some(input) sets (or updates) the settings, None disables them. Like the on-chain
+    // TopUpSettings, an update keeps what has already been used.
+    async fn upsert_auto_top_up_intent(&self, owner: &Pubkey, position_pda: &Pubkey, input: Option<&AutoTopUpInput>) -> Result<OperationId> {
+        let mut tx = self.pool.begin().await?;
+        let id = match input {
+            Some(input) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO auto_top_up_settings (position_pda, target_mr, max_total)
+                    VALUES (?1, ?2, ?3)
+                    ON CONFLICT (position_pda) DO UPDATE SET
+                        target_mr = EXCLUDED.target_mr, max_total = EXCLUDED.max_total, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                    "#,
+                )
+                .bind(key(position_pda))
+                .bind(to_db(input.target_mr)?)
+                .bind(to_db(input.max_total)?)
+                .execute(&mut tx)
+                .await?;
+                record_intent(&mut tx, "set_auto_top_up", owner, position_pda, json!(input)).await?
+            }
+            None => {
+                sqlx::query("DELETE FROM auto_top_up_settings WHERE position_pda = ?1")
+                    .bind(key(position_pda))
+                    .execute(&mut tx)
+                    .await?;
+                record_intent(&mut tx, "disable_auto_top_up", owner, position_pda, json!({})).await?
+            }
+        };
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn insert_auto_top_up_intent(&self, keeper: &Pubkey, position_pda: &Pubkey, mark_price: u64) -> Result<Option<OperationId>> {
+        let mut tx = self.pool.begin().await?;
+        let in_flight: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM operations
+                WHERE kind = 'auto_top_up' AND target = ?1 AND status IN ('intent', 'submitted')
+            )
+            "#,
+        )
+        .bind(key(position_pda))
+        .fetch_one(&mut tx)
+        .await?;
+        if in_flight {
+            return Ok(None);
+        }
+        let id = record_intent(&mut tx, "auto_top_up", keeper, position_pda, json!({ "mark_price": mark_price })).await?;
+        tx.commit().await?;
+        Ok(Some(id))
+    }
+
+    async fn insert_batch_close_intent(&self, owner: &Pubkey, user_pda: &Pubkey, reduce_bps: u16, legs: &[BatchCloseLeg]) -> Result<OperationId> {
+        let state = if reduce_bps >= 10_000 { PositionState::Closing } else { PositionState::Modifying };
+        let mut tx = self.pool.begin().await?;
+        for leg in legs {
+            set_state(&mut tx, &leg.position_pda, state, "('open', 'modifying')").await?;
+        }
+        let payload = json!({ "reduce_bps": reduce_bps, "legs": legs });
+        let id = record_intent(&mut tx, "close_all", owner, user_pda, payload).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn mark_submitted(&self, id: OperationId, signature: &str, last_valid_block_height: u64) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE operations SET status = 'submitted', signature = ?2, last_valid_block_height = ?3, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+            WHERE id = ?1 AND status IN ('intent', 'submitted')
+            "#,
+        )
+        .bind(id)
+        .bind(signature)
+        .bind(to_db(last_valid_block_height)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn record_prepared(&self, id: OperationId, message: &[u8], last_valid_block_height: u64) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE operations SET message = ?2, last_valid_block_height = ?3, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+            WHERE id = ?1 AND status = 'intent'
+            "#,
+        )
+        .bind(id)
+        .bind(message)
+        .bind(to_db(last_valid_block_height)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn finish_operation(&self, id: OperationId, status: OperationStatus, error: Option<&str>) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        // BEGIN IMMEDIATE isn't available through the pool, so the write lock is taken by the
+        // first statement: a no-op update that makes a concurrent finish wait for this one
+        sqlx::query("UPDATE operations SET status = status WHERE id = ?1").bind(id).execute(&mut tx).await?;
+        let row = sqlx::query("SELECT kind, target, payload, status FROM operations WHERE id = ?1")
+            .bind(id)
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or_else(|| anyhow!("operation {id} not found"))?;
+        if parse_operation_status(row.try_get("status")?)?.is_finished() {
+            return Ok(false);
+        }
+        let payload: serde_json::Value = serde_json::from_str(row.try_get("payload")?)?;
+        let transitions = operation_transitions(row.try_get("kind")?, &pubkey(row.try_get("target")?)?, &payload)?;
+
+        sqlx::query("UPDATE operations SET status = ?2, error = ?3, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?1")
+            .bind(id)
+            .bind(operation_status_str(status))
+            .bind(error)
+            .execute(&mut tx)
+            .await?;
+        for t in transitions {
+            set_state(&mut tx, &t.pda, t.to(status), &format!("('{}')", state_str(t.in_flight))).await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn fetch_operation(&self, id: OperationId) -> Result<Option<Operation>> {
+        let row = sqlx::query(&format!("{OPERATION_SELECT} WHERE id = ?1")).bind(id).fetch_optional(&self.pool).await?;
+        row.as_ref().map(operation).transpose()
+    }
+
+    async fn fetch_inflight_operations(&self, before: DateTime<Utc>) -> Result<Vec<Operation>> {
+        let rows = sqlx::query(&format!(
+            "{OPERATION_SELECT} WHERE status IN ('intent', 'submitted') AND created_at < ?1 ORDER BY created_at, id"
+        ))
+        .bind(timestamp(before))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(operation).collect()
+    }
+
+    // ---------- reads ----------
+
+    async fn fetch_position_view(&self, position_pda: &Pubkey) -> Result<Option<PositionView>> {
+        let row = sqlx::query(&format!("{POSITION_SELECT} WHERE p.pda = ?1"))
+            .bind(key(position_pda))
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(position_view).transpose()
+    }
+
+    // Every position that isn't closed, across sub-accounts unless sub_id is given
+    async fn fetch_positions_by_owner(&self, owner: &Pubkey, sub_id: Option<u16>) -> Result<Vec<PositionView>> {
+        let rows = sqlx::query(&format!(
+            "{POSITION_SELECT} WHERE p.owner = ?1 AND (?2 IS NULL OR p.sub_id = ?2) AND p.state <> 'closed' ORDER BY p.sub_id, p.symbol"
+        ))
+        .bind(key(owner))
+        .bind(sub_id.map(i32::from))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(position_view).collect()
+    }
+
+    async fn fetch_sub_accounts(&self, owner: &Pubkey) -> Result<Vec<SubAccountView>> {
+        let rows = sqlx::query(&format!("{SUB_ACCOUNT_SELECT} WHERE u.owner = ?1 GROUP BY u.owner, u.sub_id ORDER BY u.sub_id"))
+            .bind(key(owner))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(|row| sub_account_view(row, &self.program_id)).collect()
+    }
+
+    async fn fetch_open_positions(&self) -> Result<Vec<PositionView>> {
+        let rows = sqlx::query(&format!("{POSITION_SELECT} WHERE p.state IN {LIVE_STATES} ORDER BY p.updated_at"))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(position_view).collect()
+    }
+
+    // ---------- monitor output ----------
+
+    async fn insert_liq_alert(&self, p: &PositionView, margin_ratio: f64, price: f64) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO margin_alerts (position_pda, owner, symbol, margin_ratio, mark_price, liquidation_price)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+        )
+        .bind(key(&p.pda))
+        .bind(key(&p.owner))
+        .bind(&p.symbol)
+        .bind(margin_ratio)
+        .bind(price.round() as i64)
+        .bind(to_db(p.liquidation_price)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Updates the live uPnL and the position's 1m snapshot bucket in one transaction
+    async fn upsert_position_snapshot(&self, p: &PositionView, price: f64, unrealized_pnl: i64, margin_ratio: f64) -> Result<()> {
+        let equity = equity(p, unrealized_pnl)?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE positions SET unrealized_pnl = ?2, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE pda = ?1")
+            .bind(key(&p.pda))
+            .bind(unrealized_pnl)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO pnl_snapshots
+                (bucket_start, granularity, owner, symbol, position_pda, mark_price, unrealized_pnl, realized_pnl_cum, equity, margin_ratio)
+            VALUES (strftime('%Y-%m-%dT%H:%M:00Z', 'now'), '1m', ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            ON CONFLICT (position_pda, granularity, bucket_start) DO UPDATE SET
+                mark_price = EXCLUDED.mark_price, unrealized_pnl = EXCLUDED.unrealized_pnl,
+                realized_pnl_cum = EXCLUDED.realized_pnl_cum, equity = EXCLUDED.equity,
+                margin_ratio = EXCLUDED.margin_ratio
+            "#,
+        )
+        .bind(key(&p.owner))
+        .bind(&p.symbol)
+        .bind(key(&p.pda))
+        .bind(price.round() as i64)
+        .bind(unrealized_pnl)
+        .bind(p.realized_pnl)
+        .bind(equity)
+        .bind(margin_ratio)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // ---------- indexer ----------
+
+    // A change is recorded in position_modifications at most once (signature, event_index);
+    // only a newly recorded one touches positions, so replays and overlapping WS/poll
+    // deliveries are no-ops
+    async fn apply_chain_tx(&self, cursor: &str, chain_tx: &ChainTx) -> Result<usize> {
+        let mut tx = self.pool.begin().await?;
+        let mut applied = 0;
+        for change in &chain_tx.changes {
+            let basis = match &change.update {
+                PositionUpdate::Transferred { from, .. } => from,
+                _ => &change.position_pda,
+            };
+            let before: Option<(i64, i64)> = sqlx::query_as("SELECT size_base, margin FROM positions WHERE pda = ?1")
+                .bind(key(basis))
+                .fetch_optional(&mut tx)
+                .await?;
+            let Some(m) = modification(&change.update, before)? else {
+                tracing::warn!("{} event {}: no row for position {}, skipped", chain_tx.signature, change.event_index, basis);
+                continue;
+            };
+            let recorded = sqlx::query(
+                r#"
+                INSERT INTO position_modifications
+                    (slot, signature, instruction_index, event_index, position_pda, kind, base_delta, margin_delta, price, realized_pnl_delta)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                ON CONFLICT (signature, event_index) DO NOTHING
+                "#,
+            )
+            .bind(to_db(chain_tx.slot)?)
+            .bind(&chain_tx.signature)
+            .bind(change.instruction_index as i32)
+            .bind(change.event_index as i32)
+            .bind(key(&change.position_pda))
+            .bind(m.kind)
+            .bind(m.base_delta)
+            .bind(m.margin_delta)
+            .bind(m.price)
+            .bind(m.realized_pnl_delta)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+            if recorded == 0 {
+                continue;
+            }
+            apply_change(&mut tx, chain_tx, change).await?;
+            applied += 1;
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO indexer_cursors (name, slot, signature) VALUES (?1, ?2, ?3)
+            ON CONFLICT (name) DO UPDATE SET slot = EXCLUDED.slot, signature = EXCLUDED.signature, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+            WHERE indexer_cursors.slot <= EXCLUDED.slot
+            "#,
+        )
+        .bind(cursor)
+        .bind(to_db(chain_tx.slot)?)
+        .bind(&chain_tx.signature)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(applied)
+    }
+
+    async fn fetch_cursor(&self, cursor: &str) -> Result<Option<ChainCursor>> {
+        let row: Option<(i64, String)> = sqlx::query_as("SELECT slot, signature FROM indexer_cursors WHERE name = ?1")
+            .bind(cursor)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|(slot, signature)| Ok(ChainCursor { slot: from_db(slot)?, signature })).transpose()
+    }
+
+    // ---------- reconciler ----------
+
+    async fn fetch_stored_positions(&self) -> Result<Vec<StoredPosition>> {
+        let rows = sqlx::query(&format!("{POSITION_SELECT} ORDER BY p.pda")).fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| {
+                let last_slot = row.try_get::<Option<i64>, _>("last_slot")?.map(from_db).transpose()?;
+                Ok(StoredPosition { view: position_view(row)?, last_slot })
+            })
+            .collect()
+    }
+
+    async fn fetch_all_sub_accounts(&self) -> Result<Vec<SubAccountView>> {
+        let rows = sqlx::query(&format!("{SUB_ACCOUNT_SELECT} GROUP BY u.owner, u.sub_id ORDER BY u.owner, u.sub_id"))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(|row| sub_account_view(row, &self.program_id)).collect()
+    }
+
+    async fn repair_position(&self, chain: &PositionView, slot: u64) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        ensure_user(&mut tx, &chain.owner, chain.sub_id).await?;
+        let written = sqlx::query(
+            r#"
+            INSERT INTO positions (pda, owner, sub_id, symbol, side, size_base, entry_price, margin, leverage,
+                                   realized_pnl, liquidation_price, state, last_slot)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, 'open', ?12)
+            ON CONFLICT (pda) DO UPDATE SET
+                size_base = EXCLUDED.size_base, entry_price = EXCLUDED.entry_price, margin = EXCLUDED.margin,
+                leverage = EXCLUDED.leverage, realized_pnl = EXCLUDED.realized_pnl,
+                liquidation_price = EXCLUDED.liquidation_price, state = 'open', last_slot = EXCLUDED.last_slot,
+                updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+            WHERE (positions.last_slot IS NULL OR positions.last_slot <= EXCLUDED.last_slot)
+              AND positions.state NOT IN ('opening', 'modifying', 'closing', 'liquidating')
+            "#,
+        )
+        .bind(key(&chain.pda))
+        .bind(key(&chain.owner))
+        .bind(i32::from(chain.sub_id))
+        .bind(&chain.symbol)
+        .bind(side_str(chain.side))
+        .bind(to_db(chain.size)?)
+        .bind(to_db(chain.entry_price)?)
+        .bind(to_db(chain.margin)?)
+        .bind(i32::from(chain.leverage))
+        .bind(chain.realized_pnl)
+        .bind(to_db(chain.liquidation_price)?)
+        .bind(to_db(slot)?)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        tx.commit().await?;
+        Ok(written > 0)
+    }
+
+    async fn repair_sub_account(&self, chain: &SubAccountView) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO users (owner, sub_id, margin_mode, total_collateral, locked_collateral, total_realized_pnl)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT (owner, sub_id) DO UPDATE SET
+                margin_mode = EXCLUDED.margin_mode, total_collateral = EXCLUDED.total_collateral,
+                locked_collateral = EXCLUDED.locked_collateral, total_realized_pnl = EXCLUDED.total_realized_pnl,
+                updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+            "#,
+        )
+        .bind(key(&chain.owner))
+        .bind(i32::from(chain.sub_id))
+        .bind(margin_mode_str(chain.margin_mode))
+        .bind(to_db(chain.total_collateral)?)
+        .bind(to_db(chain.locked_collateral)?)
+        .bind(chain.total_pnl)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn record_reconcile(&self, report: &ReconcileReport) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let run_id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO reconcile_runs (ran_at, slot, duration_ms, positions_checked, users_checked, drifted, repaired)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            RETURNING id
+            "#,
+        )
+        .bind(report.ran_at)
+        .bind(to_db(report.slot)?)
+        .bind(to_db(report.duration_ms)?)
+        .bind(i32::try_from(report.positions_checked)?)
+        .bind(i32::try_from(report.users_checked)?)
+        .bind(i32::try_from(report.drifts.len())?)
+        .bind(i32::try_from(report.repaired())?)
+        .fetch_one(&mut tx)
+        .await?;
+        for drift in &report.drifts {
+            sqlx::query(
+                r#"
+                INSERT INTO reconcile_drifts (run_id, account, address, field, chain_value, db_value, repaired)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                "#,
+            )
+            .bind(run_id)
+            .bind(drift_account_str(drift.account))
+            .bind(key(&drift.address))
+            .bind(&drift.field)
+            .bind(&drift.chain)
+            .bind(&drift.db)
+            .bind(drift.repaired)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // ---------- access (delegates and API keys) ----------
+
+    async fn add_delegate(&self, owner: &Pubkey, delegate: &Pubkey) -> Result<bool> {
+        let result = sqlx::query("INSERT INTO delegates (owner, delegate) VALUES (?1, ?2) ON CONFLICT DO NOTHING")
+            .bind(key(owner))
+            .bind(key(delegate))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn remove_delegate(&self, owner: &Pubkey, delegate: &Pubkey) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM delegates WHERE owner = ?1 AND delegate = ?2")
+            .bind(key(owner))
+            .bind(key(delegate))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn is_delegate(&self, owner: &Pubkey, delegate: &Pubkey) -> Result<bool> {
+        let found: Option<i32> = sqlx::query_scalar("SELECT 1 FROM delegates WHERE owner = ?1 AND delegate = ?2")
+            .bind(key(owner))
+            .bind(key(delegate))
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(found.is_some())
+    }
+
+    async fn fetch_delegates(&self, owner: &Pubkey) -> Result<Vec<Pubkey>> {
+        let rows: Vec<Vec<u8>> = sqlx::query_scalar("SELECT delegate FROM delegates WHERE owner = ?1 ORDER BY created_at, delegate")
+            .bind(key(owner))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.into_iter().map(pubkey).collect()
+    }
+
+    async fn insert_api_key(&self, owner: &Pubkey, key_hash: &[u8], scopes: &[Scope], label: Option<&str>) -> Result<ApiKeyId> {
+        let id = sqlx::query_scalar("INSERT INTO api_keys (owner, key_hash, scopes, label) VALUES (?1, ?2, ?3, ?4) RETURNING id")
+            .bind(key(owner))
+            .bind(key_hash)
+            .bind(scopes_str(scopes))
+            .bind(label)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(id)
+    }
+
+    async fn fetch_api_key(&self, key_hash: &[u8]) -> Result<Option<ApiKey>> {
+        let row = sqlx::query(&format!("{API_KEY_SELECT} WHERE key_hash = ?1 AND revoked_at IS NULL"))
+            .bind(key_hash)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(api_key).transpose()
+    }
+
+    async fn fetch_api_keys(&self, owner: &Pubkey) -> Result<Vec<ApiKey>> {
+        let rows = sqlx::query(&format!("{API_KEY_SELECT} WHERE owner = ?1 AND revoked_at IS NULL ORDER BY id"))
+            .bind(key(owner))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(api_key).collect()
+    }
+
+    async fn revoke_api_key(&self, owner: &Pubkey, id: ApiKeyId) -> Result<bool> {
+        let result = sqlx::query("UPDATE api_keys SET revoked_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?1 AND owner = ?2 AND revoked_at IS NULL")
+            .bind(id)
+            .bind(key(owner))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+// Writes a change's post-instruction state to positions. Rows already updated from a later
+// slot are left alone, so an event delivered late can't rewind a position.
+async fn apply_change(tx: &mut Transaction<'_, Sqlite>, chain_tx: &ChainTx, change: &ChainChange) -> Result<()> {
+    let pda = key(&change.position_pda);
+    let slot = to_db(chain_tx.slot)?;
+    let signature = &chain_tx.signature;
+    match &change.update {
+        PositionUpdate::Opened { owner, sub_id, symbol, side, size, entry_price, margin, leverage, liquidation_price } => {
+            ensure_user(tx, owner, *sub_id).await?;
+            sqlx::query(
+                r#"
+                INSERT INTO positions (pda, owner, sub_id, symbol, side, size_base, entry_price, margin, leverage,
+                                            liquidation_price, state, last_slot, last_signature)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, 'open', ?11, ?12)
+                ON CONFLICT (pda) DO UPDATE SET
+                    owner = EXCLUDED.owner, sub_id = EXCLUDED.sub_id, symbol = EXCLUDED.symbol, side = EXCLUDED.side,
+                    size_base = EXCLUDED.size_base, entry_price = EXCLUDED.entry_price, margin = EXCLUDED.margin,
+                    leverage = EXCLUDED.leverage, liquidation_price = EXCLUDED.liquidation_price, unrealized_pnl = 0,
+                    realized_pnl = 0, state = 'open', closed_at = NULL, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                    opened_at = CASE WHEN positions.state = 'closed' THEN strftime('%Y-%m-%dT%H:%M:%fZ', 'now') ELSE positions.opened_at END,
+                    last_slot = EXCLUDED.last_slot, last_signature = EXCLUDED.last_signature
+                WHERE positions.last_slot IS NULL OR positions.last_slot <= EXCLUDED.last_slot
+                "#,
+            )
+            .bind(&pda)
+            .bind(key(owner))
+            .bind(i32::from(*sub_id))
+            .bind(symbol)
+            .bind(side_str(*side))
+            .bind(to_db(*size)?)
+            .bind(to_db(*entry_price)?)
+            .bind(to_db(*margin)?)
+            .bind(i32::from(*leverage))
+            .bind(to_db(*liquidation_price)?)
+            .bind(slot)
+            .bind(signature)
+            .execute(&mut *tx)
+            .await?;
+        }
+        PositionUpdate::Modified { size, margin, leverage, unrealized_pnl, liquidation_price, .. } => {
+            sqlx::query(
+                r#"
+                UPDATE positions SET size_base = ?2, margin = ?3, leverage = ?4, unrealized_pnl = ?5,
+                    liquidation_price = ?6, state = 'open', last_slot = ?7, last_signature = ?8, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                WHERE pda = ?1 AND (last_slot IS NULL OR last_slot <= ?7)
+                "#,
+            )
+            .bind(&pda)
+            .bind(to_db(*size)?)
+            .bind(to_db(*margin)?)
+            .bind(i32::from(*leverage))
+            .bind(*unrealized_pnl)
+            .bind(to_db(*liquidation_price)?)
+            .bind(slot)
+            .bind(signature)
+            .execute(&mut *tx)
+            .await?;
+        }
+        PositionUpdate::Closed { realized_pnl, .. } => {
+            sqlx::query(
+                r#"
+                UPDATE positions SET size_base = 0, margin = 0, unrealized_pnl = 0, realized_pnl = realized_pnl + ?2,
+                    state = 'closed', closed_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), last_slot = ?3, last_signature = ?4, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                WHERE pda = ?1 AND (last_slot IS NULL OR last_slot <= ?3)
+                "#,
+            )
+            .bind(&pda)
+            .bind(*realized_pnl)
+            .bind(slot)
+            .bind(signature)
+            .execute(&mut *tx)
+            .await?;
+        }
+        PositionUpdate::ToppedUp { margin, liquidation_price, .. } => {
+            sqlx::query(
+                r#"
+                UPDATE positions SET margin = ?2, liquidation_price = ?3, last_slot = ?4, last_signature = ?5, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                WHERE pda = ?1 AND (last_slot IS NULL OR last_slot <= ?4)
+                "#,
+            )
+            .bind(&pda)
+            .bind(to_db(*margin)?)
+            .bind(to_db(*liquidation_price)?)
+            .bind(slot)
+            .bind(signature)
+            .execute(&mut *tx)
+            .await?;
+        }
+        PositionUpdate::Transferred { from, owner, sub_id, size, margin } => {
+            // The new position is a copy of the old one under the recipient's sub-account
+            ensure_user(tx, owner, *sub_id).await?;
+            sqlx::query(
+                r#"
+                INSERT INTO positions (pda, owner, sub_id, symbol, side, size_base, entry_price, margin, leverage,
+                                            unrealized_pnl, realized_pnl, liquidation_price, state, last_slot, last_signature)
+                SELECT ?1, ?2, ?3, symbol, side, ?4, entry_price, ?5, leverage, unrealized_pnl, realized_pnl,
+                       liquidation_price, 'open', ?6, ?7
+                FROM positions WHERE pda = ?8
+                ON CONFLICT (pda) DO UPDATE SET
+                    owner = EXCLUDED.owner, sub_id = EXCLUDED.sub_id, symbol = EXCLUDED.symbol, side = EXCLUDED.side,
+                    size_base = EXCLUDED.size_base, entry_price = EXCLUDED.entry_price, margin = EXCLUDED.margin,
+                    leverage = EXCLUDED.leverage, unrealized_pnl = EXCLUDED.unrealized_pnl,
+                    realized_pnl = EXCLUDED.realized_pnl, liquidation_price = EXCLUDED.liquidation_price, state = 'open',
+                    opened_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), closed_at = NULL, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                    last_slot = EXCLUDED.last_slot, last_signature = EXCLUDED.last_signature
+                WHERE positions.last_slot IS NULL OR positions.last_slot <= EXCLUDED.last_slot
+                "#,
+            )
+            .bind(&pda)
+            .bind(key(owner))
+            .bind(i32::from(*sub_id))
+            .bind(to_db(*size)?)
+            .bind(to_db(*margin)?)
+            .bind(slot)
+            .bind(signature)
+            .bind(key(from))
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query(
+                r#"
+                UPDATE positions SET size_base = 0, margin = 0, unrealized_pnl = 0, state = 'closed', closed_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                    last_slot = ?2, last_signature = ?3, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                WHERE pda = ?1 AND (last_slot IS NULL OR last_slot <= ?2)
+                "#,
+            )
+            .bind(key(from))
+            .bind(slot)
+            .bind(signature)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn ensure_user(tx: &mut Transaction<'_, Sqlite>, owner: &Pubkey, sub_id: u16) -> Result<()> {
+    sqlx::query("INSERT INTO users (owner, sub_id) VALUES (?1, ?2) ON CONFLICT (owner, sub_id) DO NOTHING")
+        .bind(key(owner))
+        .bind(i32::from(sub_id))
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+// Moves a position to `state` if it is currently in one of `from` (an SQL list literal)
+async fn set_state(tx: &mut Transaction<'_, Sqlite>, position_pda: &Pubkey, state: PositionState, from: &str) -> Result<()> {
+    sqlx::query(&format!("UPDATE positions SET state = ?2, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE pda = ?1 AND state IN {from}"))
+        .bind(key(position_pda))
+        .bind(state_str(state))
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+// Inserts an operation in status intent and returns its id. updated_at has no column default
+// here (SQLite can't add one to an existing table), so it is set explicitly.
+async fn record_intent(tx: &mut Transaction<'_, Sqlite>, kind: &str, owner: &Pubkey, target: &Pubkey, payload: serde_json::Value) -> Result<OperationId> {
+    let id = sqlx::query_scalar(
+        r#"
+        INSERT INTO operations (kind, owner, target, payload, status, updated_at)
+        VALUES (?1, ?2, ?3, ?4, 'intent', strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+        RETURNING id
+        "#,
+    )
+    .bind(kind)
+    .bind(key(owner))
+    .bind(key(target))
+    .bind(payload.to_string())
+    .fetch_one(&mut *tx)
+    .await?;
+    Ok(id)
+}
+
+// Timestamps are stored as TEXT in strftime's '%Y-%m-%dT%H:%M:%fZ' form; values compared
+// against them have to be written the same way
+fn timestamp(t: DateTime<Utc>) -> String {
+    t.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
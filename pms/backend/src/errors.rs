@@ -1,5 +1,8 @@
+use perp_sdk::PerpError;
+use solana_sdk::transaction::TransactionError;
 use thiserror::Error;
 
+// Request errors raised by the services (as anyhow errors); api/error.rs maps them to statuses
 #[derive(Debug, Error)]
 pub enum SvcError {
     #[error("RPC error: {0}")]
@@ -10,6 +13,44 @@ pub enum SvcError {
     Serde(String),
     #[error("Invalid input: {0}")]
     Invalid(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    // The request doesn't fit the current state (a position mid-operation, an operation
+    // already submitted)
+    #[error("Conflict: {0}")]
+    Conflict(String),
 }
 
 pub type SvcResult<T> = Result<T, SvcError>;
+
+// Why a transaction didn't reach the requested commitment (solana/tx.rs)
+#[derive(Debug, Error)]
+pub enum TxError {
+    // Rejected by the program, in simulation or on-chain
+    #[error("program error: {0}")]
+    Program(PerpError),
+    #[error("transaction failed: {0}")]
+    Failed(TransactionError),
+    #[error("not confirmed after {0} attempts (blockhash expired each time)")]
+    Expired(usize),
+    #[error("RPC error: {0}")]
+    Rpc(String),
+    // The SendLog couldn't record the signed transaction, so it wasn't sent
+    #[error("signature not recorded, transaction not sent: {0}")]
+    Unrecorded(String),
+    // The signer was unreachable or refused (solana/signer.rs); nothing was sent
+    #[error("not signed: {0}")]
+    Signer(String),
+}
+
+impl From<TransactionError> for TxError {
+    fn from(err: TransactionError) -> Self {
+        match &err {
+            TransactionError::InstructionError(_, ix_err) => match PerpError::from_instruction_error(ix_err) {
+                Some(perp) => TxError::Program(perp),
+                None => TxError::Failed(err),
+            },
+            _ => TxError::Failed(err),
+        }
+    }
+}
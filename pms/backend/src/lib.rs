@@ -4,4 +4,5 @@ pub mod models;
 pub mod solana;
 pub mod services;
 pub mod db;
-pub mod api;
\ No newline at end of file
+pub mod api;
+pub mod signing_service;
@@ -1,16 +1,8 @@
-mod config;
-mod errors;
-mod models;
-mod solana;
-mod services;
-mod db;
-mod api;
-
 use anyhow::Result;
 use tracing_subscriber::{fmt, EnvFilter};
+use position_service::{api, config, db, services, solana};
 use services::{manager::PositionManager, monitor::PositionMonitor};
-use api::http::start_http_server;
-use db::repo::PgRepo;
+use api::{auth::Auth, http::{start_http_server, AppState}};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -18,16 +10,38 @@ async fn main() -> Result<()> {
     fmt().with_env_filter(EnvFilter::from_default_env()).init();
 
     let cfg = config::Config::from_env()?;
-    let repo = PgRepo::connect(&cfg.database_url).await?;
+    let repo = db::connect(&cfg.database_url, cfg.program_id).await?;
     repo.migrate().await?;
+    tracing::info!("database schema at version {:?}", repo.schema_version().await?);
 
+    // Operations from before this start are settled in the background; new ones are created
+    // after `started` and left to the requests that made them
+    let started = chrono::Utc::now();
     let sol1 = solana::client::SolanaCtx::new(&cfg).await?;
     let sol2 = solana::client::SolanaCtx::new(&cfg).await?;
-    let margin_calc = services::margin::MarginCalculator::default();
-    let pnl_tracker = services::pnl::PnLTracker::default();
+    let operations = services::operations::Operations::new(repo.clone(), sol1.tx.clone());
+    tokio::spawn(async move {
+        if let Err(e) = operations.recover(started).await {
+            tracing::error!("operation recovery failed: {:?}", e);
+        }
+    });
+    let manager = PositionManager::new(sol1, repo.clone(), cfg.program_id);
+    let hub = api::ws::WsHub::new();
+    let monitor = PositionMonitor::new(sol2, repo.clone(), cfg.price_oracle_source.clone(), cfg.risk_alert_threshold).with_hub(hub.clone());
+
+    // Keep positions in sync with the program's events
+    let source = std::sync::Arc::new(services::indexer::RpcSource::new(&cfg.rpc_url, cfg.program_id));
+    let indexer = services::indexer::Indexer::new(repo.clone(), source, cfg.program_id, cfg.ws_url.clone()).with_hub(hub.clone());
+    tokio::spawn(async move {
+        if let Err(e) = indexer.run().await {
+            tracing::error!("indexer stopped: {:?}", e);
+        }
+    });
 
-    let manager = PositionManager::new(sol1, repo.clone(), margin_calc.clone(), pnl_tracker.clone(), cfg.program_id);
-    let monitor = PositionMonitor::new(sol2, repo.clone(), cfg.price_oracle_source.clone(), cfg.risk_alert_threshold);
+    // Compare the program's accounts with the database every few minutes
+    let accounts = std::sync::Arc::new(services::reconciler::RpcAccounts::new(&cfg.rpc_url, cfg.program_id));
+    let reconciler = services::reconciler::Reconciler::new(repo.clone(), accounts, cfg.program_id);
+    tokio::spawn(reconciler.clone().run());
 
     // Start background monitor
     tokio::spawn(async move {
@@ -37,7 +51,12 @@ async fn main() -> Result<()> {
     });
 
     // Start API (HTTP + WS)
-    start_http_server(cfg.http_addr.clone(), manager, repo.clone()).await?;
+    if !cfg.auth_required {
+        tracing::warn!("AUTH=off: requests without credentials can act for any owner");
+    }
+    let auth = Auth::new(repo.clone(), cfg.auth_required, std::time::Duration::from_secs(cfg.session_ttl_secs));
+    let state = AppState { manager, repo: repo.clone(), reconciler, signing: cfg.signing_mode, auth, hub };
+    start_http_server(cfg.http_addr.clone(), state).await?;
 
     Ok(())
 }
\ No newline at end of file
@@ -1,16 +1,57 @@
+use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeZone, Utc};
 use solana_sdk::pubkey::Pubkey;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Side { Long, Short }
 
+impl From<Side> for perp_sdk::types::Side {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Long => perp_sdk::types::Side::Long,
+            Side::Short => perp_sdk::types::Side::Short,
+        }
+    }
+}
+
+impl From<perp_sdk::types::Side> for Side {
+    fn from(side: perp_sdk::types::Side) -> Self {
+        match side {
+            perp_sdk::types::Side::Long => Side::Long,
+            perp_sdk::types::Side::Short => Side::Short,
+        }
+    }
+}
+
+impl From<Side> for perp_math::Side {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Long => perp_math::Side::Long,
+            Side::Short => perp_math::Side::Short,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum MarginMode { #[default] Isolated, Cross }
+
+impl From<perp_sdk::types::MarginMode> for MarginMode {
+    fn from(mode: perp_sdk::types::MarginMode) -> Self {
+        match mode {
+            perp_sdk::types::MarginMode::Isolated => MarginMode::Isolated,
+            perp_sdk::types::MarginMode::Cross => MarginMode::Cross,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum PositionState { Opening, Open, Modifying, Closing, Closed, Liquidating }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PositionView {
     pub owner: Pubkey,
+    pub sub_id: u16,
     pub symbol: String,
     pub side: Side,
     pub size: u64,
@@ -23,10 +64,61 @@ pub struct PositionView {
     pub last_update: DateTime<Utc>,
     pub state: PositionState,
     pub pda: Pubkey,
+    #[serde(default)]
+    pub auto_top_up: Option<AutoTopUpView>,
+}
+
+impl PositionView {
+    // A decoded Position account; it exists only while the position is open
+    pub fn from_account(pda: Pubkey, p: &perp_sdk::accounts::Position) -> Self {
+        Self {
+            owner: p.owner,
+            sub_id: p.sub_id,
+            symbol: p.symbol.clone(),
+            side: p.side.into(),
+            size: p.size,
+            entry_price: p.entry_price,
+            margin: p.margin,
+            leverage: p.leverage,
+            unrealized_pnl: p.unrealized_pnl,
+            realized_pnl: p.realized_pnl,
+            liquidation_price: p.liquidation_price,
+            last_update: Utc.timestamp_opt(p.last_update, 0).single().unwrap_or_else(Utc::now),
+            state: PositionState::Open,
+            pda,
+            auto_top_up: None,
+        }
+    }
+}
+
+// Mirrors the on-chain TopUpSettings account (PDA ["top_up", position])
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoTopUpView {
+    pub target_mr: u64, // scaled by 1e6
+    pub max_total: u64,
+    pub used: u64,
+}
+
+impl AutoTopUpView {
+    pub fn target_ratio(&self) -> f64 {
+        self.target_mr as f64 / 1_000_000.0
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.max_total.saturating_sub(self.used)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoTopUpInput {
+    pub target_mr: u64, // scaled by 1e6, e.g. 50_000 = 5%
+    pub max_total: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenPositionInput {
+    #[serde(default)]
+    pub sub_id: u16,
     pub symbol: String,
     pub side: Side,
     pub size: u64,
@@ -36,10 +128,288 @@ pub struct OpenPositionInput {
     pub quote_mint: Pubkey,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubAccountView {
+    pub owner: Pubkey,
+    pub sub_id: u16,
+    pub margin_mode: MarginMode,
+    pub total_collateral: u64,
+    pub locked_collateral: u64,
+    pub total_pnl: i64,
+    pub position_count: u32,
+    pub pda: Pubkey,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferCollateralInput {
+    pub from_sub_id: u16,
+    pub to_sub_id: u16,
+    pub amount: u64,
+}
+
+// The recipient co-signs, or has called approve_position_transfer beforehand
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferPositionInput {
+    pub to_owner: Pubkey,
+    #[serde(default)]
+    pub to_sub_id: u16,
+    #[serde(default)]
+    pub recipient_approved: bool,
+}
+
+fn default_reduce_bps() -> u16 { 10_000 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloseAllInput {
+    #[serde(default)]
+    pub sub_id: u16,
+    #[serde(default = "default_reduce_bps")]
+    pub reduce_bps: u16,                      // 10_000 closes every position
+    pub prices: HashMap<String, u64>,         // symbol -> expected exit price
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchCloseLeg {
+    pub position_pda: Pubkey,
+    pub symbol: String,
+    pub price: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ModifyAction {
     IncreaseSize { add_size: u64, price: u64, add_margin: u64 },
     DecreaseSize { reduce_size: u64, price: u64 },
     AddMargin { amount: u64 },
     RemoveMargin { amount: u64, price: u64 },
-}
\ No newline at end of file
+}
+
+impl From<ModifyAction> for perp_sdk::types::ModifyKind {
+    fn from(action: ModifyAction) -> Self {
+        use perp_sdk::types::ModifyKind;
+        match action {
+            ModifyAction::IncreaseSize { add_size, price, add_margin } => ModifyKind::IncreaseSize { add_size, price, add_margin },
+            ModifyAction::DecreaseSize { reduce_size, price } => ModifyKind::DecreaseSize { reduce_size, price },
+            ModifyAction::AddMargin { amount } => ModifyKind::AddMargin { amount },
+            ModifyAction::RemoveMargin { amount, price } => ModifyKind::RemoveMargin { amount, price },
+        }
+    }
+}
+
+// ---------- indexer ----------
+
+// One confirmed program transaction, reduced to the position changes its events describe.
+// Failed transactions have no changes but still move the indexer's cursor past them.
+#[derive(Debug, Clone)]
+pub struct ChainTx {
+    pub slot: u64,
+    pub signature: String,
+    pub changes: Vec<ChainChange>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChainChange {
+    pub instruction_index: u32,
+    pub event_index: u32, // (signature, event_index) is the idempotency key
+    pub position_pda: Pubkey,
+    pub update: PositionUpdate,
+}
+
+// Values are the program's post-instruction state unless named as a delta
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PositionUpdate {
+    Opened {
+        #[serde_as(as = "serde_with::DisplayFromStr")]
+        owner: Pubkey,
+        sub_id: u16,
+        symbol: String,
+        side: Side,
+        size: u64,
+        entry_price: u64,
+        margin: u64,
+        leverage: u16,
+        liquidation_price: u64,
+    },
+    Modified { size: u64, margin: u64, leverage: u16, price: u64, unrealized_pnl: i64, liquidation_price: u64 },
+    Closed { size_closed: u64, exit_price: u64, realized_pnl: i64 },
+    ToppedUp { amount: u64, mark_price: u64, margin: u64, liquidation_price: u64 },
+    // position_pda is the new position; `from` is closed
+    Transferred {
+        #[serde_as(as = "serde_with::DisplayFromStr")]
+        from: Pubkey,
+        #[serde_as(as = "serde_with::DisplayFromStr")]
+        owner: Pubkey,
+        sub_id: u16,
+        size: u64,
+        margin: u64,
+    },
+}
+
+// Where an indexer (live or backfill) has got to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainCursor {
+    pub slot: u64,
+    pub signature: String,
+}
+
+// ---------- reconciler ----------
+
+// A positions row with the slot it was last written from (None for intent-only rows)
+#[derive(Debug, Clone)]
+pub struct StoredPosition {
+    pub view: PositionView,
+    pub last_slot: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftAccount { Position, User }
+
+// One field where the database disagrees with the program account. Repaired drift was
+// overwritten with the on-chain value; the rest needs an operator (see services/reconciler.rs).
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Drift {
+    pub account: DriftAccount,
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    pub address: Pubkey,
+    pub field: String,
+    pub chain: String,
+    pub db: String,
+    pub repaired: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconcileReport {
+    pub ran_at: DateTime<Utc>,
+    pub slot: u64,
+    pub duration_ms: u64,
+    pub positions_checked: usize,
+    pub users_checked: usize,
+    pub drifts: Vec<Drift>,
+}
+
+impl ReconcileReport {
+    pub fn repaired(&self) -> usize {
+        self.drifts.iter().filter(|d| d.repaired).count()
+    }
+
+    pub fn unresolved(&self) -> impl Iterator<Item = &Drift> {
+        self.drifts.iter().filter(|d| !d.repaired)
+    }
+}
+
+// ---------- transactions ----------
+
+// A position transaction confirmed at the service's commitment, with the position account as
+// it was read afterwards (None once the position is closed) and, when the transaction closed
+// it, the fill from its PositionClosed event
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Serialize)]
+pub struct TxReceipt {
+    pub signature: String,
+    pub slot: u64,
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    pub position_pda: Pubkey,
+    pub position: Option<PositionView>,
+    pub closed: Option<CloseFill>,
+}
+
+// What closing a position realized; payout is what left the position for the owner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CloseFill {
+    pub size_closed: u64,
+    pub exit_price: u64,
+    pub realized_pnl: i64,
+    pub payout: u64,
+}
+
+// An unsigned transaction for the owner's wallet (SIGNING_MODE=wallet): bincode, base64. It
+// has to be signed and handed to /tx/submit before its blockhash expires, after which the
+// operation fails.
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Serialize)]
+pub struct PreparedTx {
+    pub operation_id: OperationId,
+    pub transaction: String,
+    pub blockhash: String,
+    pub last_valid_block_height: u64,
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    pub position_pda: Pubkey,
+}
+
+// close_all_positions: every position it covered, and the one transaction that closed them
+// (None when there was nothing to close)
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchReceipt {
+    #[serde_as(as = "Vec<serde_with::DisplayFromStr>")]
+    pub positions: Vec<Pubkey>,
+    pub signature: Option<String>,
+}
+
+// ---------- operations ----------
+
+pub type OperationId = i64;
+
+// intent (recorded, nothing sent yet) → submitted (a signed transaction may be in flight)
+// → confirmed | failed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationStatus { Intent, Submitted, Confirmed, Failed }
+
+impl OperationStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, OperationStatus::Confirmed | OperationStatus::Failed)
+    }
+}
+
+// What an API key may do; a wallet session may do all of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    // GET /users/:owner/positions and the sub-account position lists
+    Read,
+    // open, modify, close, close-all, auto top-up, /tx/submit
+    Trade,
+    // moving positions or collateral
+    Transfer,
+    // the /admin endpoints; only on keys made with the api_key tool, never on a session
+    Admin,
+}
+
+pub type ApiKeyId = i64;
+
+// An api_keys row; the key itself is only shown when it is created
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKey {
+    pub id: ApiKeyId,
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    pub owner: Pubkey,
+    pub scopes: Vec<Scope>,
+    pub label: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// An operations row: one mutation and the transaction carrying it. `signature` is the last
+// transaction signed for it, valid up to `last_valid_block_height`.
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Serialize)]
+pub struct Operation {
+    pub id: OperationId,
+    pub kind: String,
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    pub owner: Pubkey,
+    #[serde_as(as = "serde_with::DisplayFromStr")]
+    pub target: Pubkey,
+    pub status: OperationStatus,
+    pub signature: Option<String>,
+    pub last_valid_block_height: Option<u64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    // The unsigned message handed to a wallet, for operations prepared in wallet mode
+    #[serde(skip)]
+    pub message: Option<Vec<u8>>,
+}
@@ -0,0 +1,129 @@
+use std::{str::FromStr, sync::Arc};
+use anyhow::{bail, Result};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use tracing::info;
+
+use crate::{
+    db::SharedRepo,
+    models::{ChainCursor, ChainTx},
+    services::indexer::{replay, take_over, ChainSource, SignatureInfo, SIGNATURE_PAGE},
+};
+
+// Cursor a backfill run moves as it replays, up to the live cursor; the live indexer waits
+// until it reaches the take-over point
+pub const BACKFILL_CURSOR: &str = "backfill";
+
+// Where history starts: the first slot to replay, or a transaction to replay from (inclusive)
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackfillStart {
+    Slot(u64),
+    Signature(Signature),
+}
+
+impl FromStr for BackfillStart {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.parse::<u64>() {
+            Ok(slot) => Ok(Self::Slot(slot)),
+            Err(_) => Ok(Self::Signature(s.parse()?)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BackfillReport {
+    pub transactions: usize,
+    pub changes: usize,
+    // The live cursor: where the run stopped and the live indexer took over
+    pub end: Option<ChainCursor>,
+}
+
+// Replays the program's history into the database, oldest first, from a starting slot or
+// signature up to and including the live cursor. The live indexer applies everything after
+// its cursor, once this has reached where it took over, so the two meet without a gap and
+// never write the same rows at once; a change seen by both is dropped by the
+// (signature, event_index) key.
+pub struct Backfill {
+    repo: SharedRepo,
+    source: Arc<dyn ChainSource>,
+    program_id: Pubkey,
+}
+
+impl Backfill {
+    pub fn new(repo: SharedRepo, source: Arc<dyn ChainSource>, program_id: Pubkey) -> Self {
+        Self { repo, source, program_id }
+    }
+
+    pub async fn run(&self, start: &BackfillStart) -> Result<BackfillReport> {
+        let mut report = BackfillReport::default();
+        let Some(end) = take_over(&self.repo, self.source.as_ref()).await? else {
+            info!("backfill: program has no transactions");
+            return Ok(report);
+        };
+        let end_info = SignatureInfo { slot: end.slot, signature: end.signature.parse()?, failed: false };
+
+        // Walk back from the end until the start is in the page, keeping only where each page
+        // begins, then replay the pages oldest first: one page in memory however long the history
+        let mut anchors = vec![];
+        if *start != BackfillStart::Signature(end_info.signature) {
+            anchors.push(end_info.signature);
+        }
+        while let Some(before) = anchors.last() {
+            let page = self.page(before).await?;
+            let reached = match start {
+                BackfillStart::Slot(slot) => page.len() < SIGNATURE_PAGE || page.last().is_none_or(|oldest| oldest.slot < *slot),
+                BackfillStart::Signature(signature) if page.iter().any(|info| info.signature == *signature) => true,
+                BackfillStart::Signature(signature) if page.len() < SIGNATURE_PAGE => {
+                    bail!("start transaction {signature} not found before {}", end.signature)
+                }
+                BackfillStart::Signature(_) => false,
+            };
+            match page.last() {
+                Some(oldest) if !reached => anchors.push(oldest.signature),
+                _ => break,
+            }
+        }
+
+        for (i, anchor) in anchors.iter().enumerate().rev() {
+            let mut page = self.page(anchor).await?;
+            page.reverse();
+            // Only the oldest page reaches back past the start
+            if i == anchors.len() - 1 {
+                page = match start {
+                    BackfillStart::Slot(slot) => page.into_iter().filter(|info| info.slot >= *slot).collect(),
+                    BackfillStart::Signature(signature) => {
+                        page.into_iter().skip_while(|info| info.signature != *signature).collect()
+                    }
+                };
+            }
+            for info in &page {
+                self.replay(info, &mut report).await?;
+            }
+            if let Some(last) = page.last() {
+                info!("backfill: {} transactions, {} changes, at slot {}", report.transactions, report.changes, last.slot);
+            }
+        }
+
+        // Last the transaction the live indexer took over after, which it left to the backfill.
+        // Starting after it, the run only marks the history done.
+        if matches!(start, BackfillStart::Slot(slot) if end.slot < *slot) {
+            let done = ChainTx { slot: end.slot, signature: end.signature.clone(), changes: vec![] };
+            self.repo.apply_chain_tx(BACKFILL_CURSOR, &done).await?;
+        } else {
+            self.replay(&end_info, &mut report).await?;
+        }
+        report.end = Some(end);
+        Ok(report)
+    }
+
+    async fn page(&self, before: &Signature) -> Result<Vec<SignatureInfo>> {
+        self.source.signature_page(Some(before), None, SIGNATURE_PAGE).await
+    }
+
+    async fn replay(&self, info: &SignatureInfo, report: &mut BackfillReport) -> Result<()> {
+        report.changes += replay(&self.repo, self.source.as_ref(), &self.program_id, BACKFILL_CURSOR, info, None).await?;
+        report.transactions += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,425 @@
+use std::{path::Path, sync::Arc, time::Duration};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::{FutureExt, Stream, StreamExt};
+use perp_events::{PerpEvent, TxEvents};
+use solana_client::{
+    nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+    rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::{RpcTransactionConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter},
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding};
+use tracing::{debug, info, warn};
+
+use crate::{
+    api::ws::WsHub,
+    db::SharedRepo,
+    models::{ChainChange, ChainCursor, ChainTx, PositionUpdate},
+    services::backfill::BACKFILL_CURSOR,
+    solana::pda,
+};
+
+// Cursor the live indexer keeps in indexer_cursors
+pub const LIVE_CURSOR: &str = "live";
+// Where the live indexer took over on an empty database; it never moves again
+pub const TAKEOVER_CURSOR: &str = "takeover";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+// How long to stay on polling after the log subscription drops before subscribing again
+const RESUBSCRIBE_AFTER: Duration = Duration::from_secs(30);
+// getSignaturesForAddress page size (the RPC maximum)
+pub(crate) const SIGNATURE_PAGE: usize = 1000;
+
+#[derive(Debug, Clone)]
+pub struct SignatureInfo {
+    pub slot: u64,
+    pub signature: Signature,
+    pub failed: bool,
+}
+
+// Where confirmed program transactions come from; RpcSource in production
+#[async_trait]
+pub trait ChainSource: Send + Sync {
+    // Up to `limit` program transactions older than `before` (from the newest when None) and
+    // newer than `until`, newest first, the way getSignaturesForAddress pages them
+    async fn signature_page(
+        &self,
+        before: Option<&Signature>,
+        until: Option<&Signature>,
+        limit: usize,
+    ) -> Result<Vec<SignatureInfo>>;
+    async fn transaction(&self, signature: &Signature) -> Result<TxEvents>;
+
+    // Program transactions newer than `until`, oldest first
+    async fn signatures_since(&self, until: &Signature) -> Result<Vec<SignatureInfo>> {
+        // Pages come newest first; walk back with `before` until the cursor is reached
+        let mut out: Vec<SignatureInfo> = vec![];
+        loop {
+            let page = self.signature_page(out.last().map(|s| &s.signature), Some(until), SIGNATURE_PAGE).await?;
+            let full = page.len() == SIGNATURE_PAGE;
+            out.extend(page);
+            if !full {
+                break;
+            }
+        }
+        out.reverse();
+        Ok(out)
+    }
+}
+
+pub struct RpcSource {
+    client: RpcClient,
+    program_id: Pubkey,
+}
+
+impl RpcSource {
+    pub fn new(rpc_url: &str, program_id: Pubkey) -> Self {
+        Self { client: RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed()), program_id }
+    }
+}
+
+#[async_trait]
+impl ChainSource for RpcSource {
+    async fn signature_page(
+        &self,
+        before: Option<&Signature>,
+        until: Option<&Signature>,
+        limit: usize,
+    ) -> Result<Vec<SignatureInfo>> {
+        let page = self
+            .client
+            .get_signatures_for_address_with_config(
+                &self.program_id,
+                GetConfirmedSignaturesForAddress2Config {
+                    before: before.copied(),
+                    until: until.copied(),
+                    limit: Some(limit),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .await?;
+        page.into_iter()
+            .map(|status| Ok(SignatureInfo { slot: status.slot, signature: status.signature.parse()?, failed: status.err.is_some() }))
+            .collect()
+    }
+
+    async fn transaction(&self, signature: &Signature) -> Result<TxEvents> {
+        let tx = self
+            .client
+            .get_transaction_with_config(
+                signature,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .await?;
+        Ok(perp_events::from_confirmed_transaction(&self.program_id, &tx)?)
+    }
+}
+
+// getTransaction results saved as JSON files (any encoding) standing in for RPC, for the
+// backfill bin and tests. Transactions are ordered by slot, then by file name within a slot.
+pub struct RecordedSource {
+    program_id: Pubkey,
+    txs: Vec<(SignatureInfo, EncodedConfirmedTransactionWithStatusMeta)>,
+}
+
+impl RecordedSource {
+    pub fn open(dir: impl AsRef<Path>, program_id: Pubkey) -> Result<Self> {
+        let mut files = vec![];
+        for entry in std::fs::read_dir(dir.as_ref())? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                files.push(path);
+            }
+        }
+        files.sort();
+
+        let mut txs = vec![];
+        for path in files {
+            let tx: EncodedConfirmedTransactionWithStatusMeta =
+                serde_json::from_str(&std::fs::read_to_string(&path)?).map_err(|e| anyhow!("{}: {e}", path.display()))?;
+            let signature = perp_events::rpc::signature(&tx.transaction.transaction)
+                .ok_or_else(|| anyhow!("{}: no signature", path.display()))?;
+            let failed = tx.transaction.meta.as_ref().is_some_and(|m| m.err.is_some());
+            txs.push((SignatureInfo { slot: tx.slot, signature, failed }, tx));
+        }
+        // Stable, so file name order holds within a slot
+        txs.sort_by_key(|(info, _)| info.slot);
+        Ok(Self { program_id, txs })
+    }
+
+    fn position(&self, signature: &Signature) -> Result<usize> {
+        self.txs.iter().position(|(info, _)| info.signature == *signature).ok_or_else(|| anyhow!("{signature} not recorded"))
+    }
+}
+
+#[async_trait]
+impl ChainSource for RecordedSource {
+    async fn signature_page(
+        &self,
+        before: Option<&Signature>,
+        until: Option<&Signature>,
+        limit: usize,
+    ) -> Result<Vec<SignatureInfo>> {
+        let end = before.map(|s| self.position(s)).transpose()?.unwrap_or(self.txs.len());
+        let start = until.map(|s| self.position(s)).transpose()?.map_or(0, |i| i + 1).min(end);
+        Ok(self.txs[start..end].iter().rev().take(limit).map(|(info, _)| info.clone()).collect())
+    }
+
+    async fn transaction(&self, signature: &Signature) -> Result<TxEvents> {
+        let (_, tx) = &self.txs[self.position(signature)?];
+        Ok(perp_events::from_confirmed_transaction(&self.program_id, tx)?)
+    }
+}
+
+// Writes what the program's events say into positions and position_modifications, walking
+// getSignaturesForAddress forward from the stored cursor. A log subscription on WS_URL only
+// says when to walk: a message it drops is picked up by the next walk, and the cursor never
+// moves past a transaction that wasn't applied. Without the subscription it walks every
+// POLL_INTERVAL.
+#[derive(Clone)]
+pub struct Indexer {
+    repo: SharedRepo,
+    source: Arc<dyn ChainSource>,
+    program_id: Pubkey,
+    ws_url: String,
+    hub: Option<WsHub>,
+}
+
+impl Indexer {
+    pub fn new(repo: SharedRepo, source: Arc<dyn ChainSource>, program_id: Pubkey, ws_url: String) -> Self {
+        Self { repo, source, program_id, ws_url, hub: None }
+    }
+
+    // Publishes what it applies to /ws subscribers
+    pub fn with_hub(mut self, hub: WsHub) -> Self {
+        self.hub = Some(hub);
+        self
+    }
+
+    pub async fn run(self) -> Result<()> {
+        loop {
+            match self.follow_logs().await {
+                Ok(()) => warn!("log subscription closed, polling"),
+                Err(e) => warn!("log subscription failed: {e}, polling"),
+            }
+            let resubscribe_at = tokio::time::Instant::now() + RESUBSCRIBE_AFTER;
+            while tokio::time::Instant::now() < resubscribe_at {
+                if let Err(e) = self.poll_once().await {
+                    warn!("indexer poll failed: {e}");
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+
+    async fn follow_logs(&self) -> Result<()> {
+        let client = PubsubClient::new(&self.ws_url).await?;
+        let (logs, _unsubscribe) = client
+            .logs_subscribe(
+                RpcTransactionLogsFilter::Mentions(vec![self.program_id.to_string()]),
+                RpcTransactionLogsConfig { commitment: Some(CommitmentConfig::confirmed()) },
+            )
+            .await?;
+        info!("indexer subscribed to {} logs", self.program_id);
+        self.follow(logs.map(|_| ())).await
+    }
+
+    // Walks from the cursor now and whenever `notifications` yields, until it ends. A burst of
+    // notifications is one walk.
+    pub async fn follow(&self, mut notifications: impl Stream<Item = ()> + Unpin) -> Result<()> {
+        // Anything confirmed before the subscription started
+        self.poll_once().await?;
+        while notifications.next().await.is_some() {
+            while let Some(Some(())) = notifications.next().now_or_never() {}
+            self.poll_once().await?;
+        }
+        Ok(())
+    }
+
+    // Applies every program transaction after the cursor, once the backfill has caught up to
+    // where the live indexer took over. Returns the number of new changes.
+    pub async fn poll_once(&self) -> Result<usize> {
+        let Some(cursor) = take_over(&self.repo, self.source.as_ref()).await? else {
+            return Ok(0);
+        };
+        if !backfilled(&self.repo).await? {
+            debug!("indexer waiting for the backfill to reach slot {}", cursor.slot);
+            return Ok(0);
+        }
+        let mut applied = 0;
+        for info in self.source.signatures_since(&cursor.signature.parse()?).await? {
+            applied += replay(&self.repo, self.source.as_ref(), &self.program_id, LIVE_CURSOR, &info, self.hub.as_ref()).await?;
+        }
+        Ok(applied)
+    }
+}
+
+// The live cursor. With none yet it is set to the newest program transaction without applying
+// it: the live indexer takes over after that, and history up to it is services/backfill.rs's.
+pub(crate) async fn take_over(repo: &SharedRepo, source: &dyn ChainSource) -> Result<Option<ChainCursor>> {
+    if let Some(cursor) = repo.fetch_cursor(LIVE_CURSOR).await? {
+        return Ok(Some(cursor));
+    }
+    let Some(newest) = source.signature_page(None, None, 1).await?.into_iter().next() else {
+        return Ok(None);
+    };
+    let mark = ChainTx { slot: newest.slot, signature: newest.signature.to_string(), changes: vec![] };
+    // The take-over point first: a live cursor without it would never wait for the backfill
+    repo.apply_chain_tx(TAKEOVER_CURSOR, &mark).await?;
+    repo.apply_chain_tx(LIVE_CURSOR, &mark).await?;
+    info!("indexer taking over after {} (slot {})", newest.signature, newest.slot);
+    repo.fetch_cursor(LIVE_CURSOR).await
+}
+
+// Whether the backfill has replayed up to the take-over point. Until it has, events about
+// positions opened before it have no row to apply to, and the backfill would record its
+// history against rows the live indexer already moved on. A database the live indexer took
+// over before take-over points were kept has none and doesn't wait.
+pub(crate) async fn backfilled(repo: &SharedRepo) -> Result<bool> {
+    let Some(takeover) = repo.fetch_cursor(TAKEOVER_CURSOR).await? else {
+        return Ok(true);
+    };
+    Ok(repo.fetch_cursor(BACKFILL_CURSOR).await?.is_some_and(|b| b.slot >= takeover.slot))
+}
+
+// Fetches and applies one program transaction under `cursor`; a failed one only moves the cursor
+pub(crate) async fn replay(
+    repo: &SharedRepo,
+    source: &dyn ChainSource,
+    program_id: &Pubkey,
+    cursor: &str,
+    info: &SignatureInfo,
+    hub: Option<&WsHub>,
+) -> Result<usize> {
+    if info.failed {
+        let empty = ChainTx { slot: info.slot, signature: info.signature.to_string(), changes: vec![] };
+        return repo.apply_chain_tx(cursor, &empty).await;
+    }
+    apply_events(repo, program_id, cursor, &source.transaction(&info.signature).await?, hub).await
+}
+
+// Applies one transaction's events under `cursor` and, when they were new, publishes them to
+// `hub`
+pub(crate) async fn apply_events(
+    repo: &SharedRepo,
+    program_id: &Pubkey,
+    cursor: &str,
+    events: &TxEvents,
+    hub: Option<&WsHub>,
+) -> Result<usize> {
+    if events.truncated {
+        warn!("{}: logs truncated, later events missing", events.signature);
+    }
+    for (index, e) in &events.skipped {
+        warn!("{} event {index}: {e}", events.signature);
+    }
+    let tx = chain_tx(program_id, events)?;
+    let applied = repo.apply_chain_tx(cursor, &tx).await?;
+    if let (Some(hub), true) = (hub, applied > 0) {
+        publish(repo, hub, &tx).await;
+    }
+    Ok(applied)
+}
+
+// Sends each change as a position.event, and the positions it touched as they are now. A
+// transfer is sent for both positions, so the old owner's subscribers see it too.
+async fn publish(repo: &SharedRepo, hub: &WsHub, tx: &ChainTx) {
+    let mut touched: Vec<Pubkey> = vec![];
+    for change in &tx.changes {
+        let mut pdas = vec![change.position_pda];
+        if let PositionUpdate::Transferred { from, .. } = &change.update {
+            pdas.push(*from);
+        }
+        for pda in pdas {
+            match repo.fetch_position_view(&pda).await {
+                Ok(Some(view)) => hub.position_event(&view, tx.slot, &tx.signature, change),
+                Ok(None) => continue,
+                Err(e) => warn!("{}: not published: {e}", tx.signature),
+            }
+            if !touched.contains(&pda) {
+                touched.push(pda);
+            }
+        }
+    }
+    for pda in touched {
+        if let Ok(Some(view)) = repo.fetch_position_view(&pda).await {
+            hub.position_update(&view);
+        }
+    }
+}
+
+// Reduces decoded events to the position changes the database tracks; events about
+// collateral, the pool or liquidity don't touch positions and are dropped here.
+pub fn chain_tx(program_id: &Pubkey, events: &TxEvents) -> Result<ChainTx> {
+    let mut changes = vec![];
+    for record in &events.events {
+        let position = |owner: &Pubkey, sub_id: u16, symbol: &str| pda::position_pda(program_id, owner, sub_id, symbol).0;
+        // Events from before sub_id was appended to them are all from the default sub-account
+        let (position_pda, update) = match &record.event {
+            PerpEvent::PositionOpened(e) => {
+                let sub_id = e.sub_id.0.unwrap_or(0);
+                (
+                    position(&e.owner, sub_id, &e.symbol),
+                    PositionUpdate::Opened {
+                        owner: e.owner,
+                        sub_id,
+                        symbol: e.symbol.clone(),
+                        side: e.side.into(),
+                        size: e.size,
+                        entry_price: e.entry_price,
+                        margin: e.initial_margin,
+                        leverage: e.leverage,
+                        liquidation_price: e.liquidation_price,
+                    },
+                )
+            }
+            PerpEvent::PositionModified(e) => (
+                position(&e.owner, e.sub_id.0.unwrap_or(0), &e.symbol),
+                PositionUpdate::Modified {
+                    size: e.size,
+                    margin: e.margin,
+                    leverage: e.leverage,
+                    price: e.price,
+                    unrealized_pnl: e.unrealized_pnl,
+                    liquidation_price: e.liquidation_price,
+                },
+            ),
+            PerpEvent::PositionClosed(e) => (
+                position(&e.owner, e.sub_id.0.unwrap_or(0), &e.symbol),
+                PositionUpdate::Closed { size_closed: e.size_closed, exit_price: e.exit_price, realized_pnl: e.realized_pnl },
+            ),
+            PerpEvent::MarginToppedUp(e) => (
+                e.position,
+                PositionUpdate::ToppedUp {
+                    amount: e.amount,
+                    mark_price: e.mark_price,
+                    margin: e.margin,
+                    liquidation_price: e.liquidation_price,
+                },
+            ),
+            PerpEvent::PositionTransferred(e) => (
+                e.position,
+                PositionUpdate::Transferred {
+                    from: position(&e.from_owner, e.from_sub_id, &e.symbol),
+                    owner: e.to_owner,
+                    sub_id: e.to_sub_id,
+                    size: e.size,
+                    margin: e.margin,
+                },
+            ),
+            _ => continue,
+        };
+        changes.push(ChainChange {
+            instruction_index: u32::try_from(record.instruction_index).map_err(|_| anyhow!("instruction index overflow"))?,
+            event_index: u32::try_from(record.event_index).map_err(|_| anyhow!("event index overflow"))?,
+            position_pda,
+            update,
+        });
+    }
+    Ok(ChainTx { slot: events.slot, signature: events.signature.to_string(), changes })
+}
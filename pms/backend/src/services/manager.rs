@@ -1,72 +1,272 @@
 use std::sync::Arc;
 use anyhow::Result;
-use solana_sdk::{pubkey::Pubkey, signature::Signer};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signature::Signature, transaction::Transaction};
+use perp_events::PerpEvent;
+use tracing::warn;
+use perp_sdk::{accounts::Position, constants::MAX_BATCH_POSITIONS, types::BatchLeg, InstructionBuilder, ProgramAccount};
 
-use crate::{models::{OpenPositionInput, ModifyAction, PositionView}, solana::{client::SolanaCtx, pda}, db::repo::PgRepo};
-use super::{margin::MarginCalculator, pnl::PnLTracker};
+use crate::{errors::SvcError, models::{OpenPositionInput, ModifyAction, PositionView, PositionState, SubAccountView, TransferCollateralInput, TransferPositionInput, AutoTopUpInput, CloseAllInput, BatchCloseLeg, BatchReceipt, CloseFill, OperationId, PreparedTx, TxReceipt}, solana::{client::SolanaCtx, pda, tx::Confirmed}, db::SharedRepo};
+use super::operations::Operations;
 
 #[derive(Clone)]
 pub struct PositionManager {
     sol: Arc<SolanaCtx>,
-    repo: PgRepo,
-    margin: MarginCalculator,
-    pnl: PnLTracker,
+    repo: SharedRepo,
+    ops: Operations,
     program_id: Pubkey,
 }
 
 impl PositionManager {
-    pub fn new(sol: SolanaCtx, repo: PgRepo, margin: MarginCalculator, pnl: PnLTracker, program_id: Pubkey) -> Self {
-        Self { sol: Arc::new(sol), repo, margin, pnl, program_id }
+    pub fn new(sol: SolanaCtx, repo: SharedRepo, program_id: Pubkey) -> Self {
+        let ops = Operations::new(repo.clone(), sol.tx.clone());
+        Self { sol: Arc::new(sol), repo, ops, program_id }
     }
 
-    // Opens a position by sending the Anchor instruction "open_position"
-    pub async fn open_position(&self, input: OpenPositionInput) -> Result<Pubkey> {
-        let owner = self.sol.payer.pubkey();
-        let (user_pda, _ub) = pda::user_pda(&self.program_id, &owner);
-        let (position_pda, _pb) = pda::position_pda(&self.program_id, &owner, &input.symbol);
-        let (vault_pda, _vb) = pda::vault_pda(&self.program_id, &input.quote_mint);
-        let (vault_auth_pda, _ab) = pda::vault_authority_pda(&self.program_id);
+    // The service's key: the owner of what open_position opens
+    pub fn signer(&self) -> Pubkey {
+        self.sol.signer.address()
+    }
 
-        // Build and send tx through Anchor client with IDL loaded (recommend embedding IDL JSON)
-        // Pseudo:
-        // self.sol.program
-        //   .request()
-        //   .accounts(position_manager::accounts::OpenPosition { ... })
-        //   .args(position_manager::instruction::OpenPosition { ... })
-        //   .send()?;
+    // Opens a position by sending the Anchor instruction "open_position", with the service's
+    // keypair as owner. The operation is recorded before sending, so the row (opening) exists
+    // before the transaction can land; it turns open once confirmed, closed if the open fails.
+    pub async fn open_position(&self, input: OpenPositionInput) -> Result<TxReceipt> {
+        let (op, ix, position_pda) = self.record_open(&self.sol.signer.address(), &input).await?;
+        self.send(op, ix, position_pda).await
+    }
 
-        // For skeleton purposes, we'll just record intent in DB, actual tx code to be filled once IDL is wired.
-        self.repo.insert_position_open_intent(&owner, &position_pda, &input).await?;
+    pub async fn modify_position(&self, owner: Pubkey, sub_id: u16, symbol: &str, action: ModifyAction) -> Result<TxReceipt> {
+        self.ensure_signer(&owner)?;
+        let (op, ix, position_pda) = self.record_modify(&owner, sub_id, symbol, &action).await?;
+        self.send(op, ix, position_pda).await
+    }
 
-        Ok(position_pda)
+    pub async fn close_position(&self, owner: Pubkey, sub_id: u16, symbol: &str, exit_price: u64) -> Result<TxReceipt> {
+        self.ensure_signer(&owner)?;
+        let (op, ix, position_pda) = self.record_close(&owner, sub_id, symbol, exit_price).await?;
+        self.send(op, ix, position_pda).await
     }
 
-    pub async fn modify_position(&self, owner: Pubkey, symbol: &str, action: ModifyAction) -> Result<()> {
-        let (position_pda, _pb) = pda::position_pda(&self.program_id, &owner, symbol);
+    // ---------- wallet signing (SIGNING_MODE=wallet) ----------
 
-        // TODO: send "modify_position" instruction via Anchor client
-        self.repo.insert_position_modify_intent(&owner, &position_pda, &action).await?;
-        Ok(())
+    // The same operations, returned unsigned for `owner`'s wallet; submit_signed relays them
+    pub async fn prepare_open(&self, owner: Pubkey, input: OpenPositionInput) -> Result<PreparedTx> {
+        let (op, ix, position_pda) = self.record_open(&owner, &input).await?;
+        self.prepare(op, ix, &owner, position_pda).await
     }
 
-    pub async fn close_position(&self, owner: Pubkey, symbol: &str, exit_price: u64, funding_payment: i64) -> Result<()> {
-        let (position_pda, _pb) = pda::position_pda(&self.program_id, &owner, symbol);
+    pub async fn prepare_modify(&self, owner: Pubkey, sub_id: u16, symbol: &str, action: ModifyAction) -> Result<PreparedTx> {
+        let (op, ix, position_pda) = self.record_modify(&owner, sub_id, symbol, &action).await?;
+        self.prepare(op, ix, &owner, position_pda).await
+    }
+
+    pub async fn prepare_close(&self, owner: Pubkey, sub_id: u16, symbol: &str, exit_price: u64) -> Result<PreparedTx> {
+        let (op, ix, position_pda) = self.record_close(&owner, sub_id, symbol, exit_price).await?;
+        self.prepare(op, ix, &owner, position_pda).await
+    }
+
+    // Relays a prepared operation's transaction once the wallet has signed it, and waits for
+    // the configured commitment like the service-signed calls
+    pub async fn submit_signed(&self, op: OperationId, tx: &Transaction) -> Result<TxReceipt> {
+        let operation = self.repo.fetch_operation(op).await?.ok_or_else(|| SvcError::NotFound(format!("operation {op}")))?;
+        let confirmed = self.ops.relay(op, tx).await?;
+        self.receipt(confirmed, operation.target).await
+    }
+
+    async fn prepare(&self, op: OperationId, ix: Instruction, owner: &Pubkey, position_pda: Pubkey) -> Result<PreparedTx> {
+        let (tx, last_valid_block_height) = self.ops.prepare(op, &[ix], owner).await?;
+        Ok(PreparedTx {
+            operation_id: op,
+            transaction: STANDARD.encode(bincode::serialize(&tx)?),
+            blockhash: tx.message.recent_blockhash.to_string(),
+            last_valid_block_height,
+            position_pda,
+        })
+    }
+
+    // ---------- operations shared by both signing modes ----------
+
+    async fn record_open(&self, owner: &Pubkey, input: &OpenPositionInput) -> Result<(OperationId, Instruction, Pubkey)> {
+        // the program's tier table, so an open it would refuse with LeverageExceeded isn't sent
+        let notional = perp_math::notional(input.size, input.entry_price)?;
+        anyhow::ensure!(
+            u64::try_from(notional).ok().and_then(|n| perp_math::leverage_tier(input.leverage, n)).is_some(),
+            SvcError::Invalid(format!("no leverage tier allows {}x on a notional of {notional}", input.leverage))
+        );
+        let (position_pda, _pb) = pda::position_pda(&self.program_id, owner, input.sub_id, &input.symbol);
+        // The pool/vault are per quote mint, so build against the input's mint
+        let ix = InstructionBuilder::new(self.program_id, input.quote_mint)
+            .open_position(owner, input.sub_id, &input.symbol, input.side.into(), input.size, input.leverage, input.entry_price);
+
+        let op = self.repo.insert_position_open_intent(owner, &position_pda, input).await?;
+        Ok((op, ix, position_pda))
+    }
+
+    async fn record_modify(&self, owner: &Pubkey, sub_id: u16, symbol: &str, action: &ModifyAction) -> Result<(OperationId, Instruction, Pubkey)> {
+        let (position_pda, _pb) = pda::position_pda(&self.program_id, owner, sub_id, symbol);
+        let ix = self.sol.instructions().modify_position(owner, sub_id, symbol, action.clone().into());
+
+        let op = self.repo.insert_position_modify_intent(owner, &position_pda, action).await?;
+        Ok((op, ix, position_pda))
+    }
+
+    async fn record_close(&self, owner: &Pubkey, sub_id: u16, symbol: &str, exit_price: u64) -> Result<(OperationId, Instruction, Pubkey)> {
+        let (position_pda, _pb) = pda::position_pda(&self.program_id, owner, sub_id, symbol);
+        let ix = self.sol.instructions().close_position(owner, sub_id, symbol, exit_price);
+
+        let op = self.repo.insert_position_close_intent(owner, &position_pda, exit_price).await?;
+        Ok((op, ix, position_pda))
+    }
+
+    // Signs with the service keypair and waits for the configured commitment, settling the
+    // operation (services/operations.rs), then reads the position account back. Program
+    // rejections surface as TxError::Program.
+    async fn send(&self, op: OperationId, ix: Instruction, position_pda: Pubkey) -> Result<TxReceipt> {
+        let confirmed = self.execute(op, ix).await?;
+        self.receipt(confirmed, position_pda).await
+    }
+
+    async fn receipt(&self, confirmed: Confirmed, position_pda: Pubkey) -> Result<TxReceipt> {
+        let position = match self.sol.tx.account_data(&position_pda).await? {
+            Some(data) => Some(PositionView::from_account(position_pda, &Position::try_deserialize(&data)?)),
+            None => None,
+        };
+        let closed = match position {
+            Some(_) => None,
+            None => self.close_fill(&confirmed.signature, &position_pda).await,
+        };
+        Ok(TxReceipt { signature: confirmed.signature.to_string(), slot: confirmed.slot, position_pda, position, closed })
+    }
 
-        // TODO: send "close_position" instruction via Anchor client
-        self.repo.insert_position_close_intent(&owner, &position_pda, exit_price, funding_payment).await?;
+    // The PositionClosed the transaction logged for the position. The transaction has landed by
+    // now, so failing to read it back leaves the receipt without the fill rather than failing.
+    async fn close_fill(&self, signature: &Signature, position_pda: &Pubkey) -> Option<CloseFill> {
+        let logs = match self.sol.tx.logs(signature).await {
+            Ok(logs) => logs?,
+            Err(e) => {
+                warn!("reading the logs of {signature}: {e}");
+                return None;
+            }
+        };
+        let events = perp_events::parse_logs(&self.program_id, 0, *signature, &logs).events;
+        events.into_iter().find_map(|record| match record.event {
+            PerpEvent::PositionClosed(e) => {
+                let pda = pda::position_pda(&self.program_id, &e.owner, e.sub_id.0.unwrap_or(0), &e.symbol).0;
+                (pda == *position_pda).then_some(CloseFill { size_closed: e.size_closed, exit_price: e.exit_price, realized_pnl: e.realized_pnl, payout: e.payout })
+            }
+            _ => None,
+        })
+    }
+
+    async fn execute(&self, op: OperationId, ix: Instruction) -> Result<Confirmed> {
+        Ok(self.ops.execute(op, &[ix], &*self.sol.signer).await?)
+    }
+
+    // The owner has to sign, and the service only holds its own key
+    fn ensure_signer(&self, owner: &Pubkey) -> Result<()> {
+        let signer = self.sol.signer.address();
+        anyhow::ensure!(*owner == signer, SvcError::Conflict(format!("position owner {owner} is not the service signer {signer}")));
         Ok(())
     }
 
+    // Closes (or trims by reduce_bps) every open position of a sub-account with
+    // "close_all_positions"; books larger than one batch are split per transaction.
+    pub async fn close_all_positions(&self, owner: Pubkey, input: CloseAllInput) -> Result<BatchReceipt> {
+        self.ensure_signer(&owner)?;
+        anyhow::ensure!(input.reduce_bps > 0 && input.reduce_bps <= 10_000, SvcError::Invalid("reduce_bps must be in 1..=10000".into()));
+        let positions = self.repo.fetch_positions_by_owner(&owner, Some(input.sub_id)).await?;
+
+        let mut legs = Vec::new();
+        for p in positions.iter().filter(|p| matches!(p.state, PositionState::Open | PositionState::Modifying)) {
+            let price = *input.prices.get(&p.symbol).ok_or_else(|| SvcError::Invalid(format!("missing price for {}", p.symbol)))?;
+            legs.push(BatchCloseLeg {
+                position_pda: p.pda,
+                symbol: p.symbol.clone(),
+                price,
+            });
+        }
+
+        // one transaction, so every leg settles or none does; a bigger book has to be
+        // closed in explicit calls (e.g. per sub-account)
+        anyhow::ensure!(
+            legs.len() <= MAX_BATCH_POSITIONS,
+            SvcError::Invalid(format!("{} positions to close, one transaction takes at most {MAX_BATCH_POSITIONS}", legs.len()))
+        );
+        if legs.is_empty() {
+            return Ok(BatchReceipt { positions: Vec::new(), signature: None });
+        }
+
+        let (user_pda, _) = pda::user_pda(&self.program_id, &owner, input.sub_id);
+        let ix_legs: Vec<(&str, BatchLeg)> = legs.iter().map(|l| (l.symbol.as_str(), BatchLeg { price: l.price })).collect();
+        let ix = self.sol.instructions().close_all_positions(&owner, input.sub_id, &ix_legs, input.reduce_bps);
+        let op = self.repo.insert_batch_close_intent(&owner, &user_pda, input.reduce_bps, &legs).await?;
+        let signature = self.execute(op, ix).await?.signature.to_string();
+        Ok(BatchReceipt { positions: legs.iter().map(|l| l.position_pda).collect(), signature: Some(signature) })
+    }
+
     // Query on-chain position (via IDL) or from DB snapshot
-    pub async fn get_position(&self, owner: Pubkey, symbol: &str) -> Result<Option<PositionView>> {
-        let (pda, _) = pda::position_pda(&self.program_id, &owner, symbol);
-        // If you embed the IDL, you can do:
-        // let pos: OnChainPosition = self.sol.program.account(pda)?;
-        // Convert to PositionView and return.
+    pub async fn get_position(&self, owner: Pubkey, sub_id: u16, symbol: &str) -> Result<Option<PositionView>> {
+        let (pda, _) = pda::position_pda(&self.program_id, &owner, sub_id, symbol);
+        // On-chain: fetch the account data and decode with perp_sdk::accounts::Position::try_deserialize
         self.repo.fetch_position_view(&pda).await
     }
 
-    pub async fn list_positions_by_user(&self, owner: Pubkey) -> Result<Vec<PositionView>> {
-        self.repo.fetch_positions_by_owner(&owner).await
+    // `sub_id: None` lists positions across every sub-account of the owner
+    pub async fn list_positions_by_user(&self, owner: Pubkey, sub_id: Option<u16>) -> Result<Vec<PositionView>> {
+        self.repo.fetch_positions_by_owner(&owner, sub_id).await
+    }
+
+    pub async fn list_sub_accounts(&self, owner: Pubkey) -> Result<Vec<SubAccountView>> {
+        self.repo.fetch_sub_accounts(&owner).await
+    }
+
+    // Moves free collateral between two sub-accounts ("transfer_collateral" instruction)
+    pub async fn transfer_collateral(&self, owner: Pubkey, input: TransferCollateralInput) -> Result<Confirmed> {
+        self.ensure_signer(&owner)?;
+        let (from_pda, _) = pda::user_pda(&self.program_id, &owner, input.from_sub_id);
+        let (to_pda, _) = pda::user_pda(&self.program_id, &owner, input.to_sub_id);
+        let ix = self.sol.instructions().transfer_collateral(&owner, input.from_sub_id, input.to_sub_id, input.amount);
+
+        let op = self.repo.insert_collateral_transfer_intent(&owner, &from_pda, &to_pda, &input).await?;
+        self.execute(op, ix).await
+    }
+
+    // Moves a position to another owner's sub-account ("transfer_position"); the receipt is
+    // for the position's new PDA, which is derived from the recipient.
+    pub async fn transfer_position(&self, owner: Pubkey, sub_id: u16, symbol: &str, input: TransferPositionInput) -> Result<TxReceipt> {
+        self.ensure_signer(&owner)?;
+        if !input.recipient_approved {
+            self.ensure_signer(&input.to_owner)?;
+        }
+        let (from_pda, _) = pda::position_pda(&self.program_id, &owner, sub_id, symbol);
+        let (to_pda, _) = pda::position_pda(&self.program_id, &input.to_owner, input.to_sub_id, symbol);
+        // Without a prior approval the recipient has to co-sign
+        let ix = self.sol.instructions()
+            .transfer_position(&owner, sub_id, symbol, &input.to_owner, input.to_sub_id, !input.recipient_approved);
+
+        let op = self.repo.insert_position_transfer_intent(&owner, &from_pda, &to_pda, &input).await?;
+        self.send(op, ix, to_pda).await
+    }
+
+    // Opts the position into auto_top_up ("set_auto_top_up"); the monitor submits the
+    // top-ups once MR falls below target_mr.
+    pub async fn set_auto_top_up(&self, owner: Pubkey, sub_id: u16, symbol: &str, input: AutoTopUpInput) -> Result<Confirmed> {
+        self.ensure_signer(&owner)?;
+        anyhow::ensure!(input.target_mr > 0 && input.target_mr < 1_000_000, SvcError::Invalid("target_mr must be in 1..1000000".into()));
+        let (position_pda, _) = pda::position_pda(&self.program_id, &owner, sub_id, symbol);
+        let ix = self.sol.instructions().set_auto_top_up(&owner, sub_id, symbol, input.target_mr, input.max_total);
+
+        let op = self.repo.upsert_auto_top_up_intent(&owner, &position_pda, Some(&input)).await?;
+        self.execute(op, ix).await
+    }
+
+    pub async fn disable_auto_top_up(&self, owner: Pubkey, sub_id: u16, symbol: &str) -> Result<Confirmed> {
+        self.ensure_signer(&owner)?;
+        let (position_pda, _) = pda::position_pda(&self.program_id, &owner, sub_id, symbol);
+        let ix = self.sol.instructions().disable_auto_top_up(&owner, sub_id, symbol);
+
+        let op = self.repo.upsert_auto_top_up_intent(&owner, &position_pda, None).await?;
+        self.execute(op, ix).await
     }
-}
\ No newline at end of file
+}
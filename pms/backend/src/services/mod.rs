@@ -1,5 +1,7 @@
+pub mod backfill;
+pub mod indexer;
 pub mod manager;
-pub mod margin;
 pub mod monitor;
+pub mod operations;
 pub mod oracle;
-pub mod pnl;
\ No newline at end of file
+pub mod reconciler;
\ No newline at end of file
@@ -1,26 +1,77 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 use anyhow::Result;
-use tracing::info;
+use async_trait::async_trait;
+use chrono::Utc;
+use perp_sdk::{accounts::PoolMarket, ProgramAccount};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use tracing::{info, warn};
 
-use crate::{db::repo::PgRepo, solana::client::SolanaCtx};
-use super::oracle::{PriceOracle, MockOracle};
-use crate::models::{PositionView, PositionState};
+use crate::{api::ws::WsHub, db::SharedRepo, solana::{client::SolanaCtx, pda}};
+use super::{operations::Operations, oracle::{PriceOracle, MockOracle}};
+use crate::models::PositionView;
+
+// Where the monitor reads pool markets; RpcMarkets in production
+#[async_trait]
+pub trait MarketSource: Send + Sync {
+    // The configured pool's market for `symbol`, None if it was never initialized
+    async fn pool_market(&self, symbol: &str) -> Result<Option<PoolMarket>>;
+}
+
+pub struct RpcMarkets {
+    client: RpcClient,
+    program_id: Pubkey,
+    pool: Pubkey,
+}
+
+impl RpcMarkets {
+    pub fn new(rpc_url: &str, program_id: Pubkey, quote_mint: Pubkey) -> Self {
+        let pool = pda::pool_pda(&program_id, &quote_mint).0;
+        Self { client: RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed()), program_id, pool }
+    }
+}
+
+#[async_trait]
+impl MarketSource for RpcMarkets {
+    async fn pool_market(&self, symbol: &str) -> Result<Option<PoolMarket>> {
+        let address = pda::pool_market_pda(&self.program_id, &self.pool, symbol).0;
+        let account = self.client.get_account_with_commitment(&address, CommitmentConfig::confirmed()).await?.value;
+        Ok(match account {
+            Some(account) => Some(PoolMarket::try_deserialize(&account.data)?),
+            None => None,
+        })
+    }
+}
 
 #[derive(Clone)]
 pub struct PositionMonitor {
-    sol: std::sync::Arc<SolanaCtx>,
-    repo: PgRepo,
+    sol: Arc<SolanaCtx>,
+    repo: SharedRepo,
+    ops: Operations,
+    markets: Arc<dyn MarketSource>,
     oracle_src: String,
     alert_threshold: f64,
+    hub: Option<WsHub>,
 }
 
 impl PositionMonitor {
-    pub fn new(sol: SolanaCtx, repo: PgRepo, oracle_src: String, alert_threshold: f64) -> Self {
-        Self { sol: std::sync::Arc::new(sol), repo, oracle_src, alert_threshold }
+    pub fn new(sol: SolanaCtx, repo: SharedRepo, oracle_src: String, alert_threshold: f64) -> Self {
+        let ops = Operations::new(repo.clone(), sol.tx.clone());
+        let markets = Arc::new(RpcMarkets::new(&sol.rpc_url, sol.program_id, sol.quote_mint));
+        Self { sol: Arc::new(sol), repo, ops, markets, oracle_src, alert_threshold, hub: None }
+    }
+
+    // Publishes each scan's PnL and margin alerts to /ws subscribers
+    pub fn with_hub(mut self, hub: WsHub) -> Self {
+        self.hub = Some(hub);
+        self
     }
 
     pub async fn run(self) -> Result<()> {
-        let oracle = MockOracle::default(); // replace with Pyth impl
+        if self.oracle_src != "mock" {
+            warn!("PRICE_ORACLE_SOURCE={} is not implemented yet, using mock prices", self.oracle_src);
+        }
+        let oracle = MockOracle; // replace with Pyth impl
         loop {
             self.scan(&oracle).await?;
             tokio::time::sleep(Duration::from_secs(2)).await;
@@ -39,22 +90,70 @@ impl PositionMonitor {
 
     async fn check_position(&self, p: &PositionView, oracle: &impl PriceOracle) -> Result<()> {
         let price = oracle.price(&p.symbol).await?;
-        let size = p.size as f64;
-        let entry = p.entry_price as f64;
-        let collateral = p.margin as f64;
-
-        let upnl = if matches!(p.side, crate::models::Side::Long) {
-            size * (price - entry)
-        } else {
-            size * (entry - price)
-        };
-        let mr = (collateral + upnl) / (size * price).max(1.0);
+        let mark_price = price.round() as u64;
+
+        let (upnl, mr) = health_at(p, mark_price)?;
 
         if mr < self.alert_threshold {
             info!("ALERT: {:?} {} MR={:.4} at price {:.2}", p.owner, p.symbol, mr, price);
             self.repo.insert_liq_alert(p, mr, price).await?;
+            if let Some(hub) = &self.hub {
+                hub.margin_alert(p, mark_price, mr);
+            }
+        }
+        if let Some(top_up) = &p.auto_top_up {
+            if top_up.remaining() > 0 {
+                self.check_auto_top_up(p, top_up.target_ratio()).await?;
+            }
+        }
+        let upnl = i64::try_from(upnl)?;
+        self.repo.upsert_position_snapshot(p, price, upnl, mr).await?;
+        if let Some(hub) = &self.hub {
+            hub.pnl_update(p, mark_price, upnl, mr);
+        }
+        Ok(())
+    }
+
+    // The program only tops up below the target at the pool market's fresh mark, so that
+    // is the price checked here, not the oracle's: a stale mark or one above the target
+    // would just fail on-chain.
+    async fn check_auto_top_up(&self, p: &PositionView, target_ratio: f64) -> Result<()> {
+        let Some(market) = self.markets.pool_market(&p.symbol).await? else {
+            return Ok(());
+        };
+        let Some(mark_price) = market.fresh_mark(Utc::now().timestamp()) else {
+            return Ok(());
+        };
+        if health_at(p, mark_price)?.1 < target_ratio {
+            self.submit_auto_top_up(p, mark_price).await?;
         }
-        self.repo.upsert_position_snapshot(p, price, upnl as i64, mr).await?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    // auto_top_up is permissionless; the monitor signs as keeper. The program re-checks
+    // MR against the owner's settings and sizes the top-up itself; the intent records the
+    // mark the monitor saw. A top-up still in flight from an earlier scan isn't repeated.
+    async fn submit_auto_top_up(&self, p: &PositionView, mark_price: u64) -> Result<()> {
+        let keeper = self.sol.signer.address();
+        let Some(op) = self.repo.insert_auto_top_up_intent(&keeper, &p.pda, mark_price).await? else {
+            return Ok(());
+        };
+        info!("AUTO TOP-UP: {:?} {} at mark {}", p.owner, p.symbol, mark_price);
+
+        let ix = self.sol.instructions().auto_top_up(&keeper, &p.owner, p.sub_id, &p.symbol);
+        self.ops.execute(op, &[ix], &*self.sol.signer).await?;
+        Ok(())
+    }
+}
+
+// uPnL and margin ratio at `mark_price`, with the same fixed-point math as the program,
+// so alerts fire where liquidation would
+fn health_at(p: &PositionView, mark_price: u64) -> Result<(i128, f64)> {
+    let upnl = perp_math::unrealized_pnl(p.side.into(), p.size, p.entry_price, mark_price)?;
+    let equity = perp_math::equity(p.margin, upnl)?;
+    let mr = match perp_math::margin_ratio(equity, perp_math::notional(p.size, mark_price)?)? {
+        Some(mr) => mr as f64 / perp_math::RATE_SCALE as f64,
+        None => f64::INFINITY,
+    };
+    Ok((upnl, mr))
+}
@@ -0,0 +1,206 @@
+use std::time::Duration;
+use anyhow::{bail, ensure, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use serde::Serialize;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::Transaction,
+};
+use tracing::{info, warn};
+
+use crate::{
+    db::SharedRepo,
+    errors::{SvcError, TxError},
+    models::{Operation, OperationId, OperationStatus},
+    solana::{
+        signer::TxSigner,
+        tx::{Confirmed, SendLog, TxSubmitter},
+    },
+};
+
+// How long to wait before asking again when the RPC fails while tracking a transaction
+const RETRY_EVERY: Duration = Duration::from_secs(5);
+
+// Drives operations rows through their lifecycle.
+//
+// The repo's insert_*_intent methods record an operation as intent and put its positions in
+// an in-flight state (opening, modifying, closing). execute() then signs and sends the
+// transaction, recording each signature as submitted before it is sent, and finishes the
+// operation as confirmed or failed, which moves the positions on (see
+// db::operation_transitions). Since the signature is written first, an operation whose
+// process died can always be settled: recover() fails the ones that never got sent and
+// follows the rest to confirmation or blockhash expiry.
+//
+// In wallet mode prepare() stores the unsigned message instead of sending, and relay() takes
+// the signed transaction back; from there it is tracked the same way.
+#[derive(Clone)]
+pub struct Operations {
+    repo: SharedRepo,
+    tx: TxSubmitter,
+}
+
+// What recover() did with the operations it found in flight
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RecoveryReport {
+    // Recorded (or prepared for a wallet) but never sent
+    pub expired: usize,
+    pub confirmed: usize,
+    pub failed: usize,
+}
+
+struct OperationLog<'a> {
+    repo: &'a SharedRepo,
+    id: OperationId,
+}
+
+#[async_trait]
+impl SendLog for OperationLog<'_> {
+    async fn signed(&self, signature: &Signature, last_valid_block_height: u64) -> Result<()> {
+        self.repo.mark_submitted(self.id, &signature.to_string(), last_valid_block_height).await
+    }
+}
+
+impl Operations {
+    pub fn new(repo: SharedRepo, tx: TxSubmitter) -> Self {
+        Self { repo, tx }
+    }
+
+    // Sends operation `id`'s transaction and settles it. If the RPC fails after a transaction
+    // went out, that transaction may still land, so the operation stays submitted and is
+    // followed in the background; the caller gets the RPC error either way.
+    pub async fn execute(&self, id: OperationId, instructions: &[Instruction], payer: &dyn TxSigner) -> Result<Confirmed, TxError> {
+        let log = OperationLog { repo: &self.repo, id };
+        let result = self.tx.submit_logged(instructions, payer, Some(&log)).await;
+        self.settle(id, result).await
+    }
+
+    // Builds operation `id`'s transaction unsigned, for `payer`'s wallet, and stores its
+    // message. If nothing is relayed for it by the time its blockhash expires, the operation
+    // fails.
+    pub async fn prepare(&self, id: OperationId, instructions: &[Instruction], payer: &Pubkey) -> Result<(Transaction, u64)> {
+        let (tx, last_valid) = self.tx.prepare(instructions, payer).await?;
+        self.repo.record_prepared(id, &tx.message_data(), last_valid).await?;
+        let this = self.clone();
+        tokio::spawn(async move { this.expire_unsubmitted(id, last_valid).await });
+        Ok((tx, last_valid))
+    }
+
+    // Relays the wallet-signed transaction of a prepared operation and settles it like
+    // execute(). Only the prepared message, fully signed, is accepted.
+    pub async fn relay(&self, id: OperationId, tx: &Transaction) -> Result<Confirmed> {
+        let op = self.repo.fetch_operation(id).await?.ok_or_else(|| SvcError::NotFound(format!("operation {id}")))?;
+        ensure!(op.status == OperationStatus::Intent, SvcError::Conflict(format!("operation {id} is already {:?}", op.status)));
+        let (Some(message), Some(last_valid)) = (&op.message, op.last_valid_block_height) else {
+            bail!(SvcError::Conflict(format!("operation {id} wasn't prepared for a wallet")));
+        };
+        ensure!(tx.message_data() == *message, SvcError::Invalid(format!("transaction doesn't match the one prepared for operation {id}")));
+        tx.verify().map_err(|e| SvcError::Invalid(format!("transaction isn't fully signed: {e}")))?;
+
+        let log = OperationLog { repo: &self.repo, id };
+        let result = self.tx.relay(tx, last_valid, Some(&log)).await;
+        Ok(self.settle(id, result).await?)
+    }
+
+    async fn settle(&self, id: OperationId, result: Result<Confirmed, TxError>) -> Result<Confirmed, TxError> {
+        match &result {
+            Ok(_) => self.finish(id, OperationStatus::Confirmed, None).await,
+            Err(e) => {
+                if let TxError::Rpc(_) = e {
+                    if let Ok(Some(op)) = self.repo.fetch_operation(id).await {
+                        if op.status == OperationStatus::Submitted {
+                            warn!("operation {id}: {e}; tracking {} in the background", op.signature.as_deref().unwrap_or("-"));
+                            let this = self.clone();
+                            tokio::spawn(async move { this.track(&op).await });
+                            return result;
+                        }
+                    }
+                }
+                self.finish(id, OperationStatus::Failed, Some(&e.to_string())).await
+            }
+        }
+        result
+    }
+
+    // Fails a prepared operation that is still unsubmitted once its blockhash has expired,
+    // when the wallet can no longer get it on-chain
+    async fn expire_unsubmitted(&self, id: OperationId, last_valid: u64) -> bool {
+        while let Err(e) = self.tx.wait_for_expiry(last_valid).await {
+            warn!("operation {id}: {e}; retrying");
+            tokio::time::sleep(RETRY_EVERY).await;
+        }
+        match self.repo.fetch_operation(id).await {
+            Ok(Some(op)) if op.status == OperationStatus::Intent => {
+                self.finish(id, OperationStatus::Failed, Some("prepared transaction expired before it was submitted")).await;
+                true
+            }
+            Ok(_) => false,
+            Err(e) => {
+                warn!("operation {id}: {e:?}");
+                false
+            }
+        }
+    }
+
+    // Settles every operation still in flight that was created before `before` (the
+    // service's start). Runs until each has an outcome.
+    pub async fn recover(&self, before: DateTime<Utc>) -> Result<RecoveryReport> {
+        let mut report = RecoveryReport::default();
+        let (mut submitted, mut prepared) = (vec![], vec![]);
+        for op in self.repo.fetch_inflight_operations(before).await? {
+            match (op.status, op.last_valid_block_height) {
+                (OperationStatus::Submitted, _) => submitted.push(op),
+                // Handed to a wallet, which may still send it until the blockhash expires
+                (_, Some(last_valid)) => prepared.push((op.id, last_valid)),
+                _ => {
+                    self.finish(op.id, OperationStatus::Failed, Some("not submitted before the service restarted")).await;
+                    report.expired += 1;
+                }
+            }
+        }
+        let waits = prepared.iter().map(|&(id, last_valid)| self.expire_unsubmitted(id, last_valid));
+        report.expired += join_all(waits).await.into_iter().filter(|expired| *expired).count();
+        for status in join_all(submitted.iter().map(|op| self.track(op))).await {
+            match status {
+                OperationStatus::Confirmed => report.confirmed += 1,
+                _ => report.failed += 1,
+            }
+        }
+        info!("recovered operations: {report:?}");
+        Ok(report)
+    }
+
+    // Follows a submitted operation's last signed transaction until it confirms, fails or its
+    // blockhash expires, and finishes the operation accordingly
+    async fn track(&self, op: &Operation) -> OperationStatus {
+        let (status, error) = match (op.signature.as_deref().map(str::parse::<Signature>), op.last_valid_block_height) {
+            (Some(Ok(signature)), Some(last_valid)) => loop {
+                match self.tx.await_signature(&signature, last_valid).await {
+                    Ok(Some(_)) => break (OperationStatus::Confirmed, None),
+                    Ok(None) => break (OperationStatus::Failed, Some("blockhash expired before the transaction landed".to_string())),
+                    Err(TxError::Rpc(e)) => {
+                        warn!("operation {}: {e}; retrying", op.id);
+                        tokio::time::sleep(RETRY_EVERY).await;
+                    }
+                    Err(e) => break (OperationStatus::Failed, Some(e.to_string())),
+                }
+            },
+            _ => (OperationStatus::Failed, Some("submitted without a valid signature".to_string())),
+        };
+        self.finish(op.id, status, error.as_deref()).await;
+        status
+    }
+
+    // A failure to record the outcome is only logged: the operation stays in flight and the
+    // next start's recovery settles it
+    async fn finish(&self, id: OperationId, status: OperationStatus, error: Option<&str>) {
+        match self.repo.finish_operation(id, status, error).await {
+            Ok(true) => info!("operation {id} {status:?}{}", error.map(|e| format!(": {e}")).unwrap_or_default()),
+            Ok(false) => {}
+            Err(e) => warn!("operation {id}: couldn't record {status:?}: {e:?}"),
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use std::{collections::HashMap, fmt::Display, sync::Arc, time::{Duration, Instant}};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use perp_sdk::accounts::{Position, ProgramAccount, UserAccount};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::{
+    db::{state_str, SharedRepo},
+    models::{Drift, DriftAccount, PositionState, PositionView, ReconcileReport, StoredPosition, SubAccountView},
+    solana::pda,
+};
+
+const RECONCILE_EVERY: Duration = Duration::from_secs(300);
+
+// The program's Position and UserAccount accounts, read at (or after) `slot`
+#[derive(Debug, Clone, Default)]
+pub struct ChainSnapshot {
+    pub slot: u64,
+    pub positions: Vec<(Pubkey, Position)>,
+    pub users: Vec<(Pubkey, UserAccount)>,
+}
+
+// Where account state comes from; RpcAccounts in production
+#[async_trait]
+pub trait AccountSource: Send + Sync {
+    async fn snapshot(&self) -> Result<ChainSnapshot>;
+}
+
+pub struct RpcAccounts {
+    client: RpcClient,
+    program_id: Pubkey,
+}
+
+impl RpcAccounts {
+    pub fn new(rpc_url: &str, program_id: Pubkey) -> Self {
+        Self { client: RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed()), program_id }
+    }
+
+    // Every account of type T, selected by its discriminator. Accounts that don't decode are
+    // logged and left out.
+    async fn accounts<T: ProgramAccount>(&self) -> Result<Vec<(Pubkey, T)>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, T::discriminator().to_vec()))]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let accounts = self.client.get_program_accounts_with_config(&self.program_id, config).await?;
+        Ok(accounts
+            .into_iter()
+            .filter_map(|(address, account)| match T::try_deserialize(&account.data) {
+                Ok(decoded) => Some((address, decoded)),
+                Err(e) => {
+                    warn!("reconciler: {} {address} doesn't decode: {e}", T::NAME);
+                    None
+                }
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl AccountSource for RpcAccounts {
+    // The slot is read first, so every account is at least as new as it
+    async fn snapshot(&self) -> Result<ChainSnapshot> {
+        let slot = self.client.get_slot().await?;
+        Ok(ChainSnapshot { slot, positions: self.accounts().await?, users: self.accounts().await? })
+    }
+}
+
+// Compares the program's accounts with the positions and users tables, field by field.
+//
+// Value fields (sizes, prices, margin, collateral, PnL) are safe to repair: the account is
+// authoritative and the indexer would have written the same values had it seen every event,
+// so they are overwritten. Identity fields (owner, sub-account, symbol, side), an account at
+// an address other than its PDA, and open/closed disagreements mean the database and the
+// chain describe different things; those are left alone, logged as DRIFT and reported.
+//
+// Rows the indexer wrote after the snapshot's slot, and positions with an operation in
+// flight (modifying, closing, liquidating), are skipped rather than reported.
+#[derive(Clone)]
+pub struct Reconciler {
+    repo: SharedRepo,
+    source: Arc<dyn AccountSource>,
+    program_id: Pubkey,
+    latest: Arc<RwLock<Option<ReconcileReport>>>,
+}
+
+impl Reconciler {
+    pub fn new(repo: SharedRepo, source: Arc<dyn AccountSource>, program_id: Pubkey) -> Self {
+        Self { repo, source, program_id, latest: Arc::new(RwLock::new(None)) }
+    }
+
+    pub async fn run(self) -> Result<()> {
+        loop {
+            if let Err(e) = self.reconcile_once().await {
+                warn!("reconcile failed: {:?}", e);
+            }
+            tokio::time::sleep(RECONCILE_EVERY).await;
+        }
+    }
+
+    // The most recent run's report, if one has finished
+    pub async fn latest(&self) -> Option<ReconcileReport> {
+        self.latest.read().await.clone()
+    }
+
+    pub async fn reconcile_once(&self) -> Result<ReconcileReport> {
+        let started = Instant::now();
+        let ran_at = Utc::now();
+        let snapshot = self.source.snapshot().await?;
+        let stored = self.repo.fetch_stored_positions().await?;
+        let users = self.repo.fetch_all_sub_accounts().await?;
+
+        let mut drifts = self.reconcile_positions(&snapshot, stored).await?;
+        drifts.extend(self.reconcile_users(&snapshot, users).await?);
+
+        let report = ReconcileReport {
+            ran_at,
+            slot: snapshot.slot,
+            duration_ms: u64::try_from(started.elapsed().as_millis())?,
+            positions_checked: snapshot.positions.len(),
+            users_checked: snapshot.users.len(),
+            drifts,
+        };
+        for d in report.unresolved() {
+            warn!("DRIFT: {:?} {} {}: chain={} db={}", d.account, d.address, d.field, d.chain, d.db);
+        }
+        info!(
+            "reconciled {} positions, {} users at slot {}: {} drifted, {} repaired",
+            report.positions_checked,
+            report.users_checked,
+            report.slot,
+            report.drifts.len(),
+            report.repaired()
+        );
+        self.repo.record_reconcile(&report).await?;
+        *self.latest.write().await = Some(report.clone());
+        Ok(report)
+    }
+
+    async fn reconcile_positions(&self, snapshot: &ChainSnapshot, stored: Vec<StoredPosition>) -> Result<Vec<Drift>> {
+        let mut stored: HashMap<Pubkey, StoredPosition> = stored.into_iter().map(|s| (s.view.pda, s)).collect();
+        let mut drifts = vec![];
+
+        for (address, account) in &snapshot.positions {
+            let chain = PositionView::from_account(*address, account);
+            let expected = pda::position_pda(&self.program_id, &chain.owner, chain.sub_id, &chain.symbol).0;
+            if expected != *address {
+                drifts.push(unsafe_drift(DriftAccount::Position, *address, "address", expected, "-"));
+                stored.remove(address);
+                continue;
+            }
+            let Some(row) = stored.remove(address) else {
+                let repaired = self.repo.repair_position(&chain, snapshot.slot).await?;
+                drifts.push(Drift {
+                    account: DriftAccount::Position,
+                    address: *address,
+                    field: "row".to_string(),
+                    chain: "present".to_string(),
+                    db: "missing".to_string(),
+                    repaired,
+                });
+                continue;
+            };
+            if in_flight(&row, snapshot.slot) {
+                continue;
+            }
+            let db = &row.view;
+
+            let identity = [
+                ("owner", chain.owner.to_string(), db.owner.to_string()),
+                ("sub_id", chain.sub_id.to_string(), db.sub_id.to_string()),
+                ("symbol", chain.symbol.clone(), db.symbol.clone()),
+                ("side", format!("{:?}", chain.side), format!("{:?}", db.side)),
+            ];
+            let mismatched: Vec<Drift> = identity
+                .into_iter()
+                .filter(|(_, c, d)| c != d)
+                .map(|(field, c, d)| unsafe_drift(DriftAccount::Position, *address, field, c, d))
+                .collect();
+            if !mismatched.is_empty() {
+                drifts.extend(mismatched);
+                continue;
+            }
+            if db.state == PositionState::Closed {
+                drifts.push(unsafe_drift(DriftAccount::Position, *address, "state", "open", "closed"));
+                continue;
+            }
+
+            let mut found = vec![];
+            if db.state == PositionState::Opening {
+                // The open landed but its event hasn't been indexed yet
+                found.push(("state", "open".to_string(), "opening".to_string()));
+            }
+            found.extend(diff_fields([
+                ("size", chain.size.to_string(), db.size.to_string()),
+                ("entry_price", chain.entry_price.to_string(), db.entry_price.to_string()),
+                ("margin", chain.margin.to_string(), db.margin.to_string()),
+                ("leverage", chain.leverage.to_string(), db.leverage.to_string()),
+                ("realized_pnl", chain.realized_pnl.to_string(), db.realized_pnl.to_string()),
+                ("liquidation_price", chain.liquidation_price.to_string(), db.liquidation_price.to_string()),
+            ]));
+            if found.is_empty() {
+                continue;
+            }
+            let repaired = self.repo.repair_position(&chain, snapshot.slot).await?;
+            drifts.extend(found.into_iter().map(|(field, chain, db)| Drift {
+                account: DriftAccount::Position,
+                address: *address,
+                field: field.to_string(),
+                chain,
+                db,
+                repaired,
+            }));
+        }
+
+        // Rows with no account: the program closes Position accounts, so a row still open
+        // means a close (or liquidation) the indexer missed. Unsent opens have no account yet.
+        for row in stored.into_values() {
+            let db = &row.view;
+            if matches!(db.state, PositionState::Closed | PositionState::Opening) || in_flight(&row, snapshot.slot) {
+                continue;
+            }
+            drifts.push(unsafe_drift(DriftAccount::Position, db.pda, "state", "missing", state_str(db.state)));
+        }
+        Ok(drifts)
+    }
+
+    async fn reconcile_users(&self, snapshot: &ChainSnapshot, stored: Vec<SubAccountView>) -> Result<Vec<Drift>> {
+        let stored: HashMap<Pubkey, SubAccountView> = stored.into_iter().map(|u| (u.pda, u)).collect();
+        let mut drifts = vec![];
+
+        // Users rows without an account are left alone: operations record them before the
+        // program creates the account
+        for (address, account) in &snapshot.users {
+            let chain = chain_sub_account(&self.program_id, account);
+            if chain.pda != *address {
+                drifts.push(unsafe_drift(DriftAccount::User, *address, "address", chain.pda, "-"));
+                continue;
+            }
+            let found: Vec<(&str, String, String)> = match stored.get(address) {
+                None => vec![("row", "present".to_string(), "missing".to_string())],
+                Some(db) => diff_fields([
+                    ("margin_mode", format!("{:?}", chain.margin_mode), format!("{:?}", db.margin_mode)),
+                    ("total_collateral", chain.total_collateral.to_string(), db.total_collateral.to_string()),
+                    ("locked_collateral", chain.locked_collateral.to_string(), db.locked_collateral.to_string()),
+                    ("total_pnl", chain.total_pnl.to_string(), db.total_pnl.to_string()),
+                ])
+                .collect(),
+            };
+            if found.is_empty() {
+                continue;
+            }
+            self.repo.repair_sub_account(&chain).await?;
+            drifts.extend(found.into_iter().map(|(field, chain, db)| Drift {
+                account: DriftAccount::User,
+                address: *address,
+                field: field.to_string(),
+                chain,
+                db,
+                repaired: true,
+            }));
+        }
+        Ok(drifts)
+    }
+}
+
+// Written from an event newer than the snapshot, or waiting on a transaction; either way the
+// account may not reflect it yet
+fn in_flight(row: &StoredPosition, slot: u64) -> bool {
+    row.last_slot.is_some_and(|s| s > slot)
+        || matches!(row.view.state, PositionState::Modifying | PositionState::Closing | PositionState::Liquidating)
+}
+
+fn diff_fields<const N: usize>(fields: [(&str, String, String); N]) -> impl Iterator<Item = (&str, String, String)> {
+    fields.into_iter().filter(|(_, chain, db)| chain != db)
+}
+
+fn unsafe_drift(account: DriftAccount, address: Pubkey, field: &str, chain: impl Display, db: impl Display) -> Drift {
+    Drift { account, address, field: field.to_string(), chain: chain.to_string(), db: db.to_string(), repaired: false }
+}
+
+fn chain_sub_account(program_id: &Pubkey, u: &UserAccount) -> SubAccountView {
+    SubAccountView {
+        owner: u.owner,
+        sub_id: u.sub_id,
+        margin_mode: u.margin_mode.into(),
+        total_collateral: u.total_collateral,
+        locked_collateral: u.locked_collateral,
+        total_pnl: u.total_pnl,
+        position_count: u.position_count,
+        pda: pda::user_pda(program_id, &u.owner, u.sub_id).0,
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use anyhow::{anyhow, ensure, Result};
+use axum::{
+    extract::State,
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_sdk::{
+    message::Message,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use tracing::{info, warn};
+
+use crate::solana::signer::{PubkeyResponse, SignError, SignRequest, SignResponse};
+
+// The signing service behind SIGNER=remote (src/bin/remote_signer.rs serves it); the API
+// host only has the client, solana/signer.rs's RemoteSigner.
+
+// Anchor instructions the service sends with its own key, which the signing service allows
+// for PROGRAM_ID unless SIGNER_ALLOW says otherwise. Transfers are left out: they move
+// positions and collateral to accounts named in the message, so the signer would sign away
+// whatever the key controls; list transfer_position and transfer_collateral in SIGNER_ALLOW
+// to allow them.
+pub const SERVICE_INSTRUCTIONS: &[&str] = &[
+    "open_position",
+    "modify_position",
+    "close_position",
+    "close_all_positions",
+    "set_auto_top_up",
+    "disable_auto_top_up",
+    "auto_top_up",
+];
+
+// What a signing service puts its key to: messages it pays for, whose every instruction calls
+// an allowed Anchor instruction (matched by discriminator) of an allowed program
+#[derive(Debug, Clone, Default)]
+pub struct SigningPolicy {
+    programs: HashMap<Pubkey, HashSet<[u8; 8]>>,
+}
+
+impl SigningPolicy {
+    pub fn allow(mut self, program: Pubkey, instructions: &[&str]) -> Self {
+        let allowed = self.programs.entry(program).or_default();
+        allowed.extend(instructions.iter().map(|name| perp_sdk::discriminator("global", name)));
+        self
+    }
+
+    // "PROGRAM:ix,ix;PROGRAM:ix", as SIGNER_ALLOW takes it
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut policy = Self::default();
+        for entry in spec.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (program, instructions) = entry.split_once(':').ok_or_else(|| anyhow!("expected PROGRAM:instruction,..., got {entry}"))?;
+            let instructions: Vec<&str> = instructions.split(',').map(str::trim).filter(|i| !i.is_empty()).collect();
+            ensure!(!instructions.is_empty(), "no instructions allowed for {program}");
+            policy = policy.allow(program.trim().parse()?, &instructions);
+        }
+        ensure!(!policy.programs.is_empty(), "the allow-list is empty");
+        Ok(policy)
+    }
+
+    pub fn check(&self, signer: &Pubkey, message: &Message) -> Result<()> {
+        ensure!(message.account_keys.first() == Some(signer), "fee payer is not the signer");
+        ensure!(!message.instructions.is_empty(), "message has no instructions");
+        for (i, ix) in message.instructions.iter().enumerate() {
+            let program = message.account_keys.get(ix.program_id_index as usize).ok_or_else(|| anyhow!("instruction {i}: bad program index"))?;
+            let allowed = self.programs.get(program).ok_or_else(|| anyhow!("instruction {i}: program {program} not allowed"))?;
+            let discriminator: Option<[u8; 8]> = ix.data.get(..8).and_then(|d| d.try_into().ok());
+            ensure!(discriminator.is_some_and(|d| allowed.contains(&d)), "instruction {i}: not an allowed instruction of {program}");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct Service {
+    keypair: Arc<Keypair>,
+    policy: Arc<SigningPolicy>,
+    token: Option<Arc<str>>,
+}
+
+type Refusal = (StatusCode, Json<SignError>);
+
+fn refuse(status: StatusCode, error: impl ToString) -> Refusal {
+    (status, Json(SignError { error: error.to_string() }))
+}
+
+impl Service {
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), Refusal> {
+        let Some(token) = &self.token else { return Ok(()) };
+        let bearer = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok()).and_then(|v| v.strip_prefix("Bearer "));
+        match bearer {
+            Some(given) if given == &**token => Ok(()),
+            _ => Err(refuse(StatusCode::UNAUTHORIZED, "bad or missing token")),
+        }
+    }
+}
+
+// Signs what `policy` allows with `keypair`, requiring `token` when one is given
+pub fn signing_service(keypair: Keypair, policy: SigningPolicy, token: Option<String>) -> Router {
+    let service = Service { keypair: Arc::new(keypair), policy: Arc::new(policy), token: token.map(Into::into) };
+    Router::new()
+        .route("/pubkey", get(service_pubkey))
+        .route("/sign", post(service_sign))
+        .with_state(service)
+}
+
+async fn service_pubkey(State(svc): State<Service>, headers: HeaderMap) -> Result<Json<PubkeyResponse>, Refusal> {
+    svc.authorize(&headers)?;
+    Ok(Json(PubkeyResponse { pubkey: svc.keypair.pubkey().to_string() }))
+}
+
+async fn service_sign(
+    State(svc): State<Service>,
+    headers: HeaderMap,
+    Json(req): Json<SignRequest>,
+) -> Result<Json<SignResponse>, Refusal> {
+    svc.authorize(&headers)?;
+    let data = STANDARD.decode(&req.message).map_err(|e| refuse(StatusCode::BAD_REQUEST, e))?;
+    let message: Message = bincode::deserialize(&data).map_err(|e| refuse(StatusCode::BAD_REQUEST, e))?;
+    // Trailing bytes would be signed without having been checked
+    if message.serialize() != data {
+        return Err(refuse(StatusCode::BAD_REQUEST, "message is not canonically serialized"));
+    }
+    if let Err(e) = svc.policy.check(&svc.keypair.pubkey(), &message) {
+        warn!("refused to sign: {e}");
+        return Err(refuse(StatusCode::FORBIDDEN, e));
+    }
+    let signature = svc.keypair.sign_message(&data);
+    info!("signed {signature}");
+    Ok(Json(SignResponse { signature: signature.to_string() }))
+}
@@ -1,23 +1,34 @@
 use std::sync::Arc;
 use anyhow::Result;
-use anchor_client::{Client, Cluster, Program};
-use solana_sdk::{signature::{read_keypair_file, Keypair}, commitment_config::CommitmentConfig, pubkey::Pubkey};
+use solana_sdk::pubkey::Pubkey;
+use perp_sdk::InstructionBuilder;
+use solana_client::nonblocking::rpc_client::RpcClient;
 
 use crate::config::Config;
+use super::{signer::{self, TxSigner}, tx::TxSubmitter};
 
 pub struct SolanaCtx {
     pub program_id: Pubkey,
-    pub payer: Arc<Keypair>,
+    pub quote_mint: Pubkey,
+    pub signer: Arc<dyn TxSigner>,
     pub rpc_url: String,
+    pub tx: TxSubmitter,
 }
 
 impl SolanaCtx {
     pub async fn new(cfg: &Config) -> Result<Self> {
-        let payer = Arc::new(read_keypair_file(&cfg.keypair_path).map_err(|e| anyhow::anyhow!("Failed to read keypair: {}", e))?);
+        let signer = signer::connect(&cfg.signer).await?;
         Ok(Self { 
             program_id: cfg.program_id,
-            payer,
+            quote_mint: cfg.quote_mint,
+            signer,
             rpc_url: cfg.rpc_url.clone(),
+            tx: TxSubmitter::new(Arc::new(RpcClient::new(cfg.rpc_url.clone())), cfg.commitment),
         })
     }
-}
\ No newline at end of file
+
+    // Builders for the configured program and quote mint
+    pub fn instructions(&self) -> InstructionBuilder {
+        InstructionBuilder::new(self.program_id, self.quote_mint)
+    }
+}
@@ -1,2 +1,4 @@
 pub mod pda;
-pub mod client;
\ No newline at end of file
+pub mod client;
+pub mod tx;
+pub mod signer;
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    bs58,
+    message::Message,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signature, Signer},
+};
+use tracing::info;
+
+// Signs the service's transactions as their fee payer. The key need not be on this host
+// (RemoteSigner), so signing is async and can be refused.
+#[async_trait]
+pub trait TxSigner: Send + Sync {
+    // The signing key's public key (named apart from Signer::pubkey, which Keypair also has)
+    fn address(&self) -> Pubkey;
+    async fn sign(&self, message: &Message) -> Result<Signature>;
+}
+
+// A key held in memory: read from a keypair file, or from a base58 secret in the environment
+#[async_trait]
+impl TxSigner for Keypair {
+    fn address(&self) -> Pubkey {
+        self.pubkey()
+    }
+
+    async fn sign(&self, message: &Message) -> Result<Signature> {
+        Ok(self.sign_message(&message.serialize()))
+    }
+}
+
+// Where the service's key comes from (SIGNER)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignerSource {
+    // A keypair JSON file (KEYPAIR_PATH)
+    File(String),
+    // The name of the variable holding the base58 secret key (SIGNER_SECRET), so the secret
+    // itself isn't copied into the config
+    Env(String),
+    // A signing service (SIGNER_URL, with SIGNER_TOKEN sent as a bearer token)
+    Remote { url: String, token: Option<String> },
+}
+
+pub async fn connect(source: &SignerSource) -> Result<Arc<dyn TxSigner>> {
+    let signer: Arc<dyn TxSigner> = match source {
+        SignerSource::File(path) => Arc::new(read_keypair_file(path).map_err(|e| anyhow!("failed to read keypair {path}: {e}"))?),
+        SignerSource::Env(var) => Arc::new(keypair_from_base58(&std::env::var(var).with_context(|| format!("{var} is not set"))?)?),
+        SignerSource::Remote { url, token } => Arc::new(RemoteSigner::connect(url, token.clone()).await?),
+    };
+    info!("signing as {} ({})", signer.address(), match source {
+        SignerSource::File(_) => "keypair file",
+        SignerSource::Env(_) => "environment",
+        SignerSource::Remote { .. } => "remote signer",
+    });
+    Ok(signer)
+}
+
+pub fn keypair_from_base58(secret: &str) -> Result<Keypair> {
+    let bytes = bs58::decode(secret.trim()).into_vec().map_err(|_| anyhow!("secret key is not base58"))?;
+    Keypair::from_bytes(&bytes).map_err(|_| anyhow!("secret key must be 64 bytes (secret and public key)"))
+}
+
+// ---------- remote signing protocol ----------
+//
+// GET  /pubkey  -> { pubkey }
+// POST /sign    { message: base64 of the serialized message } -> { signature } (base58)
+// Both take `Authorization: Bearer <token>` when the service has a token; a message the
+// service's policy doesn't allow gets 403 { error }. The service side is signing_service.rs.
+
+#[derive(Serialize, Deserialize)]
+pub struct PubkeyResponse {
+    pub pubkey: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SignRequest {
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SignResponse {
+    pub signature: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SignError {
+    pub error: String,
+}
+
+// Client of a signing service; the key stays with the service
+pub struct RemoteSigner {
+    http: reqwest::Client,
+    url: String,
+    token: Option<String>,
+    pubkey: Pubkey,
+}
+
+impl RemoteSigner {
+    // Asks the service for its public key, which every signature is then checked against
+    pub async fn connect(url: &str, token: Option<String>) -> Result<Self> {
+        let http = reqwest::Client::new();
+        let url = url.trim_end_matches('/').to_string();
+        let mut request = http.get(format!("{url}/pubkey"));
+        if let Some(token) = &token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await.with_context(|| format!("signer {url} unreachable"))?;
+        ensure!(response.status().is_success(), "signer {url}: {}", response.status());
+        let pubkey = response.json::<PubkeyResponse>().await?.pubkey.parse()?;
+        Ok(Self { http, url, token, pubkey })
+    }
+}
+
+#[async_trait]
+impl TxSigner for RemoteSigner {
+    fn address(&self) -> Pubkey {
+        self.pubkey
+    }
+
+    async fn sign(&self, message: &Message) -> Result<Signature> {
+        let data = message.serialize();
+        let mut request = self.http.post(format!("{}/sign", self.url)).json(&SignRequest { message: STANDARD.encode(&data) });
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await.with_context(|| format!("signer {} unreachable", self.url))?;
+        let status = response.status();
+        if !status.is_success() {
+            let reason = response.json::<SignError>().await.map(|e| e.error).unwrap_or_default();
+            bail!("signer refused ({status}): {reason}");
+        }
+        let signature: Signature = response.json::<SignResponse>().await?.signature.parse()?;
+        ensure!(signature.verify(self.pubkey.as_ref(), &data), "signer returned a signature that doesn't verify");
+        Ok(signature)
+    }
+}
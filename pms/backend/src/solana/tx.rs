@@ -0,0 +1,267 @@
+use std::{sync::Arc, time::Duration};
+use anyhow::Result;
+use async_trait::async_trait;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcSendTransactionConfig, RpcSimulateTransactionConfig, RpcTransactionConfig},
+    rpc_request::RpcRequest,
+};
+use solana_sdk::{
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    hash::Hash,
+    instruction::Instruction,
+    message::Message,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, TransactionStatus, UiTransactionEncoding};
+use tracing::{debug, info, warn};
+
+use super::signer::TxSigner;
+use crate::errors::TxError;
+
+// Fresh blockhashes (each re-signs the transaction) before giving up
+const MAX_ATTEMPTS: usize = 3;
+// Retries of a single sendTransaction call that fails at the RPC
+const SEND_RETRIES: usize = 3;
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// The RPC calls a submission makes; RpcClient in production
+#[async_trait]
+pub trait TxRpc: Send + Sync {
+    // Blockhash and the last block height a transaction using it can land in
+    async fn latest_blockhash(&self, commitment: CommitmentConfig) -> Result<(Hash, u64)>;
+    async fn block_height(&self, commitment: CommitmentConfig) -> Result<u64>;
+    // The error the transaction would fail with, and the program logs
+    async fn simulate(&self, tx: &Transaction, commitment: CommitmentConfig) -> Result<(Option<TransactionError>, Vec<String>)>;
+    async fn send(&self, tx: &Transaction) -> Result<Signature>;
+    async fn status(&self, signature: &Signature) -> Result<Option<TransactionStatus>>;
+    async fn account_data(&self, address: &Pubkey, commitment: CommitmentConfig) -> Result<Option<Vec<u8>>>;
+    // A landed transaction's log messages, None if the node doesn't have it at `commitment`
+    async fn logs(&self, signature: &Signature, commitment: CommitmentConfig) -> Result<Option<Vec<String>>>;
+}
+
+#[async_trait]
+impl TxRpc for RpcClient {
+    async fn latest_blockhash(&self, commitment: CommitmentConfig) -> Result<(Hash, u64)> {
+        Ok(self.get_latest_blockhash_with_commitment(commitment).await?)
+    }
+
+    async fn block_height(&self, commitment: CommitmentConfig) -> Result<u64> {
+        Ok(self.get_block_height_with_commitment(commitment).await?)
+    }
+
+    async fn simulate(&self, tx: &Transaction, commitment: CommitmentConfig) -> Result<(Option<TransactionError>, Vec<String>)> {
+        let config = RpcSimulateTransactionConfig { sig_verify: true, commitment: Some(commitment), ..Default::default() };
+        let result = self.simulate_transaction_with_config(tx, config).await?.value;
+        Ok((result.err, result.logs.unwrap_or_default()))
+    }
+
+    // Preflight already ran as the simulation; the submitter does its own rebroadcasting
+    async fn send(&self, tx: &Transaction) -> Result<Signature> {
+        let config = RpcSendTransactionConfig { skip_preflight: true, max_retries: Some(0), ..Default::default() };
+        Ok(self.send_transaction_with_config(tx, config).await?)
+    }
+
+    // Searches history too: a transaction followed after a restart may have dropped out of the
+    // node's recent status cache since it landed
+    async fn status(&self, signature: &Signature) -> Result<Option<TransactionStatus>> {
+        Ok(self.get_signature_statuses_with_history(&[*signature]).await?.value.into_iter().next().flatten())
+    }
+
+    async fn account_data(&self, address: &Pubkey, commitment: CommitmentConfig) -> Result<Option<Vec<u8>>> {
+        Ok(self.get_account_with_commitment(address, commitment).await?.value.map(|a| a.data))
+    }
+
+    // getTransaction serves confirmed and finalized only; it answers null for a transaction it
+    // doesn't have, which get_transaction would report as a parse error
+    async fn logs(&self, signature: &Signature, commitment: CommitmentConfig) -> Result<Option<Vec<String>>> {
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(if commitment.is_finalized() { commitment } else { CommitmentConfig::confirmed() }),
+            max_supported_transaction_version: Some(0),
+        };
+        let tx: Option<EncodedConfirmedTransactionWithStatusMeta> =
+            self.send(RpcRequest::GetTransaction, serde_json::json!([signature.to_string(), config])).await?;
+        Ok(tx.and_then(|tx| tx.transaction.meta).and_then(|meta| meta.log_messages.into()))
+    }
+}
+
+// Told about each signed transaction before it is sent, so the signature is durable before
+// the transaction can land (services/operations.rs records it on the operation). An error
+// stops the submission with nothing sent.
+#[async_trait]
+pub trait SendLog: Send + Sync {
+    async fn signed(&self, signature: &Signature, last_valid_block_height: u64) -> Result<()>;
+}
+
+// A transaction that reached the submitter's commitment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Confirmed {
+    pub signature: Signature,
+    pub slot: u64,
+}
+
+// Signs, simulates, sends and confirms transactions.
+//
+// The first attempt is simulated before anything is sent, so a request the program rejects
+// fails fast with its PerpError and costs no fee. The signed transaction is rebroadcast
+// while it's pending; if its blockhash expires before it lands, it is re-signed with a fresh
+// one (an expired transaction can no longer land, so it can't execute twice).
+#[derive(Clone)]
+pub struct TxSubmitter {
+    rpc: Arc<dyn TxRpc>,
+    commitment: CommitmentConfig,
+    poll_interval: Duration,
+}
+
+impl TxSubmitter {
+    pub fn new(rpc: Arc<dyn TxRpc>, commitment: CommitmentLevel) -> Self {
+        Self { rpc, commitment: CommitmentConfig { commitment }, poll_interval: POLL_INTERVAL }
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub async fn submit(&self, instructions: &[Instruction], payer: &dyn TxSigner) -> Result<Confirmed, TxError> {
+        self.submit_logged(instructions, payer, None).await
+    }
+
+    pub async fn submit_logged(&self, instructions: &[Instruction], payer: &dyn TxSigner, log: Option<&dyn SendLog>) -> Result<Confirmed, TxError> {
+        for attempt in 1..=MAX_ATTEMPTS {
+            let (blockhash, last_valid) = self.rpc.latest_blockhash(self.commitment).await.map_err(rpc)?;
+            let tx = sign(instructions, payer, blockhash).await?;
+            if let Some(confirmed) = self.attempt(&tx, last_valid, attempt == 1, log).await? {
+                return Ok(confirmed);
+            }
+            warn!("{} expired unconfirmed, re-signing with a new blockhash", tx.signatures[0]);
+        }
+        Err(TxError::Expired(MAX_ATTEMPTS))
+    }
+
+    // An unsigned transaction with `payer` as fee payer and a fresh blockhash, and the last
+    // block height it can land in, for a wallet to sign
+    pub async fn prepare(&self, instructions: &[Instruction], payer: &Pubkey) -> Result<(Transaction, u64), TxError> {
+        let (blockhash, last_valid) = self.rpc.latest_blockhash(self.commitment).await.map_err(rpc)?;
+        let message = Message::new_with_blockhash(instructions, Some(payer), &blockhash);
+        Ok((Transaction::new_unsigned(message), last_valid))
+    }
+
+    // Simulates, sends and confirms a transaction signed elsewhere. It can't be re-signed
+    // here, so it has the one blockhash to land with.
+    pub async fn relay(&self, tx: &Transaction, last_valid_block_height: u64, log: Option<&dyn SendLog>) -> Result<Confirmed, TxError> {
+        self.attempt(tx, last_valid_block_height, true, log).await?.ok_or(TxError::Expired(1))
+    }
+
+    // Returns once the chain is past `last_valid_block_height`, when no transaction using a
+    // blockhash valid up to it can land any more
+    pub async fn wait_for_expiry(&self, last_valid_block_height: u64) -> Result<(), TxError> {
+        while self.rpc.block_height(self.commitment).await.map_err(rpc)? <= last_valid_block_height {
+            tokio::time::sleep(self.poll_interval).await;
+        }
+        Ok(())
+    }
+
+    // Waits on a transaction sent earlier (by a previous run, say) without rebroadcasting it;
+    // None once its blockhash has expired with it unseen, after which it can never land
+    pub async fn await_signature(&self, signature: &Signature, last_valid_block_height: u64) -> Result<Option<Confirmed>, TxError> {
+        self.confirm(None, signature, last_valid_block_height).await
+    }
+
+    // Account data as of the submitter's commitment, None if the account doesn't exist
+    pub async fn account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>, TxError> {
+        self.rpc.account_data(address, self.commitment).await.map_err(rpc)
+    }
+
+    // A confirmed transaction's log messages; None if the node can't serve it yet, as when
+    // the submitter waits for processed only
+    pub async fn logs(&self, signature: &Signature) -> Result<Option<Vec<String>>, TxError> {
+        self.rpc.logs(signature, self.commitment).await.map_err(rpc)
+    }
+
+    // One signed transaction: simulated if asked, logged, sent and confirmed. None if its
+    // blockhash expired before it landed.
+    async fn attempt(&self, tx: &Transaction, last_valid: u64, simulate: bool, log: Option<&dyn SendLog>) -> Result<Option<Confirmed>, TxError> {
+        if simulate {
+            let (err, logs) = self.rpc.simulate(tx, self.commitment).await.map_err(rpc)?;
+            if let Some(err) = err {
+                debug!("simulation failed: {err}; logs: {logs:?}");
+                return Err(err.into());
+            }
+        }
+        if let Some(log) = log {
+            log.signed(&tx.signatures[0], last_valid).await.map_err(|e| TxError::Unrecorded(e.to_string()))?;
+        }
+        let signature = self.send(tx).await?;
+        info!("sent {signature}");
+        self.confirm(Some(tx), &signature, last_valid).await
+    }
+
+    async fn send(&self, tx: &Transaction) -> Result<Signature, TxError> {
+        let mut tries = 0;
+        loop {
+            match self.rpc.send(tx).await {
+                Ok(signature) => return Ok(signature),
+                Err(e) if tries < SEND_RETRIES => {
+                    tries += 1;
+                    warn!("send failed ({tries}/{SEND_RETRIES}): {e}");
+                    tokio::time::sleep(self.poll_interval * tries as u32).await;
+                }
+                Err(e) => return Err(rpc(e)),
+            }
+        }
+    }
+
+    // Waits for the commitment, rebroadcasting `tx` if given; None if the blockhash expired
+    // with the transaction unseen
+    async fn confirm(&self, tx: Option<&Transaction>, signature: &Signature, last_valid: u64) -> Result<Option<Confirmed>, TxError> {
+        loop {
+            tokio::time::sleep(self.poll_interval).await;
+            match self.rpc.status(signature).await.map_err(rpc)? {
+                Some(status) => {
+                    if let Some(err) = status.err {
+                        return Err(err.into());
+                    }
+                    if status.satisfies_commitment(self.commitment) {
+                        return Ok(Some(Confirmed { signature: *signature, slot: status.slot }));
+                    }
+                    // Landed; it can no longer expire, only wait for the commitment
+                }
+                None => {
+                    if self.rpc.block_height(self.commitment).await.map_err(rpc)? > last_valid {
+                        // It may have landed between the two queries; only unseen after the
+                        // blockhash expired does it stay unseen
+                        match self.rpc.status(signature).await.map_err(rpc)? {
+                            None => return Ok(None),
+                            Some(_) => continue,
+                        }
+                    }
+                    if let Some(tx) = tx {
+                        if let Err(e) = self.rpc.send(tx).await {
+                            debug!("rebroadcast of {signature} failed: {e}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The payer is the only signer of what the service sends
+async fn sign(instructions: &[Instruction], payer: &dyn TxSigner, blockhash: Hash) -> Result<Transaction, TxError> {
+    let message = Message::new_with_blockhash(instructions, Some(&payer.address()), &blockhash);
+    if message.header.num_required_signatures != 1 {
+        return Err(TxError::Signer(format!("needs {} signatures, the payer can only give one", message.header.num_required_signatures)));
+    }
+    let signature = payer.sign(&message).await.map_err(|e| TxError::Signer(e.to_string()))?;
+    let mut tx = Transaction::new_unsigned(message);
+    tx.signatures[0] = signature;
+    Ok(tx)
+}
+
+fn rpc(e: anyhow::Error) -> TxError {
+    TxError::Rpc(e.to_string())
+}
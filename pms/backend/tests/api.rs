@@ -0,0 +1,306 @@
+// REST responses: the router served on a local port, with the database in in-memory SQLite
+// and a scripted RPC behind the manager
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use borsh::BorshSerialize;
+use perp_events::events::{Appended, Event, PositionClosed};
+use perp_sdk::{constants::MAX_BATCH_POSITIONS, PerpError};
+use position_service::api::{auth::Auth, http::{router, AppState}, ws::WsHub};
+use position_service::config::SigningMode;
+use position_service::db::{PositionRepo, SharedRepo, SqliteRepo};
+use position_service::models::{OpenPositionInput, OperationStatus, PositionState, Side};
+use position_service::services::{
+    manager::PositionManager,
+    reconciler::{AccountSource, ChainSnapshot, Reconciler},
+};
+use position_service::solana::{client::SolanaCtx, tx::{TxRpc, TxSubmitter}};
+use serde_json::{json, Value};
+use solana_sdk::{
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    hash::Hash,
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+
+const PROGRAM_ID: Pubkey = perp_sdk::PROGRAM_ID;
+const SYMBOL: &str = "SOL-PERP";
+
+// Rejects every transaction in simulation with `simulation`, or fails every call when `down`.
+// With `landed` set every transaction confirms, having logged those lines.
+#[derive(Default)]
+struct ScriptedRpc {
+    simulation: Option<TransactionError>,
+    down: bool,
+    landed: Option<Vec<String>>,
+}
+
+impl ScriptedRpc {
+    fn check(&self) -> Result<()> {
+        if self.down {
+            bail!("connection refused");
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TxRpc for ScriptedRpc {
+    async fn latest_blockhash(&self, _: CommitmentConfig) -> Result<(Hash, u64)> {
+        self.check()?;
+        Ok((Hash::new_unique(), 100))
+    }
+
+    async fn block_height(&self, _: CommitmentConfig) -> Result<u64> {
+        self.check()?;
+        Ok(0)
+    }
+
+    async fn simulate(&self, _: &Transaction, _: CommitmentConfig) -> Result<(Option<TransactionError>, Vec<String>)> {
+        self.check()?;
+        Ok((self.simulation.clone(), vec![]))
+    }
+
+    async fn send(&self, tx: &Transaction) -> Result<Signature> {
+        self.check()?;
+        Ok(tx.signatures[0])
+    }
+
+    async fn status(&self, _: &Signature) -> Result<Option<TransactionStatus>> {
+        self.check()?;
+        Ok(self.landed.as_ref().map(|_| TransactionStatus {
+            slot: 50,
+            confirmations: None,
+            status: Ok(()),
+            err: None,
+            confirmation_status: Some(TransactionConfirmationStatus::Confirmed),
+        }))
+    }
+
+    async fn account_data(&self, _: &Pubkey, _: CommitmentConfig) -> Result<Option<Vec<u8>>> {
+        self.check()?;
+        Ok(None)
+    }
+
+    async fn logs(&self, _: &Signature, _: CommitmentConfig) -> Result<Option<Vec<String>>> {
+        self.check()?;
+        Ok(self.landed.clone())
+    }
+}
+
+// position_manager's logs for a transaction emitting `event`
+fn logged<E: Event + BorshSerialize>(event: &E) -> Vec<String> {
+    let data = [E::discriminator().to_vec(), event.try_to_vec().unwrap()].concat();
+    vec![
+        format!("Program {PROGRAM_ID} invoke [1]"),
+        format!("Program data: {}", STANDARD.encode(data)),
+        format!("Program {PROGRAM_ID} success"),
+    ]
+}
+
+struct NoAccounts;
+
+#[async_trait]
+impl AccountSource for NoAccounts {
+    async fn snapshot(&self) -> Result<ChainSnapshot> {
+        Ok(ChainSnapshot::default())
+    }
+}
+
+struct Api {
+    url: String,
+    http: reqwest::Client,
+    repo: Arc<SqliteRepo>,
+    // The service's key, which owns the positions it can act on
+    signer: Pubkey,
+}
+
+async fn serve(rpc: ScriptedRpc) -> Api {
+    serve_as(Keypair::new(), rpc).await
+}
+
+async fn serve_as(keypair: Keypair, rpc: ScriptedRpc) -> Api {
+    let db = Arc::new(SqliteRepo::connect("sqlite::memory:").await.unwrap());
+    db.migrate().await.unwrap();
+    let repo: SharedRepo = db.clone();
+    let signer = keypair.pubkey();
+    let sol = SolanaCtx {
+        program_id: PROGRAM_ID,
+        quote_mint: Pubkey::new_unique(),
+        signer: Arc::new(keypair),
+        rpc_url: String::new(),
+        tx: TxSubmitter::new(Arc::new(rpc), CommitmentLevel::Confirmed).with_poll_interval(Duration::from_millis(1)),
+    };
+    let manager = PositionManager::new(sol, repo.clone(), PROGRAM_ID);
+    let reconciler = Reconciler::new(repo.clone(), Arc::new(NoAccounts), PROGRAM_ID);
+    let auth = Auth::new(repo.clone(), false, Duration::from_secs(900));
+    let app = router(AppState { manager, repo, reconciler, signing: SigningMode::Service, auth, hub: WsHub::new() });
+
+    let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(app.into_make_service());
+    let url = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+    Api { url, http: reqwest::Client::new(), repo: db, signer }
+}
+
+impl Api {
+    async fn call(&self, method: reqwest::Method, path: &str, body: Option<Value>) -> (u16, Value) {
+        let mut request = self.http.request(method, format!("{}{path}", self.url));
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await.unwrap();
+        (response.status().as_u16(), response.json().await.unwrap_or(Value::Null))
+    }
+
+    // An open position of `owner`, as the database has it
+    async fn open(&self, owner: &Pubkey) -> Pubkey {
+        self.open_symbol(owner, SYMBOL).await
+    }
+
+    async fn open_symbol(&self, owner: &Pubkey, symbol: &str) -> Pubkey {
+        let pda = perp_sdk::pda::position_pda(&PROGRAM_ID, owner, 0, symbol).0;
+        let input = OpenPositionInput {
+            sub_id: 0,
+            symbol: symbol.to_string(),
+            side: Side::Long,
+            size: 10,
+            leverage: 10,
+            entry_price: 100_000_000,
+            margin_token_account: Pubkey::new_unique(),
+            quote_mint: Pubkey::new_unique(),
+        };
+        let op = self.repo.insert_position_open_intent(owner, &pda, &input).await.unwrap();
+        self.repo.finish_operation(op, OperationStatus::Confirmed, None).await.unwrap();
+        pda
+    }
+}
+
+fn code(body: &Value) -> &str {
+    body["error"]["code"].as_str().unwrap_or_default()
+}
+
+const PUT: reqwest::Method = reqwest::Method::PUT;
+const GET: reqwest::Method = reqwest::Method::GET;
+const DELETE: reqwest::Method = reqwest::Method::DELETE;
+
+fn add_margin() -> Option<Value> {
+    Some(json!({ "type": "add_margin", "amount": 1_000_000 }))
+}
+
+#[tokio::test]
+async fn invalid_input_is_a_400() {
+    let api = serve(ScriptedRpc::default()).await;
+    let (status, body) = api.call(GET, "/positions/not-a-pubkey", None).await;
+    assert_eq!((status, code(&body)), (400, "invalid_input"));
+    assert!(body["error"]["message"].as_str().unwrap().contains("not-a-pubkey"));
+
+    let pda = api.open(&api.signer).await;
+    let (status, body) = api.call(PUT, &format!("/positions/{pda}/modify"), Some(json!({ "type": "grow" }))).await;
+    assert_eq!((status, code(&body)), (400, "invalid_input"));
+
+    let (status, body) = api.call(GET, &format!("/users/{}/positions?sub_id=first", api.signer), None).await;
+    assert_eq!((status, code(&body)), (400, "invalid_input"));
+
+    let (status, body) = api.call(PUT, &format!("/positions/{pda}/auto-top-up"), Some(json!({ "target_mr": 0, "max_total": 1 }))).await;
+    assert_eq!((status, code(&body)), (400, "invalid_input"));
+
+    // 100x is capped at 50k notional by the program's tier table
+    let open = json!({
+        "sub_id": 1, "symbol": SYMBOL, "side": "Long", "size": 1_000, "leverage": 100, "entry_price": 100_000_000,
+        "margin_token_account": Pubkey::new_unique().to_string(), "quote_mint": Pubkey::new_unique().to_string(),
+    });
+    let (status, body) = api.call(reqwest::Method::POST, "/positions/open", Some(open)).await;
+    assert_eq!((status, code(&body)), (400, "invalid_input"), "{body}");
+}
+
+#[tokio::test]
+async fn close_all_beyond_one_transaction_is_a_400() {
+    let api = serve(ScriptedRpc::default()).await;
+    let mut prices = serde_json::Map::new();
+    for i in 0..=MAX_BATCH_POSITIONS {
+        let symbol = format!("P{i}-PERP");
+        api.open_symbol(&api.signer, &symbol).await;
+        prices.insert(symbol, json!(100_000_000));
+    }
+
+    // all legs settle in one transaction or none do, so the book isn't split
+    let path = format!("/users/{}/positions/close-all", api.signer);
+    let (status, body) = api.call(reqwest::Method::POST, &path, Some(json!({ "prices": prices }))).await;
+    assert_eq!((status, code(&body)), (400, "invalid_input"));
+    let positions = api.repo.fetch_positions_by_owner(&api.signer, Some(0)).await.unwrap();
+    assert!(positions.iter().all(|p| p.state == PositionState::Open));
+}
+
+#[tokio::test]
+async fn closes_return_the_fill_from_the_close_event() {
+    let signer = Keypair::new();
+    let closed = PositionClosed {
+        owner: signer.pubkey(),
+        symbol: SYMBOL.to_string(),
+        size_closed: 10,
+        exit_price: 110_000_000,
+        realized_pnl: 100_000_000,
+        payout: 199_000_000,
+        sub_id: Appended(Some(0)),
+    };
+    let api = serve_as(signer, ScriptedRpc { landed: Some(logged(&closed)), ..Default::default() }).await;
+    let pda = api.open(&api.signer).await;
+
+    let (status, body) = api.call(DELETE, &format!("/positions/{pda}/close"), Some(json!({ "exit_price": 110_000_000 }))).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["closed"], json!({ "size_closed": 10, "exit_price": 110_000_000, "realized_pnl": 100_000_000, "payout": 199_000_000 }));
+}
+
+#[tokio::test]
+async fn unknown_positions_are_a_404() {
+    let api = serve(ScriptedRpc::default()).await;
+    let missing = Pubkey::new_unique();
+    let (status, body) = api.call(PUT, &format!("/positions/{missing}/modify"), add_margin()).await;
+    assert_eq!((status, code(&body)), (404, "not_found"));
+
+    // Reading one is still a 200 with null
+    let (status, body) = api.call(GET, &format!("/positions/{missing}"), None).await;
+    assert_eq!((status, &body["position"]), (200, &Value::Null));
+}
+
+#[tokio::test]
+async fn positions_mid_operation_are_a_409() {
+    let api = serve(ScriptedRpc::default()).await;
+    let pda = api.open(&api.signer).await;
+    api.repo.insert_position_close_intent(&api.signer, &pda, 110_000_000).await.unwrap();
+
+    let (status, body) = api.call(PUT, &format!("/positions/{pda}/modify"), add_margin()).await;
+    assert_eq!((status, code(&body)), (409, "conflict"));
+    assert!(body["error"]["message"].as_str().unwrap().contains("closing"));
+
+    // And positions the service can't sign for
+    let theirs = api.open(&Pubkey::new_unique()).await;
+    let (status, body) = api.call(DELETE, &format!("/positions/{theirs}/close"), Some(json!({ "exit_price": 1 }))).await;
+    assert_eq!((status, code(&body)), (409, "conflict"));
+}
+
+#[tokio::test]
+async fn program_rejections_are_a_422_with_the_perp_error() {
+    let rejection = TransactionError::InstructionError(0, InstructionError::Custom(PerpError::LeverageExceeded.code()));
+    let api = serve(ScriptedRpc { simulation: Some(rejection), ..Default::default() }).await;
+    let pda = api.open(&api.signer).await;
+
+    let (status, body) = api.call(PUT, &format!("/positions/{pda}/modify"), add_margin()).await;
+    assert_eq!((status, code(&body)), (422, "program_error"));
+    assert_eq!(body["error"]["program_error"], json!({ "name": "LeverageExceeded", "code": PerpError::LeverageExceeded.code() }));
+}
+
+#[tokio::test]
+async fn rpc_failures_are_a_502() {
+    let api = serve(ScriptedRpc { down: true, ..Default::default() }).await;
+    let pda = api.open(&api.signer).await;
+    let (status, body) = api.call(PUT, &format!("/positions/{pda}/modify"), add_margin()).await;
+    assert_eq!((status, code(&body)), (502, "rpc_error"));
+}
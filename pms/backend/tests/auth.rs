@@ -0,0 +1,313 @@
+// Who may call the owner endpoints: wallet sessions from a signed nonce, delegates, and
+// scoped API keys, against the router served on a local port with AUTH required
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use position_service::api::{auth::{create_api_key, Auth}, http::{router, AppState}, ws::WsHub};
+use position_service::config::SigningMode;
+use position_service::db::{PositionRepo, SharedRepo, SqliteRepo};
+use position_service::models::{OpenPositionInput, OperationStatus, Scope, Side};
+use position_service::services::{
+    manager::PositionManager,
+    reconciler::{AccountSource, ChainSnapshot, Reconciler},
+};
+use position_service::solana::{client::SolanaCtx, tx::{TxRpc, TxSubmitter}};
+use reqwest::Method;
+use serde_json::{json, Value};
+use solana_sdk::{
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    hash::Hash,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_status::TransactionStatus;
+
+const PROGRAM_ID: Pubkey = perp_sdk::PROGRAM_ID;
+
+// Nothing here gets as far as the chain
+struct NoRpc;
+
+#[async_trait]
+impl TxRpc for NoRpc {
+    async fn latest_blockhash(&self, _: CommitmentConfig) -> Result<(Hash, u64)> {
+        bail!("no rpc")
+    }
+
+    async fn block_height(&self, _: CommitmentConfig) -> Result<u64> {
+        bail!("no rpc")
+    }
+
+    async fn simulate(&self, _: &Transaction, _: CommitmentConfig) -> Result<(Option<TransactionError>, Vec<String>)> {
+        bail!("no rpc")
+    }
+
+    async fn send(&self, _: &Transaction) -> Result<Signature> {
+        bail!("no rpc")
+    }
+
+    async fn status(&self, _: &Signature) -> Result<Option<TransactionStatus>> {
+        bail!("no rpc")
+    }
+
+    async fn account_data(&self, _: &Pubkey, _: CommitmentConfig) -> Result<Option<Vec<u8>>> {
+        bail!("no rpc")
+    }
+
+    async fn logs(&self, _: &Signature, _: CommitmentConfig) -> Result<Option<Vec<String>>> {
+        bail!("no rpc")
+    }
+}
+
+struct NoAccounts;
+
+#[async_trait]
+impl AccountSource for NoAccounts {
+    async fn snapshot(&self) -> Result<ChainSnapshot> {
+        Ok(ChainSnapshot::default())
+    }
+}
+
+struct Api {
+    url: String,
+    http: reqwest::Client,
+    repo: Arc<SqliteRepo>,
+}
+
+async fn serve() -> Api {
+    let db = Arc::new(SqliteRepo::connect("sqlite::memory:").await.unwrap());
+    db.migrate().await.unwrap();
+    let repo: SharedRepo = db.clone();
+    let sol = SolanaCtx {
+        program_id: PROGRAM_ID,
+        quote_mint: Pubkey::new_unique(),
+        signer: Arc::new(Keypair::new()),
+        rpc_url: String::new(),
+        tx: TxSubmitter::new(Arc::new(NoRpc), CommitmentLevel::Confirmed),
+    };
+    let manager = PositionManager::new(sol, repo.clone(), PROGRAM_ID);
+    let reconciler = Reconciler::new(repo.clone(), Arc::new(NoAccounts), PROGRAM_ID);
+    let auth = Auth::new(repo.clone(), true, Duration::from_secs(900));
+    let app = router(AppState { manager, repo, reconciler, signing: SigningMode::Wallet, auth, hub: WsHub::new() });
+
+    let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(app.into_make_service());
+    let url = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+    Api { url, http: reqwest::Client::new(), repo: db }
+}
+
+// How a request identifies itself
+enum As<'a> {
+    Nobody,
+    Session(&'a str),
+    Key(&'a str),
+}
+
+impl Api {
+    async fn call(&self, who: As<'_>, method: Method, path: &str, body: Option<Value>) -> (u16, Value) {
+        let mut request = self.http.request(method, format!("{}{path}", self.url));
+        request = match who {
+            As::Nobody => request,
+            As::Session(token) => request.bearer_auth(token),
+            As::Key(key) => request.header("x-api-key", key),
+        };
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await.unwrap();
+        (response.status().as_u16(), response.json().await.unwrap_or(Value::Null))
+    }
+
+    // Signs the nonce's message with `wallet` and returns the session token
+    async fn sign_in(&self, wallet: &Keypair) -> String {
+        let (nonce, body) = self.challenge(&wallet.pubkey()).await;
+        let signature = wallet.sign_message(body["message"].as_str().unwrap().as_bytes());
+        let (status, body) = self.call(As::Nobody, Method::POST, "/auth/session", Some(session(wallet, &nonce, &signature))).await;
+        assert_eq!(status, 200, "{body}");
+        body["token"].as_str().unwrap().to_string()
+    }
+
+    async fn challenge(&self, wallet: &Pubkey) -> (String, Value) {
+        let (status, body) = self.call(As::Nobody, Method::POST, "/auth/nonce", Some(json!({ "pubkey": wallet.to_string() }))).await;
+        assert_eq!(status, 200, "{body}");
+        (body["nonce"].as_str().unwrap().to_string(), body)
+    }
+
+    // An open position of `owner`, as the database has it
+    async fn open(&self, owner: &Pubkey) -> Pubkey {
+        let pda = perp_sdk::pda::position_pda(&PROGRAM_ID, owner, 0, "SOL-PERP").0;
+        let input = OpenPositionInput {
+            sub_id: 0,
+            symbol: "SOL-PERP".to_string(),
+            side: Side::Long,
+            size: 10,
+            leverage: 10,
+            entry_price: 100_000_000,
+            margin_token_account: Pubkey::new_unique(),
+            quote_mint: Pubkey::new_unique(),
+        };
+        let op = self.repo.insert_position_open_intent(owner, &pda, &input).await.unwrap();
+        self.repo.finish_operation(op, OperationStatus::Confirmed, None).await.unwrap();
+        pda
+    }
+}
+
+fn session(wallet: &Keypair, nonce: &str, signature: &Signature) -> Value {
+    json!({ "pubkey": wallet.pubkey().to_string(), "nonce": nonce, "signature": signature.to_string() })
+}
+
+fn positions(owner: &Pubkey) -> String {
+    format!("/users/{owner}/positions")
+}
+
+fn code(body: &Value) -> &str {
+    body["error"]["code"].as_str().unwrap_or_default()
+}
+
+#[tokio::test]
+async fn signed_nonces_become_sessions() {
+    let api = serve().await;
+    let wallet = Keypair::new();
+    let (status, body) = api.call(As::Nobody, Method::GET, &positions(&wallet.pubkey()), None).await;
+    assert_eq!((status, code(&body)), (401, "unauthorized"));
+
+    let token = api.sign_in(&wallet).await;
+    let (status, _) = api.call(As::Session(&token), Method::GET, &positions(&wallet.pubkey()), None).await;
+    assert_eq!(status, 200);
+
+    // Signed out, the token is no good
+    let (status, _) = api.call(As::Session(&token), Method::DELETE, "/auth/session", None).await;
+    assert_eq!(status, 200);
+    let (status, _) = api.call(As::Session(&token), Method::GET, &positions(&wallet.pubkey()), None).await;
+    assert_eq!(status, 401);
+}
+
+#[tokio::test]
+async fn nonces_need_the_wallet_signature_and_work_once() {
+    let api = serve().await;
+    let wallet = Keypair::new();
+    let (nonce, body) = api.challenge(&wallet.pubkey()).await;
+    let message = body["message"].as_str().unwrap().as_bytes();
+
+    // Signed by another key
+    let forged = Keypair::new().sign_message(message);
+    let (status, _) = api.call(As::Nobody, Method::POST, "/auth/session", Some(session(&wallet, &nonce, &forged))).await;
+    assert_eq!(status, 401);
+
+    // A failed attempt uses the nonce up too
+    let signature = wallet.sign_message(message);
+    let (status, _) = api.call(As::Nobody, Method::POST, "/auth/session", Some(session(&wallet, &nonce, &signature))).await;
+    assert_eq!(status, 401);
+
+    let (nonce, body) = api.challenge(&wallet.pubkey()).await;
+    let signature = wallet.sign_message(body["message"].as_str().unwrap().as_bytes());
+    let (status, _) = api.call(As::Nobody, Method::POST, "/auth/session", Some(session(&wallet, &nonce, &signature))).await;
+    assert_eq!(status, 200);
+    let (status, _) = api.call(As::Nobody, Method::POST, "/auth/session", Some(session(&wallet, &nonce, &signature))).await;
+    assert_eq!(status, 401);
+}
+
+#[tokio::test]
+async fn only_owners_and_their_delegates_act_for_them() {
+    let api = serve().await;
+    let (owner, other) = (Keypair::new(), Keypair::new());
+    let pda = api.open(&owner.pubkey()).await;
+    let (owner_token, other_token) = (api.sign_in(&owner).await, api.sign_in(&other).await);
+
+    let (status, body) = api.call(As::Session(&other_token), Method::GET, &positions(&owner.pubkey()), None).await;
+    assert_eq!((status, code(&body)), (403, "forbidden"));
+    let close = Some(json!({ "exit_price": 1 }));
+    let (status, _) = api.call(As::Session(&other_token), Method::DELETE, &format!("/positions/{pda}/close"), close).await;
+    assert_eq!(status, 403);
+
+    let delegate = Some(json!({ "delegate": other.pubkey().to_string() }));
+    let (status, _) = api.call(As::Session(&owner_token), Method::POST, "/auth/delegates", delegate).await;
+    assert_eq!(status, 200);
+    let (status, _) = api.call(As::Session(&other_token), Method::GET, &positions(&owner.pubkey()), None).await;
+    assert_eq!(status, 200);
+    // But not the other way around
+    let (status, _) = api.call(As::Session(&owner_token), Method::GET, &positions(&other.pubkey()), None).await;
+    assert_eq!(status, 403);
+
+    let path = format!("/auth/delegates/{}", other.pubkey());
+    let (status, _) = api.call(As::Session(&owner_token), Method::DELETE, &path, None).await;
+    assert_eq!(status, 200);
+    let (status, _) = api.call(As::Session(&other_token), Method::GET, &positions(&owner.pubkey()), None).await;
+    assert_eq!(status, 403);
+}
+
+#[tokio::test]
+async fn api_keys_are_limited_to_their_scopes() {
+    let api = serve().await;
+    let owner = Keypair::new();
+    let pda = api.open(&owner.pubkey()).await;
+    let token = api.sign_in(&owner).await;
+    let (status, body) = api.call(As::Session(&token), Method::POST, "/auth/api-keys", Some(json!({ "scopes": ["read"], "label": "bot" }))).await;
+    assert_eq!(status, 200, "{body}");
+    let (id, key) = (body["id"].as_i64().unwrap(), body["key"].as_str().unwrap().to_string());
+
+    let (status, _) = api.call(As::Key(&key), Method::GET, &positions(&owner.pubkey()), None).await;
+    assert_eq!(status, 200);
+    let modify = Some(json!({ "type": "add_margin", "amount": 1 }));
+    let (status, body) = api.call(As::Key(&key), Method::PUT, &format!("/positions/{pda}/modify"), modify).await;
+    assert_eq!((status, code(&body)), (403, "forbidden"));
+    assert!(body["error"]["message"].as_str().unwrap().contains("trade"));
+    // Keys can't mint more keys
+    let (status, _) = api.call(As::Key(&key), Method::POST, "/auth/api-keys", Some(json!({ "scopes": ["trade"] }))).await;
+    assert_eq!(status, 401);
+
+    let (status, body) = api.call(As::Session(&token), Method::GET, "/auth/api-keys", None).await;
+    assert_eq!((status, &body["api_keys"][0]["scopes"]), (200, &json!(["read"])));
+    assert!(!body.to_string().contains(&key), "the key itself is never listed");
+    let (status, _) = api.call(As::Session(&token), Method::DELETE, &format!("/auth/api-keys/{id}"), None).await;
+    assert_eq!(status, 200);
+    let (status, _) = api.call(As::Key(&key), Method::GET, &positions(&owner.pubkey()), None).await;
+    assert_eq!(status, 401);
+}
+
+#[tokio::test]
+async fn each_wallet_keeps_only_its_latest_nonces() {
+    let api = serve().await;
+    let wallet = Keypair::new();
+    let mut challenges = Vec::new();
+    for _ in 0..5 {
+        challenges.push(api.challenge(&wallet.pubkey()).await);
+    }
+    let sign_in = |(nonce, body): &(String, Value)| {
+        let signature = wallet.sign_message(body["message"].as_str().unwrap().as_bytes());
+        api.call(As::Nobody, Method::POST, "/auth/session", Some(session(&wallet, nonce, &signature)))
+    };
+    // The fifth pushed out the first
+    assert_eq!(sign_in(&challenges[0]).await.0, 401);
+    assert_eq!(sign_in(&challenges[1]).await.0, 200);
+    assert_eq!(sign_in(&challenges[4]).await.0, 200);
+}
+
+#[tokio::test]
+async fn reconcile_needs_an_admin_key() {
+    let api = serve().await;
+    let (status, _) = api.call(As::Nobody, Method::POST, "/admin/reconcile", None).await;
+    assert_eq!(status, 401);
+
+    let wallet = Keypair::new();
+    let token = api.sign_in(&wallet).await;
+    let (status, body) = api.call(As::Session(&token), Method::GET, "/admin/reconcile", None).await;
+    assert_eq!((status, code(&body)), (403, "forbidden"));
+    // Nor can a wallet make itself an admin key
+    let (status, _) = api.call(As::Session(&token), Method::POST, "/auth/api-keys", Some(json!({ "scopes": ["admin"] }))).await;
+    assert_eq!(status, 403);
+
+    let repo: SharedRepo = api.repo.clone();
+    let (_, trader) = create_api_key(&repo, &wallet.pubkey(), &[Scope::Read, Scope::Trade, Scope::Transfer], None).await.unwrap();
+    let (status, _) = api.call(As::Key(&trader), Method::POST, "/admin/reconcile", None).await;
+    assert_eq!(status, 403);
+
+    let (_, admin) = create_api_key(&repo, &Pubkey::new_unique(), &[Scope::Admin], Some("ops")).await.unwrap();
+    let (status, body) = api.call(As::Key(&admin), Method::POST, "/admin/reconcile", None).await;
+    assert_eq!((status, body["report"]["positions_checked"].as_u64()), (200, Some(0)), "{body}");
+    let (status, _) = api.call(As::Key(&admin), Method::GET, "/admin/reconcile", None).await;
+    assert_eq!(status, 200);
+}
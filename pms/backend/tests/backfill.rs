@@ -0,0 +1,151 @@
+// Backfill over tests/fixtures/backfill, recorded getTransaction results standing in for RPC:
+//
+//   1000  open_btc              DN3j… opens BTC-PERP short on sub-account 1
+//   1000  open_sol              4qhL… opens SOL-PERP long on sub-account 0
+//   1001  increase_sol          SOL-PERP to 15_000_000
+//   1002  failed_remove_margin  failed, no events
+//   1003  close_sol             SOL-PERP closed, realized 150_000_000
+//   1004  add_margin_btc        BTC-PERP margin to 1_500_000_000
+use std::sync::Arc;
+
+use position_service::db::{PositionRepo, SharedRepo, SqliteRepo};
+use position_service::models::{ChainTx, PositionState};
+use position_service::services::backfill::{Backfill, BackfillStart, BACKFILL_CURSOR};
+use position_service::services::indexer::{Indexer, RecordedSource, LIVE_CURSOR, TAKEOVER_CURSOR};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+const PROGRAM_ID: Pubkey = perp_sdk::PROGRAM_ID;
+const ALICE: &str = "4qhLYcqyfrRUb4VPVLv8Ljtg1XF47VExcY9S48Ug78tg";
+const BOB: &str = "DN3jNzugqv4WYZuaPyDEi2xf85U9F1uHM9Sc1K97Zzgs";
+const OPEN_SOL: &str = "gW7wo7awcoGdSES3S1xzo2SECgEV5RXbrP6HGK5kgDJCGfZmt3pRkYkaVX5PjdDxJTmSjudGkS4j1ivgtQRrsCa";
+const INCREASE_SOL: &str = "5gacCMgr8VcjrdwmxqZsk62b94SsNLDdqZwJryqxrXiyY8E9Y91i9TpvSwcTDCPbqvZhoA1PQWDCWAcxUeaaBA5g";
+const ADD_MARGIN_BTC: &str = "3V2hsHcedWUmi8GvNPA5fQUHAC1M4ZQW9jTUqiLhWXzXRCq452ZJ2aPuBEn6H67fhv3EcnMSyhsUAH6D6PbvT5ff";
+
+struct Setup {
+    db: Arc<SqliteRepo>,
+    repo: SharedRepo,
+    source: Arc<RecordedSource>,
+}
+
+impl Setup {
+    async fn new() -> Self {
+        let db = Arc::new(SqliteRepo::connect("sqlite::memory:").await.unwrap());
+        db.migrate().await.unwrap();
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/backfill");
+        let source = Arc::new(RecordedSource::open(dir, PROGRAM_ID).unwrap());
+        Self { repo: db.clone(), db, source }
+    }
+
+    fn backfill(&self) -> Backfill {
+        Backfill::new(self.repo.clone(), self.source.clone(), PROGRAM_ID)
+    }
+
+    fn indexer(&self) -> Indexer {
+        Indexer::new(self.repo.clone(), self.source.clone(), PROGRAM_ID, "ws://unused".to_string())
+    }
+
+    async fn modifications(&self) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM position_modifications").fetch_one(self.db.pool()).await.unwrap()
+    }
+}
+
+fn sol() -> Pubkey {
+    perp_sdk::pda::position_pda(&PROGRAM_ID, &ALICE.parse().unwrap(), 0, "SOL-PERP").0
+}
+
+fn btc() -> Pubkey {
+    perp_sdk::pda::position_pda(&PROGRAM_ID, &BOB.parse().unwrap(), 1, "BTC-PERP").0
+}
+
+fn signature(s: &str) -> Signature {
+    s.parse().unwrap()
+}
+
+#[tokio::test]
+async fn replays_recorded_history_in_slot_order() {
+    let setup = Setup::new().await;
+
+    // File names sort add_margin_btc first; replaying in that order would skip it as unknown
+    let report = setup.backfill().run(&BackfillStart::Slot(0)).await.unwrap();
+    assert_eq!((report.transactions, report.changes), (6, 5));
+
+    let sol = setup.repo.fetch_position_view(&sol()).await.unwrap().unwrap();
+    assert_eq!((sol.size, sol.margin, sol.state), (0, 0, PositionState::Closed));
+    assert_eq!(sol.realized_pnl, 150_000_000);
+    let btc = setup.repo.fetch_position_view(&btc()).await.unwrap().unwrap();
+    assert_eq!((btc.sub_id, btc.size, btc.margin, btc.leverage), (1, 100_000, 1_500_000_000, 4));
+    assert_eq!(btc.state, PositionState::Open);
+
+    // On an empty database the run ends at the newest transaction and hands over there
+    let live = setup.repo.fetch_cursor(LIVE_CURSOR).await.unwrap().unwrap();
+    assert_eq!((live.slot, live.signature.as_str()), (1004, ADD_MARGIN_BTC));
+    assert_eq!(report.end, Some(live.clone()));
+    assert_eq!(setup.repo.fetch_cursor(BACKFILL_CURSOR).await.unwrap(), Some(live));
+    assert_eq!(setup.indexer().poll_once().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn starts_from_a_slot_or_signature() {
+    // open_btc shares slot 1000 but comes before open_sol
+    let setup = Setup::new().await;
+    let report = setup.backfill().run(&BackfillStart::Signature(signature(OPEN_SOL))).await.unwrap();
+    assert_eq!((report.transactions, report.changes), (5, 3));
+    assert_eq!(setup.repo.fetch_position_view(&sol()).await.unwrap().unwrap().state, PositionState::Closed);
+    // Without its open the later margin change has nothing to apply to
+    assert!(setup.repo.fetch_position_view(&btc()).await.unwrap().is_none());
+
+    let setup = Setup::new().await;
+    let report = setup.backfill().run(&BackfillStart::Slot(1001)).await.unwrap();
+    assert_eq!((report.transactions, report.changes), (4, 0));
+    assert_eq!(setup.modifications().await, 0);
+
+    // Starting after the take-over replays nothing, but lets the live indexer go
+    let setup = Setup::new().await;
+    let report = setup.backfill().run(&BackfillStart::Slot(2000)).await.unwrap();
+    assert_eq!(report.transactions, 0);
+    assert_eq!(setup.repo.fetch_cursor(BACKFILL_CURSOR).await.unwrap(), report.end);
+}
+
+#[tokio::test]
+async fn hands_off_to_the_live_indexer() {
+    let setup = Setup::new().await;
+    // The live indexer took over after increase_sol, and waits there for the backfill
+    let mark = ChainTx { slot: 1001, signature: INCREASE_SOL.to_string(), changes: vec![] };
+    setup.repo.apply_chain_tx(TAKEOVER_CURSOR, &mark).await.unwrap();
+    setup.repo.apply_chain_tx(LIVE_CURSOR, &mark).await.unwrap();
+    assert_eq!(setup.indexer().poll_once().await.unwrap(), 0);
+    assert_eq!(setup.repo.fetch_cursor(LIVE_CURSOR).await.unwrap().unwrap().signature, INCREASE_SOL);
+    assert_eq!(setup.modifications().await, 0);
+
+    let report = setup.backfill().run(&BackfillStart::Slot(0)).await.unwrap();
+    assert_eq!((report.transactions, report.changes), (3, 3));
+    assert_eq!(report.end.unwrap().signature, INCREASE_SOL);
+    assert_eq!(setup.repo.fetch_position_view(&sol()).await.unwrap().unwrap().size, 15_000_000);
+
+    // The live indexer picks up right after, and together they cover everything once
+    assert_eq!(setup.indexer().poll_once().await.unwrap(), 2);
+    assert_eq!(setup.modifications().await, 5);
+    assert_eq!(setup.repo.fetch_position_view(&sol()).await.unwrap().unwrap().state, PositionState::Closed);
+    assert_eq!(setup.repo.fetch_position_view(&btc()).await.unwrap().unwrap().margin, 1_500_000_000);
+
+    // A second run now ends at the newer live cursor and applies nothing again
+    let report = setup.backfill().run(&BackfillStart::Slot(0)).await.unwrap();
+    assert_eq!((report.transactions, report.changes), (6, 0));
+    assert_eq!(report.end.unwrap().signature, ADD_MARGIN_BTC);
+    assert_eq!(setup.modifications().await, 5);
+}
+
+#[tokio::test]
+async fn unknown_start_signature_is_an_error() {
+    let setup = Setup::new().await;
+    let err = setup.backfill().run(&BackfillStart::Signature(Signature::new_unique())).await.unwrap_err();
+    assert!(err.to_string().contains("not found"), "unexpected error: {err}");
+    assert_eq!(setup.modifications().await, 0);
+}
+
+#[test]
+fn start_parses_as_slot_or_signature() {
+    assert_eq!("1000".parse::<BackfillStart>().unwrap(), BackfillStart::Slot(1000));
+    assert_eq!(OPEN_SOL.parse::<BackfillStart>().unwrap(), BackfillStart::Signature(signature(OPEN_SOL)));
+    assert!("not-a-start".parse::<BackfillStart>().is_err());
+}
@@ -0,0 +1,236 @@
+// Indexer against an in-memory chain standing in for RPC, writing to in-memory SQLite
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use perp_events::{
+    events::{Appended, CollateralDeposited, PoolFill, PositionClosed, PositionModified, PositionOpened},
+    EventRecord, PerpEvent, TxEvents,
+};
+use perp_sdk::types::Side;
+use position_service::api::ws::{Stream, WsHub};
+use position_service::db::{PositionRepo, SharedRepo, SqliteRepo};
+use position_service::models::PositionState;
+use position_service::models::ChainTx;
+use position_service::services::backfill::BACKFILL_CURSOR;
+use position_service::services::indexer::{chain_tx, ChainSource, Indexer, SignatureInfo, LIVE_CURSOR};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+const PROGRAM_ID: Pubkey = perp_sdk::PROGRAM_ID;
+const SYMBOL: &str = "SOL-PERP";
+
+#[derive(Default)]
+struct FakeChain {
+    txs: Mutex<Vec<TxEvents>>,
+    polled_until: Mutex<Vec<Option<Signature>>>,
+}
+
+impl FakeChain {
+    fn push(&self, slot: u64, failed: bool, events: Vec<PerpEvent>) -> Signature {
+        let signature = Signature::new_unique();
+        let events = events
+            .into_iter()
+            .enumerate()
+            .map(|(i, event)| EventRecord { slot, signature, instruction_index: 0, stack_height: 1, event_index: i, event })
+            .collect();
+        let events = if failed { vec![] } else { events };
+        self.txs.lock().unwrap().push(TxEvents { slot, signature, failed, truncated: false, events, skipped: vec![] });
+        signature
+    }
+}
+
+#[async_trait]
+impl ChainSource for FakeChain {
+    async fn signature_page(
+        &self,
+        before: Option<&Signature>,
+        until: Option<&Signature>,
+        limit: usize,
+    ) -> Result<Vec<SignatureInfo>> {
+        self.polled_until.lock().unwrap().push(until.copied());
+        let txs = self.txs.lock().unwrap();
+        let position = |s: &Signature| txs.iter().position(|t| t.signature == *s).unwrap();
+        let end = before.map_or(txs.len(), position);
+        let start = until.map_or(0, |u| position(u) + 1);
+        Ok(txs[start..end]
+            .iter()
+            .rev()
+            .take(limit)
+            .map(|t| SignatureInfo { slot: t.slot, signature: t.signature, failed: t.failed })
+            .collect())
+    }
+
+    async fn transaction(&self, signature: &Signature) -> Result<TxEvents> {
+        let txs = self.txs.lock().unwrap();
+        Ok(txs.iter().find(|t| t.signature == *signature).expect("unknown signature").clone())
+    }
+}
+
+async fn setup() -> (SharedRepo, Arc<FakeChain>, Indexer) {
+    let repo = SqliteRepo::connect("sqlite::memory:").await.unwrap();
+    repo.migrate().await.unwrap();
+    let repo: SharedRepo = Arc::new(repo);
+    let chain = Arc::new(FakeChain::default());
+    let indexer = Indexer::new(repo.clone(), chain.clone(), PROGRAM_ID, "ws://unused".to_string());
+    (repo, chain, indexer)
+}
+
+// What a backfill run up to the take-over point leaves behind, without replaying anything
+async fn backfilled(repo: &SharedRepo) {
+    let live = repo.fetch_cursor(LIVE_CURSOR).await.unwrap().unwrap();
+    repo.apply_chain_tx(BACKFILL_CURSOR, &ChainTx { slot: live.slot, signature: live.signature, changes: vec![] }).await.unwrap();
+}
+
+fn opened(owner: Pubkey, sub_id: Option<u16>) -> PerpEvent {
+    PerpEvent::PositionOpened(PositionOpened {
+        owner,
+        symbol: SYMBOL.to_string(),
+        side: Side::Short,
+        size: 10_000_000,
+        leverage: 10,
+        entry_price: 180_000_000,
+        initial_margin: 180_000_000,
+        liquidation_price: 196_000_000,
+        sub_id: Appended(sub_id),
+    })
+}
+
+fn fill(owner: Pubkey) -> PerpEvent {
+    PerpEvent::PoolFill(PoolFill {
+        owner,
+        symbol: SYMBOL.to_string(),
+        taker_side: Side::Short,
+        notional: 1,
+        oracle_price: 1,
+        execution_price: 1,
+        spread_bps: 0,
+        long_open_interest: 0,
+        short_open_interest: 1,
+    })
+}
+
+#[tokio::test]
+async fn poll_resumes_from_the_cursor() {
+    let (repo, chain, indexer) = setup().await;
+    let owner = Pubkey::new_unique();
+    let pda = perp_sdk::pda::position_pda(&PROGRAM_ID, &owner, 2, SYMBOL).0;
+
+    // First start: the live indexer takes over after the newest transaction, leaving it and
+    // everything before to the backfill, and waits for it
+    let earlier = chain.push(99, false, vec![opened(Pubkey::new_unique(), None)]);
+    assert_eq!(indexer.poll_once().await.unwrap(), 0);
+    let cursor = repo.fetch_cursor(LIVE_CURSOR).await.unwrap().unwrap();
+    assert_eq!((cursor.slot, cursor.signature), (99, earlier.to_string()));
+    assert!(repo.fetch_open_positions().await.unwrap().is_empty());
+
+    chain.push(100, false, vec![fill(owner), opened(owner, Some(2))]);
+    chain.push(101, true, vec![]);
+    let modified = PerpEvent::PositionModified(PositionModified {
+        owner,
+        symbol: SYMBOL.to_string(),
+        size: 5_000_000,
+        margin: 180_000_000,
+        leverage: 10,
+        price: 179_000_000,
+        unrealized_pnl: 0,
+        liquidation_price: 214_000_000,
+        sub_id: Appended(Some(2)),
+    });
+    let last = chain.push(102, false, vec![fill(owner), modified]);
+
+    assert_eq!(indexer.poll_once().await.unwrap(), 0);
+    backfilled(&repo).await;
+    assert_eq!(indexer.poll_once().await.unwrap(), 2);
+    let cursor = repo.fetch_cursor(LIVE_CURSOR).await.unwrap().unwrap();
+    assert_eq!((cursor.slot, cursor.signature), (102, last.to_string()));
+    let view = repo.fetch_position_view(&pda).await.unwrap().unwrap();
+    assert_eq!((view.sub_id, view.size, view.state), (2, 5_000_000, PositionState::Open));
+
+    // Nothing new: each poll starts after the cursor (the first one right after taking over),
+    // and waiting for the backfill walks nothing
+    assert_eq!(indexer.poll_once().await.unwrap(), 0);
+    assert_eq!(*chain.polled_until.lock().unwrap(), [None, Some(earlier), Some(last)]);
+
+    let closed = PerpEvent::PositionClosed(PositionClosed {
+        owner,
+        symbol: SYMBOL.to_string(),
+        size_closed: 5_000_000,
+        exit_price: 170_000_000,
+        realized_pnl: 50_000_000,
+        payout: 230_000_000,
+        sub_id: Appended(Some(2)),
+    });
+    chain.push(103, false, vec![closed]);
+    assert_eq!(indexer.poll_once().await.unwrap(), 1);
+    assert_eq!(repo.fetch_position_view(&pda).await.unwrap().unwrap().state, PositionState::Closed);
+}
+
+#[tokio::test]
+async fn dropped_notifications_are_caught_up_in_order() {
+    let (repo, chain, indexer) = setup().await;
+    let owner = Pubkey::new_unique();
+    chain.push(99, false, vec![]);
+    assert_eq!(indexer.poll_once().await.unwrap(), 0);
+    backfilled(&repo).await;
+    chain.push(100, false, vec![opened(owner, Some(0))]);
+    let closed = PerpEvent::PositionClosed(PositionClosed {
+        owner,
+        symbol: SYMBOL.to_string(),
+        size_closed: 10_000_000,
+        exit_price: 170_000_000,
+        realized_pnl: 100_000_000,
+        payout: 280_000_000,
+        sub_id: Appended(Some(0)),
+    });
+    let last = chain.push(101, false, vec![closed]);
+
+    // The subscription delivered only the close; the open before it is applied first anyway
+    indexer.follow(futures::stream::iter([()])).await.unwrap();
+    let pda = perp_sdk::pda::position_pda(&PROGRAM_ID, &owner, 0, SYMBOL).0;
+    assert_eq!(repo.fetch_position_view(&pda).await.unwrap().unwrap().state, PositionState::Closed);
+    assert_eq!(repo.fetch_cursor(LIVE_CURSOR).await.unwrap().unwrap().signature, last.to_string());
+}
+
+#[tokio::test]
+async fn applied_changes_are_published() {
+    let (repo, chain, indexer) = setup().await;
+    let hub = WsHub::new();
+    let indexer = indexer.with_hub(hub.clone());
+    let mut rx = hub.subscribe();
+    let owner = Pubkey::new_unique();
+    chain.push(99, false, vec![]);
+    indexer.poll_once().await.unwrap();
+    backfilled(&repo).await;
+    let signature = chain.push(100, false, vec![fill(owner), opened(owner, Some(1))]);
+    indexer.poll_once().await.unwrap();
+
+    let pda = perp_sdk::pda::position_pda(&PROGRAM_ID, &owner, 1, SYMBOL).0;
+    let event = rx.try_recv().unwrap();
+    assert_eq!((event.stream, event.owner, event.position), (Stream::Events, owner, pda));
+    assert_eq!((event.data["event"]["kind"].as_str(), event.data["event_index"].as_u64()), (Some("opened"), Some(1)));
+    assert_eq!(event.data["signature"], signature.to_string());
+    let update = rx.try_recv().unwrap();
+    assert_eq!((update.stream, update.position), (Stream::Positions, pda));
+    assert_eq!(update.data["size"], 10_000_000);
+
+    // Seen again, nothing is new and nothing is sent
+    indexer.poll_once().await.unwrap();
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn events_map_to_position_changes() {
+    let owner = Pubkey::new_unique();
+    let chain = FakeChain::default();
+    let deposit = PerpEvent::CollateralDeposited(CollateralDeposited { owner, sub_id: 3, amount: 1, total_collateral: 1 });
+    chain.push(7, false, vec![deposit, fill(owner), opened(owner, None)]);
+    let events = chain.txs.lock().unwrap()[0].clone();
+
+    let tx = chain_tx(&PROGRAM_ID, &events).unwrap();
+    assert_eq!((tx.slot, tx.signature.as_str()), (7, events.signature.to_string().as_str()));
+    // Collateral and pool events don't touch positions; the open keeps its event index
+    assert_eq!(tx.changes.len(), 1);
+    assert_eq!(tx.changes[0].event_index, 2);
+    // Logged before sub_id was appended: the default sub-account
+    assert_eq!(tx.changes[0].position_pda, perp_sdk::pda::position_pda(&PROGRAM_ID, &owner, 0, SYMBOL).0);
+}
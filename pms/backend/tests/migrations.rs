@@ -0,0 +1,189 @@
+// Upgrade and rollback of the versioned migrations, each case on a database nothing else
+// has touched: a new in-memory SQLite database, or a Postgres database created for the test
+// next to TEST_DATABASE_URL and dropped afterwards. The Postgres cases are ignored unless
+// run with `cargo test -- --include-ignored`.
+use async_trait::async_trait;
+use position_service::db::{self, PgRepo, PositionRepo, SqliteRepo};
+use position_service::models::{OpenPositionInput, Side};
+use solana_sdk::{pubkey::Pubkey, signature::{Keypair, Signer}};
+
+#[async_trait]
+trait Catalog: PositionRepo {
+    fn latest() -> i64;
+    async fn tables(&self) -> Vec<String>;
+    async fn tamper_checksum(&self, version: i64);
+}
+
+#[async_trait]
+impl Catalog for SqliteRepo {
+    fn latest() -> i64 {
+        db::sqlite::MIGRATOR.iter().map(|m| m.version).max().unwrap()
+    }
+
+    async fn tables(&self) -> Vec<String> {
+        sqlx::query_scalar(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name <> '_sqlx_migrations' ORDER BY name",
+        )
+        .fetch_all(self.pool())
+        .await
+        .unwrap()
+    }
+
+    async fn tamper_checksum(&self, version: i64) {
+        sqlx::query("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = ?1")
+            .bind(version)
+            .execute(self.pool())
+            .await
+            .unwrap();
+    }
+}
+
+#[async_trait]
+impl Catalog for PgRepo {
+    fn latest() -> i64 {
+        db::repo::MIGRATOR.iter().map(|m| m.version).max().unwrap()
+    }
+
+    async fn tables(&self) -> Vec<String> {
+        sqlx::query_scalar("SELECT tablename::TEXT FROM pg_tables WHERE schemaname = 'perp' ORDER BY tablename")
+            .fetch_all(self.pool())
+            .await
+            .unwrap()
+    }
+
+    async fn tamper_checksum(&self, version: i64) {
+        sqlx::query("UPDATE _sqlx_migrations SET checksum = '\\x00' WHERE version = $1")
+            .bind(version)
+            .execute(self.pool())
+            .await
+            .unwrap();
+    }
+}
+
+// A scratch database on the TEST_DATABASE_URL server, dropped again on finish
+struct FreshPg {
+    admin: sqlx::PgPool,
+    name: String,
+    repo: PgRepo,
+}
+
+impl FreshPg {
+    async fn create() -> Self {
+        let url = std::env::var("TEST_DATABASE_URL").expect("the Postgres cases need TEST_DATABASE_URL");
+        let admin = sqlx::PgPool::connect(&url).await.unwrap();
+        let name = format!("perp_migrations_{}", hex(&random_key().to_bytes()[..6]));
+        sqlx::query(&format!("CREATE DATABASE {name}")).execute(&admin).await.unwrap();
+
+        let (base, query) = url.split_once('?').map_or((url.as_str(), None), |(b, q)| (b, Some(q)));
+        let (server, _) = base.rsplit_once('/').unwrap();
+        let scratch = match query {
+            Some(q) => format!("{server}/{name}?{q}"),
+            None => format!("{server}/{name}"),
+        };
+        let repo = PgRepo::connect(&scratch).await.unwrap();
+        Self { admin, name, repo }
+    }
+
+    async fn finish(self) {
+        self.repo.pool().close().await;
+        sqlx::query(&format!("DROP DATABASE {} WITH (FORCE)", self.name)).execute(&self.admin).await.unwrap();
+    }
+}
+
+macro_rules! migration_cases {
+    ($($case:ident),* $(,)?) => {
+        mod sqlite {
+            $(#[tokio::test]
+            async fn $case() {
+                super::$case(&position_service::db::SqliteRepo::connect("sqlite::memory:").await.unwrap()).await;
+            })*
+        }
+
+        mod postgres {
+            $(#[tokio::test]
+            #[ignore = "needs TEST_DATABASE_URL"]
+            async fn $case() {
+                let fresh = super::FreshPg::create().await;
+                super::$case(&fresh.repo).await;
+                fresh.finish().await;
+            })*
+        }
+    };
+}
+
+migration_cases!(
+    fresh_database_migrates_to_latest,
+    rollback_and_upgrade_keep_earlier_data,
+    rollback_to_zero_drops_everything,
+    edited_migration_is_rejected,
+);
+
+fn random_key() -> Pubkey {
+    Keypair::new().pubkey()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+async fn fresh_database_migrates_to_latest<R: Catalog>(repo: &R) {
+    assert_eq!(repo.schema_version().await.unwrap(), None);
+    assert!(repo.tables().await.is_empty());
+
+    repo.migrate().await.unwrap();
+    assert_eq!(repo.schema_version().await.unwrap(), Some(R::latest()));
+    let tables = repo.tables().await;
+    for table in ["positions", "users", "markets", "operations", "test_scenario_results", "delegates", "api_keys", "demo_positions", "demo_orders"] {
+        assert!(tables.iter().any(|t| t == table), "{table} missing from {tables:?}");
+    }
+
+    // A second run finds nothing pending
+    repo.migrate().await.unwrap();
+    assert_eq!(repo.schema_version().await.unwrap(), Some(R::latest()));
+}
+
+async fn rollback_and_upgrade_keep_earlier_data<R: Catalog>(repo: &R) {
+    repo.migrate().await.unwrap();
+
+    let owner = random_key();
+    let input = OpenPositionInput {
+        sub_id: 0,
+        symbol: "SOL-PERP".to_string(),
+        side: Side::Short,
+        size: 1_000_000,
+        leverage: 5,
+        entry_price: 180_000_000,
+        margin_token_account: random_key(),
+        quote_mint: random_key(),
+    };
+    let (pda, _) = perp_sdk::pda::position_pda(&perp_sdk::PROGRAM_ID, &owner, 0, &input.symbol);
+    repo.insert_position_open_intent(&owner, &pda, &input).await.unwrap();
+
+    repo.rollback(1).await.unwrap();
+    assert_eq!(repo.schema_version().await.unwrap(), Some(1));
+    assert!(!repo.tables().await.iter().any(|t| t == "test_scenario_results"));
+    assert!(repo.fetch_position_view(&pda).await.unwrap().is_some());
+
+    repo.migrate().await.unwrap();
+    assert_eq!(repo.schema_version().await.unwrap(), Some(R::latest()));
+    assert!(repo.tables().await.iter().any(|t| t == "test_scenario_results"));
+    assert_eq!(repo.fetch_position_view(&pda).await.unwrap().unwrap().size, 1_000_000);
+}
+
+async fn rollback_to_zero_drops_everything<R: Catalog>(repo: &R) {
+    repo.migrate().await.unwrap();
+    repo.rollback(0).await.unwrap();
+    assert_eq!(repo.schema_version().await.unwrap(), None);
+    assert!(repo.tables().await.is_empty());
+
+    repo.migrate().await.unwrap();
+    assert_eq!(repo.schema_version().await.unwrap(), Some(R::latest()));
+}
+
+async fn edited_migration_is_rejected<R: Catalog>(repo: &R) {
+    repo.migrate().await.unwrap();
+    repo.tamper_checksum(1).await;
+
+    let err = repo.migrate().await.unwrap_err();
+    assert!(err.to_string().contains("modified"), "unexpected error: {err}");
+}
@@ -0,0 +1,304 @@
+// Operations driven through execute() and recover() against a scripted RPC, with the database
+// in in-memory SQLite
+use std::collections::HashSet;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use perp_sdk::{types, InstructionBuilder, PerpError};
+use position_service::db::{PositionRepo, SharedRepo, SqliteRepo};
+use position_service::errors::TxError;
+use position_service::models::{OpenPositionInput, OperationId, OperationStatus, PositionState, Side};
+use position_service::services::operations::{Operations, RecoveryReport};
+use position_service::solana::tx::{TxRpc, TxSubmitter};
+use solana_sdk::{
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    hash::Hash,
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+
+const PROGRAM_ID: Pubkey = perp_sdk::PROGRAM_ID;
+const SYMBOL: &str = "SOL-PERP";
+
+// Transactions land (confirmed) if they were sent while `land` was set, or were listed in
+// `landed` up front; anything else is never seen. The block height only moves when a test sets it.
+#[derive(Default)]
+struct ScriptedRpc {
+    simulation: Option<TransactionError>,
+    land: bool,
+    landed: Mutex<HashSet<Signature>>,
+    height: AtomicU64,
+}
+
+#[async_trait]
+impl TxRpc for ScriptedRpc {
+    async fn latest_blockhash(&self, _: CommitmentConfig) -> Result<(Hash, u64)> {
+        Ok((Hash::new_unique(), self.height.load(Ordering::SeqCst) + 2))
+    }
+
+    async fn block_height(&self, _: CommitmentConfig) -> Result<u64> {
+        Ok(self.height.load(Ordering::SeqCst))
+    }
+
+    async fn simulate(&self, _: &Transaction, _: CommitmentConfig) -> Result<(Option<TransactionError>, Vec<String>)> {
+        Ok((self.simulation.clone(), vec![]))
+    }
+
+    async fn send(&self, tx: &Transaction) -> Result<Signature> {
+        if self.land {
+            self.landed.lock().unwrap().insert(tx.signatures[0]);
+        }
+        Ok(tx.signatures[0])
+    }
+
+    async fn status(&self, signature: &Signature) -> Result<Option<TransactionStatus>> {
+        Ok(self.landed.lock().unwrap().contains(signature).then(|| TransactionStatus {
+            slot: 50,
+            confirmations: None,
+            status: Ok(()),
+            err: None,
+            confirmation_status: Some(TransactionConfirmationStatus::Confirmed),
+        }))
+    }
+
+    async fn account_data(&self, _: &Pubkey, _: CommitmentConfig) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    async fn logs(&self, _: &Signature, _: CommitmentConfig) -> Result<Option<Vec<String>>> {
+        Ok(None)
+    }
+}
+
+async fn setup(rpc: ScriptedRpc) -> (Arc<SqliteRepo>, Arc<ScriptedRpc>, Operations) {
+    let db = Arc::new(SqliteRepo::connect("sqlite::memory:").await.unwrap());
+    db.migrate().await.unwrap();
+    let repo: SharedRepo = db.clone();
+    let rpc = Arc::new(rpc);
+    let submitter = TxSubmitter::new(rpc.clone(), CommitmentLevel::Confirmed).with_poll_interval(Duration::from_millis(1));
+    (db, rpc, Operations::new(repo, submitter))
+}
+
+fn open_input() -> OpenPositionInput {
+    OpenPositionInput {
+        sub_id: 0,
+        symbol: SYMBOL.to_string(),
+        side: Side::Long,
+        size: 10,
+        leverage: 10,
+        entry_price: 100_000_000,
+        margin_token_account: Pubkey::new_unique(),
+        quote_mint: Pubkey::new_unique(),
+    }
+}
+
+fn pda(owner: &Pubkey) -> Pubkey {
+    perp_sdk::pda::position_pda(&PROGRAM_ID, owner, 0, SYMBOL).0
+}
+
+fn instructions(payer: &Keypair) -> Vec<Instruction> {
+    vec![InstructionBuilder::new(PROGRAM_ID, Pubkey::new_unique())
+        .open_position(&payer.pubkey(), 0, SYMBOL, types::Side::Long, 10, 10, 100_000_000)]
+}
+
+// An open position of a new owner, with its close recorded (position closing)
+async fn closing(repo: &SqliteRepo) -> (Pubkey, OperationId) {
+    let owner = Pubkey::new_unique();
+    let opened = repo.insert_position_open_intent(&owner, &pda(&owner), &open_input()).await.unwrap();
+    repo.finish_operation(opened, OperationStatus::Confirmed, None).await.unwrap();
+    let close = repo.insert_position_close_intent(&owner, &pda(&owner), 110_000_000).await.unwrap();
+    (owner, close)
+}
+
+async fn state(repo: &SqliteRepo, owner: &Pubkey) -> PositionState {
+    repo.fetch_position_view(&pda(owner)).await.unwrap().unwrap().state
+}
+
+#[tokio::test]
+async fn execute_records_the_signature_and_settles() {
+    let (repo, _, ops) = setup(ScriptedRpc { land: true, ..Default::default() }).await;
+    let payer = Keypair::new();
+    let owner = payer.pubkey();
+    let id = repo.insert_position_open_intent(&owner, &pda(&owner), &open_input()).await.unwrap();
+    assert_eq!(state(&repo, &owner).await, PositionState::Opening);
+
+    let confirmed = ops.execute(id, &instructions(&payer), &payer).await.unwrap();
+    let op = repo.fetch_operation(id).await.unwrap().unwrap();
+    assert_eq!(op.status, OperationStatus::Confirmed);
+    assert_eq!(op.signature, Some(confirmed.signature.to_string()));
+    assert!(op.last_valid_block_height.is_some());
+    assert_eq!(state(&repo, &owner).await, PositionState::Open);
+}
+
+#[tokio::test]
+async fn rejected_operations_fail_and_restore_the_position() {
+    let rejection = TransactionError::InstructionError(0, InstructionError::Custom(PerpError::LeverageExceeded.code()));
+    let (repo, _, ops) = setup(ScriptedRpc { simulation: Some(rejection), ..Default::default() }).await;
+    let (owner, close) = closing(&repo).await;
+    assert_eq!(state(&repo, &owner).await, PositionState::Closing);
+
+    let payer = Keypair::new();
+    let err = ops.execute(close, &instructions(&payer), &payer).await.unwrap_err();
+    assert!(matches!(err, TxError::Program(PerpError::LeverageExceeded)), "unexpected error: {err}");
+    let op = repo.fetch_operation(close).await.unwrap().unwrap();
+    // Rejected in simulation, so nothing was ever signed for the chain
+    assert_eq!((op.status, op.error), (OperationStatus::Failed, Some(err.to_string())));
+    assert_eq!(state(&repo, &owner).await, PositionState::Open);
+}
+
+#[tokio::test]
+async fn recovery_settles_operations_left_in_flight() {
+    let (repo, rpc, ops) = setup(ScriptedRpc::default()).await;
+    rpc.height.store(10, Ordering::SeqCst);
+    // Recorded but never sent; sent and landed; sent and never seen before its blockhash expired
+    let (unsent, unsent_op) = closing(&repo).await;
+    let (landed, landed_op) = closing(&repo).await;
+    let signature = Signature::new_unique();
+    rpc.landed.lock().unwrap().insert(signature);
+    repo.mark_submitted(landed_op, &signature.to_string(), 100).await.unwrap();
+    let (lost, lost_op) = closing(&repo).await;
+    repo.mark_submitted(lost_op, &Signature::new_unique().to_string(), 0).await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    let started = chrono::Utc::now();
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    // Made by this run, so not recovery's to settle
+    let (current, current_op) = closing(&repo).await;
+
+    let report = ops.recover(started).await.unwrap();
+    assert_eq!(report, RecoveryReport { expired: 1, confirmed: 1, failed: 1 });
+
+    let status = |id| {
+        let repo = repo.clone();
+        async move { repo.fetch_operation(id).await.unwrap().unwrap().status }
+    };
+    assert_eq!(status(unsent_op).await, OperationStatus::Failed);
+    assert_eq!(status(landed_op).await, OperationStatus::Confirmed);
+    assert_eq!(status(lost_op).await, OperationStatus::Failed);
+    assert_eq!(status(current_op).await, OperationStatus::Intent);
+
+    // Nothing is left closing but the position with a request still in progress
+    assert_eq!(state(&repo, &unsent).await, PositionState::Open);
+    assert_eq!(state(&repo, &landed).await, PositionState::Closed);
+    assert_eq!(state(&repo, &lost).await, PositionState::Open);
+    assert_eq!(state(&repo, &current).await, PositionState::Closing);
+
+    // A second run finds nothing from before the start
+    assert_eq!(ops.recover(started).await.unwrap(), RecoveryReport::default());
+}
+
+// ---------- wallet signing ----------
+
+async fn status(repo: &SqliteRepo, id: OperationId) -> OperationStatus {
+    repo.fetch_operation(id).await.unwrap().unwrap().status
+}
+
+#[tokio::test]
+async fn prepared_transactions_are_relayed_once_the_wallet_signs() {
+    let (repo, _, ops) = setup(ScriptedRpc { land: true, ..Default::default() }).await;
+    let wallet = Keypair::new();
+    let owner = wallet.pubkey();
+    let id = repo.insert_position_open_intent(&owner, &pda(&owner), &open_input()).await.unwrap();
+
+    let (mut tx, last_valid) = ops.prepare(id, &instructions(&wallet), &owner).await.unwrap();
+    let op = repo.fetch_operation(id).await.unwrap().unwrap();
+    assert_eq!((op.status, op.last_valid_block_height), (OperationStatus::Intent, Some(last_valid)));
+    assert_eq!(op.message, Some(tx.message_data()));
+
+    let blockhash = tx.message.recent_blockhash;
+    tx.sign(&[&wallet], blockhash);
+    let confirmed = ops.relay(id, &tx).await.unwrap();
+    let op = repo.fetch_operation(id).await.unwrap().unwrap();
+    assert_eq!(op.status, OperationStatus::Confirmed);
+    assert_eq!(op.signature, Some(confirmed.signature.to_string()));
+    assert_eq!(state(&repo, &owner).await, PositionState::Open);
+
+    // Relaying it again is refused rather than sent twice
+    assert!(ops.relay(id, &tx).await.is_err());
+}
+
+#[tokio::test]
+async fn relay_only_accepts_the_prepared_message_fully_signed() {
+    let (repo, _, ops) = setup(ScriptedRpc { land: true, ..Default::default() }).await;
+    let wallet = Keypair::new();
+    let owner = wallet.pubkey();
+    let id = repo.insert_position_open_intent(&owner, &pda(&owner), &open_input()).await.unwrap();
+    let (tx, _) = ops.prepare(id, &instructions(&wallet), &owner).await.unwrap();
+    let blockhash = tx.message.recent_blockhash;
+
+    // Unsigned
+    assert!(ops.relay(id, &tx).await.is_err());
+    // Signed by someone else
+    let mut forged = tx.clone();
+    assert!(forged.try_sign(&[&Keypair::new()], blockhash).is_err());
+    assert!(ops.relay(id, &forged).await.is_err());
+    // A different transaction, properly signed
+    let mut other = Transaction::new_with_payer(&instructions(&wallet), Some(&owner));
+    other.sign(&[&wallet], blockhash);
+    assert!(ops.relay(id, &other).await.is_err());
+
+    // None of it was sent, and the operation is still waiting on the wallet
+    let op = repo.fetch_operation(id).await.unwrap().unwrap();
+    assert_eq!((op.status, op.signature), (OperationStatus::Intent, None));
+    assert_eq!(state(&repo, &owner).await, PositionState::Opening);
+
+    // An operation that wasn't prepared can't be relayed into
+    let (_, close) = closing(&repo).await;
+    let mut signed = tx.clone();
+    signed.sign(&[&wallet], blockhash);
+    assert!(ops.relay(close, &signed).await.is_err());
+}
+
+#[tokio::test]
+async fn prepared_operations_fail_once_their_blockhash_expires() {
+    let (repo, rpc, ops) = setup(ScriptedRpc::default()).await;
+    let (owner, close) = closing(&repo).await;
+    let wallet = Keypair::new();
+    let (_, last_valid) = ops.prepare(close, &instructions(&wallet), &owner).await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(status(&repo, close).await, OperationStatus::Intent);
+
+    rpc.height.store(last_valid + 1, Ordering::SeqCst);
+    for _ in 0..100 {
+        if status(&repo, close).await == OperationStatus::Failed {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    let op = repo.fetch_operation(close).await.unwrap().unwrap();
+    assert_eq!(op.status, OperationStatus::Failed);
+    assert!(op.error.unwrap().contains("expired"));
+    assert_eq!(state(&repo, &owner).await, PositionState::Open);
+}
+
+#[tokio::test]
+async fn recovery_waits_out_prepared_operations() {
+    let (repo, rpc, ops) = setup(ScriptedRpc::default()).await;
+    let (owner, close) = closing(&repo).await;
+    // Prepared by a previous run, whose wallet never sent it
+    repo.record_prepared(close, &[1, 2, 3], 20).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    let started = chrono::Utc::now();
+
+    let recovery = tokio::spawn({
+        let ops = ops.clone();
+        async move { ops.recover(started).await.unwrap() }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    // Still valid, so the wallet could still send it
+    assert_eq!(status(&repo, close).await, OperationStatus::Intent);
+
+    rpc.height.store(21, Ordering::SeqCst);
+    assert_eq!(recovery.await.unwrap(), RecoveryReport { expired: 1, ..Default::default() });
+    assert_eq!(status(&repo, close).await, OperationStatus::Failed);
+    assert_eq!(state(&repo, &owner).await, PositionState::Open);
+}
@@ -0,0 +1,207 @@
+// Reconciler against a fixed account snapshot standing in for getProgramAccounts, with the
+// database in in-memory SQLite
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use perp_sdk::accounts::{Position, UserAccount};
+use perp_sdk::types;
+use position_service::db::{PositionRepo, SharedRepo, SqliteRepo};
+use position_service::models::{
+    ChainChange, ChainTx, Drift, DriftAccount, MarginMode, PositionState, PositionUpdate, Side,
+};
+use position_service::services::reconciler::{AccountSource, ChainSnapshot, Reconciler};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+const PROGRAM_ID: Pubkey = perp_sdk::PROGRAM_ID;
+const SYMBOL: &str = "SOL-PERP";
+
+#[derive(Default)]
+struct FakeAccounts(Mutex<ChainSnapshot>);
+
+#[async_trait]
+impl AccountSource for FakeAccounts {
+    async fn snapshot(&self) -> Result<ChainSnapshot> {
+        Ok(self.0.lock().unwrap().clone())
+    }
+}
+
+impl FakeAccounts {
+    fn set(&self, slot: u64, positions: Vec<Position>, users: Vec<UserAccount>) {
+        let positions = positions
+            .into_iter()
+            .map(|p| (perp_sdk::pda::position_pda(&PROGRAM_ID, &p.owner, p.sub_id, &p.symbol).0, p))
+            .collect();
+        let users = users.into_iter().map(|u| (perp_sdk::pda::user_pda(&PROGRAM_ID, &u.owner, u.sub_id).0, u)).collect();
+        *self.0.lock().unwrap() = ChainSnapshot { slot, positions, users };
+    }
+}
+
+async fn setup() -> (Arc<SqliteRepo>, Arc<FakeAccounts>, Reconciler) {
+    let db = Arc::new(SqliteRepo::connect("sqlite::memory:").await.unwrap());
+    db.migrate().await.unwrap();
+    let repo: SharedRepo = db.clone();
+    let accounts = Arc::new(FakeAccounts::default());
+    let reconciler = Reconciler::new(repo, accounts.clone(), PROGRAM_ID);
+    (db, accounts, reconciler)
+}
+
+fn position(owner: Pubkey, size: u64, margin: u64) -> Position {
+    Position {
+        owner,
+        sub_id: 0,
+        symbol: SYMBOL.to_string(),
+        side: types::Side::Long,
+        size,
+        entry_price: 100_000_000,
+        margin,
+        leverage: 10,
+        unrealized_pnl: 0,
+        realized_pnl: 0,
+        funding_accrued: 0,
+        liquidation_price: 91_000_000,
+        last_update: 0,
+        bump: 255,
+        funding_index: 0,
+    }
+}
+
+fn user(owner: Pubkey, total_collateral: u64) -> UserAccount {
+    UserAccount {
+        owner,
+        sub_id: 0,
+        margin_mode: types::MarginMode::Isolated,
+        total_collateral,
+        locked_collateral: 0,
+        total_pnl: 0,
+        position_count: 1,
+        bump: 255,
+    }
+}
+
+fn pda(owner: &Pubkey) -> Pubkey {
+    perp_sdk::pda::position_pda(&PROGRAM_ID, owner, 0, SYMBOL).0
+}
+
+// What the indexer writes for the program's PositionOpened at `slot`
+async fn indexed_open(repo: &SqliteRepo, slot: u64, p: &Position) {
+    let update = PositionUpdate::Opened {
+        owner: p.owner,
+        sub_id: p.sub_id,
+        symbol: p.symbol.clone(),
+        side: p.side.into(),
+        size: p.size,
+        entry_price: p.entry_price,
+        margin: p.margin,
+        leverage: p.leverage,
+        liquidation_price: p.liquidation_price,
+    };
+    let change = ChainChange { instruction_index: 0, event_index: 0, position_pda: pda(&p.owner), update };
+    let tx = ChainTx { slot, signature: Signature::new_unique().to_string(), changes: vec![change] };
+    repo.apply_chain_tx("test", &tx).await.unwrap();
+}
+
+fn fields(drifts: &[Drift]) -> Vec<(&str, bool)> {
+    drifts.iter().map(|d| (d.field.as_str(), d.repaired)).collect()
+}
+
+#[tokio::test]
+async fn matching_state_reports_no_drift() {
+    let (repo, accounts, reconciler) = setup().await;
+    let owner = Pubkey::new_unique();
+    let account = position(owner, 10, 100_000_000);
+    indexed_open(&repo, 10, &account).await;
+    accounts.set(20, vec![account], vec![]);
+
+    let report = reconciler.reconcile_once().await.unwrap();
+    assert_eq!((report.slot, report.positions_checked), (20, 1));
+    assert!(report.drifts.is_empty());
+    assert_eq!(reconciler.latest().await.unwrap().slot, 20);
+}
+
+#[tokio::test]
+async fn value_drift_is_repaired() {
+    let (repo, accounts, reconciler) = setup().await;
+    let owner = Pubkey::new_unique();
+    indexed_open(&repo, 10, &position(owner, 10, 100_000_000)).await;
+    // A margin change the indexer missed, and a sub-account it never recorded collateral for
+    accounts.set(20, vec![position(owner, 10, 150_000_000)], vec![user(owner, 400_000_000)]);
+
+    let report = reconciler.reconcile_once().await.unwrap();
+    assert_eq!(fields(&report.drifts), [("margin", true), ("total_collateral", true)]);
+    assert_eq!((report.drifts[0].chain.as_str(), report.drifts[0].db.as_str()), ("150000000", "100000000"));
+    assert_eq!(report.unresolved().count(), 0);
+    assert_eq!(repo.fetch_position_view(&pda(&owner)).await.unwrap().unwrap().margin, 150_000_000);
+    assert_eq!(repo.fetch_sub_accounts(&owner).await.unwrap()[0].total_collateral, 400_000_000);
+
+    // Repaired drift doesn't come back
+    assert!(reconciler.reconcile_once().await.unwrap().drifts.is_empty());
+    let runs: Vec<(i64, i64)> = sqlx::query_as("SELECT drifted, repaired FROM reconcile_runs ORDER BY id")
+        .fetch_all(repo.pool())
+        .await
+        .unwrap();
+    assert_eq!(runs, [(2, 2), (0, 0)]);
+}
+
+#[tokio::test]
+async fn missing_rows_are_inserted() {
+    let (repo, accounts, reconciler) = setup().await;
+    let owner = Pubkey::new_unique();
+    accounts.set(20, vec![position(owner, 10, 100_000_000)], vec![user(owner, 1)]);
+
+    let report = reconciler.reconcile_once().await.unwrap();
+    assert_eq!(fields(&report.drifts), [("row", true), ("row", true)]);
+    let view = repo.fetch_position_view(&pda(&owner)).await.unwrap().unwrap();
+    assert_eq!((view.size, view.side, view.state), (10, Side::Long, PositionState::Open));
+    assert_eq!(repo.fetch_sub_accounts(&owner).await.unwrap()[0].margin_mode, MarginMode::Isolated);
+}
+
+#[tokio::test]
+async fn unsafe_drift_is_reported_not_repaired() {
+    let (repo, accounts, reconciler) = setup().await;
+    let (flipped, gone) = (Pubkey::new_unique(), Pubkey::new_unique());
+    indexed_open(&repo, 10, &position(flipped, 10, 100_000_000)).await;
+    indexed_open(&repo, 10, &position(gone, 10, 100_000_000)).await;
+    // The account under `flipped`'s PDA is short; `gone`'s was closed without the indexer seeing it
+    let mut short = position(flipped, 20, 100_000_000);
+    short.side = types::Side::Short;
+    accounts.set(20, vec![short], vec![]);
+
+    let report = reconciler.reconcile_once().await.unwrap();
+    let mut unresolved: Vec<(Pubkey, &str, &str)> =
+        report.unresolved().map(|d| (d.address, d.field.as_str(), d.chain.as_str())).collect();
+    unresolved.sort();
+    let mut expected = vec![(pda(&flipped), "side", "Short"), (pda(&gone), "state", "missing")];
+    expected.sort();
+    assert_eq!(unresolved, expected);
+    assert!(report.drifts.iter().all(|d| d.account == DriftAccount::Position && !d.repaired));
+
+    // Both rows are as the indexer left them
+    let view = repo.fetch_position_view(&pda(&flipped)).await.unwrap().unwrap();
+    assert_eq!((view.side, view.size), (Side::Long, 10));
+    assert_eq!(repo.fetch_position_view(&pda(&gone)).await.unwrap().unwrap().state, PositionState::Open);
+}
+
+#[tokio::test]
+async fn rows_newer_than_the_snapshot_are_skipped() {
+    let (repo, accounts, reconciler) = setup().await;
+    let (newer, opening) = (Pubkey::new_unique(), Pubkey::new_unique());
+    indexed_open(&repo, 30, &position(newer, 10, 100_000_000)).await;
+    // An open intent that hasn't landed yet: no account, nothing to report
+    let input = position_service::models::OpenPositionInput {
+        sub_id: 0,
+        symbol: SYMBOL.to_string(),
+        side: Side::Long,
+        size: 10,
+        leverage: 10,
+        entry_price: 100_000_000,
+        margin_token_account: Pubkey::new_unique(),
+        quote_mint: Pubkey::new_unique(),
+    };
+    repo.insert_position_open_intent(&opening, &pda(&opening), &input).await.unwrap();
+    accounts.set(20, vec![position(newer, 5, 50_000_000)], vec![]);
+
+    let report = reconciler.reconcile_once().await.unwrap();
+    assert!(report.drifts.is_empty(), "unexpected drift: {:?}", report.drifts);
+    assert_eq!(repo.fetch_position_view(&pda(&newer)).await.unwrap().unwrap().size, 10);
+}
@@ -0,0 +1,705 @@
+// One suite, run against every PositionRepo implementation. SQLite runs in memory on
+// every `cargo test`; the Postgres cases are ignored by default and run with
+// `cargo test -- --include-ignored` and TEST_DATABASE_URL pointing at a scratch database
+// (tests use fresh random keys, so they don't interfere with each other or earlier runs).
+use async_trait::async_trait;
+use position_service::db::{PgRepo, PositionRepo, SqliteRepo};
+use position_service::models::*;
+use solana_sdk::{pubkey::Pubkey, signature::{Keypair, Signature, Signer}};
+
+const PROGRAM_ID: Pubkey = perp_sdk::PROGRAM_ID;
+
+// State changes the indexer will make on confirmation, done directly in SQL until then
+#[async_trait]
+trait Harness: PositionRepo {
+    async fn set_state(&self, pda: &Pubkey, state: &str);
+    async fn operation_kinds(&self, owner: &Pubkey) -> Vec<String>;
+    async fn modification_kinds(&self, pda: &Pubkey) -> Vec<String>;
+    async fn drift_fields(&self, address: &Pubkey) -> Vec<(String, bool)>;
+}
+
+#[async_trait]
+impl Harness for SqliteRepo {
+    async fn set_state(&self, pda: &Pubkey, state: &str) {
+        sqlx::query("UPDATE positions SET state = ?2 WHERE pda = ?1")
+            .bind(pda.to_bytes().to_vec())
+            .bind(state)
+            .execute(self.pool())
+            .await
+            .unwrap();
+    }
+
+    async fn operation_kinds(&self, owner: &Pubkey) -> Vec<String> {
+        sqlx::query_scalar("SELECT kind FROM operations WHERE owner = ?1 ORDER BY id")
+            .bind(owner.to_bytes().to_vec())
+            .fetch_all(self.pool())
+            .await
+            .unwrap()
+    }
+
+    async fn modification_kinds(&self, pda: &Pubkey) -> Vec<String> {
+        sqlx::query_scalar("SELECT kind FROM position_modifications WHERE position_pda = ?1 ORDER BY id")
+            .bind(pda.to_bytes().to_vec())
+            .fetch_all(self.pool())
+            .await
+            .unwrap()
+    }
+
+    async fn drift_fields(&self, address: &Pubkey) -> Vec<(String, bool)> {
+        sqlx::query_as("SELECT field, repaired FROM reconcile_drifts WHERE address = ?1 ORDER BY id")
+            .bind(address.to_bytes().to_vec())
+            .fetch_all(self.pool())
+            .await
+            .unwrap()
+    }
+}
+
+#[async_trait]
+impl Harness for PgRepo {
+    async fn set_state(&self, pda: &Pubkey, state: &str) {
+        sqlx::query("UPDATE perp.positions SET state = $2 WHERE pda = $1")
+            .bind(pda.to_bytes().to_vec())
+            .bind(state)
+            .execute(self.pool())
+            .await
+            .unwrap();
+    }
+
+    async fn operation_kinds(&self, owner: &Pubkey) -> Vec<String> {
+        sqlx::query_scalar("SELECT kind FROM perp.operations WHERE owner = $1 ORDER BY id")
+            .bind(owner.to_bytes().to_vec())
+            .fetch_all(self.pool())
+            .await
+            .unwrap()
+    }
+
+    async fn modification_kinds(&self, pda: &Pubkey) -> Vec<String> {
+        sqlx::query_scalar("SELECT kind FROM perp.position_modifications WHERE position_pda = $1 ORDER BY id")
+            .bind(pda.to_bytes().to_vec())
+            .fetch_all(self.pool())
+            .await
+            .unwrap()
+    }
+
+    async fn drift_fields(&self, address: &Pubkey) -> Vec<(String, bool)> {
+        sqlx::query_as("SELECT field, repaired FROM perp.reconcile_drifts WHERE address = $1 ORDER BY id")
+            .bind(address.to_bytes().to_vec())
+            .fetch_all(self.pool())
+            .await
+            .unwrap()
+    }
+}
+
+async fn sqlite_repo() -> SqliteRepo {
+    let repo = SqliteRepo::connect("sqlite::memory:").await.unwrap().with_program_id(PROGRAM_ID);
+    repo.migrate().await.unwrap();
+    repo
+}
+
+async fn pg_repo() -> PgRepo {
+    let url = std::env::var("TEST_DATABASE_URL").expect("the Postgres cases need TEST_DATABASE_URL");
+    let repo = PgRepo::connect(&url).await.unwrap().with_program_id(PROGRAM_ID);
+    repo.migrate().await.unwrap();
+    repo
+}
+
+macro_rules! repo_suite {
+    ($($case:ident),* $(,)?) => {
+        mod sqlite {
+            $(#[tokio::test]
+            async fn $case() {
+                super::$case(&super::sqlite_repo().await).await;
+            })*
+        }
+
+        mod postgres {
+            $(#[tokio::test]
+            #[ignore = "needs TEST_DATABASE_URL"]
+            async fn $case() {
+                super::$case(&super::pg_repo().await).await;
+            })*
+        }
+    };
+}
+
+repo_suite!(
+    migrate_is_idempotent,
+    open_intent_records_an_opening_position,
+    open_intent_only_replaces_closed_rows,
+    positions_by_owner_filter_sub_accounts,
+    sub_accounts_count_live_positions,
+    intents_move_live_positions,
+    auto_top_up_settings_round_trip,
+    failed_writes_roll_back,
+    monitor_writes_snapshots_and_alerts,
+    chain_events_drive_the_position_lifecycle,
+    replaying_a_chain_tx_applies_nothing,
+    late_events_do_not_rewind_positions,
+    changes_for_unknown_positions_are_skipped,
+    transfers_move_the_position,
+    cursor_only_moves_forward,
+    reconciler_repairs_overwrite_values_only,
+    reconciler_repairs_users,
+    reconcile_runs_are_recorded,
+    operations_settle_position_states,
+    finished_operations_are_left_alone,
+    in_flight_operations_are_listed_until_finished,
+    delegates_round_trip,
+    api_keys_are_found_by_hash_until_revoked,
+);
+
+fn random_key() -> Pubkey {
+    Keypair::new().pubkey()
+}
+
+fn open_input(sub_id: u16, symbol: &str, size: u64) -> OpenPositionInput {
+    OpenPositionInput {
+        sub_id,
+        symbol: symbol.to_string(),
+        side: Side::Long,
+        size,
+        leverage: 10,
+        entry_price: 100_000_000,
+        margin_token_account: random_key(),
+        quote_mint: random_key(),
+    }
+}
+
+// Opens (as an intent) and returns the position PDA
+async fn open(repo: &impl Harness, owner: &Pubkey, sub_id: u16, symbol: &str, size: u64) -> Pubkey {
+    let (pda, _) = perp_sdk::pda::position_pda(&PROGRAM_ID, owner, sub_id, symbol);
+    repo.insert_position_open_intent(owner, &pda, &open_input(sub_id, symbol, size)).await.unwrap();
+    pda
+}
+
+// A confirmed transaction at `slot` with one change per event
+fn chain_tx(slot: u64, changes: Vec<(Pubkey, PositionUpdate)>) -> ChainTx {
+    let changes = changes
+        .into_iter()
+        .enumerate()
+        .map(|(i, (position_pda, update))| ChainChange { instruction_index: 0, event_index: i as u32, position_pda, update })
+        .collect();
+    ChainTx { slot, signature: Signature::new_unique().to_string(), changes }
+}
+
+fn opened(owner: Pubkey, sub_id: u16, size: u64) -> PositionUpdate {
+    PositionUpdate::Opened {
+        owner,
+        sub_id,
+        symbol: "SOL-PERP".to_string(),
+        side: Side::Long,
+        size,
+        entry_price: 101_000_000,
+        margin: 101_000_000,
+        leverage: 10,
+        liquidation_price: 91_000_000,
+    }
+}
+
+fn modified(size: u64, margin: u64) -> PositionUpdate {
+    PositionUpdate::Modified { size, margin, leverage: 10, price: 102_000_000, unrealized_pnl: 0, liquidation_price: 92_000_000 }
+}
+
+async fn state(repo: &impl Harness, pda: &Pubkey) -> PositionState {
+    repo.fetch_position_view(pda).await.unwrap().unwrap().state
+}
+
+async fn migrate_is_idempotent(repo: &impl Harness) {
+    repo.migrate().await.unwrap();
+    repo.migrate().await.unwrap();
+}
+
+async fn open_intent_records_an_opening_position(repo: &impl Harness) {
+    let owner = random_key();
+    let pda = open(repo, &owner, 3, "SOL-PERP", 10).await;
+
+    let view = repo.fetch_position_view(&pda).await.unwrap().unwrap();
+    assert_eq!((view.owner, view.sub_id, view.symbol.as_str(), view.side), (owner, 3, "SOL-PERP", Side::Long));
+    assert_eq!((view.size, view.entry_price, view.leverage), (10, 100_000_000, 10));
+    // 10 × 100 at 10x, in the program's units
+    assert_eq!(view.margin, 100_000_000);
+    assert_eq!(view.state, PositionState::Opening);
+    assert!(view.auto_top_up.is_none());
+    assert_eq!(repo.operation_kinds(&owner).await, ["open"]);
+
+    // Not confirmed yet, so the monitor doesn't watch it
+    let watched = repo.fetch_open_positions().await.unwrap();
+    assert!(watched.iter().all(|p| p.pda != pda));
+    assert!(repo.fetch_position_view(&random_key()).await.unwrap().is_none());
+}
+
+async fn open_intent_only_replaces_closed_rows(repo: &impl Harness) {
+    let owner = random_key();
+    let pda = open(repo, &owner, 0, "SOL-PERP", 10).await;
+    open(repo, &owner, 0, "SOL-PERP", 20).await;
+    assert_eq!(repo.fetch_position_view(&pda).await.unwrap().unwrap().size, 10);
+
+    repo.set_state(&pda, "closed").await;
+    open(repo, &owner, 0, "SOL-PERP", 30).await;
+    let view = repo.fetch_position_view(&pda).await.unwrap().unwrap();
+    assert_eq!((view.size, view.state), (30, PositionState::Opening));
+}
+
+async fn positions_by_owner_filter_sub_accounts(repo: &impl Harness) {
+    let owner = random_key();
+    open(repo, &owner, 0, "SOL-PERP", 10).await;
+    open(repo, &owner, 1, "BTC-PERP", 1).await;
+    let closed = open(repo, &owner, 1, "ETH-PERP", 5).await;
+    repo.set_state(&closed, "closed").await;
+    open(repo, &random_key(), 0, "SOL-PERP", 10).await;
+
+    let all = repo.fetch_positions_by_owner(&owner, None).await.unwrap();
+    let symbols: Vec<(u16, &str)> = all.iter().map(|p| (p.sub_id, p.symbol.as_str())).collect();
+    assert_eq!(symbols, [(0, "SOL-PERP"), (1, "BTC-PERP")]);
+
+    let sub_1 = repo.fetch_positions_by_owner(&owner, Some(1)).await.unwrap();
+    assert_eq!(sub_1.len(), 1);
+    assert_eq!(sub_1[0].symbol, "BTC-PERP");
+    assert!(repo.fetch_positions_by_owner(&owner, Some(2)).await.unwrap().is_empty());
+}
+
+async fn sub_accounts_count_live_positions(repo: &impl Harness) {
+    let owner = random_key();
+    open(repo, &owner, 0, "SOL-PERP", 10).await;
+    let closed = open(repo, &owner, 0, "BTC-PERP", 1).await;
+    repo.set_state(&closed, "closed").await;
+    open(repo, &owner, 2, "ETH-PERP", 5).await;
+
+    // A transfer intent registers the receiving sub-account
+    let (from, to) = (perp_sdk::pda::user_pda(&PROGRAM_ID, &owner, 0).0, perp_sdk::pda::user_pda(&PROGRAM_ID, &owner, 4).0);
+    let transfer = TransferCollateralInput { from_sub_id: 0, to_sub_id: 4, amount: 1_000 };
+    repo.insert_collateral_transfer_intent(&owner, &from, &to, &transfer).await.unwrap();
+
+    let subs = repo.fetch_sub_accounts(&owner).await.unwrap();
+    let counts: Vec<(u16, u32)> = subs.iter().map(|s| (s.sub_id, s.position_count)).collect();
+    assert_eq!(counts, [(0, 1), (2, 1), (4, 0)]);
+    for sub in &subs {
+        assert_eq!(sub.owner, owner);
+        assert_eq!(sub.pda, perp_sdk::pda::user_pda(&PROGRAM_ID, &owner, sub.sub_id).0);
+        assert_eq!(sub.margin_mode, MarginMode::Isolated);
+    }
+    assert_eq!(repo.operation_kinds(&owner).await, ["open", "open", "open", "transfer_collateral"]);
+}
+
+async fn intents_move_live_positions(repo: &impl Harness) {
+    let owner = random_key();
+    let pda = open(repo, &owner, 0, "SOL-PERP", 10).await;
+
+    // Nothing to modify until the open is confirmed
+    repo.insert_position_modify_intent(&owner, &pda, &ModifyAction::AddMargin { amount: 5 }).await.unwrap();
+    assert_eq!(state(repo, &pda).await, PositionState::Opening);
+
+    repo.set_state(&pda, "open").await;
+    repo.insert_position_modify_intent(&owner, &pda, &ModifyAction::AddMargin { amount: 5 }).await.unwrap();
+    assert_eq!(state(repo, &pda).await, PositionState::Modifying);
+    repo.insert_position_close_intent(&owner, &pda, 110_000_000).await.unwrap();
+    assert_eq!(state(repo, &pda).await, PositionState::Closing);
+    repo.insert_position_modify_intent(&owner, &pda, &ModifyAction::AddMargin { amount: 5 }).await.unwrap();
+    assert_eq!(state(repo, &pda).await, PositionState::Closing);
+
+    let other = open(repo, &owner, 0, "BTC-PERP", 1).await;
+    let user = perp_sdk::pda::user_pda(&PROGRAM_ID, &owner, 0).0;
+    let legs = |pda| vec![BatchCloseLeg { position_pda: pda, symbol: "BTC-PERP".to_string(), price: 60_000_000_000 }];
+    repo.set_state(&other, "open").await;
+    repo.insert_batch_close_intent(&owner, &user, 5_000, &legs(other)).await.unwrap();
+    assert_eq!(state(repo, &other).await, PositionState::Modifying);
+    repo.insert_batch_close_intent(&owner, &user, 10_000, &legs(other)).await.unwrap();
+    assert_eq!(state(repo, &other).await, PositionState::Closing);
+
+    let to_owner = random_key();
+    let to_pda = perp_sdk::pda::position_pda(&PROGRAM_ID, &to_owner, 0, "SOL-PERP").0;
+    let transfer = TransferPositionInput { to_owner, to_sub_id: 0, recipient_approved: true };
+    repo.insert_position_transfer_intent(&owner, &pda, &to_pda, &transfer).await.unwrap();
+
+    assert_eq!(
+        repo.operation_kinds(&owner).await,
+        ["open", "modify", "modify", "close", "modify", "open", "close_all", "close_all", "transfer_position"]
+    );
+}
+
+async fn auto_top_up_settings_round_trip(repo: &impl Harness) {
+    let owner = random_key();
+    let pda = open(repo, &owner, 0, "SOL-PERP", 10).await;
+
+    repo.upsert_auto_top_up_intent(&owner, &pda, Some(&AutoTopUpInput { target_mr: 50_000, max_total: 1_000 })).await.unwrap();
+    let top_up = repo.fetch_position_view(&pda).await.unwrap().unwrap().auto_top_up.unwrap();
+    assert_eq!((top_up.target_mr, top_up.max_total, top_up.used), (50_000, 1_000, 0));
+
+    repo.upsert_auto_top_up_intent(&owner, &pda, Some(&AutoTopUpInput { target_mr: 80_000, max_total: 2_000 })).await.unwrap();
+    let top_up = repo.fetch_position_view(&pda).await.unwrap().unwrap().auto_top_up.unwrap();
+    assert_eq!((top_up.target_mr, top_up.max_total), (80_000, 2_000));
+
+    // one top-up in flight per position: the next scan doesn't queue another behind it
+    let keeper = random_key();
+    let id = repo.insert_auto_top_up_intent(&keeper, &pda, 95_000_000).await.unwrap().unwrap();
+    assert!(repo.insert_auto_top_up_intent(&keeper, &pda, 94_000_000).await.unwrap().is_none());
+    repo.finish_operation(id, OperationStatus::Confirmed, None).await.unwrap();
+    assert!(repo.insert_auto_top_up_intent(&keeper, &pda, 94_000_000).await.unwrap().is_some());
+    assert_eq!(repo.operation_kinds(&keeper).await, ["auto_top_up", "auto_top_up"]);
+
+    repo.upsert_auto_top_up_intent(&owner, &pda, None).await.unwrap();
+    assert!(repo.fetch_position_view(&pda).await.unwrap().unwrap().auto_top_up.is_none());
+    assert_eq!(repo.operation_kinds(&owner).await, ["open", "set_auto_top_up", "set_auto_top_up", "disable_auto_top_up"]);
+}
+
+async fn failed_writes_roll_back(repo: &impl Harness) {
+    // Settings for a position the store doesn't know violate the foreign key, and the
+    // intent written in the same transaction must not survive
+    let owner = random_key();
+    let unknown = random_key();
+    let input = AutoTopUpInput { target_mr: 50_000, max_total: 1_000 };
+    assert!(repo.upsert_auto_top_up_intent(&owner, &unknown, Some(&input)).await.is_err());
+    assert!(repo.operation_kinds(&owner).await.is_empty());
+
+    // Sizes past i64::MAX can't be stored; nothing of the open is kept
+    let (pda, _) = perp_sdk::pda::position_pda(&PROGRAM_ID, &owner, 0, "SOL-PERP");
+    let mut huge = open_input(0, "SOL-PERP", 1);
+    huge.entry_price = 1;
+    huge.size = u64::MAX;
+    assert!(repo.insert_position_open_intent(&owner, &pda, &huge).await.is_err());
+    assert!(repo.fetch_position_view(&pda).await.unwrap().is_none());
+    assert!(repo.fetch_sub_accounts(&owner).await.unwrap().is_empty());
+}
+
+async fn monitor_writes_snapshots_and_alerts(repo: &impl Harness) {
+    let owner = random_key();
+    let pda = open(repo, &owner, 0, "SOL-PERP", 10).await;
+    repo.set_state(&pda, "open").await;
+
+    let view = repo.fetch_open_positions().await.unwrap().into_iter().find(|p| p.pda == pda).unwrap();
+    repo.upsert_position_snapshot(&view, 95_000_000.0, -50_000_000, 0.052).await.unwrap();
+    repo.upsert_position_snapshot(&view, 94_000_000.0, -60_000_000, f64::INFINITY).await.unwrap();
+    repo.insert_liq_alert(&view, 0.04, 94_000_000.0).await.unwrap();
+
+    let updated = repo.fetch_position_view(&pda).await.unwrap().unwrap();
+    assert_eq!(updated.unrealized_pnl, -60_000_000);
+    assert!(updated.last_update >= view.last_update);
+}
+
+async fn chain_events_drive_the_position_lifecycle(repo: &impl Harness) {
+    let owner = random_key();
+    let pda = open(repo, &owner, 0, "SOL-PERP", 10).await;
+    let cursor = random_key().to_string();
+
+    assert_eq!(repo.apply_chain_tx(&cursor, &chain_tx(10, vec![(pda, opened(owner, 0, 10))])).await.unwrap(), 1);
+    let view = repo.fetch_position_view(&pda).await.unwrap().unwrap();
+    assert_eq!(view.state, PositionState::Open);
+    // The program's execution price and margin replace the intent's
+    assert_eq!((view.size, view.entry_price, view.margin, view.liquidation_price), (10, 101_000_000, 101_000_000, 91_000_000));
+    assert!(repo.fetch_open_positions().await.unwrap().iter().any(|p| p.pda == pda));
+
+    repo.apply_chain_tx(&cursor, &chain_tx(11, vec![(pda, modified(15, 150_000_000))])).await.unwrap();
+    let topped_up = PositionUpdate::ToppedUp { amount: 5_000_000, mark_price: 95_000_000, margin: 155_000_000, liquidation_price: 90_000_000 };
+    repo.apply_chain_tx(&cursor, &chain_tx(12, vec![(pda, topped_up)])).await.unwrap();
+    let view = repo.fetch_position_view(&pda).await.unwrap().unwrap();
+    assert_eq!((view.size, view.margin, view.liquidation_price), (15, 155_000_000, 90_000_000));
+
+    let closed = PositionUpdate::Closed { size_closed: 15, exit_price: 103_000_000, realized_pnl: 7_000_000 };
+    repo.apply_chain_tx(&cursor, &chain_tx(13, vec![(pda, closed)])).await.unwrap();
+    let view = repo.fetch_position_view(&pda).await.unwrap().unwrap();
+    assert_eq!((view.state, view.size, view.margin, view.realized_pnl), (PositionState::Closed, 0, 0, 7_000_000));
+
+    assert_eq!(repo.modification_kinds(&pda).await, ["open", "increase", "top_up", "close"]);
+}
+
+async fn replaying_a_chain_tx_applies_nothing(repo: &impl Harness) {
+    let owner = random_key();
+    let pda = perp_sdk::pda::position_pda(&PROGRAM_ID, &owner, 0, "SOL-PERP").0;
+    let cursor = random_key().to_string();
+
+    // Opened by someone else: the indexer creates the user and position rows
+    let open_tx = chain_tx(10, vec![(pda, opened(owner, 0, 10))]);
+    assert_eq!(repo.apply_chain_tx(&cursor, &open_tx).await.unwrap(), 1);
+    assert_eq!(repo.apply_chain_tx(&cursor, &open_tx).await.unwrap(), 0);
+
+    let add_margin = chain_tx(11, vec![(pda, modified(10, 120_000_000))]);
+    assert_eq!(repo.apply_chain_tx(&cursor, &add_margin).await.unwrap(), 1);
+    assert_eq!(repo.apply_chain_tx(&cursor, &add_margin).await.unwrap(), 0);
+
+    assert_eq!(repo.fetch_position_view(&pda).await.unwrap().unwrap().margin, 120_000_000);
+    assert_eq!(repo.modification_kinds(&pda).await, ["open", "add_margin"]);
+    assert_eq!(repo.fetch_sub_accounts(&owner).await.unwrap().len(), 1);
+}
+
+async fn late_events_do_not_rewind_positions(repo: &impl Harness) {
+    let owner = random_key();
+    let pda = perp_sdk::pda::position_pda(&PROGRAM_ID, &owner, 0, "SOL-PERP").0;
+    let cursor = random_key().to_string();
+
+    repo.apply_chain_tx(&cursor, &chain_tx(10, vec![(pda, opened(owner, 0, 10))])).await.unwrap();
+    repo.apply_chain_tx(&cursor, &chain_tx(30, vec![(pda, modified(20, 200_000_000))])).await.unwrap();
+    // Recorded, but the row already reflects slot 30
+    assert_eq!(repo.apply_chain_tx(&cursor, &chain_tx(20, vec![(pda, modified(12, 120_000_000))])).await.unwrap(), 1);
+
+    let view = repo.fetch_position_view(&pda).await.unwrap().unwrap();
+    assert_eq!((view.size, view.margin), (20, 200_000_000));
+}
+
+async fn changes_for_unknown_positions_are_skipped(repo: &impl Harness) {
+    let pda = random_key();
+    let cursor = random_key().to_string();
+    let tx = chain_tx(10, vec![(pda, modified(10, 100_000_000))]);
+
+    assert_eq!(repo.apply_chain_tx(&cursor, &tx).await.unwrap(), 0);
+    assert!(repo.fetch_position_view(&pda).await.unwrap().is_none());
+    assert!(repo.modification_kinds(&pda).await.is_empty());
+    // The transaction is still behind the cursor
+    assert_eq!(repo.fetch_cursor(&cursor).await.unwrap(), Some(ChainCursor { slot: 10, signature: tx.signature }));
+}
+
+async fn transfers_move_the_position(repo: &impl Harness) {
+    let (owner, recipient) = (random_key(), random_key());
+    let from = perp_sdk::pda::position_pda(&PROGRAM_ID, &owner, 0, "SOL-PERP").0;
+    let to = perp_sdk::pda::position_pda(&PROGRAM_ID, &recipient, 2, "SOL-PERP").0;
+    let cursor = random_key().to_string();
+
+    repo.apply_chain_tx(&cursor, &chain_tx(10, vec![(from, opened(owner, 0, 10))])).await.unwrap();
+    let transferred = PositionUpdate::Transferred { from, owner: recipient, sub_id: 2, size: 10, margin: 101_000_000 };
+    assert_eq!(repo.apply_chain_tx(&cursor, &chain_tx(11, vec![(to, transferred)])).await.unwrap(), 1);
+
+    assert_eq!(state(repo, &from).await, PositionState::Closed);
+    let view = repo.fetch_position_view(&to).await.unwrap().unwrap();
+    assert_eq!((view.owner, view.sub_id, view.side, view.state), (recipient, 2, Side::Long, PositionState::Open));
+    assert_eq!((view.size, view.entry_price, view.margin), (10, 101_000_000, 101_000_000));
+    assert_eq!(repo.modification_kinds(&to).await, ["transfer_in"]);
+    assert_eq!(repo.fetch_sub_accounts(&recipient).await.unwrap()[0].sub_id, 2);
+}
+
+async fn cursor_only_moves_forward(repo: &impl Harness) {
+    let cursor = random_key().to_string();
+    assert!(repo.fetch_cursor(&cursor).await.unwrap().is_none());
+
+    let at_50 = chain_tx(50, vec![]);
+    repo.apply_chain_tx(&cursor, &at_50).await.unwrap();
+    repo.apply_chain_tx(&cursor, &chain_tx(40, vec![])).await.unwrap();
+    assert_eq!(repo.fetch_cursor(&cursor).await.unwrap(), Some(ChainCursor { slot: 50, signature: at_50.signature }));
+
+    let at_60 = chain_tx(60, vec![]);
+    repo.apply_chain_tx(&cursor, &at_60).await.unwrap();
+    assert_eq!(repo.fetch_cursor(&cursor).await.unwrap().unwrap().signature, at_60.signature);
+}
+
+async fn reconciler_repairs_overwrite_values_only(repo: &impl Harness) {
+    let owner = random_key();
+    let pda = perp_sdk::pda::position_pda(&PROGRAM_ID, &owner, 0, "SOL-PERP").0;
+    let cursor = random_key().to_string();
+    repo.apply_chain_tx(&cursor, &chain_tx(10, vec![(pda, opened(owner, 0, 10))])).await.unwrap();
+    let mut chain = repo.fetch_position_view(&pda).await.unwrap().unwrap();
+    chain.size = 15;
+    chain.margin = 150_000_000;
+    chain.realized_pnl = -3;
+
+    assert!(repo.repair_position(&chain, 20).await.unwrap());
+    let stored = repo.fetch_stored_positions().await.unwrap().into_iter().find(|s| s.view.pda == pda).unwrap();
+    assert_eq!((stored.view.size, stored.view.margin, stored.view.realized_pnl), (15, 150_000_000, -3));
+    assert_eq!((stored.view.state, stored.last_slot), (PositionState::Open, Some(20)));
+
+    // A snapshot older than the row's last event doesn't overwrite it
+    chain.size = 1;
+    assert!(!repo.repair_position(&chain, 15).await.unwrap());
+    assert_eq!(repo.fetch_position_view(&pda).await.unwrap().unwrap().size, 15);
+
+    // Nor does any snapshot while an operation on the position is in flight
+    repo.insert_position_modify_intent(&owner, &pda, &ModifyAction::AddMargin { amount: 5 }).await.unwrap();
+    assert!(!repo.repair_position(&chain, 30).await.unwrap());
+    assert_eq!(state(repo, &pda).await, PositionState::Modifying);
+
+    // A position the indexer never saw is inserted, with its sub-account
+    let other = random_key();
+    let missing = PositionView { owner: other, sub_id: 3, pda: perp_sdk::pda::position_pda(&PROGRAM_ID, &other, 3, "SOL-PERP").0, ..chain };
+    assert!(repo.repair_position(&missing, 20).await.unwrap());
+    assert_eq!(state(repo, &missing.pda).await, PositionState::Open);
+    assert_eq!(repo.fetch_sub_accounts(&other).await.unwrap()[0].sub_id, 3);
+}
+
+async fn reconciler_repairs_users(repo: &impl Harness) {
+    let owner = random_key();
+    let pda = perp_sdk::pda::user_pda(&PROGRAM_ID, &owner, 1).0;
+    let chain = SubAccountView {
+        owner,
+        sub_id: 1,
+        margin_mode: MarginMode::Cross,
+        total_collateral: 5_000_000,
+        locked_collateral: 1_000_000,
+        total_pnl: -250,
+        position_count: 0,
+        pda,
+    };
+    repo.repair_sub_account(&chain).await.unwrap();
+    repo.repair_sub_account(&SubAccountView { total_collateral: 6_000_000, ..chain.clone() }).await.unwrap();
+
+    let all = repo.fetch_all_sub_accounts().await.unwrap();
+    let stored = all.iter().find(|u| u.pda == pda).unwrap();
+    assert_eq!((stored.owner, stored.sub_id, stored.margin_mode), (owner, 1, MarginMode::Cross));
+    assert_eq!((stored.total_collateral, stored.locked_collateral, stored.total_pnl), (6_000_000, 1_000_000, -250));
+}
+
+async fn reconcile_runs_are_recorded(repo: &impl Harness) {
+    let address = random_key();
+    let drift = |field: &str, repaired| Drift {
+        account: DriftAccount::Position,
+        address,
+        field: field.to_string(),
+        chain: "1".to_string(),
+        db: "2".to_string(),
+        repaired,
+    };
+    let report = ReconcileReport {
+        ran_at: chrono::Utc::now(),
+        slot: 100,
+        duration_ms: 12,
+        positions_checked: 3,
+        users_checked: 2,
+        drifts: vec![drift("size", true), drift("side", false)],
+    };
+    repo.record_reconcile(&report).await.unwrap();
+    assert_eq!(repo.drift_fields(&address).await, [("size".to_string(), true), ("side".to_string(), false)]);
+}
+
+async fn operations_settle_position_states(repo: &impl Harness) {
+    let owner = random_key();
+    let pda = open(repo, &owner, 0, "SOL-PERP", 10).await;
+    let opened = latest_operation(repo, &owner).await;
+    assert_eq!(repo.fetch_operation(opened).await.unwrap().unwrap().status, OperationStatus::Intent);
+
+    repo.mark_submitted(opened, "sig-1", 150).await.unwrap();
+    repo.mark_submitted(opened, "sig-2", 300).await.unwrap();
+    let op = repo.fetch_operation(opened).await.unwrap().unwrap();
+    assert_eq!((op.kind.as_str(), op.owner, op.target), ("open", owner, pda));
+    assert_eq!((op.status, op.signature.as_deref(), op.last_valid_block_height), (OperationStatus::Submitted, Some("sig-2"), Some(300)));
+
+    assert!(repo.finish_operation(opened, OperationStatus::Confirmed, None).await.unwrap());
+    assert_eq!(state(repo, &pda).await, PositionState::Open);
+
+    // A failed modify or close puts the position back to open
+    let modify = repo.insert_position_modify_intent(&owner, &pda, &ModifyAction::AddMargin { amount: 5 }).await.unwrap();
+    assert_eq!(state(repo, &pda).await, PositionState::Modifying);
+    assert!(repo.finish_operation(modify, OperationStatus::Failed, Some("program error: LeverageExceeded")).await.unwrap());
+    assert_eq!(state(repo, &pda).await, PositionState::Open);
+    assert_eq!(repo.fetch_operation(modify).await.unwrap().unwrap().error.as_deref(), Some("program error: LeverageExceeded"));
+
+    let close = repo.insert_position_close_intent(&owner, &pda, 110_000_000).await.unwrap();
+    repo.finish_operation(close, OperationStatus::Failed, Some("expired")).await.unwrap();
+    assert_eq!(state(repo, &pda).await, PositionState::Open);
+    let close = repo.insert_position_close_intent(&owner, &pda, 110_000_000).await.unwrap();
+    repo.finish_operation(close, OperationStatus::Confirmed, None).await.unwrap();
+    assert_eq!(state(repo, &pda).await, PositionState::Closed);
+
+    // An open that never lands leaves a closed row behind
+    let never = open(repo, &owner, 0, "BTC-PERP", 1).await;
+    let op = latest_operation(repo, &owner).await;
+    repo.finish_operation(op, OperationStatus::Failed, None).await.unwrap();
+    assert_eq!(state(repo, &never).await, PositionState::Closed);
+
+    // close_all moves every leg; a partial close leaves them open
+    let legs: Vec<BatchCloseLeg> = ["ETH-PERP", "JUP-PERP"]
+        .into_iter()
+        .map(|symbol| BatchCloseLeg { position_pda: perp_sdk::pda::position_pda(&PROGRAM_ID, &owner, 0, symbol).0, symbol: symbol.to_string(), price: 1 })
+        .collect();
+    for leg in &legs {
+        open(repo, &owner, 0, &leg.symbol, 1).await;
+        repo.set_state(&leg.position_pda, "open").await;
+    }
+    let user = perp_sdk::pda::user_pda(&PROGRAM_ID, &owner, 0).0;
+    let trim = repo.insert_batch_close_intent(&owner, &user, 5_000, &legs).await.unwrap();
+    repo.finish_operation(trim, OperationStatus::Confirmed, None).await.unwrap();
+    let close_all = repo.insert_batch_close_intent(&owner, &user, 10_000, &legs).await.unwrap();
+    assert_eq!(state(repo, &legs[1].position_pda).await, PositionState::Closing);
+    repo.finish_operation(close_all, OperationStatus::Confirmed, None).await.unwrap();
+    for leg in &legs {
+        assert_eq!(state(repo, &leg.position_pda).await, PositionState::Closed);
+    }
+}
+
+async fn finished_operations_are_left_alone(repo: &impl Harness) {
+    let owner = random_key();
+    let pda = open(repo, &owner, 0, "SOL-PERP", 10).await;
+    repo.set_state(&pda, "open").await;
+    let close = repo.insert_position_close_intent(&owner, &pda, 110_000_000).await.unwrap();
+    assert!(repo.finish_operation(close, OperationStatus::Failed, Some("expired")).await.unwrap());
+
+    // A second outcome (say, from recovery racing the request) changes nothing
+    assert!(!repo.finish_operation(close, OperationStatus::Confirmed, None).await.unwrap());
+    repo.mark_submitted(close, "late", 1).await.unwrap();
+    let op = repo.fetch_operation(close).await.unwrap().unwrap();
+    assert_eq!((op.status, op.signature, op.error.as_deref()), (OperationStatus::Failed, None, Some("expired")));
+    assert_eq!(state(repo, &pda).await, PositionState::Open);
+
+    // A state the indexer wrote in the meantime wins over the operation's
+    let modify = repo.insert_position_modify_intent(&owner, &pda, &ModifyAction::AddMargin { amount: 5 }).await.unwrap();
+    repo.set_state(&pda, "liquidating").await;
+    repo.finish_operation(modify, OperationStatus::Failed, None).await.unwrap();
+    assert_eq!(state(repo, &pda).await, PositionState::Liquidating);
+    assert!(repo.fetch_operation(-1).await.unwrap().is_none());
+}
+
+async fn in_flight_operations_are_listed_until_finished(repo: &impl Harness) {
+    let owner = random_key();
+    open(repo, &owner, 0, "SOL-PERP", 10).await;
+    let submitted = latest_operation(repo, &owner).await;
+    repo.mark_submitted(submitted, "sig", 10).await.unwrap();
+    let user = perp_sdk::pda::user_pda(&PROGRAM_ID, &owner, 1).0;
+    let transfer = TransferCollateralInput { from_sub_id: 1, to_sub_id: 2, amount: 5 };
+    let intent = repo.insert_collateral_transfer_intent(&owner, &user, &user, &transfer).await.unwrap();
+    let finished = repo.insert_collateral_transfer_intent(&owner, &user, &user, &transfer).await.unwrap();
+    repo.finish_operation(finished, OperationStatus::Confirmed, None).await.unwrap();
+
+    let mine = |ops: Vec<Operation>| -> Vec<(OperationId, OperationStatus)> {
+        ops.into_iter().filter(|o| o.owner == owner).map(|o| (o.id, o.status)).collect()
+    };
+    let listed = mine(repo.fetch_inflight_operations(far_future()).await.unwrap());
+    assert_eq!(listed, [(submitted, OperationStatus::Submitted), (intent, OperationStatus::Intent)]);
+    // Only operations created before the cut-off
+    let cutoff = chrono::Utc::now() - chrono::Duration::hours(1);
+    assert!(mine(repo.fetch_inflight_operations(cutoff).await.unwrap()).is_empty());
+}
+
+async fn delegates_round_trip(repo: &impl Harness) {
+    let owner = random_key();
+    let (a, b) = (random_key(), random_key());
+    assert!(repo.add_delegate(&owner, &a).await.unwrap());
+    assert!(!repo.add_delegate(&owner, &a).await.unwrap());
+    assert!(repo.add_delegate(&owner, &b).await.unwrap());
+    let mut delegates = repo.fetch_delegates(&owner).await.unwrap();
+    delegates.sort();
+    let mut expected = vec![a, b];
+    expected.sort();
+    assert_eq!(delegates, expected);
+    // Delegation goes one way
+    assert!(repo.is_delegate(&owner, &a).await.unwrap());
+    assert!(!repo.is_delegate(&a, &owner).await.unwrap());
+
+    assert!(repo.remove_delegate(&owner, &a).await.unwrap());
+    assert!(!repo.remove_delegate(&owner, &a).await.unwrap());
+    assert!(!repo.is_delegate(&owner, &a).await.unwrap());
+    assert_eq!(repo.fetch_delegates(&owner).await.unwrap(), [b]);
+}
+
+async fn api_keys_are_found_by_hash_until_revoked(repo: &impl Harness) {
+    let owner = random_key();
+    let hash = random_key().to_bytes().to_vec();
+    let id = repo.insert_api_key(&owner, &hash, &[Scope::Read, Scope::Trade], Some("bot")).await.unwrap();
+    let key = repo.fetch_api_key(&hash).await.unwrap().unwrap();
+    assert_eq!((key.id, key.owner, key.scopes.as_slice(), key.label.as_deref()), (id, owner, &[Scope::Read, Scope::Trade][..], Some("bot")));
+    let other = repo.insert_api_key(&owner, &random_key().to_bytes(), &[Scope::Read], None).await.unwrap();
+    let listed: Vec<ApiKeyId> = repo.fetch_api_keys(&owner).await.unwrap().iter().map(|k| k.id).collect();
+    assert_eq!(listed, [id, other]);
+
+    // Only the owner can revoke it, and only once
+    assert!(!repo.revoke_api_key(&random_key(), id).await.unwrap());
+    assert!(repo.revoke_api_key(&owner, id).await.unwrap());
+    assert!(!repo.revoke_api_key(&owner, id).await.unwrap());
+    assert!(repo.fetch_api_key(&hash).await.unwrap().is_none());
+    let listed: Vec<ApiKeyId> = repo.fetch_api_keys(&owner).await.unwrap().iter().map(|k| k.id).collect();
+    assert_eq!(listed, [other]);
+}
+
+// The newest in-flight operation of `owner`: the one `open` just recorded
+async fn latest_operation(repo: &impl Harness, owner: &Pubkey) -> OperationId {
+    let ops = repo.fetch_inflight_operations(far_future()).await.unwrap();
+    ops.iter().filter(|o| o.owner == *owner).map(|o| o.id).max().unwrap()
+}
+
+fn far_future() -> chrono::DateTime<chrono::Utc> {
+    chrono::Utc::now() + chrono::Duration::hours(1)
+}
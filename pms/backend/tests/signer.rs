@@ -0,0 +1,106 @@
+// The signers: an in-memory key, and RemoteSigner against the reference signing service on a
+// local port
+use std::net::SocketAddr;
+
+use perp_sdk::{types::Side, InstructionBuilder};
+use position_service::signing_service::{signing_service, SigningPolicy, SERVICE_INSTRUCTIONS};
+use position_service::solana::signer::{keypair_from_base58, RemoteSigner, TxSigner};
+use solana_sdk::{
+    hash::Hash,
+    instruction::Instruction,
+    message::Message,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+};
+
+const PROGRAM_ID: Pubkey = perp_sdk::PROGRAM_ID;
+const TOKEN: &str = "test-token";
+
+fn open(owner: &Pubkey) -> Instruction {
+    InstructionBuilder::new(PROGRAM_ID, Pubkey::new_unique()).open_position(owner, 0, "SOL-PERP", Side::Long, 10, 10, 100_000_000)
+}
+
+fn message(payer: &Pubkey, instructions: &[Instruction]) -> Message {
+    Message::new_with_blockhash(instructions, Some(payer), &Hash::new_unique())
+}
+
+// Serves the signing service for `keypair` with the service's default allow-list
+async fn serve(keypair: Keypair) -> String {
+    let policy = SigningPolicy::default().allow(PROGRAM_ID, SERVICE_INSTRUCTIONS);
+    let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(signing_service(keypair, policy, Some(TOKEN.to_string())).into_make_service());
+    let url = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+    url
+}
+
+#[tokio::test]
+async fn keypairs_sign_the_serialized_message() {
+    let keypair = Keypair::new();
+    let msg = message(&keypair.pubkey(), &[open(&keypair.pubkey())]);
+    let signature = keypair.sign(&msg).await.unwrap();
+    assert!(signature.verify(keypair.pubkey().as_ref(), &msg.serialize()));
+
+    let restored = keypair_from_base58(&keypair.to_base58_string()).unwrap();
+    assert_eq!(restored.address(), keypair.pubkey());
+    assert!(keypair_from_base58("not base58!").is_err());
+    assert!(keypair_from_base58(&Pubkey::new_unique().to_string()).is_err());
+}
+
+#[tokio::test]
+async fn remote_signer_signs_allowed_instructions() {
+    let keypair = Keypair::new();
+    let key = keypair.pubkey();
+    let signer = RemoteSigner::connect(&serve(keypair).await, Some(TOKEN.to_string())).await.unwrap();
+    assert_eq!(signer.address(), key);
+
+    let msg = message(&key, &[open(&key)]);
+    let signature = signer.sign(&msg).await.unwrap();
+    assert!(signature.verify(key.as_ref(), &msg.serialize()));
+}
+
+#[tokio::test]
+async fn remote_signer_refuses_what_the_allow_list_does_not_cover() {
+    let keypair = Keypair::new();
+    let key = keypair.pubkey();
+    let signer = RemoteSigner::connect(&serve(keypair).await, Some(TOKEN.to_string())).await.unwrap();
+
+    // Another program: moving the signer's lamports
+    let drain = system_instruction::transfer(&key, &Pubkey::new_unique(), 1_000_000_000);
+    let err = signer.sign(&message(&key, std::slice::from_ref(&drain))).await.unwrap_err();
+    assert!(err.to_string().contains("403"), "unexpected error: {err}");
+    // Smuggled in next to an allowed one
+    assert!(signer.sign(&message(&key, &[open(&key), drain])).await.is_err());
+
+    // The right program, but an instruction the service never sends
+    let builder = InstructionBuilder::new(PROGRAM_ID, Pubkey::new_unique());
+    let pool_update = builder.remove_liquidity(&key, 1, &["SOL-PERP"]);
+    assert!(signer.sign(&message(&key, &[pool_update])).await.is_err());
+    // Or one it does send, but that hands the key's positions to someone else
+    let transfer = builder.transfer_position(&key, 0, "SOL-PERP", &Pubkey::new_unique(), 0, false);
+    assert!(signer.sign(&message(&key, &[transfer])).await.is_err());
+
+    // Paid for by someone else
+    let payer = Pubkey::new_unique();
+    assert!(signer.sign(&message(&payer, &[open(&key)])).await.is_err());
+}
+
+#[tokio::test]
+async fn remote_signer_requires_the_token() {
+    let url = serve(Keypair::new()).await;
+    assert!(RemoteSigner::connect(&url, None).await.is_err());
+    assert!(RemoteSigner::connect(&url, Some("wrong".to_string())).await.is_err());
+}
+
+#[test]
+fn allow_lists_parse() {
+    let program = Pubkey::new_unique();
+    let key = Keypair::new().pubkey();
+    let policy = SigningPolicy::parse(&format!("{PROGRAM_ID}: open_position, close_position ;{program}:transfer_position")).unwrap();
+    assert!(policy.check(&key, &message(&key, &[open(&key)])).is_ok());
+
+    assert!(SigningPolicy::parse("").is_err());
+    assert!(SigningPolicy::parse(&PROGRAM_ID.to_string()).is_err());
+    assert!(SigningPolicy::parse(&format!("{PROGRAM_ID}:")).is_err());
+    assert!(SigningPolicy::parse("nope:open_position").is_err());
+}
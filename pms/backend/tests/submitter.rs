@@ -0,0 +1,218 @@
+// TxSubmitter against a scripted RPC: blockhashes, block heights and how each signed
+// transaction lands are all decided by the test
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use perp_sdk::{types::Side, InstructionBuilder, PerpError};
+use position_service::errors::TxError;
+use position_service::solana::tx::{TxRpc, TxSubmitter};
+use solana_sdk::{
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    hash::Hash,
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+
+// What happens to the transaction signed on each attempt
+#[derive(Clone)]
+enum Landing {
+    Never,
+    // Seen at each status poll in turn; the last one repeats
+    Statuses(Vec<TransactionConfirmationStatus>),
+    Fails(TransactionError),
+    // Unseen for this many status polls, then confirmed
+    Late(usize),
+}
+
+#[derive(Default)]
+struct ScriptedRpc {
+    simulation: Option<TransactionError>,
+    landings: Vec<Landing>,
+    send_failures: AtomicUsize,
+    height: AtomicU64,
+    simulated: AtomicUsize,
+    // Distinct signatures in the order they were first sent, and how often each status was polled
+    sent: Mutex<Vec<Signature>>,
+    polls: Mutex<Vec<usize>>,
+}
+
+impl ScriptedRpc {
+    fn landing(landings: Vec<Landing>) -> Self {
+        Self { landings, ..Default::default() }
+    }
+
+    fn sent(&self) -> Vec<Signature> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl TxRpc for ScriptedRpc {
+    // Each blockhash stays valid for two more blocks
+    async fn latest_blockhash(&self, _: CommitmentConfig) -> Result<(Hash, u64)> {
+        Ok((Hash::new_unique(), self.height.load(Ordering::SeqCst) + 2))
+    }
+
+    // Every look at the height moves it on a block
+    async fn block_height(&self, _: CommitmentConfig) -> Result<u64> {
+        Ok(self.height.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+
+    async fn simulate(&self, _: &Transaction, _: CommitmentConfig) -> Result<(Option<TransactionError>, Vec<String>)> {
+        self.simulated.fetch_add(1, Ordering::SeqCst);
+        Ok((self.simulation.clone(), vec![]))
+    }
+
+    async fn send(&self, tx: &Transaction) -> Result<Signature> {
+        if self.send_failures.load(Ordering::SeqCst) > 0 {
+            self.send_failures.fetch_sub(1, Ordering::SeqCst);
+            return Err(anyhow!("connection reset"));
+        }
+        let signature = tx.signatures[0];
+        let mut sent = self.sent.lock().unwrap();
+        if !sent.contains(&signature) {
+            sent.push(signature);
+            self.polls.lock().unwrap().push(0);
+        }
+        Ok(signature)
+    }
+
+    async fn status(&self, signature: &Signature) -> Result<Option<TransactionStatus>> {
+        let attempt = self.sent.lock().unwrap().iter().position(|s| s == signature).unwrap();
+        let poll = {
+            let mut polls = self.polls.lock().unwrap();
+            polls[attempt] += 1;
+            polls[attempt] - 1
+        };
+        let status = |err: Option<TransactionError>, confirmation| TransactionStatus {
+            slot: 40 + attempt as u64,
+            confirmations: Some(1),
+            status: err.clone().map_or(Ok(()), Err),
+            err,
+            confirmation_status: Some(confirmation),
+        };
+        Ok(match &self.landings[attempt] {
+            Landing::Never => None,
+            Landing::Statuses(seen) => Some(status(None, seen[poll.min(seen.len() - 1)].clone())),
+            Landing::Fails(err) => Some(status(Some(err.clone()), TransactionConfirmationStatus::Processed)),
+            Landing::Late(unseen) if poll < *unseen => None,
+            Landing::Late(_) => Some(status(None, TransactionConfirmationStatus::Confirmed)),
+        })
+    }
+
+    async fn account_data(&self, _: &Pubkey, _: CommitmentConfig) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    async fn logs(&self, _: &Signature, _: CommitmentConfig) -> Result<Option<Vec<String>>> {
+        Ok(None)
+    }
+}
+
+fn submitter(rpc: &Arc<ScriptedRpc>) -> TxSubmitter {
+    TxSubmitter::new(rpc.clone(), CommitmentLevel::Confirmed).with_poll_interval(Duration::from_millis(1))
+}
+
+fn open(payer: &Keypair) -> Vec<Instruction> {
+    let ix = InstructionBuilder::new(perp_sdk::PROGRAM_ID, Pubkey::new_unique())
+        .open_position(&payer.pubkey(), 0, "SOL-PERP", Side::Long, 10_000_000, 10, 180_000_000);
+    vec![ix]
+}
+
+fn program_error(error: PerpError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error.code()))
+}
+
+#[tokio::test]
+async fn waits_for_the_commitment() {
+    use TransactionConfirmationStatus::{Confirmed, Processed};
+    let rpc = Arc::new(ScriptedRpc::landing(vec![Landing::Statuses(vec![Processed, Processed, Confirmed])]));
+    let payer = Keypair::new();
+
+    let confirmed = submitter(&rpc).submit(&open(&payer), &payer).await.unwrap();
+    assert_eq!(rpc.sent(), [confirmed.signature]);
+    assert_eq!(confirmed.slot, 40);
+    assert_eq!(*rpc.polls.lock().unwrap(), [3]);
+    assert_eq!(rpc.simulated.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn simulation_rejections_are_decoded_and_nothing_is_sent() {
+    let rpc = Arc::new(ScriptedRpc { simulation: Some(program_error(PerpError::LeverageExceeded)), ..Default::default() });
+    let payer = Keypair::new();
+
+    let err = submitter(&rpc).submit(&open(&payer), &payer).await.unwrap_err();
+    assert!(matches!(err, TxError::Program(PerpError::LeverageExceeded)), "unexpected error: {err}");
+    assert!(rpc.sent().is_empty());
+}
+
+#[tokio::test]
+async fn on_chain_failures_are_decoded() {
+    let payer = Keypair::new();
+    let rpc = Arc::new(ScriptedRpc::landing(vec![Landing::Fails(program_error(PerpError::PoolSkewExceeded))]));
+    let err = submitter(&rpc).submit(&open(&payer), &payer).await.unwrap_err();
+    assert!(matches!(err, TxError::Program(PerpError::PoolSkewExceeded)), "unexpected error: {err}");
+
+    // Not one of the program's errors: kept as the transaction error
+    let rpc = Arc::new(ScriptedRpc::landing(vec![Landing::Fails(TransactionError::InsufficientFundsForFee)]));
+    let err = submitter(&rpc).submit(&open(&payer), &payer).await.unwrap_err();
+    assert!(matches!(err, TxError::Failed(TransactionError::InsufficientFundsForFee)), "unexpected error: {err}");
+}
+
+#[tokio::test]
+async fn expired_blockhashes_are_refreshed() {
+    let rpc = Arc::new(ScriptedRpc::landing(vec![
+        Landing::Never,
+        Landing::Statuses(vec![TransactionConfirmationStatus::Finalized]),
+    ]));
+    let payer = Keypair::new();
+
+    let confirmed = submitter(&rpc).submit(&open(&payer), &payer).await.unwrap();
+    // Re-signed with a new blockhash, so a new signature; only the first attempt is simulated
+    let sent = rpc.sent();
+    assert_eq!(sent.len(), 2);
+    assert_eq!((confirmed.signature, confirmed.slot), (sent[1], 41));
+    assert_eq!(rpc.simulated.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn landing_as_the_blockhash_expires_is_not_an_expiry() {
+    // Unseen until the poll after the height passes the blockhash's last valid one
+    let rpc = Arc::new(ScriptedRpc::landing(vec![Landing::Late(3), Landing::Never]));
+    let payer = Keypair::new();
+
+    let confirmed = submitter(&rpc).submit(&open(&payer), &payer).await.unwrap();
+    assert_eq!(rpc.sent(), [confirmed.signature]);
+    assert_eq!(*rpc.polls.lock().unwrap(), [5]);
+}
+
+#[tokio::test]
+async fn gives_up_when_every_blockhash_expires() {
+    let rpc = Arc::new(ScriptedRpc::landing(vec![Landing::Never; 3]));
+    let payer = Keypair::new();
+
+    let err = submitter(&rpc).submit(&open(&payer), &payer).await.unwrap_err();
+    assert!(matches!(err, TxError::Expired(3)), "unexpected error: {err}");
+    assert_eq!(rpc.sent().len(), 3);
+}
+
+#[tokio::test]
+async fn send_failures_are_retried() {
+    let rpc = Arc::new(ScriptedRpc::landing(vec![Landing::Statuses(vec![TransactionConfirmationStatus::Confirmed])]));
+    rpc.send_failures.store(2, Ordering::SeqCst);
+    let payer = Keypair::new();
+    assert!(submitter(&rpc).submit(&open(&payer), &payer).await.is_ok());
+
+    let rpc = Arc::new(ScriptedRpc::landing(vec![Landing::Never]));
+    rpc.send_failures.store(10, Ordering::SeqCst);
+    let err = submitter(&rpc).submit(&open(&payer), &payer).await.unwrap_err();
+    assert!(matches!(err, TxError::Rpc(_)), "unexpected error: {err}");
+}
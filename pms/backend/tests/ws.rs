@@ -0,0 +1,224 @@
+// /ws: the router served on a local port, with messages published straight to its hub
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::StreamExt;
+use position_service::api::{auth::{create_api_key, Auth}, http::{router, AppState}, ws::WsHub};
+use position_service::config::SigningMode;
+use position_service::db::{PositionRepo, SharedRepo, SqliteRepo};
+use position_service::models::{PositionState, PositionView, Scope, Side};
+use position_service::services::{
+    manager::PositionManager,
+    reconciler::{AccountSource, ChainSnapshot, Reconciler},
+};
+use position_service::solana::{client::SolanaCtx, tx::{TxRpc, TxSubmitter}};
+use serde_json::Value;
+use solana_sdk::{
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    hash::Hash,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    transaction::{Transaction, TransactionError},
+};
+use solana_transaction_status::TransactionStatus;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{self, client::IntoClientRequest, protocol::frame::coding::CloseCode, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+
+const PROGRAM_ID: Pubkey = perp_sdk::PROGRAM_ID;
+
+struct NoRpc;
+
+#[async_trait]
+impl TxRpc for NoRpc {
+    async fn latest_blockhash(&self, _: CommitmentConfig) -> Result<(Hash, u64)> {
+        bail!("no rpc")
+    }
+
+    async fn block_height(&self, _: CommitmentConfig) -> Result<u64> {
+        bail!("no rpc")
+    }
+
+    async fn simulate(&self, _: &Transaction, _: CommitmentConfig) -> Result<(Option<TransactionError>, Vec<String>)> {
+        bail!("no rpc")
+    }
+
+    async fn send(&self, _: &Transaction) -> Result<Signature> {
+        bail!("no rpc")
+    }
+
+    async fn status(&self, _: &Signature) -> Result<Option<TransactionStatus>> {
+        bail!("no rpc")
+    }
+
+    async fn account_data(&self, _: &Pubkey, _: CommitmentConfig) -> Result<Option<Vec<u8>>> {
+        bail!("no rpc")
+    }
+
+    async fn logs(&self, _: &Signature, _: CommitmentConfig) -> Result<Option<Vec<String>>> {
+        bail!("no rpc")
+    }
+}
+
+struct NoAccounts;
+
+#[async_trait]
+impl AccountSource for NoAccounts {
+    async fn snapshot(&self) -> Result<ChainSnapshot> {
+        Ok(ChainSnapshot::default())
+    }
+}
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+struct Api {
+    url: String,
+    hub: WsHub,
+    repo: SharedRepo,
+}
+
+async fn serve(auth_required: bool, hub: WsHub) -> Api {
+    let db = Arc::new(SqliteRepo::connect("sqlite::memory:").await.unwrap());
+    db.migrate().await.unwrap();
+    let repo: SharedRepo = db;
+    let sol = SolanaCtx {
+        program_id: PROGRAM_ID,
+        quote_mint: Pubkey::new_unique(),
+        signer: Arc::new(Keypair::new()),
+        rpc_url: String::new(),
+        tx: TxSubmitter::new(Arc::new(NoRpc), CommitmentLevel::Confirmed),
+    };
+    let manager = PositionManager::new(sol, repo.clone(), PROGRAM_ID);
+    let reconciler = Reconciler::new(repo.clone(), Arc::new(NoAccounts), PROGRAM_ID);
+    let auth = Auth::new(repo.clone(), auth_required, Duration::from_secs(900));
+    let state = AppState { manager, repo: repo.clone(), reconciler, signing: SigningMode::Service, auth, hub: hub.clone() };
+
+    let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(router(state).into_make_service());
+    let url = format!("ws://{}", server.local_addr());
+    tokio::spawn(server);
+    Api { url, hub, repo }
+}
+
+impl Api {
+    async fn connect(&self, query: &str) -> Socket {
+        connect_async(format!("{}/ws?{query}", self.url)).await.unwrap().0
+    }
+}
+
+// A position as the hub publishes it
+fn position(owner: Pubkey) -> PositionView {
+    PositionView {
+        owner,
+        sub_id: 0,
+        symbol: "SOL-PERP".to_string(),
+        side: Side::Long,
+        size: 10,
+        entry_price: 100_000_000,
+        margin: 10_000_000,
+        leverage: 10,
+        unrealized_pnl: 0,
+        realized_pnl: 0,
+        liquidation_price: 91_000_000,
+        last_update: Utc::now(),
+        state: PositionState::Open,
+        pda: Pubkey::new_unique(),
+        auto_top_up: None,
+    }
+}
+
+// The next data message (pings are answered by the client library)
+async fn next(socket: &mut Socket) -> Value {
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), socket.next()).await.expect("no message").unwrap().unwrap();
+        if let Message::Text(text) = message {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn messages_are_typed_and_filtered_by_owner() {
+    let api = serve(false, WsHub::new()).await;
+    let (mine, theirs) = (position(Pubkey::new_unique()), position(Pubkey::new_unique()));
+    let mut socket = api.connect(&format!("owner={}", mine.owner)).await;
+
+    api.hub.pnl_update(&theirs, 100_000_000, 5, 0.5);
+    api.hub.pnl_update(&mine, 101_000_000, 7, f64::INFINITY);
+    api.hub.position_update(&mine);
+
+    let pnl = next(&mut socket).await;
+    assert_eq!((pnl["type"].as_str(), pnl["owner"].as_str()), (Some("pnl.update"), Some(mine.owner.to_string().as_str())));
+    assert_eq!(pnl["position"], mine.pda.to_string());
+    assert_eq!((&pnl["data"]["unrealized_pnl"], &pnl["data"]["margin_ratio"]), (&Value::from(7), &Value::Null));
+    let update = next(&mut socket).await;
+    assert_eq!((update["type"].as_str(), &update["data"]["size"]), (Some("positions.update"), &Value::from(10)));
+}
+
+#[tokio::test]
+async fn streams_and_positions_narrow_the_subscription() {
+    let api = serve(false, WsHub::new()).await;
+    let (watched, other) = (position(Pubkey::new_unique()), position(Pubkey::new_unique()));
+    let mut socket = api.connect(&format!("position={}&streams=alerts", watched.pda)).await;
+
+    api.hub.pnl_update(&watched, 100_000_000, 0, 0.01);
+    api.hub.margin_alert(&other, 100_000_000, 0.01);
+    api.hub.margin_alert(&watched, 92_000_000, 0.02);
+
+    let alert = next(&mut socket).await;
+    assert_eq!((alert["type"].as_str(), alert["position"].as_str()), (Some("alerts.margin"), Some(watched.pda.to_string().as_str())));
+    assert_eq!(alert["data"]["mark_price"], 92_000_000);
+
+    let bad = connect_async(format!("{}/ws?streams=trades", api.url)).await;
+    assert!(matches!(bad, Err(tungstenite::Error::Http(r)) if r.status() == 400));
+}
+
+#[tokio::test]
+async fn slow_consumers_are_disconnected() {
+    let api = serve(false, WsHub::with_capacity(4)).await;
+    let p = position(Pubkey::new_unique());
+    let mut socket = api.connect("").await;
+
+    // More than the hub holds before the connection gets to read any of it
+    for i in 0..32 {
+        api.hub.pnl_update(&p, 100_000_000, i, 0.5);
+    }
+    let close = loop {
+        match tokio::time::timeout(Duration::from_secs(5), socket.next()).await.expect("not disconnected") {
+            Some(Ok(Message::Close(frame))) => break frame,
+            Some(Ok(_)) => continue,
+            other => panic!("expected a close frame, got {other:?}"),
+        }
+    };
+    assert_eq!(close.unwrap().code, CloseCode::Again);
+}
+
+#[tokio::test]
+async fn subscriptions_are_authorized_like_the_rest_api() {
+    let api = serve(true, WsHub::new()).await;
+    let owner = position(Pubkey::new_unique());
+    let unauthenticated = connect_async(format!("{}/ws?owner={}", api.url, owner.owner)).await;
+    assert!(matches!(unauthenticated, Err(tungstenite::Error::Http(r)) if r.status() == 401));
+
+    let (_, key) = create_api_key(&api.repo, &owner.owner, &[Scope::Read], None).await.unwrap();
+    let request = |query: String| {
+        let mut request = format!("{}/ws?{query}", api.url).into_client_request().unwrap();
+        request.headers_mut().insert("x-api-key", key.parse().unwrap());
+        request
+    };
+    // Someone else's positions, or everyone's
+    let theirs = connect_async(request(format!("owner={}", Pubkey::new_unique()))).await;
+    assert!(matches!(theirs, Err(tungstenite::Error::Http(r)) if r.status() == 403));
+    let everyone = connect_async(request(String::new())).await;
+    assert!(matches!(everyone, Err(tungstenite::Error::Http(r)) if r.status() == 403));
+
+    let mut socket = connect_async(request(format!("owner={}", owner.owner))).await.unwrap().0;
+    api.hub.position_update(&owner);
+    assert_eq!(next(&mut socket).await["owner"], owner.owner.to_string());
+}
@@ -0,0 +1,254 @@
+// Event layouts, borsh-identical to programs/position_manager/src/events.rs.
+// Fields are only ever appended to an event, so decoding ignores trailing bytes
+// and an older decoder still reads events from a newer program; fields this decoder
+// knows were appended are Appended<T>, so it still reads events from older programs.
+use std::io::{Read, Write};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use perp_sdk::{discriminator, types::Side};
+use solana_program::pubkey::Pubkey;
+
+use crate::EventError;
+
+pub trait Event: BorshDeserialize {
+    const NAME: &'static str;
+
+    fn discriminator() -> [u8; 8] {
+        discriminator("event", Self::NAME)
+    }
+}
+
+/// A field appended to an event after it first shipped: None in logs written by a
+/// program version that didn't emit it yet. Only valid as the last field of an event.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Appended<T>(pub Option<T>);
+
+impl<T: BorshDeserialize> BorshDeserialize for Appended<T> {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest)?;
+        if rest.is_empty() {
+            return Ok(Appended(None));
+        }
+        T::deserialize(&mut rest.as_slice()).map(|v| Appended(Some(v)))
+    }
+}
+
+impl<T: BorshSerialize> BorshSerialize for Appended<T> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        match &self.0 {
+            Some(v) => v.serialize(writer),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PositionOpened {
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub side: Side,
+    pub size: u64,
+    pub leverage: u16,
+    pub entry_price: u64,
+    pub initial_margin: u64,
+    pub liquidation_price: u64,
+    pub sub_id: Appended<u16>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PositionModified {
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub size: u64,
+    pub margin: u64,
+    pub leverage: u16,
+    pub price: u64,
+    pub unrealized_pnl: i64,
+    pub liquidation_price: u64,
+    pub sub_id: Appended<u16>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PositionClosed {
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub size_closed: u64,
+    pub exit_price: u64,
+    pub realized_pnl: i64,
+    pub payout: u64,
+    pub sub_id: Appended<u16>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CollateralDeposited {
+    pub owner: Pubkey,
+    pub sub_id: u16,
+    pub amount: u64,
+    pub total_collateral: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CollateralWithdrawn {
+    pub owner: Pubkey,
+    pub sub_id: u16,
+    pub amount: u64,
+    pub total_collateral: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CollateralTransferred {
+    pub owner: Pubkey,
+    pub from_sub_id: u16,
+    pub to_sub_id: u16,
+    pub amount: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PoolFill {
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub taker_side: Side,
+    pub notional: u64,
+    pub oracle_price: u64,
+    pub execution_price: u64,
+    pub spread_bps: u16,
+    pub long_open_interest: u64,
+    pub short_open_interest: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PoolPnlSettled {
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub pool_pnl: i64,
+    pub liquidity: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LiquidityAdded {
+    pub provider: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub liquidity: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LiquidityRemoved {
+    pub provider: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub liquidity: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PositionTransferred {
+    pub from_owner: Pubkey,
+    pub from_sub_id: u16,
+    pub to_owner: Pubkey,
+    pub to_sub_id: u16,
+    pub symbol: String,
+    pub size: u64,
+    pub margin: u64,
+    pub position: Pubkey,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AutoTopUpConfigured {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub target_mr: u64,
+    pub max_total: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MarginToppedUp {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub keeper: Pubkey,
+    pub amount: u64,
+    pub mark_price: u64,
+    pub margin_ratio_before: i64,
+    pub margin: u64,
+    pub liquidation_price: u64,
+}
+
+macro_rules! perp_events {
+    ($($name:ident),* $(,)?) => {
+        $(impl Event for $name {
+            const NAME: &'static str = stringify!($name);
+        })*
+
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub enum PerpEvent {
+            $($name($name),)*
+            /// Discriminator this decoder doesn't know, e.g. an event added in a later
+            /// program version; kept raw so callers can log or store it.
+            Unknown { discriminator: [u8; 8], data: Vec<u8> },
+        }
+
+        impl PerpEvent {
+            /// Decodes discriminator ++ borsh body, as found in a `Program data:` line.
+            pub fn decode(data: &[u8]) -> Result<Self, EventError> {
+                if data.len() < 8 {
+                    return Err(EventError::TooShort);
+                }
+                let (disc, mut body) = data.split_at(8);
+                $(if disc == $name::discriminator() {
+                    return $name::deserialize(&mut body)
+                        .map(PerpEvent::$name)
+                        .map_err(|e| EventError::Malformed($name::NAME, e.to_string()));
+                })*
+                let mut discriminator = [0u8; 8];
+                discriminator.copy_from_slice(disc);
+                Ok(PerpEvent::Unknown { discriminator, data: body.to_vec() })
+            }
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(PerpEvent::$name(_) => $name::NAME,)*
+                    PerpEvent::Unknown { .. } => "Unknown",
+                }
+            }
+        }
+    };
+}
+
+perp_events!(
+    PositionOpened,
+    PositionModified,
+    PositionClosed,
+    CollateralDeposited,
+    CollateralWithdrawn,
+    CollateralTransferred,
+    PoolFill,
+    PoolPnlSettled,
+    LiquidityAdded,
+    LiquidityRemoved,
+    PositionTransferred,
+    AutoTopUpConfigured,
+    MarginToppedUp,
+);
+
+impl PerpEvent {
+    /// Owner of the position or sub-account the event is about (the provider for
+    /// liquidity events, the sender for transfers).
+    pub fn owner(&self) -> Option<&Pubkey> {
+        match self {
+            PerpEvent::PositionOpened(e) => Some(&e.owner),
+            PerpEvent::PositionModified(e) => Some(&e.owner),
+            PerpEvent::PositionClosed(e) => Some(&e.owner),
+            PerpEvent::CollateralDeposited(e) => Some(&e.owner),
+            PerpEvent::CollateralWithdrawn(e) => Some(&e.owner),
+            PerpEvent::CollateralTransferred(e) => Some(&e.owner),
+            PerpEvent::PoolFill(e) => Some(&e.owner),
+            PerpEvent::PoolPnlSettled(e) => Some(&e.owner),
+            PerpEvent::LiquidityAdded(e) => Some(&e.provider),
+            PerpEvent::LiquidityRemoved(e) => Some(&e.provider),
+            PerpEvent::PositionTransferred(e) => Some(&e.from_owner),
+            PerpEvent::AutoTopUpConfigured(e) => Some(&e.owner),
+            PerpEvent::MarginToppedUp(e) => Some(&e.owner),
+            PerpEvent::Unknown { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! Decoder for the events position_manager emits with `emit!`.
+//!
+//! Anchor writes each event as a `Program data: <base64>` log line holding the
+//! 8-byte event discriminator followed by the borsh-encoded struct. `logs` walks a
+//! transaction's log messages, attributes every line to the program and top-level
+//! instruction that produced it and decodes ours into [`PerpEvent`]s; `rpc` does the
+//! same starting from a `getTransaction` result.
+
+pub mod events;
+pub mod logs;
+pub mod rpc;
+
+pub use events::PerpEvent;
+pub use logs::{parse_logs, EventRecord, TxEvents};
+pub use rpc::from_confirmed_transaction;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EventError {
+    #[error("event data shorter than a discriminator")]
+    TooShort,
+    #[error("invalid base64 in program data: {0}")]
+    Base64(String),
+    #[error("invalid {0} data: {1}")]
+    Malformed(&'static str, String),
+    #[error("transaction has no signature")]
+    MissingSignature,
+    #[error("transaction has no status meta")]
+    MissingMeta,
+}
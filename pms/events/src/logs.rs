@@ -0,0 +1,110 @@
+// Walks a transaction's log messages the way the runtime writes them:
+//   Program <id> invoke [<depth>]
+//   Program log: ... / Program data: <base64> ... / Program return: <id> <base64>
+//   Program <id> consumed <n> of <m> compute units
+//   Program <id> success | Program <id> failed: <reason>
+// `Program data:` lines belong to the program on top of the invoke stack, so events
+// from other programs (e.g. strategy_vault around a CPI) are never decoded as ours.
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+
+use crate::{EventError, PerpEvent};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventRecord {
+    pub slot: u64,
+    pub signature: Signature,
+    /// Top-level instruction the event came from; CPIs report their caller's index.
+    pub instruction_index: usize,
+    /// 1 when position_manager was called directly, higher when reached through CPI.
+    pub stack_height: usize,
+    /// Position among this program's data lines in the transaction, counting ones that
+    /// didn't decode, so (signature, event_index) stays a stable key across decoder versions.
+    pub event_index: usize,
+    pub event: PerpEvent,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxEvents {
+    pub slot: u64,
+    pub signature: Signature,
+    /// The transaction failed, so nothing it emitted took effect and `events` is empty.
+    pub failed: bool,
+    /// The runtime cut the logs short ("Log truncated"); later events are missing.
+    pub truncated: bool,
+    pub events: Vec<EventRecord>,
+    /// Data lines of ours that couldn't be decoded, by event_index.
+    pub skipped: Vec<(usize, EventError)>,
+}
+
+pub fn parse_logs<S: AsRef<str>>(program_id: &Pubkey, slot: u64, signature: Signature, logs: &[S]) -> TxEvents {
+    let program = program_id.to_string();
+    let mut out = TxEvents { slot, signature, failed: false, truncated: false, events: vec![], skipped: vec![] };
+    let mut stack: Vec<&str> = vec![];
+    let mut instruction_index = 0usize;
+    let mut event_index = 0usize;
+
+    for line in logs.iter().map(AsRef::as_ref) {
+        if let Some(data) = line.strip_prefix("Program data: ") {
+            if stack.last() != Some(&program.as_str()) {
+                continue;
+            }
+            match decode_program_data(data) {
+                Ok(event) => out.events.push(EventRecord {
+                    slot,
+                    signature,
+                    instruction_index: instruction_index.saturating_sub(1),
+                    stack_height: stack.len(),
+                    event_index,
+                    event,
+                }),
+                Err(e) => out.skipped.push((event_index, e)),
+            }
+            event_index += 1;
+        } else if line.starts_with("Program log: ") || line.starts_with("Program return: ") {
+            continue;
+        } else if line == "Log truncated" {
+            out.truncated = true;
+        } else if let Some(rest) = line.strip_prefix("Program ") {
+            let mut words = rest.split_whitespace();
+            let (Some(id), Some(verb)) = (words.next(), words.next()) else { continue };
+            match verb {
+                "invoke" => {
+                    let depth = words
+                        .next()
+                        .and_then(|d| d.strip_prefix('[')?.strip_suffix(']')?.parse::<usize>().ok())
+                        .unwrap_or(stack.len() + 1);
+                    // Resync on the reported depth in case a success/failed line was lost.
+                    stack.truncate(depth.saturating_sub(1));
+                    stack.push(id);
+                    if depth == 1 {
+                        instruction_index += 1;
+                    }
+                }
+                "success" => {
+                    stack.pop();
+                }
+                "failed:" => {
+                    stack.pop();
+                    out.failed = true;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if out.failed {
+        out.events.clear();
+    }
+    out
+}
+
+// sol_log_data writes each field as its own base64 word; Anchor emits a single field.
+fn decode_program_data(data: &str) -> Result<PerpEvent, EventError> {
+    let mut bytes = vec![];
+    for field in data.split_whitespace() {
+        bytes.extend(STANDARD.decode(field).map_err(|e| EventError::Base64(e.to_string()))?);
+    }
+    PerpEvent::decode(&bytes)
+}
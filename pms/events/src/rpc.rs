@@ -0,0 +1,35 @@
+// Entry point for getTransaction results (any encoding).
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction,
+};
+
+use crate::{parse_logs, EventError, TxEvents};
+
+pub fn from_confirmed_transaction(
+    program_id: &Pubkey,
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<TxEvents, EventError> {
+    let signature = signature(&tx.transaction.transaction).ok_or(EventError::MissingSignature)?;
+    let meta = tx.transaction.meta.as_ref().ok_or(EventError::MissingMeta)?;
+    let logs: &[String] = match &meta.log_messages {
+        OptionSerializer::Some(logs) => logs,
+        OptionSerializer::None | OptionSerializer::Skip => &[],
+    };
+    let mut events = parse_logs(program_id, tx.slot, signature, logs);
+    if meta.err.is_some() {
+        events.failed = true;
+        events.events.clear();
+    }
+    Ok(events)
+}
+
+/// First signature of the transaction, which is its id.
+pub fn signature(tx: &EncodedTransaction) -> Option<Signature> {
+    match tx {
+        EncodedTransaction::Json(ui) => ui.signatures.first()?.parse().ok(),
+        EncodedTransaction::Accounts(list) => list.signatures.first()?.parse().ok(),
+        binary => binary.decode()?.signatures.first().copied(),
+    }
+}
@@ -0,0 +1,154 @@
+// Recorded transactions in tests/fixtures: getTransaction results (base64 and json
+// encodings) and raw log messages for CPI, failure and newer-program cases.
+use perp_events::events::{Appended, Event, PoolFill, PositionClosed, PositionOpened};
+use perp_events::{from_confirmed_transaction, parse_logs, rpc, EventError, PerpEvent};
+use perp_sdk::{discriminator, types::Side, PROGRAM_ID};
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::TransactionError;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+}
+
+fn confirmed(name: &str) -> EncodedConfirmedTransactionWithStatusMeta {
+    serde_json::from_str(&fixture(name)).unwrap()
+}
+
+fn log_lines(name: &str) -> Vec<String> {
+    fixture(name).lines().map(str::to_string).collect()
+}
+
+fn names(events: &[perp_events::EventRecord]) -> Vec<&'static str> {
+    events.iter().map(|r| r.event.name()).collect()
+}
+
+#[test]
+fn test_open_position_from_get_transaction() {
+    let tx = confirmed("open_position.json");
+    let parsed = from_confirmed_transaction(&PROGRAM_ID, &tx).unwrap();
+
+    assert_eq!(parsed.slot, 287_345_112);
+    assert_eq!(Some(parsed.signature), rpc::signature(&tx.transaction.transaction));
+    assert!(!parsed.failed && !parsed.truncated && parsed.skipped.is_empty());
+    assert_eq!(names(&parsed.events), ["PoolFill", "PositionOpened"]);
+
+    // The compute budget instruction comes first
+    for (i, record) in parsed.events.iter().enumerate() {
+        assert_eq!(record.instruction_index, 1);
+        assert_eq!(record.stack_height, 1);
+        assert_eq!(record.event_index, i);
+        assert_eq!((record.slot, record.signature), (parsed.slot, parsed.signature));
+    }
+    let PerpEvent::PositionOpened(opened) = &parsed.events[1].event else { panic!("expected PositionOpened") };
+    assert_eq!(opened.symbol, "SOL-PERP");
+    assert_eq!(opened.side, Side::Long);
+    assert_eq!((opened.size, opened.leverage, opened.entry_price), (10_000_000, 10, 180_018_000));
+    assert_eq!(parsed.events[1].event.owner(), Some(&opened.owner));
+    // Recorded before sub_id was appended to the event
+    assert_eq!(opened.sub_id, Appended(None));
+}
+
+#[test]
+fn test_json_encoded_multi_instruction_transaction() {
+    let tx = confirmed("close_all_positions.json");
+    let parsed = from_confirmed_transaction(&PROGRAM_ID, &tx).unwrap();
+    let expected: Signature = match &tx.transaction.transaction {
+        solana_transaction_status::EncodedTransaction::Json(ui) => ui.signatures[0].parse().unwrap(),
+        _ => panic!("fixture is json encoded"),
+    };
+
+    assert_eq!(parsed.signature, expected);
+    assert_eq!(
+        names(&parsed.events),
+        ["CollateralDeposited", "PoolFill", "PoolPnlSettled", "PositionClosed", "PoolFill", "PoolPnlSettled", "PositionClosed"]
+    );
+    let indexes: Vec<usize> = parsed.events.iter().map(|r| r.instruction_index).collect();
+    assert_eq!(indexes, [0, 1, 1, 1, 1, 1, 1]);
+
+    let closed: Vec<&str> = parsed
+        .events
+        .iter()
+        .filter_map(|r| match &r.event {
+            PerpEvent::PositionClosed(c) => Some(c.symbol.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(closed, ["SOL-PERP", "BTC-PERP"]);
+}
+
+#[test]
+fn test_failed_transaction_has_no_events() {
+    let mut tx = confirmed("open_position.json");
+    tx.transaction.meta.as_mut().unwrap().err =
+        Some(TransactionError::InstructionError(1, InstructionError::Custom(6009)));
+    let parsed = from_confirmed_transaction(&PROGRAM_ID, &tx).unwrap();
+    assert!(parsed.failed);
+    assert!(parsed.events.is_empty());
+
+    // Same from the logs alone: the event was logged before the program failed
+    let parsed = parse_logs(&PROGRAM_ID, 1, Signature::default(), &log_lines("failed_remove_margin.txt"));
+    assert!(parsed.failed);
+    assert!(parsed.events.is_empty());
+}
+
+#[test]
+fn test_events_under_cpi_keep_the_callers_instruction() {
+    let parsed = parse_logs(&PROGRAM_ID, 7, Signature::default(), &log_lines("vault_cpi.txt"));
+
+    // strategy_vault's own NavMarked line is not ours and is neither decoded nor skipped
+    assert_eq!(names(&parsed.events), ["PoolFill", "PositionOpened"]);
+    assert!(parsed.skipped.is_empty());
+    for record in &parsed.events {
+        assert_eq!(record.instruction_index, 0);
+        assert_eq!(record.stack_height, 2);
+    }
+    let PerpEvent::PositionOpened(opened) = &parsed.events[1].event else { panic!("expected PositionOpened") };
+    assert_eq!(opened.side, Side::Short);
+}
+
+#[test]
+fn test_newer_program_versions_degrade_gracefully() {
+    let parsed = parse_logs(&PROGRAM_ID, 9, Signature::default(), &log_lines("future_version.txt"));
+    assert!(!parsed.failed);
+    assert!(parsed.truncated);
+
+    // Fields appended to a known event are ignored
+    let PerpEvent::PositionOpened(opened) = &parsed.events[0].event else { panic!("expected PositionOpened") };
+    assert_eq!(opened.entry_price, 180_018_000);
+
+    // An event this decoder doesn't know is kept raw
+    let PerpEvent::Unknown { discriminator: disc, data } = &parsed.events[1].event else { panic!("expected Unknown") };
+    assert_eq!(*disc, discriminator("event", "PositionLiquidated"));
+    assert!(!data.is_empty());
+    assert_eq!(parsed.events[1].instruction_index, 1);
+
+    // Undecodable lines are reported, and still take up an event index
+    assert_eq!(parsed.skipped.len(), 2);
+    assert_eq!(parsed.skipped[0].0, 2);
+    assert!(matches!(parsed.skipped[0].1, EventError::Malformed(PositionClosed::NAME, _)));
+    assert_eq!(parsed.skipped[1].0, 3);
+    assert!(matches!(parsed.skipped[1].1, EventError::Base64(_)));
+
+    let last = parsed.events.last().unwrap();
+    assert!(matches!(last.event, PerpEvent::PoolFill(_)));
+    assert_eq!((last.instruction_index, last.event_index), (2, 4));
+}
+
+#[test]
+fn test_other_program_ids_are_ignored() {
+    let other = Pubkey::new_unique();
+    let parsed = from_confirmed_transaction(&other, &confirmed("open_position.json")).unwrap();
+    assert!(parsed.events.is_empty() && parsed.skipped.is_empty());
+}
+
+#[test]
+fn test_decode_rejects_short_data() {
+    assert_eq!(PerpEvent::decode(&PoolFill::discriminator()[..4]), Err(EventError::TooShort));
+    assert!(matches!(
+        PerpEvent::decode(&PositionOpened::discriminator()),
+        Err(EventError::Malformed(PositionOpened::NAME, _))
+    ));
+}
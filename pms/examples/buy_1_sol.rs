@@ -8,11 +8,12 @@ async fn main() -> Result<()> {
     let client = RpcClient::new("https://api.devnet.solana.com");
     let trader = Keypair::new();
     let program_id = solana_sdk::pubkey::Pubkey::new_unique();
+    let quote_mint = solana_sdk::pubkey::Pubkey::new_unique();
     
     println!("💰 Buying 1 SOL at market price with 20x leverage");
     println!("Price: $180 | Margin required: $9");
     
-    let signature = sol_long_20x(&client, &trader, &program_id).await?;
+    let signature = sol_long_20x(&client, &trader, &program_id, &quote_mint).await?;
     println!("✅ 1 SOL purchased: {}", signature);
     
     Ok(())
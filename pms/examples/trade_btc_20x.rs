@@ -8,11 +8,12 @@ async fn main() -> Result<()> {
     let client = RpcClient::new("https://api.devnet.solana.com");
     let trader = Keypair::new();
     let program_id = solana_sdk::pubkey::Pubkey::new_unique();
+    let quote_mint = solana_sdk::pubkey::Pubkey::new_unique();
     
     println!("📈 Buying 1 SOL with 20x Leverage");
     println!("Trader: {}", trader.pubkey());
     
-    match sol_long_20x(&client, &trader, &program_id).await {
+    match sol_long_20x(&client, &trader, &program_id, &quote_mint).await {
         Ok(signature) => println!("✅ SOL trade successful: {}", signature),
         Err(e) => println!("❌ SOL trade failed: {}", e),
     }
@@ -0,0 +1,204 @@
+//! Fixed-point perp math. Sizes, prices and margins are raw u64 units; rates
+//! (MMR, MR) are scaled by `RATE_SCALE`. Every operation is checked, and
+//! prices are floored.
+//!
+//! notional    = size * price
+//! IM          = notional / leverage
+//! MM          = notional * mmr / RATE_SCALE
+//! MR          = (margin + uPnL) * RATE_SCALE / notional
+//! liquidation = the price where MR == mmr
+//! bankruptcy  = the price where margin + uPnL == 0 (liquidation at mmr = 0)
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use core::fmt;
+
+pub const RATE_SCALE: u128 = 1_000_000; // 1e6
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Long,
+    Short,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathError {
+    Overflow,
+    Underflow,
+    DivisionByZero,
+    InvalidSize,
+}
+
+impl fmt::Display for MathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MathError::Overflow => "math overflow",
+            MathError::Underflow => "math underflow",
+            MathError::DivisionByZero => "division by zero",
+            MathError::InvalidSize => "invalid position size",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MathError {}
+
+pub type Result<T> = core::result::Result<T, MathError>;
+
+pub fn notional(size: u64, price: u64) -> Result<u128> {
+    (size as u128).checked_mul(price as u128).ok_or(MathError::Overflow)
+}
+
+pub fn initial_margin(notional: u128, leverage: u16) -> Result<u128> {
+    notional.checked_div(leverage as u128).ok_or(MathError::DivisionByZero)
+}
+
+pub fn maintenance_margin(notional: u128, mmr: u64) -> Result<u128> {
+    Ok(notional.checked_mul(mmr as u128).ok_or(MathError::Overflow)? / RATE_SCALE)
+}
+
+/// IM strictly above MM; otherwise the position is liquidatable the moment it opens.
+pub fn covers_maintenance(margin: u128, notional: u128, mmr: u64) -> Result<bool> {
+    let im = margin.checked_mul(RATE_SCALE).ok_or(MathError::Overflow)?;
+    let mm = notional.checked_mul(mmr as u128).ok_or(MathError::Overflow)?;
+    Ok(im > mm)
+}
+
+pub fn unrealized_pnl(side: Side, size: u64, entry: u64, price: u64) -> Result<i128> {
+    let diff = match side {
+        Side::Long => price as i128 - entry as i128,
+        Side::Short => entry as i128 - price as i128,
+    };
+    (size as i128).checked_mul(diff).ok_or(MathError::Overflow)
+}
+
+pub fn equity(margin: u64, upnl: i128) -> Result<i128> {
+    (margin as i128).checked_add(upnl).ok_or(MathError::Overflow)
+}
+
+/// Scaled by RATE_SCALE; None when notional is zero (no exposure, MR is unbounded).
+pub fn margin_ratio(equity: i128, notional: u128) -> Result<Option<i128>> {
+    if notional == 0 {
+        return Ok(None);
+    }
+    let num = equity.checked_mul(RATE_SCALE as i128).ok_or(MathError::Overflow)?;
+    let den = i128::try_from(notional).map_err(|_| MathError::Overflow)?;
+    Ok(Some(num / den))
+}
+
+/// equity * RATE_SCALE <= notional * mmr, compared unrounded.
+pub fn is_liquidatable(equity: i128, notional: u128, mmr: u64) -> Result<bool> {
+    if notional == 0 {
+        return Ok(false);
+    }
+    let lhs = equity.checked_mul(RATE_SCALE as i128).ok_or(MathError::Overflow)?;
+    let rhs = notional.checked_mul(mmr as u128).ok_or(MathError::Overflow)?;
+    Ok(rhs > i128::MAX as u128 || lhs <= rhs as i128)
+}
+
+// Long:  P = (size*entry - margin) * RATE_SCALE / (size*(RATE_SCALE - mmr))
+// Short: P = (size*entry + margin) * RATE_SCALE / (size*(RATE_SCALE + mmr))
+pub fn liquidation_price(side: Side, size: u64, entry: u64, margin: u64, mmr: u64) -> Result<u64> {
+    if size == 0 {
+        return Err(MathError::InvalidSize);
+    }
+    let base = notional(size, entry)?;
+    let (numer, rate) = match side {
+        // margin >= notional: the long can't be liquidated above zero
+        Side::Long => (base.saturating_sub(margin as u128), RATE_SCALE.checked_sub(mmr as u128).ok_or(MathError::Underflow)?),
+        Side::Short => (base.checked_add(margin as u128).ok_or(MathError::Overflow)?, RATE_SCALE + mmr as u128),
+    };
+    let denom = (size as u128).checked_mul(rate).ok_or(MathError::Overflow)?;
+    if denom == 0 {
+        return Err(MathError::DivisionByZero);
+    }
+    let price = numer.checked_mul(RATE_SCALE).ok_or(MathError::Overflow)? / denom;
+    u64::try_from(price).map_err(|_| MathError::Overflow)
+}
+
+/// Price at which equity hits zero: entry -/+ margin / size.
+pub fn bankruptcy_price(side: Side, size: u64, entry: u64, margin: u64) -> Result<u64> {
+    liquidation_price(side, size, entry, margin, 0)
+}
+
+/// Size-weighted entry after adding `add_notional` worth of `add_size`.
+pub fn average_entry(size: u64, entry: u64, add_size: u64, add_notional: u128) -> Result<u64> {
+    let total_size = (size as u128).checked_add(add_size as u128).ok_or(MathError::Overflow)?;
+    if total_size == 0 {
+        return Err(MathError::InvalidSize);
+    }
+    let total = notional(size, entry)?.checked_add(add_notional).ok_or(MathError::Overflow)?;
+    u64::try_from(total / total_size).map_err(|_| MathError::Overflow)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeverageTier {
+    pub max_leverage: u16,
+    pub initial_margin_rate: u64,     // scaled by RATE_SCALE
+    pub maintenance_margin_rate: u64, // scaled by RATE_SCALE
+    pub max_position_size: u64,       // notional, in raw quote units (6 decimals)
+}
+
+/// The program's leverage tiers, lowest leverage first. Higher leverage is only
+/// allowed on smaller notionals.
+pub const LEVERAGE_TIERS: [LeverageTier; 5] = [
+    LeverageTier { max_leverage: 20, initial_margin_rate: 50_000, maintenance_margin_rate: 25_000, max_position_size: u64::MAX },
+    LeverageTier { max_leverage: 50, initial_margin_rate: 20_000, maintenance_margin_rate: 10_000, max_position_size: 100_000_000_000 },
+    LeverageTier { max_leverage: 100, initial_margin_rate: 10_000, maintenance_margin_rate: 5_000, max_position_size: 50_000_000_000 },
+    LeverageTier { max_leverage: 500, initial_margin_rate: 5_000, maintenance_margin_rate: 2_500, max_position_size: 20_000_000_000 },
+    LeverageTier { max_leverage: 1000, initial_margin_rate: 2_000, maintenance_margin_rate: 1_000, max_position_size: 5_000_000_000 },
+];
+
+/// First tier allowing `leverage` on `notional`; None when no tier does.
+pub fn leverage_tier(leverage: u16, notional: u64) -> Option<LeverageTier> {
+    LEVERAGE_TIERS.iter().copied().find(|t| leverage <= t.max_leverage && notional <= t.max_position_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ten_x_long() {
+        // 10 units at 100, 10x: IM 100, MMR 2.5%
+        let n = notional(10, 100).unwrap();
+        assert_eq!(initial_margin(n, 10).unwrap(), 100);
+        assert_eq!(maintenance_margin(n, 25_000).unwrap(), 25);
+        assert_eq!(liquidation_price(Side::Long, 10, 100, 100, 25_000).unwrap(), 92); // 900 / 9.75
+        assert_eq!(bankruptcy_price(Side::Long, 10, 100, 100).unwrap(), 90);
+        assert_eq!(liquidation_price(Side::Short, 10, 100, 100, 25_000).unwrap(), 107); // 1100 / 10.25
+        assert_eq!(bankruptcy_price(Side::Short, 10, 100, 100).unwrap(), 110);
+    }
+
+    #[test]
+    fn margin_ratio_and_liquidatable() {
+        assert_eq!(margin_ratio(50, 1_000).unwrap(), Some(50_000));
+        assert_eq!(margin_ratio(50, 0).unwrap(), None);
+        assert!(is_liquidatable(25, 1_000, 25_000).unwrap());
+        assert!(!is_liquidatable(26, 1_000, 25_000).unwrap());
+        assert!(!is_liquidatable(-1, 0, 25_000).unwrap());
+    }
+
+    #[test]
+    fn zero_size_and_leverage_rejected() {
+        assert_eq!(liquidation_price(Side::Long, 0, 100, 1, 0), Err(MathError::InvalidSize));
+        assert_eq!(initial_margin(100, 0), Err(MathError::DivisionByZero));
+        assert_eq!(liquidation_price(Side::Long, 1, 100, 1, RATE_SCALE as u64), Err(MathError::DivisionByZero));
+    }
+
+    #[test]
+    fn leverage_tiers_cap_notional() {
+        // 50x is fine up to 100k quote, 1000x only up to 5k
+        assert_eq!(leverage_tier(10, u64::MAX).unwrap().maintenance_margin_rate, 25_000);
+        assert_eq!(leverage_tier(50, 100_000_000_000).unwrap().max_leverage, 50);
+        assert_eq!(leverage_tier(50, 100_000_000_001).map(|t| t.max_leverage), None);
+        assert_eq!(leverage_tier(1000, 5_000_000_000).unwrap().maintenance_margin_rate, 1_000);
+        assert_eq!(leverage_tier(1001, 1), None);
+    }
+
+    #[test]
+    fn average_entry_weights_by_size() {
+        assert_eq!(average_entry(10, 100, 10, notional(10, 200).unwrap()).unwrap(), 150);
+        assert_eq!(average_entry(0, 0, 0, 0), Err(MathError::InvalidSize));
+    }
+}
@@ -0,0 +1,98 @@
+// Parity against a floating-point reference of the same definitions. The
+// integer results are floored, so they must land within one price unit (or one
+// unit of margin) of the exact value.
+
+use perp_math::*;
+use proptest::prelude::*;
+
+fn side() -> impl Strategy<Value = Side> {
+    prop_oneof![Just(Side::Long), Just(Side::Short)]
+}
+
+fn rate(r: u64) -> f64 {
+    r as f64 / RATE_SCALE as f64
+}
+
+// Solve margin + pnl(P) == mmr * size * P for P
+fn reference_liq(side: Side, size: f64, entry: f64, margin: f64, mmr: f64) -> f64 {
+    match side {
+        Side::Long => ((size * entry - margin) / (size * (1.0 - mmr))).max(0.0),
+        Side::Short => (size * entry + margin) / (size * (1.0 + mmr)),
+    }
+}
+
+proptest! {
+    #[test]
+    fn liquidation_price_matches_reference(
+        side in side(),
+        size in 1u64..=1_000_000,
+        entry in 1u64..=1_000_000,
+        margin in 0u64..=1_000_000_000,
+        mmr in 0u64..=100_000,
+    ) {
+        let got = liquidation_price(side, size, entry, margin, mmr).unwrap() as f64;
+        let want = reference_liq(side, size as f64, entry as f64, margin as f64, rate(mmr));
+        prop_assert!(got <= want + 1e-6 && want - got < 1.0 + 1e-6, "got {} want {}", got, want);
+    }
+
+    #[test]
+    fn bankruptcy_is_zero_equity(
+        side in side(),
+        size in 1u64..=1_000_000,
+        entry in 1u64..=1_000_000,
+        leverage in 1u16..=1000,
+    ) {
+        let margin = initial_margin(notional(size, entry).unwrap(), leverage).unwrap() as u64;
+        let p = bankruptcy_price(side, size, entry, margin).unwrap();
+        prop_assert_eq!(p, liquidation_price(side, size, entry, margin, 0).unwrap());
+        if matches!(side, Side::Long) && margin as u128 >= notional(size, entry).unwrap() {
+            prop_assert_eq!(p, 0);
+        } else {
+            // floored, so equity sits within one unit of price movement of zero
+            let eq = equity(margin, unrealized_pnl(side, size, entry, p).unwrap()).unwrap();
+            prop_assert!(eq.abs() <= size as i128, "equity {} at {}", eq, p);
+        }
+    }
+
+    #[test]
+    fn margin_ratio_matches_reference(
+        side in side(),
+        size in 1u64..=1_000_000,
+        entry in 1u64..=1_000_000,
+        price in 1u64..=1_000_000,
+        margin in 0u64..=1_000_000_000,
+    ) {
+        let eq = equity(margin, unrealized_pnl(side, size, entry, price).unwrap()).unwrap();
+        let mr = margin_ratio(eq, notional(size, price).unwrap()).unwrap().unwrap() as f64 / RATE_SCALE as f64;
+        let pnl = match side {
+            Side::Long => size as f64 * (price as f64 - entry as f64),
+            Side::Short => size as f64 * (entry as f64 - price as f64),
+        };
+        let want = (margin as f64 + pnl) / (size as f64 * price as f64);
+        prop_assert!((mr - want).abs() <= 1.0 / RATE_SCALE as f64 + want.abs() * 1e-12, "mr {} want {}", mr, want);
+    }
+
+    #[test]
+    fn liquidatable_exactly_at_or_past_liquidation_price(
+        side in side(),
+        size in 1u64..=1_000_000,
+        entry in 2u64..=1_000_000,
+        leverage in 2u16..=20,
+        mmr in 1u64..=25_000,
+    ) {
+        let n = notional(size, entry).unwrap();
+        let margin = initial_margin(n, leverage).unwrap() as u64;
+        prop_assume!(covers_maintenance(margin as u128, n, mmr).unwrap());
+        let liq = liquidation_price(side, size, entry, margin, mmr).unwrap();
+        let check = |p: u64| {
+            let eq = equity(margin, unrealized_pnl(side, size, entry, p).unwrap()).unwrap();
+            is_liquidatable(eq, notional(size, p).unwrap(), mmr).unwrap()
+        };
+        // liq is floored, so liq + 1 is past the exact price: safe for a long, liquidatable for a short
+        match side {
+            Side::Long => prop_assert!(!check(liq + 1)),
+            Side::Short => prop_assert!(check(liq + 1)),
+        }
+        prop_assert!(!check(entry));
+    }
+}
@@ -0,0 +1,11 @@
+pub const RATE_SCALE: u128 = 1_000_000; // 1e6
+pub const MAX_SYMBOL_LEN: usize = 16;
+pub const MAX_LEVERAGE: u16 = 1000;
+pub const MIN_LEVERAGE: u16 = 1;
+pub const MAX_SUB_ACCOUNTS: u16 = 32;
+pub const BPS_DENOM: u16 = 10_000;
+pub const MAX_SPREAD_BPS: u16 = 1_000; // hard ceiling on any configured spread
+pub const MAX_MARK_AGE_SECS: i64 = 60; // older pool marks can't price LP shares or top-ups
+pub const MAX_PRICE_DEVIATION_BPS: u16 = 100; // caller prices further from the mark are rejected
+pub const MAX_FUNDING_RATE_BPS: u16 = 100; // hard ceiling on the configured hourly funding rate
+pub const FUNDING_PERIOD_SECS: i64 = 3_600; // funding_rate_bps accrues per period
@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum PerpError {
+    #[msg("Math overflow")] Overflow,
+    #[msg("Math underflow")] Underflow,
+    #[msg("Division by zero")] DivisionByZero,
+    #[msg("Invalid leverage")] InvalidLeverage,
+    #[msg("Leverage exceeded for tier")] LeverageExceeded,
+    #[msg("Invalid position size")] InvalidSize,
+    #[msg("Invalid amount")] InvalidAmount,
+    #[msg("Symbol too long")] SymbolTooLong,
+    #[msg("Insufficient margin for increase")] InsufficientMarginForIncrease,
+    #[msg("Post-removal margin would breach maintenance")] MaintenanceBreach,
+    #[msg("Invalid state")] InvalidState,
+    #[msg("Invalid sub-account id")] InvalidSubAccount,
+    #[msg("Insufficient free collateral")] InsufficientFreeCollateral,
+    #[msg("Sub-account still has open positions")] PositionsOpen,
+    #[msg("Invalid pool parameters")] InvalidPoolParams,
+    #[msg("Pool utilization cap exceeded")] PoolUtilizationExceeded,
+    #[msg("Pool skew cap exceeded")] PoolSkewExceeded,
+    #[msg("Insufficient pool liquidity")] InsufficientPoolLiquidity,
+    #[msg("Pool market does not match position")] PoolMarketMismatch,
+    #[msg("Recipient must sign or pre-approve the transfer")] MissingTransferApproval,
+    #[msg("Margin ratio is above the top-up target")] TopUpNotNeeded,
+    #[msg("Nothing left to top up from budget or free collateral")] TopUpUnavailable,
+    #[msg("Initial margin must exceed maintenance margin")] InitialMarginTooLow,
+    #[msg("Pool market mark price is missing or stale")] StaleMarkPrice,
+    #[msg("Position no longer matches the transfer approval")] TransferApprovalMismatch,
+    #[msg("Price is too far from the pool market mark")] PriceOutsideMarkBand,
+}
+impl From<perp_math::MathError> for PerpError {
+    fn from(e: perp_math::MathError) -> Self {
+        match e {
+            perp_math::MathError::Overflow => PerpError::Overflow,
+            perp_math::MathError::Underflow => PerpError::Underflow,
+            perp_math::MathError::DivisionByZero => PerpError::DivisionByZero,
+            perp_math::MathError::InvalidSize => PerpError::InvalidSize,
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use anchor_lang::prelude::*;
+use crate::state::accounts::Side;
+
+#[event]
+pub struct PositionOpened {
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub side: Side,
+    pub size: u64,
+    pub leverage: u16,
+    pub entry_price: u64,
+    pub initial_margin: u64,
+    pub liquidation_price: u64,
+    pub sub_id: u16,
+}
+
+#[event]
+pub struct PositionModified {
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub size: u64,
+    pub margin: u64,
+    pub leverage: u16,
+    pub price: u64,
+    pub unrealized_pnl: i64,
+    pub liquidation_price: u64,
+    pub sub_id: u16,
+}
+
+#[event]
+pub struct PositionClosed {
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub size_closed: u64,
+    pub exit_price: u64,
+    pub realized_pnl: i64,
+    pub payout: u64,
+    pub sub_id: u16,
+}
+
+#[event]
+pub struct CollateralDeposited {
+    pub owner: Pubkey,
+    pub sub_id: u16,
+    pub amount: u64,
+    pub total_collateral: u64,
+}
+
+#[event]
+pub struct CollateralWithdrawn {
+    pub owner: Pubkey,
+    pub sub_id: u16,
+    pub amount: u64,
+    pub total_collateral: u64,
+}
+
+#[event]
+pub struct CollateralTransferred {
+    pub owner: Pubkey,
+    pub from_sub_id: u16,
+    pub to_sub_id: u16,
+    pub amount: u64,
+}
+
+#[event]
+pub struct PoolFill {
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub taker_side: Side, // Long = trader bought from the pool
+    pub notional: u64,
+    pub oracle_price: u64,
+    pub execution_price: u64,
+    pub spread_bps: u16,
+    pub long_open_interest: u64,
+    pub short_open_interest: u64,
+}
+
+#[event]
+pub struct PoolPnlSettled {
+    pub owner: Pubkey,
+    pub symbol: String,
+    pub pool_pnl: i64, // positive when the trader lost
+    pub liquidity: u64,
+}
+
+#[event]
+pub struct LiquidityAdded {
+    pub provider: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub liquidity: u64,
+}
+
+#[event]
+pub struct LiquidityRemoved {
+    pub provider: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub liquidity: u64,
+}
+
+#[event]
+pub struct PositionTransferred {
+    pub from_owner: Pubkey,
+    pub from_sub_id: u16,
+    pub to_owner: Pubkey,
+    pub to_sub_id: u16,
+    pub symbol: String,
+    pub size: u64,
+    pub margin: u64,
+    pub position: Pubkey, // new PDA under the recipient's seeds
+}
+
+#[event]
+pub struct AutoTopUpConfigured {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub target_mr: u64,
+    pub max_total: u64,
+}
+
+#[event]
+pub struct MarginToppedUp {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub keeper: Pubkey,
+    pub amount: u64,
+    pub mark_price: u64,
+    pub margin_ratio_before: i64,
+    pub margin: u64,
+    pub liquidation_price: u64,
+}
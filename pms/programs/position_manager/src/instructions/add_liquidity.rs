@@ -0,0 +1,96 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount, Transfer};
+
+use crate::errors::PerpError;
+use crate::events::LiquidityAdded;
+use crate::math::*;
+use crate::state::accounts::*;
+
+// LP shares are minted against the pool's value: liquidity (which already holds the
+// realized trader PnL) less what open positions are up at the markets' marks, so a
+// new LP neither buys into traders' unrealized losses nor dodges their gains.
+pub(crate) fn handler<'info>(ctx: Context<'_, '_, 'info, 'info, AddLiquidity<'info>>, amount: u64) -> Result<()> {
+    require!(amount > 0, PerpError::InvalidAmount);
+
+    let supply = ctx.accounts.lp_mint.supply;
+    let now = Clock::get()?.unix_timestamp;
+    let value = ctx.accounts.pool.share_value(&ctx.accounts.pool.key(), ctx.remaining_accounts, now)?;
+    let shares = if supply == 0 {
+        amount
+    } else {
+        // a pool worth nothing with shares outstanding can't price new shares
+        require!(value > 0, PerpError::InsufficientPoolLiquidity);
+        u128_to_u64(div_u128(mul_u128(amount as u128, supply as u128)?, value as u128)?)?
+    };
+    require!(shares > 0, PerpError::InvalidAmount);
+
+    token::transfer(ctx.accounts.transfer_to_vault_ctx(), amount)?;
+
+    let quote_mint = ctx.accounts.pool.quote_mint;
+    let signer_seeds: &[&[u8]] = &[b"pool", quote_mint.as_ref(), &[ctx.accounts.pool.bump]];
+    token::mint_to(ctx.accounts.mint_shares_ctx().with_signer(&[signer_seeds]), shares)?;
+
+    let pool = &mut ctx.accounts.pool;
+    pool.liquidity = pool.liquidity.checked_add(amount).ok_or(PerpError::Overflow)?;
+
+    emit!(LiquidityAdded {
+        provider: ctx.accounts.provider.key(),
+        amount,
+        shares,
+        liquidity: pool.liquidity,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AddLiquidity<'info> {
+    pub provider: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", quote_mint.key().as_ref()],
+        bump = pool.bump,
+        has_one = quote_mint,
+        has_one = lp_mint
+    )]
+    pub pool: Account<'info, LiquidityPool>,
+
+    pub quote_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub lp_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub provider_quote_ata: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = lp_mint)]
+    pub provider_lp_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", quote_mint.key().as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> AddLiquidity<'info> {
+    pub fn transfer_to_vault_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.provider_quote_ata.to_account_info(),
+            to: self.vault.to_account_info(),
+            authority: self.provider.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+
+    pub fn mint_shares_ctx(&self) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
+        let cpi_accounts = MintTo {
+            mint: self.lp_mint.to_account_info(),
+            to: self.provider_lp_ata.to_account_info(),
+            authority: self.pool.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+}
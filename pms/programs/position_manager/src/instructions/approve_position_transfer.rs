@@ -0,0 +1,58 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::PerpError;
+use crate::state::accounts::*;
+
+// Recipient side of a transfer: lets `from_owner` move its `symbol` position on
+// `from_sub_id` into the recipient's `to_sub_id` without the recipient co-signing,
+// as long as the position is no larger than `max_size` and carries at least
+// `min_margin` when it moves.
+pub(crate) fn handler(
+    ctx: Context<ApprovePositionTransfer>,
+    from_owner: Pubkey,
+    from_sub_id: u16,
+    to_sub_id: u16,
+    symbol: String,
+    max_size: u64,
+    min_margin: u64,
+) -> Result<()> {
+    require!(symbol.len() <= MAX_SYMBOL_LEN, PerpError::SymbolTooLong);
+    require!(from_sub_id < MAX_SUB_ACCOUNTS && to_sub_id < MAX_SUB_ACCOUNTS, PerpError::InvalidSubAccount);
+
+    let approval = &mut ctx.accounts.approval;
+    approval.recipient = ctx.accounts.recipient.key();
+    approval.to_sub_id = to_sub_id;
+    approval.from_owner = from_owner;
+    approval.from_sub_id = from_sub_id;
+    approval.symbol = symbol;
+    approval.max_size = max_size;
+    approval.min_margin = min_margin;
+    approval.bump = ctx.bumps.approval;
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(from_owner: Pubkey, from_sub_id: u16, to_sub_id: u16, symbol: String)]
+pub struct ApprovePositionTransfer<'info> {
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+
+    #[account(
+        init,
+        payer = recipient,
+        seeds = [
+            b"transfer_approval",
+            recipient.key().as_ref(),
+            &to_sub_id.to_le_bytes(),
+            from_owner.as_ref(),
+            &from_sub_id.to_le_bytes(),
+            symbol.as_bytes(),
+        ],
+        bump,
+        space = TransferApproval::space(MAX_SYMBOL_LEN)
+    )]
+    pub approval: Account<'info, TransferApproval>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,100 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::RATE_SCALE;
+use crate::errors::PerpError;
+use crate::events::MarginToppedUp;
+use crate::math::*;
+use crate::state::accounts::*;
+use crate::tiers::{get_leverage_tier, LEVERAGE_TIERS};
+
+use super::position_health::position_health;
+
+// Permissionless: anyone (normally the backend monitor) can move the owner's free
+// collateral into the position's margin once MR at the pool market's mark is below
+// the owner's target. Only as much as brings MR back to target, bounded by the remaining budget
+// and free collateral. Tokens stay in the vault; only the books move.
+pub(crate) fn handler(ctx: Context<AutoTopUp>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let mark_price = ctx.accounts.pool_market.fresh_mark(now)?;
+    let health = position_health(&ctx.accounts.position, mark_price)?;
+    let target = ctx.accounts.settings.target_mr;
+    require!(health.notional > 0 && health.margin_ratio < target as i64, PerpError::TopUpNotNeeded);
+
+    // equity + amount >= notional * target / RATE_SCALE, rounded up
+    let required = div_u128(
+        add_u128(mul_u128_u64(health.notional as u128, target)?, RATE_SCALE - 1)?,
+        RATE_SCALE,
+    )? as i128;
+    let needed = u64::try_from((required - health.equity as i128).max(0)).map_err(|_| PerpError::Overflow)?;
+    let amount = needed
+        .min(ctx.accounts.settings.remaining())
+        .min(ctx.accounts.user.free_collateral());
+    require!(amount > 0, PerpError::TopUpUnavailable);
+
+    let user = &mut ctx.accounts.user;
+    user.locked_collateral = user.locked_collateral.checked_add(amount).ok_or(PerpError::Overflow)?;
+
+    let settings = &mut ctx.accounts.settings;
+    settings.used = settings.used.checked_add(amount).ok_or(PerpError::Overflow)?;
+
+    let pos = &mut ctx.accounts.position;
+    pos.margin = pos.margin.checked_add(amount).ok_or(PerpError::Overflow)?;
+    let mmr = get_leverage_tier(pos.leverage, health.notional)
+        .map(|t| t.maintenance_margin_rate)
+        .unwrap_or(LEVERAGE_TIERS[0].maintenance_margin_rate);
+    pos.unrealized_pnl = health.unrealized_pnl;
+    pos.liquidation_price = calc_liquidation_price(pos.side, pos.size, pos.entry_price, pos.margin, mmr)?;
+    pos.last_update = now;
+
+    emit!(MarginToppedUp {
+        owner: pos.owner,
+        position: pos.key(),
+        keeper: ctx.accounts.keeper.key(),
+        amount,
+        mark_price,
+        margin_ratio_before: health.margin_ratio,
+        margin: pos.margin,
+        liquidation_price: pos.liquidation_price,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AutoTopUp<'info> {
+    pub keeper: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"user", position.owner.as_ref(), &position.sub_id.to_le_bytes()],
+        bump = user.bump,
+        constraint = user.owner == position.owner
+    )]
+    pub user: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"position", position.owner.as_ref(), &position.sub_id.to_le_bytes(), position.symbol.as_bytes()],
+        bump = position.bump
+    )]
+    pub position: Account<'info, Position>,
+
+    #[account(seeds = [b"pool", pool.quote_mint.as_ref()], bump = pool.bump)]
+    pub pool: Account<'info, LiquidityPool>,
+
+    #[account(
+        seeds = [b"pool_market", pool.key().as_ref(), position.symbol.as_bytes()],
+        bump = pool_market.bump,
+        has_one = pool
+    )]
+    pub pool_market: Account<'info, PoolMarket>,
+
+    #[account(
+        mut,
+        seeds = [b"top_up", position.key().as_ref()],
+        bump = settings.bump,
+        has_one = position,
+        constraint = settings.owner == position.owner
+    )]
+    pub settings: Account<'info, TopUpSettings>,
+}
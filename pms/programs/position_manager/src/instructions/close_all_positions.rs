@@ -0,0 +1,215 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+
+use crate::constants::BPS_DENOM;
+use crate::errors::PerpError;
+use crate::events::{PoolFill, PoolPnlSettled, PositionClosed, PositionModified};
+use crate::math::*;
+use crate::state::accounts::*;
+use crate::tiers::get_leverage_tier;
+
+pub const MAX_BATCH_POSITIONS: usize = 16;
+
+// One leg per (position, pool_market, top_up) triple in remaining_accounts, in the same order.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BatchLeg {
+    pub price: u64, // expected; fills at the pool market's mark within MAX_PRICE_DEVIATION_BPS
+}
+
+// Closes (reduce_bps == 10_000) or reduces by reduce_bps every position passed in
+// remaining_accounts, each followed by its pool market and its ["top_up", position]
+// address, which is closed to the owner with a fully closed position. All legs
+// succeed or the whole transaction fails.
+pub(crate) fn handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CloseAllPositions<'info>>,
+    legs: Vec<BatchLeg>,
+    reduce_bps: u16,
+) -> Result<()> {
+    let remaining = ctx.remaining_accounts;
+    require!(!legs.is_empty() && legs.len() * 3 == remaining.len(), PerpError::InvalidAmount);
+    require!(legs.len() <= MAX_BATCH_POSITIONS, PerpError::InvalidAmount);
+    require!(reduce_bps > 0 && reduce_bps <= BPS_DENOM, PerpError::InvalidAmount);
+    for (i, a) in remaining.iter().enumerate() {
+        require!(a.is_writable, PerpError::InvalidState);
+        require!(remaining[..i].iter().all(|b| b.key != a.key), PerpError::InvalidState);
+    }
+
+    let owner_key = ctx.accounts.owner.key();
+    let sub_id = ctx.accounts.user.sub_id;
+    let now = Clock::get()?.unix_timestamp;
+    let mut total_payout: u64 = 0;
+
+    let pool_key = ctx.accounts.pool.key();
+    for (leg, triple) in legs.iter().zip(remaining.chunks(3)) {
+        let info = &triple[0];
+        let mut pos: Account<'info, Position> = Account::try_from(info)?;
+        let mut market: Account<'info, PoolMarket> = Account::try_from(&triple[1])?;
+        require!(pos.owner == owner_key && pos.sub_id == sub_id, PerpError::InvalidState);
+        let expected = Pubkey::create_program_address(
+            &[b"position", owner_key.as_ref(), &sub_id.to_le_bytes(), pos.symbol.as_bytes(), &[pos.bump]],
+            ctx.program_id,
+        )
+        .map_err(|_| PerpError::InvalidState)?;
+        require_keys_eq!(expected, info.key(), PerpError::InvalidState);
+        require!(market.pool == pool_key && market.symbol == pos.symbol, PerpError::PoolMarketMismatch);
+        let top_up = &triple[2];
+        let (expected_top_up, _) = Pubkey::find_program_address(&[b"top_up", info.key.as_ref()], ctx.program_id);
+        require_keys_eq!(expected_top_up, top_up.key(), PerpError::InvalidState);
+        let price = market.checked_mark(leg.price, now)?;
+        market.accrue_funding(ctx.accounts.pool.params.funding_rate_bps, price, now)?;
+        // funding stays booked on the position until it fully closes
+        pos.accrue_funding(&market)?;
+
+        // round up so a partial reduce never rounds a small position down to zero
+        let reduce_size = u128_to_u64(div_u128(
+            add_u128(mul_u128(pos.size as u128, reduce_bps as u128)?, (BPS_DENOM - 1) as u128)?,
+            BPS_DENOM as u128,
+        )?)?
+        .min(pos.size);
+
+        // reducing a long sells to the pool and vice versa
+        let oracle_notional = u128_to_u64(mul_u128(reduce_size as u128, price as u128)?)?;
+        let (fill_price, spread_bps) = ctx.accounts.pool.execution_price(&market, pos.side.opposite(), oracle_notional, price)?;
+        let entry_notional = u128_to_u64(mul_u128(reduce_size as u128, pos.entry_price as u128)?)?;
+        ctx.accounts.pool.remove_open_interest(&mut market, pos.side, reduce_size, entry_notional);
+
+        emit!(PoolFill {
+            owner: pos.owner,
+            symbol: pos.symbol.clone(),
+            taker_side: pos.side.opposite(),
+            notional: u128_to_u64(mul_u128(reduce_size as u128, fill_price as u128)?)?,
+            oracle_price: price,
+            execution_price: fill_price,
+            spread_bps,
+            long_open_interest: market.long_open_interest,
+            short_open_interest: market.short_open_interest,
+        });
+        market.exit(ctx.program_id)?;
+
+        if reduce_size == pos.size {
+            let pnl = calc_realized_pnl_full(pos.side, pos.size, pos.entry_price, fill_price)?;
+            let net_pnl = ctx.accounts.pool.cap_trader_pnl(i128_to_i64(pnl)?.checked_add(pos.funding_accrued).ok_or(PerpError::Overflow)?);
+            let collateral_before = ctx.accounts.user.total_collateral;
+            let payout = ctx.accounts.user.settle_close(pos.margin, net_pnl)?;
+            let pool_pnl = ctx.accounts.pool.settle_trader_pnl(collateral_before, ctx.accounts.user.total_collateral, payout)?;
+            total_payout = total_payout.checked_add(payout).ok_or(PerpError::Overflow)?;
+
+            emit!(PoolPnlSettled {
+                owner: pos.owner,
+                symbol: pos.symbol.clone(),
+                pool_pnl,
+                liquidity: ctx.accounts.pool.liquidity,
+            });
+
+            emit!(PositionClosed {
+                owner: pos.owner,
+                symbol: pos.symbol.clone(),
+                size_closed: pos.size,
+                exit_price: fill_price,
+                realized_pnl: net_pnl,
+                payout,
+                sub_id: pos.sub_id,
+            });
+
+            TopUpSettings::close_if_open(top_up, &ctx.accounts.owner.to_account_info())?;
+            pos.close(ctx.accounts.owner.to_account_info())?;
+        } else {
+            let realized = calc_realized_pnl_partial(pos.side, reduce_size, pos.entry_price, fill_price)?;
+            let settled = ctx.accounts.pool.settle_partial_pnl(&mut ctx.accounts.user, &mut pos.margin, i128_to_i64(realized)?)?;
+            pos.realized_pnl = pos.realized_pnl.checked_add(settled).ok_or(PerpError::Overflow)?;
+
+            emit!(PoolPnlSettled {
+                owner: pos.owner,
+                symbol: pos.symbol.clone(),
+                pool_pnl: -settled,
+                liquidity: ctx.accounts.pool.liquidity,
+            });
+            pos.size = pos.size.checked_sub(reduce_size).ok_or(PerpError::Overflow)?;
+
+            let upnl = calc_unrealized_pnl(pos.side, pos.size, pos.entry_price, price)?;
+            pos.unrealized_pnl = i128_to_i64(upnl)?;
+            let notional_u64 = u128_to_u64(mul_u128(pos.size as u128, price as u128)?)?;
+            let tier = get_leverage_tier(pos.leverage, notional_u64)?;
+            pos.liquidation_price = calc_liquidation_price(pos.side, pos.size, pos.entry_price, pos.margin, tier.maintenance_margin_rate)?;
+            pos.last_update = now;
+
+            emit!(PositionModified {
+                owner: pos.owner,
+                symbol: pos.symbol.clone(),
+                size: pos.size,
+                margin: pos.margin,
+                leverage: pos.leverage,
+                price,
+                unrealized_pnl: pos.unrealized_pnl,
+                liquidation_price: pos.liquidation_price,
+                sub_id: pos.sub_id,
+            });
+
+            pos.exit(ctx.program_id)?;
+        }
+    }
+
+    if total_payout > 0 {
+        let signer_seeds: &[&[u8]] = &[b"vault_authority", &[ctx.accounts.vault_authority.bump]];
+        token::transfer(
+            ctx.accounts.transfer_from_vault_ctx().with_signer(&[signer_seeds]),
+            total_payout,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CloseAllPositions<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"user", owner.key().as_ref(), &user.sub_id.to_le_bytes()],
+        bump = user.bump,
+        constraint = user.owner == owner.key()
+    )]
+    pub user: Account<'info, UserAccount>,
+
+    pub quote_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", quote_mint.key().as_ref()],
+        bump = pool.bump,
+        has_one = quote_mint
+    )]
+    pub pool: Account<'info, LiquidityPool>,
+
+    #[account(mut)]
+    pub user_quote_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", quote_mint.key().as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_authority"],
+        bump = vault_authority.bump
+    )]
+    pub vault_authority: Account<'info, VaultAuthority>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> CloseAllPositions<'info> {
+    pub fn transfer_from_vault_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.user_quote_ata.to_account_info(),
+            authority: self.vault_authority.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+}
@@ -0,0 +1,153 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+
+use crate::errors::PerpError;
+use crate::events::{PoolFill, PoolPnlSettled, PositionClosed};
+use crate::math::*;
+use crate::state::accounts::*;
+
+pub(crate) fn handler(ctx: Context<ClosePosition>, exit_price: u64) -> Result<()> {
+    // exit_price is the price the caller expects; the pool fills at its mark, which has
+    // to be within MAX_PRICE_DEVIATION_BPS of it. Closing a long sells to the pool and
+    // vice versa.
+    let now = Clock::get()?.unix_timestamp;
+    let exit_price = ctx.accounts.pool_market.checked_mark(exit_price, now)?;
+    ctx.accounts.accrue_funding(exit_price, now)?;
+    let side = ctx.accounts.position.side;
+    let size = ctx.accounts.position.size;
+    let oracle_notional = u128_to_u64(mul_u128(size as u128, exit_price as u128)?)?;
+    let (fill_price, spread_bps) = ctx.accounts.pool.execution_price(&ctx.accounts.pool_market, side.opposite(), oracle_notional, exit_price)?;
+    let entry_notional = u128_to_u64(mul_u128(size as u128, ctx.accounts.position.entry_price as u128)?)?;
+    ctx.accounts.pool.remove_open_interest(&mut ctx.accounts.pool_market, side, size, entry_notional);
+
+    let pnl = calc_realized_pnl_full(side, size, ctx.accounts.position.entry_price, fill_price)?;
+    let pnl_i64 = i128_to_i64(pnl)?;
+    // funding the position paid or received over its life settles with the close
+    let net_pnl = ctx.accounts.pool.cap_trader_pnl(pnl_i64.checked_add(ctx.accounts.position.funding_accrued).ok_or(PerpError::Overflow)?);
+
+    TopUpSettings::close_if_open(&ctx.accounts.top_up, &ctx.accounts.owner.to_account_info())?;
+
+    let collateral_before = ctx.accounts.user.total_collateral;
+    let payout_u64 = ctx.accounts.user.settle_close(ctx.accounts.position.margin, net_pnl)?;
+    let pool_pnl = ctx.accounts.pool.settle_trader_pnl(collateral_before, ctx.accounts.user.total_collateral, payout_u64)?;
+
+    if payout_u64 > 0 {
+        let signer_seeds: &[&[u8]] = &[b"vault_authority", &[ctx.accounts.vault_authority.bump]];
+        token::transfer(
+            ctx.accounts.transfer_from_vault_ctx().with_signer(&[signer_seeds]),
+            payout_u64,
+        )?;
+    }
+
+    emit!(PoolFill {
+        owner: ctx.accounts.position.owner,
+        symbol: ctx.accounts.position.symbol.clone(),
+        taker_side: side.opposite(),
+        notional: u128_to_u64(mul_u128(size as u128, fill_price as u128)?)?,
+        oracle_price: exit_price,
+        execution_price: fill_price,
+        spread_bps,
+        long_open_interest: ctx.accounts.pool_market.long_open_interest,
+        short_open_interest: ctx.accounts.pool_market.short_open_interest,
+    });
+
+    emit!(PoolPnlSettled {
+        owner: ctx.accounts.position.owner,
+        symbol: ctx.accounts.position.symbol.clone(),
+        pool_pnl,
+        liquidity: ctx.accounts.pool.liquidity,
+    });
+
+    emit!(PositionClosed {
+        owner: ctx.accounts.position.owner,
+        symbol: ctx.accounts.position.symbol.clone(),
+        size_closed: size,
+        exit_price: fill_price,
+        realized_pnl: net_pnl,
+        payout: payout_u64,
+        sub_id: ctx.accounts.position.sub_id,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ClosePosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"user", owner.key().as_ref(), &user.sub_id.to_le_bytes()],
+        bump = user.bump,
+        constraint = user.owner == owner.key()
+    )]
+    pub user: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"position", owner.key().as_ref(), &position.sub_id.to_le_bytes(), position.symbol.as_bytes()],
+        bump = position.bump,
+        constraint = position.owner == owner.key() && position.sub_id == user.sub_id
+    )]
+    pub position: Account<'info, Position>,
+
+    pub quote_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", quote_mint.key().as_ref()],
+        bump = pool.bump,
+        has_one = quote_mint
+    )]
+    pub pool: Account<'info, LiquidityPool>,
+
+    #[account(
+        mut,
+        seeds = [b"pool_market", pool.key().as_ref(), position.symbol.as_bytes()],
+        bump = pool_market.bump
+    )]
+    pub pool_market: Account<'info, PoolMarket>,
+
+    #[account(mut)]
+    pub user_quote_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", quote_mint.key().as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_authority"],
+        bump = vault_authority.bump
+    )]
+    pub vault_authority: Account<'info, VaultAuthority>,
+
+    /// CHECK: the position's TopUpSettings address; closed to the owner if it exists
+    #[account(mut, seeds = [b"top_up", position.key().as_ref()], bump)]
+    pub top_up: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> ClosePosition<'info> {
+    // Brings the market's funding index and the position's accrued funding up to `now`;
+    // called before the position's size changes.
+    pub fn accrue_funding(&mut self, mark: u64, now: i64) -> Result<()> {
+        self.pool_market.accrue_funding(self.pool.params.funding_rate_bps, mark, now)?;
+        self.position.accrue_funding(&self.pool_market)
+    }
+
+    pub fn transfer_from_vault_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.user_quote_ata.to_account_info(),
+            authority: self.vault_authority.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+}
\ No newline at end of file
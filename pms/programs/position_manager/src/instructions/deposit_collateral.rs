@@ -0,0 +1,82 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+
+use crate::constants::*;
+use crate::errors::PerpError;
+use crate::events::CollateralDeposited;
+use crate::state::accounts::*;
+
+pub(crate) fn handler(ctx: Context<DepositCollateral>, sub_id: u16, amount: u64) -> Result<()> {
+    require!(amount > 0, PerpError::InvalidAmount);
+    require!(sub_id < MAX_SUB_ACCOUNTS, PerpError::InvalidSubAccount);
+
+    token::transfer(ctx.accounts.transfer_to_vault_ctx(), amount)?;
+
+    let user = &mut ctx.accounts.user;
+    user.init_if_empty(ctx.accounts.owner.key(), sub_id, ctx.bumps.user);
+    user.total_collateral = user.total_collateral.checked_add(amount).ok_or(PerpError::Overflow)?;
+
+    emit!(CollateralDeposited {
+        owner: user.owner,
+        sub_id,
+        amount,
+        total_collateral: user.total_collateral,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(sub_id: u16)]
+pub struct DepositCollateral<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        seeds = [b"user", owner.key().as_ref(), &sub_id.to_le_bytes()],
+        bump,
+        space = UserAccount::SPACE
+    )]
+    pub user: Account<'info, UserAccount>,
+
+    pub quote_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub user_quote_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        seeds = [b"vault", quote_mint.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = vault_authority
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        seeds = [b"vault_authority"],
+        bump,
+        space = 8 + 1
+    )]
+    pub vault_authority: Account<'info, VaultAuthority>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+impl<'info> DepositCollateral<'info> {
+    pub fn transfer_to_vault_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.user_quote_ata.to_account_info(),
+            to: self.vault.to_account_info(),
+            authority: self.owner.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+}
@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+
+use crate::state::accounts::*;
+
+// Closes the settings account while the position stays open. Instructions that end
+// the position close its settings themselves.
+pub(crate) fn handler(_ctx: Context<DisableAutoTopUp>) -> Result<()> {
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct DisableAutoTopUp<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"top_up", settings.position.as_ref()],
+        bump = settings.bump,
+        has_one = owner
+    )]
+    pub settings: Account<'info, TopUpSettings>,
+}
@@ -0,0 +1,72 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+use crate::state::accounts::*;
+
+// One pool per quote mint; the signer becomes the pool authority.
+pub(crate) fn handler(ctx: Context<InitializePool>, params: PoolParams) -> Result<()> {
+    params.validate()?;
+
+    let pool = &mut ctx.accounts.pool;
+    pool.authority = ctx.accounts.authority.key();
+    pool.quote_mint = ctx.accounts.quote_mint.key();
+    pool.lp_mint = ctx.accounts.lp_mint.key();
+    pool.liquidity = 0;
+    pool.open_interest = 0;
+    pool.params = params;
+    pool.bump = ctx.bumps.pool;
+    pool.market_count = 0;
+
+    ctx.accounts.vault_authority.bump = ctx.bumps.vault_authority;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"pool", quote_mint.key().as_ref()],
+        bump,
+        space = LiquidityPool::SPACE
+    )]
+    pub pool: Account<'info, LiquidityPool>,
+
+    pub quote_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"lp_mint", pool.key().as_ref()],
+        bump,
+        mint::decimals = quote_mint.decimals,
+        mint::authority = pool
+    )]
+    pub lp_mint: Account<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [b"vault", quote_mint.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = vault_authority
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [b"vault_authority"],
+        bump,
+        space = 8 + 1
+    )]
+    pub vault_authority: Account<'info, VaultAuthority>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
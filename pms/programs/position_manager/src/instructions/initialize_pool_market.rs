@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::PerpError;
+use crate::state::accounts::*;
+
+// Positions in `symbol` can only be opened once the pool has a market for it.
+pub(crate) fn handler(ctx: Context<InitializePoolMarket>, symbol: String) -> Result<()> {
+    require!(symbol.len() <= MAX_SYMBOL_LEN, PerpError::SymbolTooLong);
+
+    let market = &mut ctx.accounts.pool_market;
+    market.pool = ctx.accounts.pool.key();
+    market.symbol = symbol;
+    market.long_open_interest = 0;
+    market.short_open_interest = 0;
+    market.bump = ctx.bumps.pool_market;
+    market.long_size = 0;
+    market.short_size = 0;
+    market.mark_price = 0;
+    market.mark_updated_at = 0;
+    market.funding_index = 0;
+    market.funding_updated_at = Clock::get()?.unix_timestamp;
+
+    let pool = &mut ctx.accounts.pool;
+    pool.market_count = pool.market_count.checked_add(1).ok_or(PerpError::Overflow)?;
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(symbol: String)]
+pub struct InitializePoolMarket<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", pool.quote_mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
+    )]
+    pub pool: Account<'info, LiquidityPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"pool_market", pool.key().as_ref(), symbol.as_bytes()],
+        bump,
+        space = PoolMarket::space(MAX_SYMBOL_LEN)
+    )]
+    pub pool_market: Account<'info, PoolMarket>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,43 @@
+pub mod open_positions;
+pub mod modify_positions;
+pub mod close_positions;
+pub mod close_all_positions;
+pub mod position_health;
+pub mod deposit_collateral;
+pub mod withdraw_collateral;
+pub mod transfer_collateral;
+pub mod set_margin_mode;
+pub mod initialize_pool;
+pub mod update_pool_params;
+pub mod initialize_pool_market;
+pub mod set_mark_price;
+pub mod add_liquidity;
+pub mod remove_liquidity;
+pub mod transfer_position;
+pub mod approve_position_transfer;
+pub mod revoke_position_transfer;
+pub mod set_auto_top_up;
+pub mod disable_auto_top_up;
+pub mod auto_top_up;
+
+pub use open_positions::*;
+pub use modify_positions::*;
+pub use close_positions::*;
+pub use close_all_positions::*;
+pub use position_health::*;
+pub use deposit_collateral::*;
+pub use withdraw_collateral::*;
+pub use transfer_collateral::*;
+pub use set_margin_mode::*;
+pub use initialize_pool::*;
+pub use update_pool_params::*;
+pub use initialize_pool_market::*;
+pub use set_mark_price::*;
+pub use add_liquidity::*;
+pub use remove_liquidity::*;
+pub use transfer_position::*;
+pub use approve_position_transfer::*;
+pub use revoke_position_transfer::*;
+pub use set_auto_top_up::*;
+pub use disable_auto_top_up::*;
+pub use auto_top_up::*;
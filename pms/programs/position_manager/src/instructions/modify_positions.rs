@@ -3,7 +3,7 @@ use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
 
 use crate::constants::*;
 use crate::errors::PerpError;
-use crate::events::PositionModified;
+use crate::events::{PoolFill, PoolPnlSettled, PositionModified};
 use crate::math::*;
 use crate::state::accounts::*;
 use crate::tiers::get_leverage_tier;
@@ -16,10 +16,15 @@ pub enum ModifyKind {
     RemoveMargin { amount: u64, price: u64 },
 }
 
-pub fn handler(ctx: Context<ModifyPosition>, action: ModifyKind) -> Result<()> {
+// `price` is what the caller expects; fills and margin checks use the pool market's
+// mark, which has to be within MAX_PRICE_DEVIATION_BPS of it.
+pub(crate) fn handler(ctx: Context<ModifyPosition>, action: ModifyKind) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
     match action {
         ModifyKind::IncreaseSize { add_size, price, add_margin } => {
             require!(add_size > 0, PerpError::InvalidSize);
+            let price = ctx.accounts.pool_market.checked_mark(price, now)?;
+            ctx.accounts.accrue_funding(price, now)?;
 
             if add_margin > 0 {
                 token::transfer(ctx.accounts.transfer_to_vault_ctx(), add_margin)?;
@@ -28,10 +33,19 @@ pub fn handler(ctx: Context<ModifyPosition>, action: ModifyKind) -> Result<()> {
                 ctx.accounts.position.margin = ctx.accounts.position.margin.checked_add(add_margin).ok_or(PerpError::Overflow)?;
             }
 
+            // the added size fills at the mark + skew spread
+            let side = ctx.accounts.position.side;
+            let oracle_notional = u128_to_u64(mul_u128(add_size as u128, price as u128)?)?;
+            let (fill_price, spread_bps) = ctx.accounts.pool.execution_price(&ctx.accounts.pool_market, side, oracle_notional, price)?;
+            let fill_notional = u128_to_u64(mul_u128(add_size as u128, fill_price as u128)?)?;
+            let pool = &mut ctx.accounts.pool;
+            pool.add_open_interest(&mut ctx.accounts.pool_market, side, add_size, fill_notional)?;
+            pool.check_caps(&ctx.accounts.pool_market)?;
+
             let pos = &mut ctx.accounts.position;
 
             let new_size = pos.size.checked_add(add_size).ok_or(PerpError::Overflow)?;
-            let new_notional = mul_u128(new_size as u128, price as u128)?;
+            let new_notional = calc_notional(new_size, price)?;
             let notional_u64 = u128_to_u64(new_notional)?;
             let tier = get_leverage_tier(pos.leverage, notional_u64)?;
 
@@ -39,22 +53,34 @@ pub fn handler(ctx: Context<ModifyPosition>, action: ModifyKind) -> Result<()> {
             let lev_now = div_u128(new_notional, pos.margin as u128)?;
             require!(lev_now <= pos.leverage as u128, PerpError::InsufficientMarginForIncrease);
             require!(pos.leverage <= tier.max_leverage, PerpError::LeverageExceeded);
+            require!(
+                covers_maintenance(pos.margin as u128, new_notional, tier.maintenance_margin_rate)?,
+                PerpError::InitialMarginTooLow
+            );
 
             // weighted avg entry
             if price > 0 {
-                let old_notional = mul_u128(pos.size as u128, pos.entry_price as u128)?;
-                let add_notional = mul_u128(add_size as u128, price as u128)?;
-                let total = add_u128(old_notional, add_notional)?;
-                let new_entry = div_u128(total, new_size as u128)?;
-                pos.entry_price = u128_to_u64(new_entry)?;
+                pos.entry_price = calc_average_entry(pos.size, pos.entry_price, add_size, fill_notional as u128)?;
             }
 
             pos.size = new_size;
 
+            emit!(PoolFill {
+                owner: pos.owner,
+                symbol: pos.symbol.clone(),
+                taker_side: side,
+                notional: fill_notional,
+                oracle_price: price,
+                execution_price: fill_price,
+                spread_bps,
+                long_open_interest: ctx.accounts.pool_market.long_open_interest,
+                short_open_interest: ctx.accounts.pool_market.short_open_interest,
+            });
+
             let upnl = calc_unrealized_pnl(pos.side, pos.size, pos.entry_price, price)?;
             pos.unrealized_pnl = i128_to_i64(upnl)?;
             pos.liquidation_price = crate::math::calc_liquidation_price(pos.side, pos.size, pos.entry_price, pos.margin, tier.maintenance_margin_rate)?;
-            pos.last_update = Clock::get()?.unix_timestamp;
+            pos.last_update = now;
 
             emit!(PositionModified {
                 owner: pos.owner,
@@ -65,14 +91,44 @@ pub fn handler(ctx: Context<ModifyPosition>, action: ModifyKind) -> Result<()> {
                 price,
                 unrealized_pnl: pos.unrealized_pnl,
                 liquidation_price: pos.liquidation_price,
+                sub_id: pos.sub_id,
             });
         }
 
         ModifyKind::DecreaseSize { reduce_size, price } => {
             require!(reduce_size > 0 && reduce_size <= ctx.accounts.position.size, PerpError::InvalidSize);
+            let price = ctx.accounts.pool_market.checked_mark(price, now)?;
+            ctx.accounts.accrue_funding(price, now)?;
+
+            // reducing a long sells to the pool and vice versa
+            let side = ctx.accounts.position.side;
+            let oracle_notional = u128_to_u64(mul_u128(reduce_size as u128, price as u128)?)?;
+            let (fill_price, spread_bps) = ctx.accounts.pool.execution_price(&ctx.accounts.pool_market, side.opposite(), oracle_notional, price)?;
+            let entry_notional = u128_to_u64(mul_u128(reduce_size as u128, ctx.accounts.position.entry_price as u128)?)?;
+            ctx.accounts.pool.remove_open_interest(&mut ctx.accounts.pool_market, side, reduce_size, entry_notional);
 
-            let realized = calc_realized_pnl_partial(ctx.accounts.position.side, reduce_size, ctx.accounts.position.entry_price, price)?;
-            ctx.accounts.position.realized_pnl = ctx.accounts.position.realized_pnl.checked_add(i128_to_i64(realized)?).ok_or(PerpError::Overflow)?;
+            emit!(PoolFill {
+                owner: ctx.accounts.position.owner,
+                symbol: ctx.accounts.position.symbol.clone(),
+                taker_side: side.opposite(),
+                notional: u128_to_u64(mul_u128(reduce_size as u128, fill_price as u128)?)?,
+                oracle_price: price,
+                execution_price: fill_price,
+                spread_bps,
+                long_open_interest: ctx.accounts.pool_market.long_open_interest,
+                short_open_interest: ctx.accounts.pool_market.short_open_interest,
+            });
+
+            let realized = calc_realized_pnl_partial(side, reduce_size, ctx.accounts.position.entry_price, fill_price)?;
+            let settled = ctx.accounts.pool.settle_partial_pnl(&mut ctx.accounts.user, &mut ctx.accounts.position.margin, i128_to_i64(realized)?)?;
+            ctx.accounts.position.realized_pnl = ctx.accounts.position.realized_pnl.checked_add(settled).ok_or(PerpError::Overflow)?;
+
+            emit!(PoolPnlSettled {
+                owner: ctx.accounts.position.owner,
+                symbol: ctx.accounts.position.symbol.clone(),
+                pool_pnl: -settled,
+                liquidity: ctx.accounts.pool.liquidity,
+            });
             ctx.accounts.position.size = ctx.accounts.position.size.checked_sub(reduce_size).ok_or(PerpError::Overflow)?;
 
             let upnl = calc_unrealized_pnl(ctx.accounts.position.side, ctx.accounts.position.size, ctx.accounts.position.entry_price, price)?;
@@ -80,7 +136,7 @@ pub fn handler(ctx: Context<ModifyPosition>, action: ModifyKind) -> Result<()> {
             let new_notional_u64 = u128_to_u64(mul_u128(ctx.accounts.position.size as u128, price as u128)?)?;
             let tier = get_leverage_tier(ctx.accounts.position.leverage, new_notional_u64)?;
             ctx.accounts.position.liquidation_price = crate::math::calc_liquidation_price(ctx.accounts.position.side, ctx.accounts.position.size, ctx.accounts.position.entry_price, ctx.accounts.position.margin, tier.maintenance_margin_rate)?;
-            ctx.accounts.position.last_update = Clock::get()?.unix_timestamp;
+            ctx.accounts.position.last_update = now;
 
             emit!(PositionModified {
                 owner: ctx.accounts.position.owner,
@@ -91,6 +147,7 @@ pub fn handler(ctx: Context<ModifyPosition>, action: ModifyKind) -> Result<()> {
                 price,
                 unrealized_pnl: ctx.accounts.position.unrealized_pnl,
                 liquidation_price: ctx.accounts.position.liquidation_price,
+                sub_id: ctx.accounts.position.sub_id,
             });
         }
 
@@ -100,7 +157,7 @@ pub fn handler(ctx: Context<ModifyPosition>, action: ModifyKind) -> Result<()> {
             ctx.accounts.user.total_collateral = ctx.accounts.user.total_collateral.checked_add(amount).ok_or(PerpError::Overflow)?;
             ctx.accounts.user.locked_collateral = ctx.accounts.user.locked_collateral.checked_add(amount).ok_or(PerpError::Overflow)?;
             ctx.accounts.position.margin = ctx.accounts.position.margin.checked_add(amount).ok_or(PerpError::Overflow)?;
-            ctx.accounts.position.last_update = Clock::get()?.unix_timestamp;
+            ctx.accounts.position.last_update = now;
 
             let pos = &ctx.accounts.position;
 
@@ -113,22 +170,24 @@ pub fn handler(ctx: Context<ModifyPosition>, action: ModifyKind) -> Result<()> {
                 price: pos.entry_price,
                 unrealized_pnl: pos.unrealized_pnl,
                 liquidation_price: pos.liquidation_price,
+                sub_id: pos.sub_id,
             });
         }
 
         ModifyKind::RemoveMargin { amount, price } => {
             require!(amount > 0 && amount <= ctx.accounts.position.margin, PerpError::InvalidAmount);
+            let price = ctx.accounts.pool_market.checked_mark(price, now)?;
 
-            let notional = mul_u128(ctx.accounts.position.size as u128, price as u128)?;
+            let notional = calc_notional(ctx.accounts.position.size, price)?;
             let upnl = calc_unrealized_pnl(ctx.accounts.position.side, ctx.accounts.position.size, ctx.accounts.position.entry_price, price)?;
             let new_margin = (ctx.accounts.position.margin as i128).checked_sub(amount as i128).ok_or(PerpError::Overflow)?;
-            let mr_num = (new_margin as i128).checked_add(upnl as i128).ok_or(PerpError::Overflow)?;
+            let mr_num = new_margin.checked_add(upnl).ok_or(PerpError::Overflow)?;
             let mr_den = notional as i128;
             require!(mr_den > 0, PerpError::InvalidState);
 
             let tier = get_leverage_tier(ctx.accounts.position.leverage, u128_to_u64(notional)?)?;
-            let lhs = mul_i128_i128(mr_num as i128, crate::constants::RATE_SCALE as i128)?;
-            let rhs = mul_u128_u64(notional, tier.maintenance_margin_rate as u64)?;
+            let lhs = mul_i128_i128(mr_num, RATE_SCALE as i128)?;
+            let rhs = mul_u128_u64(notional, tier.maintenance_margin_rate)?;
             require!(lhs >= rhs as i128, PerpError::MaintenanceBreach);
 
             // transfer out from vault to user (PDA signer)
@@ -143,7 +202,7 @@ pub fn handler(ctx: Context<ModifyPosition>, action: ModifyKind) -> Result<()> {
             ctx.accounts.position.margin = ctx.accounts.position.margin.checked_sub(amount).ok_or(PerpError::Overflow)?;
             ctx.accounts.position.unrealized_pnl = i128_to_i64(upnl)?;
             ctx.accounts.position.liquidation_price = crate::math::calc_liquidation_price(ctx.accounts.position.side, ctx.accounts.position.size, ctx.accounts.position.entry_price, ctx.accounts.position.margin, tier.maintenance_margin_rate)?;
-            ctx.accounts.position.last_update = Clock::get()?.unix_timestamp;
+            ctx.accounts.position.last_update = now;
 
             let pos = &ctx.accounts.position;
 
@@ -156,6 +215,7 @@ pub fn handler(ctx: Context<ModifyPosition>, action: ModifyKind) -> Result<()> {
                 price,
                 unrealized_pnl: pos.unrealized_pnl,
                 liquidation_price: pos.liquidation_price,
+                sub_id: pos.sub_id,
             });
         }
     }
@@ -170,7 +230,7 @@ pub struct ModifyPosition<'info> {
 
     #[account(
         mut,
-        seeds = [b"user", owner.key().as_ref()],
+        seeds = [b"user", owner.key().as_ref(), &user.sub_id.to_le_bytes()],
         bump = user.bump,
         constraint = user.owner == owner.key()
     )]
@@ -178,13 +238,29 @@ pub struct ModifyPosition<'info> {
 
     #[account(
         mut,
-        seeds = [b"position", owner.key().as_ref(), position.symbol.as_bytes()],
+        seeds = [b"position", owner.key().as_ref(), &position.sub_id.to_le_bytes(), position.symbol.as_bytes()],
         bump = position.bump,
-        constraint = position.owner == owner.key()
+        constraint = position.owner == owner.key() && position.sub_id == user.sub_id
     )]
     pub position: Account<'info, Position>,
 
     pub quote_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", quote_mint.key().as_ref()],
+        bump = pool.bump,
+        has_one = quote_mint
+    )]
+    pub pool: Account<'info, LiquidityPool>,
+
+    #[account(
+        mut,
+        seeds = [b"pool_market", pool.key().as_ref(), position.symbol.as_bytes()],
+        bump = pool_market.bump
+    )]
+    pub pool_market: Account<'info, PoolMarket>,
+
     #[account(mut)]
     pub user_quote_ata: Account<'info, TokenAccount>,
 
@@ -206,6 +282,13 @@ pub struct ModifyPosition<'info> {
 }
 
 impl<'info> ModifyPosition<'info> {
+    // Brings the market's funding index and the position's accrued funding up to `now`;
+    // called before the position's size changes.
+    pub fn accrue_funding(&mut self, mark: u64, now: i64) -> Result<()> {
+        self.pool_market.accrue_funding(self.pool.params.funding_rate_bps, mark, now)?;
+        self.position.accrue_funding(&self.pool_market)
+    }
+
     pub fn transfer_to_vault_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
         let cpi_accounts = Transfer {
             from: self.user_quote_ata.to_account_info(),
@@ -0,0 +1,139 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Mint;
+
+use crate::constants::*;
+use crate::errors::PerpError;
+use crate::events::{PoolFill, PositionOpened};
+use crate::math::*;
+use crate::state::accounts::*;
+use crate::tiers::{get_leverage_tier};
+
+pub(crate) fn handler(
+    ctx: Context<OpenPosition>,
+    sub_id: u16,
+    symbol: String,
+    side: Side,
+    size: u64,
+    leverage: u16,
+    entry_price: u64,
+) -> Result<()> {
+    require!(size > 0, PerpError::InvalidSize);
+    require!((MIN_LEVERAGE..=MAX_LEVERAGE).contains(&leverage), PerpError::InvalidLeverage);
+    require!(symbol.len() <= MAX_SYMBOL_LEN, PerpError::SymbolTooLong);
+    require!(sub_id < MAX_SUB_ACCOUNTS, PerpError::InvalidSubAccount);
+
+    // entry_price is the price the caller expects; the pool fills at its mark + skew
+    // spread, and only while the two are within MAX_PRICE_DEVIATION_BPS
+    let now = Clock::get()?.unix_timestamp;
+    let oracle_price = ctx.accounts.pool_market.checked_mark(entry_price, now)?;
+    let funding_rate_bps = ctx.accounts.pool.params.funding_rate_bps;
+    ctx.accounts.pool_market.accrue_funding(funding_rate_bps, oracle_price, now)?;
+    let oracle_notional = u128_to_u64(mul_u128(size as u128, oracle_price as u128)?)?;
+    let (entry_price, spread_bps) = ctx.accounts.pool.execution_price(&ctx.accounts.pool_market, side, oracle_notional, oracle_price)?;
+
+    let notional = calc_notional(size, entry_price)?;
+    let notional_u64 = u128_to_u64(notional)?;
+    let tier = get_leverage_tier(leverage, notional_u64)?;
+
+    let pool = &mut ctx.accounts.pool;
+    pool.add_open_interest(&mut ctx.accounts.pool_market, side, size, notional_u64)?;
+    pool.check_caps(&ctx.accounts.pool_market)?;
+
+    let im = calc_initial_margin(notional, leverage)?;
+    let im_u64 = u128_to_u64(im)?;
+    // otherwise the position is liquidatable at entry (e.g. 1000x on the 0.1% MMR tier)
+    require!(covers_maintenance(im, notional, tier.maintenance_margin_rate)?, PerpError::InitialMarginTooLow);
+
+    // lock initial margin out of the sub-account's free collateral (deposit_collateral first)
+    let user = &mut ctx.accounts.user;
+    require!(im_u64 <= user.free_collateral(), PerpError::InsufficientFreeCollateral);
+    user.locked_collateral = user.locked_collateral.checked_add(im_u64).ok_or(PerpError::Overflow)?;
+    user.position_count = user.position_count.checked_add(1).ok_or(PerpError::Overflow)?;
+
+    // create position
+    let pos = &mut ctx.accounts.position;
+    pos.owner = ctx.accounts.owner.key();
+    pos.sub_id = sub_id;
+    pos.symbol = symbol.clone();
+    pos.side = side;
+    pos.size = size;
+    pos.entry_price = entry_price;
+    pos.margin = im_u64;
+    pos.leverage = leverage;
+    pos.unrealized_pnl = 0;
+    pos.realized_pnl = 0;
+    pos.funding_accrued = 0;
+    pos.funding_index = ctx.accounts.pool_market.funding_index;
+    pos.liquidation_price = calc_liquidation_price(side, size, entry_price, im_u64, tier.maintenance_margin_rate)?;
+    pos.last_update = now;
+    pos.bump = ctx.bumps.position;
+
+    emit!(PoolFill {
+        owner: pos.owner,
+        symbol: symbol.clone(),
+        taker_side: side,
+        notional: notional_u64,
+        oracle_price,
+        execution_price: entry_price,
+        spread_bps,
+        long_open_interest: ctx.accounts.pool_market.long_open_interest,
+        short_open_interest: ctx.accounts.pool_market.short_open_interest,
+    });
+
+    emit!(PositionOpened {
+        owner: pos.owner,
+        symbol,
+        side,
+        size,
+        leverage,
+        entry_price,
+        initial_margin: im_u64,
+        liquidation_price: pos.liquidation_price,
+        sub_id: pos.sub_id,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(sub_id: u16, symbol: String)]
+pub struct OpenPosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"user", owner.key().as_ref(), &sub_id.to_le_bytes()],
+        bump = user.bump,
+        constraint = user.owner == owner.key()
+    )]
+    pub user: Account<'info, UserAccount>,
+
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"position", owner.key().as_ref(), &sub_id.to_le_bytes(), symbol.as_bytes()],
+        bump,
+        space = Position::space(MAX_SYMBOL_LEN)
+    )]
+    pub position: Account<'info, Position>,
+
+    pub quote_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", quote_mint.key().as_ref()],
+        bump = pool.bump,
+        has_one = quote_mint
+    )]
+    pub pool: Account<'info, LiquidityPool>,
+
+    #[account(
+        mut,
+        seeds = [b"pool_market", pool.key().as_ref(), symbol.as_bytes()],
+        bump = pool_market.bump
+    )]
+    pub pool_market: Account<'info, PoolMarket>,
+
+    pub system_program: Program<'info, System>,
+}
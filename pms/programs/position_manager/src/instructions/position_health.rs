@@ -0,0 +1,66 @@
+use anchor_lang::prelude::*;
+
+use crate::math::*;
+use crate::state::accounts::*;
+use crate::tiers::{get_leverage_tier, LEVERAGE_TIERS};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PositionHealth {
+    pub mark_price: u64,
+    pub notional: u64,                // size * mark
+    pub unrealized_pnl: i64,
+    pub equity: i64,                  // margin + uPnL
+    pub maintenance_margin: u64,      // notional * mmr
+    pub maintenance_margin_rate: u64, // scaled by 1e6
+    pub margin_ratio: i64,            // equity / notional, scaled by 1e6; i64::MAX when notional == 0
+    pub liquidation_price: u64,
+    pub liquidatable: bool,
+}
+
+pub(crate) fn handler(ctx: Context<GetPositionHealth>, mark_price: u64) -> Result<PositionHealth> {
+    position_health(&ctx.accounts.position, mark_price)
+}
+
+/// Health of `pos` at `mark_price`, using the same MR and liquidation formulas as
+/// `modify_position`. Exposed so CPI callers can also compute it off-chain.
+pub fn position_health(pos: &Position, mark_price: u64) -> Result<PositionHealth> {
+    let notional = calc_notional(pos.size, mark_price)?;
+    let notional_u64 = u128_to_u64(notional)?;
+
+    // A mark move can push notional past the tier's size cap; fall back to the
+    // most conservative tier instead of failing the view.
+    let mmr = get_leverage_tier(pos.leverage, notional_u64)
+        .map(|t| t.maintenance_margin_rate)
+        .unwrap_or(LEVERAGE_TIERS[0].maintenance_margin_rate);
+
+    let upnl = calc_unrealized_pnl(pos.side, pos.size, pos.entry_price, mark_price)?;
+    let equity = (pos.margin as i128).checked_add(upnl).ok_or(crate::errors::PerpError::Overflow)?;
+    let mm = calc_maintenance_margin(notional, mmr)?;
+
+    let margin_ratio = match perp_math::margin_ratio(equity, notional).map_err(crate::errors::PerpError::from)? {
+        Some(mr) => i128_to_i64(mr)?,
+        None => i64::MAX,
+    };
+    let liquidatable = is_liquidatable(equity, notional, mmr)?;
+
+    Ok(PositionHealth {
+        mark_price,
+        notional: notional_u64,
+        unrealized_pnl: i128_to_i64(upnl)?,
+        equity: i128_to_i64(equity)?,
+        maintenance_margin: u128_to_u64(mm)?,
+        maintenance_margin_rate: mmr,
+        margin_ratio,
+        liquidation_price: calc_liquidation_price(pos.side, pos.size, pos.entry_price, pos.margin, mmr)?,
+        liquidatable,
+    })
+}
+
+#[derive(Accounts)]
+pub struct GetPositionHealth<'info> {
+    #[account(
+        seeds = [b"position", position.owner.as_ref(), &position.sub_id.to_le_bytes(), position.symbol.as_bytes()],
+        bump = position.bump
+    )]
+    pub position: Account<'info, Position>,
+}
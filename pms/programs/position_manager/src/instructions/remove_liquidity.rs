@@ -0,0 +1,100 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Burn, Mint, Token, TokenAccount, Transfer};
+
+use crate::constants::BPS_DENOM;
+use crate::errors::PerpError;
+use crate::events::LiquidityRemoved;
+use crate::math::*;
+use crate::state::accounts::*;
+
+// Pays shares × value / supply, with the value priced as in add_liquidity.
+pub(crate) fn handler<'info>(ctx: Context<'_, '_, 'info, 'info, RemoveLiquidity<'info>>, shares: u64) -> Result<()> {
+    require!(shares > 0 && shares <= ctx.accounts.provider_lp_ata.amount, PerpError::InvalidAmount);
+
+    let supply = ctx.accounts.lp_mint.supply;
+    let now = Clock::get()?.unix_timestamp;
+    let pool = &ctx.accounts.pool;
+    let value = pool.share_value(&pool.key(), ctx.remaining_accounts, now)?;
+    let amount = u128_to_u64(div_u128(mul_u128(shares as u128, value as u128)?, supply as u128)?)?;
+    // traders' unrealized losses count towards the value but are only paid in on close
+    let remaining = pool.liquidity.checked_sub(amount).ok_or(PerpError::InsufficientPoolLiquidity)?;
+
+    // LPs can't pull liquidity out from under open interest past the cap
+    let oi = mul_u128(pool.open_interest as u128, BPS_DENOM as u128)?;
+    require!(oi <= mul_u128(remaining as u128, pool.params.max_utilization_bps as u128)?, PerpError::PoolUtilizationExceeded);
+
+    token::burn(ctx.accounts.burn_shares_ctx(), shares)?;
+    if amount > 0 {
+        let signer_seeds: &[&[u8]] = &[b"vault_authority", &[ctx.accounts.vault_authority.bump]];
+        token::transfer(ctx.accounts.transfer_from_vault_ctx().with_signer(&[signer_seeds]), amount)?;
+    }
+
+    ctx.accounts.pool.liquidity = remaining;
+
+    emit!(LiquidityRemoved {
+        provider: ctx.accounts.provider.key(),
+        amount,
+        shares,
+        liquidity: remaining,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RemoveLiquidity<'info> {
+    pub provider: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", quote_mint.key().as_ref()],
+        bump = pool.bump,
+        has_one = quote_mint,
+        has_one = lp_mint
+    )]
+    pub pool: Account<'info, LiquidityPool>,
+
+    pub quote_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub lp_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub provider_quote_ata: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = lp_mint, token::authority = provider)]
+    pub provider_lp_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", quote_mint.key().as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"vault_authority"],
+        bump = vault_authority.bump
+    )]
+    pub vault_authority: Account<'info, VaultAuthority>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> RemoveLiquidity<'info> {
+    pub fn burn_shares_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
+        let cpi_accounts = Burn {
+            mint: self.lp_mint.to_account_info(),
+            from: self.provider_lp_ata.to_account_info(),
+            authority: self.provider.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+
+    pub fn transfer_from_vault_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.provider_quote_ata.to_account_info(),
+            authority: self.vault_authority.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+}
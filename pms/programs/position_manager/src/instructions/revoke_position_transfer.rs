@@ -0,0 +1,30 @@
+use anchor_lang::prelude::*;
+
+use crate::state::accounts::*;
+
+// Closing the approval account is enough to withdraw consent.
+pub(crate) fn handler(_ctx: Context<RevokePositionTransfer>) -> Result<()> {
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RevokePositionTransfer<'info> {
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+
+    #[account(
+        mut,
+        close = recipient,
+        seeds = [
+            b"transfer_approval",
+            recipient.key().as_ref(),
+            &approval.to_sub_id.to_le_bytes(),
+            approval.from_owner.as_ref(),
+            &approval.from_sub_id.to_le_bytes(),
+            approval.symbol.as_bytes(),
+        ],
+        bump = approval.bump,
+        has_one = recipient
+    )]
+    pub approval: Account<'info, TransferApproval>,
+}
@@ -0,0 +1,55 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::RATE_SCALE;
+use crate::errors::PerpError;
+use crate::events::AutoTopUpConfigured;
+use crate::state::accounts::*;
+
+// Creates or updates the position's top-up settings. Updating keeps `used`, so
+// raising max_total extends the budget rather than resetting it.
+pub(crate) fn handler(ctx: Context<SetAutoTopUp>, target_mr: u64, max_total: u64) -> Result<()> {
+    require!(target_mr > 0 && (target_mr as u128) < RATE_SCALE, PerpError::InvalidAmount);
+
+    let settings = &mut ctx.accounts.settings;
+    if settings.owner == Pubkey::default() {
+        settings.owner = ctx.accounts.owner.key();
+        settings.position = ctx.accounts.position.key();
+        settings.used = 0;
+        settings.bump = ctx.bumps.settings;
+    }
+    settings.target_mr = target_mr;
+    settings.max_total = max_total;
+
+    emit!(AutoTopUpConfigured {
+        owner: settings.owner,
+        position: settings.position,
+        target_mr,
+        max_total,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetAutoTopUp<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"position", owner.key().as_ref(), &position.sub_id.to_le_bytes(), position.symbol.as_bytes()],
+        bump = position.bump,
+        constraint = position.owner == owner.key()
+    )]
+    pub position: Account<'info, Position>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        seeds = [b"top_up", position.key().as_ref()],
+        bump,
+        space = TopUpSettings::SPACE
+    )]
+    pub settings: Account<'info, TopUpSettings>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::PerpError;
+use crate::state::accounts::*;
+
+pub(crate) fn handler(ctx: Context<SetMarginMode>, margin_mode: MarginMode) -> Result<()> {
+    let user = &mut ctx.accounts.user;
+    require!(user.position_count == 0, PerpError::PositionsOpen);
+    user.margin_mode = margin_mode;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetMarginMode<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"user", owner.key().as_ref(), &user.sub_id.to_le_bytes()],
+        bump = user.bump,
+        constraint = user.owner == owner.key()
+    )]
+    pub user: Account<'info, UserAccount>,
+}
@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::PerpError;
+use crate::state::accounts::*;
+
+// The pool authority publishes the market's mark; LP share pricing and auto_top_up
+// read it and refuse marks older than MAX_MARK_AGE_SECS.
+pub(crate) fn handler(ctx: Context<SetMarkPrice>, price: u64) -> Result<()> {
+    require!(price > 0, PerpError::InvalidAmount);
+
+    let market = &mut ctx.accounts.pool_market;
+    market.mark_price = price;
+    market.mark_updated_at = Clock::get()?.unix_timestamp;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetMarkPrice<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"pool", pool.quote_mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
+    )]
+    pub pool: Account<'info, LiquidityPool>,
+
+    #[account(
+        mut,
+        seeds = [b"pool_market", pool.key().as_ref(), pool_market.symbol.as_bytes()],
+        bump = pool_market.bump,
+        has_one = pool
+    )]
+    pub pool_market: Account<'info, PoolMarket>,
+}
@@ -0,0 +1,56 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::PerpError;
+use crate::events::CollateralTransferred;
+use crate::state::accounts::*;
+
+// Moves free collateral between two sub-accounts of the same owner. Tokens stay
+// in the vault; only the sub-account books change.
+pub(crate) fn handler(ctx: Context<TransferCollateral>, from_sub_id: u16, to_sub_id: u16, amount: u64) -> Result<()> {
+    require!(amount > 0, PerpError::InvalidAmount);
+    require!(from_sub_id != to_sub_id && to_sub_id < MAX_SUB_ACCOUNTS, PerpError::InvalidSubAccount);
+    require!(amount <= ctx.accounts.from_user.free_collateral(), PerpError::InsufficientFreeCollateral);
+
+    let from = &mut ctx.accounts.from_user;
+    from.total_collateral = from.total_collateral.checked_sub(amount).ok_or(PerpError::Overflow)?;
+
+    let to = &mut ctx.accounts.to_user;
+    to.init_if_empty(ctx.accounts.owner.key(), to_sub_id, ctx.bumps.to_user);
+    to.total_collateral = to.total_collateral.checked_add(amount).ok_or(PerpError::Overflow)?;
+
+    emit!(CollateralTransferred {
+        owner: ctx.accounts.owner.key(),
+        from_sub_id,
+        to_sub_id,
+        amount,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(from_sub_id: u16, to_sub_id: u16)]
+pub struct TransferCollateral<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"user", owner.key().as_ref(), &from_sub_id.to_le_bytes()],
+        bump = from_user.bump,
+        constraint = from_user.owner == owner.key()
+    )]
+    pub from_user: Account<'info, UserAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        seeds = [b"user", owner.key().as_ref(), &to_sub_id.to_le_bytes()],
+        bump,
+        space = UserAccount::SPACE
+    )]
+    pub to_user: Account<'info, UserAccount>,
+
+    pub system_program: Program<'info, System>,
+}
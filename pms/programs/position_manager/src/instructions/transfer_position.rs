@@ -0,0 +1,137 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::PerpError;
+use crate::events::PositionTransferred;
+use crate::state::accounts::*;
+
+// Moves a position to another owner's sub-account. Position PDAs are derived from
+// the owner, so the position is re-created under the recipient's seeds and the old
+// account is closed. Margin moves with it; tokens never leave the vault and the
+// pool's open interest is unchanged. The recipient either co-signs or has an
+// approval on file, which is consumed and must still cover the position. The
+// sender's auto top-up settings are closed rather than carried over.
+pub(crate) fn handler(ctx: Context<TransferPosition>, to_sub_id: u16) -> Result<()> {
+    require!(to_sub_id < MAX_SUB_ACCOUNTS, PerpError::InvalidSubAccount);
+    require!(ctx.accounts.recipient.is_signer || ctx.accounts.approval.is_some(), PerpError::MissingTransferApproval);
+
+    let owner_key = ctx.accounts.owner.key();
+    let recipient_key = ctx.accounts.recipient.key();
+    let from_sub_id = ctx.accounts.from_user.sub_id;
+    require!(owner_key != recipient_key || from_sub_id != to_sub_id, PerpError::InvalidSubAccount);
+
+    let old = &ctx.accounts.position;
+    let margin = old.margin;
+    if let Some(approval) = &ctx.accounts.approval {
+        require!(old.size <= approval.max_size && margin >= approval.min_margin, PerpError::TransferApprovalMismatch);
+    }
+    TopUpSettings::close_if_open(&ctx.accounts.top_up, &ctx.accounts.owner.to_account_info())?;
+
+    let from_user = &mut ctx.accounts.from_user;
+    from_user.locked_collateral = from_user.locked_collateral.checked_sub(margin).ok_or(PerpError::Overflow)?;
+    from_user.total_collateral = from_user.total_collateral.checked_sub(margin).ok_or(PerpError::Overflow)?;
+    from_user.position_count = from_user.position_count.saturating_sub(1);
+
+    let to_user = &mut ctx.accounts.to_user;
+    to_user.init_if_empty(recipient_key, to_sub_id, ctx.bumps.to_user);
+    to_user.total_collateral = to_user.total_collateral.checked_add(margin).ok_or(PerpError::Overflow)?;
+    to_user.locked_collateral = to_user.locked_collateral.checked_add(margin).ok_or(PerpError::Overflow)?;
+    to_user.position_count = to_user.position_count.checked_add(1).ok_or(PerpError::Overflow)?;
+
+    let pos = &mut ctx.accounts.new_position;
+    pos.owner = recipient_key;
+    pos.sub_id = to_sub_id;
+    pos.symbol = old.symbol.clone();
+    pos.side = old.side;
+    pos.size = old.size;
+    pos.entry_price = old.entry_price;
+    pos.margin = margin;
+    pos.leverage = old.leverage;
+    pos.unrealized_pnl = old.unrealized_pnl;
+    pos.realized_pnl = old.realized_pnl;
+    pos.funding_accrued = old.funding_accrued;
+    pos.funding_index = old.funding_index;
+    pos.liquidation_price = old.liquidation_price;
+    pos.last_update = Clock::get()?.unix_timestamp;
+    pos.bump = ctx.bumps.new_position;
+
+    emit!(PositionTransferred {
+        from_owner: owner_key,
+        from_sub_id,
+        to_owner: recipient_key,
+        to_sub_id,
+        symbol: pos.symbol.clone(),
+        size: pos.size,
+        margin,
+        position: pos.key(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(to_sub_id: u16)]
+pub struct TransferPosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// CHECK: receiving wallet; must sign unless `approval` is passed
+    #[account(mut)]
+    pub recipient: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"user", owner.key().as_ref(), &from_user.sub_id.to_le_bytes()],
+        bump = from_user.bump,
+        constraint = from_user.owner == owner.key()
+    )]
+    pub from_user: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"position", owner.key().as_ref(), &position.sub_id.to_le_bytes(), position.symbol.as_bytes()],
+        bump = position.bump,
+        constraint = position.owner == owner.key() && position.sub_id == from_user.sub_id
+    )]
+    pub position: Account<'info, Position>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        seeds = [b"user", recipient.key().as_ref(), &to_sub_id.to_le_bytes()],
+        bump,
+        space = UserAccount::SPACE
+    )]
+    pub to_user: Account<'info, UserAccount>,
+
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"position", recipient.key().as_ref(), &to_sub_id.to_le_bytes(), position.symbol.as_bytes()],
+        bump,
+        space = Position::space(MAX_SYMBOL_LEN)
+    )]
+    pub new_position: Account<'info, Position>,
+
+    #[account(
+        mut,
+        close = recipient,
+        seeds = [
+            b"transfer_approval",
+            recipient.key().as_ref(),
+            &to_sub_id.to_le_bytes(),
+            owner.key().as_ref(),
+            &position.sub_id.to_le_bytes(),
+            position.symbol.as_bytes(),
+        ],
+        bump = approval.bump
+    )]
+    pub approval: Option<Account<'info, TransferApproval>>,
+
+    /// CHECK: the position's TopUpSettings address; closed to the owner if it exists
+    #[account(mut, seeds = [b"top_up", position.key().as_ref()], bump)]
+    pub top_up: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
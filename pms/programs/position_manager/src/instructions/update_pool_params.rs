@@ -0,0 +1,22 @@
+use anchor_lang::prelude::*;
+
+use crate::state::accounts::*;
+
+pub(crate) fn handler(ctx: Context<UpdatePoolParams>, params: PoolParams) -> Result<()> {
+    params.validate()?;
+    ctx.accounts.pool.params = params;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdatePoolParams<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", pool.quote_mint.as_ref()],
+        bump = pool.bump,
+        has_one = authority
+    )]
+    pub pool: Account<'info, LiquidityPool>,
+}
@@ -0,0 +1,74 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+
+use crate::errors::PerpError;
+use crate::events::CollateralWithdrawn;
+use crate::state::accounts::*;
+
+pub(crate) fn handler(ctx: Context<WithdrawCollateral>, amount: u64) -> Result<()> {
+    require!(amount > 0, PerpError::InvalidAmount);
+    require!(amount <= ctx.accounts.user.free_collateral(), PerpError::InsufficientFreeCollateral);
+
+    let signer_seeds: &[&[u8]] = &[b"vault_authority", &[ctx.accounts.vault_authority.bump]];
+    token::transfer(
+        ctx.accounts.transfer_from_vault_ctx().with_signer(&[signer_seeds]),
+        amount,
+    )?;
+
+    let user = &mut ctx.accounts.user;
+    user.total_collateral = user.total_collateral.checked_sub(amount).ok_or(PerpError::Overflow)?;
+
+    emit!(CollateralWithdrawn {
+        owner: user.owner,
+        sub_id: user.sub_id,
+        amount,
+        total_collateral: user.total_collateral,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct WithdrawCollateral<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"user", owner.key().as_ref(), &user.sub_id.to_le_bytes()],
+        bump = user.bump,
+        constraint = user.owner == owner.key()
+    )]
+    pub user: Account<'info, UserAccount>,
+
+    pub quote_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub user_quote_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", quote_mint.key().as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_authority"],
+        bump = vault_authority.bump
+    )]
+    pub vault_authority: Account<'info, VaultAuthority>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> WithdrawCollateral<'info> {
+    pub fn transfer_from_vault_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.user_quote_ata.to_account_info(),
+            authority: self.vault_authority.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+}
@@ -0,0 +1,134 @@
+use anchor_lang::prelude::*;
+
+pub mod constants;
+pub mod errors;
+pub mod events;
+pub mod instructions;
+pub mod math;
+pub mod state;
+pub mod tiers;
+
+use instructions::*;
+
+pub use errors::PerpError;
+pub use state::*;
+
+declare_id!("PosMgr1111111111111111111111111111111111111");
+
+#[program]
+pub mod position_manager {
+    use super::*;
+
+    pub fn open_position(
+        ctx: Context<OpenPosition>,
+        sub_id: u16,
+        symbol: String,
+        side: Side,
+        size: u64,
+        leverage: u16,
+        entry_price: u64,
+    ) -> Result<()> {
+        instructions::open_positions::handler(ctx, sub_id, symbol, side, size, leverage, entry_price)
+    }
+
+    pub fn modify_position(ctx: Context<ModifyPosition>, action: ModifyKind) -> Result<()> {
+        instructions::modify_positions::handler(ctx, action)
+    }
+
+    pub fn close_position(ctx: Context<ClosePosition>, exit_price: u64) -> Result<()> {
+        instructions::close_positions::handler(ctx, exit_price)
+    }
+
+    // Positions to close or reduce go in remaining_accounts as (position, pool_market,
+    // top_up) triples, one triple per leg.
+    pub fn close_all_positions<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CloseAllPositions<'info>>,
+        legs: Vec<BatchLeg>,
+        reduce_bps: u16,
+    ) -> Result<()> {
+        instructions::close_all_positions::handler(ctx, legs, reduce_bps)
+    }
+
+    pub fn deposit_collateral(ctx: Context<DepositCollateral>, sub_id: u16, amount: u64) -> Result<()> {
+        instructions::deposit_collateral::handler(ctx, sub_id, amount)
+    }
+
+    pub fn withdraw_collateral(ctx: Context<WithdrawCollateral>, amount: u64) -> Result<()> {
+        instructions::withdraw_collateral::handler(ctx, amount)
+    }
+
+    pub fn transfer_collateral(ctx: Context<TransferCollateral>, from_sub_id: u16, to_sub_id: u16, amount: u64) -> Result<()> {
+        instructions::transfer_collateral::handler(ctx, from_sub_id, to_sub_id, amount)
+    }
+
+    pub fn set_margin_mode(ctx: Context<SetMarginMode>, margin_mode: MarginMode) -> Result<()> {
+        instructions::set_margin_mode::handler(ctx, margin_mode)
+    }
+
+    pub fn initialize_pool(ctx: Context<InitializePool>, params: PoolParams) -> Result<()> {
+        instructions::initialize_pool::handler(ctx, params)
+    }
+
+    pub fn update_pool_params(ctx: Context<UpdatePoolParams>, params: PoolParams) -> Result<()> {
+        instructions::update_pool_params::handler(ctx, params)
+    }
+
+    pub fn initialize_pool_market(ctx: Context<InitializePoolMarket>, symbol: String) -> Result<()> {
+        instructions::initialize_pool_market::handler(ctx, symbol)
+    }
+
+    pub fn set_mark_price(ctx: Context<SetMarkPrice>, price: u64) -> Result<()> {
+        instructions::set_mark_price::handler(ctx, price)
+    }
+
+    // Every PoolMarket of the pool goes in remaining_accounts; shares are priced
+    // against liquidity less the traders' unrealized PnL at the markets' marks.
+    pub fn add_liquidity<'info>(ctx: Context<'_, '_, 'info, 'info, AddLiquidity<'info>>, amount: u64) -> Result<()> {
+        instructions::add_liquidity::handler(ctx, amount)
+    }
+
+    pub fn remove_liquidity<'info>(ctx: Context<'_, '_, 'info, 'info, RemoveLiquidity<'info>>, shares: u64) -> Result<()> {
+        instructions::remove_liquidity::handler(ctx, shares)
+    }
+
+    // Recipient must co-sign, or pass the approval created by approve_position_transfer.
+    pub fn transfer_position(ctx: Context<TransferPosition>, to_sub_id: u16) -> Result<()> {
+        instructions::transfer_position::handler(ctx, to_sub_id)
+    }
+
+    pub fn approve_position_transfer(
+        ctx: Context<ApprovePositionTransfer>,
+        from_owner: Pubkey,
+        from_sub_id: u16,
+        to_sub_id: u16,
+        symbol: String,
+        max_size: u64,
+        min_margin: u64,
+    ) -> Result<()> {
+        instructions::approve_position_transfer::handler(ctx, from_owner, from_sub_id, to_sub_id, symbol, max_size, min_margin)
+    }
+
+    pub fn revoke_position_transfer(ctx: Context<RevokePositionTransfer>) -> Result<()> {
+        instructions::revoke_position_transfer::handler(ctx)
+    }
+
+    // target_mr is scaled by 1e6; max_total is the lifetime top-up budget in quote units.
+    pub fn set_auto_top_up(ctx: Context<SetAutoTopUp>, target_mr: u64, max_total: u64) -> Result<()> {
+        instructions::set_auto_top_up::handler(ctx, target_mr, max_total)
+    }
+
+    pub fn disable_auto_top_up(ctx: Context<DisableAutoTopUp>) -> Result<()> {
+        instructions::disable_auto_top_up::handler(ctx)
+    }
+
+    // Permissionless keeper call; fails with TopUpNotNeeded when MR is above target.
+    pub fn auto_top_up(ctx: Context<AutoTopUp>) -> Result<()> {
+        instructions::auto_top_up::handler(ctx)
+    }
+
+    // Read-only: the result is written with set_return_data, so CPI callers
+    // read it back through `cpi::get_position_health(..)?.get()`.
+    pub fn get_position_health(ctx: Context<GetPositionHealth>, mark_price: u64) -> Result<PositionHealth> {
+        instructions::position_health::handler(ctx, mark_price)
+    }
+}
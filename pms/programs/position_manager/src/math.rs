@@ -0,0 +1,81 @@
+use crate::errors::PerpError;
+use crate::state::accounts::Side;
+use anchor_lang::require;
+
+// Formulas live in perp-math so the backend and engine compute identical values;
+// these wrappers only translate Side and errors.
+fn checked<T>(r: perp_math::Result<T>) -> Result<T, anchor_lang::prelude::Error> {
+    r.map_err(|e| PerpError::from(e).into())
+}
+
+pub fn calc_notional(size: u64, price: u64) -> Result<u128, anchor_lang::prelude::Error> {
+    checked(perp_math::notional(size, price))
+}
+
+pub fn calc_initial_margin(notional: u128, leverage: u16) -> Result<u128, anchor_lang::prelude::Error> {
+    checked(perp_math::initial_margin(notional, leverage))
+}
+
+pub fn calc_maintenance_margin(notional: u128, mmr_scaled: u64) -> Result<u128, anchor_lang::prelude::Error> {
+    checked(perp_math::maintenance_margin(notional, mmr_scaled))
+}
+
+pub fn covers_maintenance(margin: u128, notional: u128, mmr_scaled: u64) -> Result<bool, anchor_lang::prelude::Error> {
+    checked(perp_math::covers_maintenance(margin, notional, mmr_scaled))
+}
+
+pub fn is_liquidatable(equity: i128, notional: u128, mmr_scaled: u64) -> Result<bool, anchor_lang::prelude::Error> {
+    checked(perp_math::is_liquidatable(equity, notional, mmr_scaled))
+}
+
+pub fn calc_unrealized_pnl(side: Side, size: u64, entry: u64, price: u64) -> Result<i128, anchor_lang::prelude::Error> {
+    checked(perp_math::unrealized_pnl(side.into(), size, entry, price))
+}
+
+pub fn calc_realized_pnl_partial(side: Side, reduce_size: u64, entry: u64, price: u64) -> Result<i128, anchor_lang::prelude::Error> {
+    calc_unrealized_pnl(side, reduce_size, entry, price)
+}
+
+pub fn calc_realized_pnl_full(side: Side, size: u64, entry: u64, price: u64) -> Result<i128, anchor_lang::prelude::Error> {
+    calc_realized_pnl_partial(side, size, entry, price)
+}
+
+pub fn calc_liquidation_price(side: Side, size: u64, entry: u64, margin: u64, mmr_scaled: u64) -> Result<u64, anchor_lang::prelude::Error> {
+    checked(perp_math::liquidation_price(side.into(), size, entry, margin, mmr_scaled))
+}
+
+pub fn calc_bankruptcy_price(side: Side, size: u64, entry: u64, margin: u64) -> Result<u64, anchor_lang::prelude::Error> {
+    checked(perp_math::bankruptcy_price(side.into(), size, entry, margin))
+}
+pub fn calc_average_entry(size: u64, entry: u64, add_size: u64, add_notional: u128) -> Result<u64, anchor_lang::prelude::Error> {
+    checked(perp_math::average_entry(size, entry, add_size, add_notional))
+}
+
+// Safe math helpers
+pub fn add_u128(a: u128, b: u128) -> Result<u128, anchor_lang::prelude::Error> { a.checked_add(b).ok_or(PerpError::Overflow.into()) }
+pub fn sub_u128(a: u128, b: u128) -> Result<u128, anchor_lang::prelude::Error> { a.checked_sub(b).ok_or(PerpError::Overflow.into()) }
+pub fn mul_u128(a: u128, b: u128) -> Result<u128, anchor_lang::prelude::Error> { a.checked_mul(b).ok_or(PerpError::Overflow.into()) }
+pub fn mul_u128_u64(a: u128, b: u64) -> Result<u128, anchor_lang::prelude::Error> { a.checked_mul(b as u128).ok_or(PerpError::Overflow.into()) }
+pub fn mul_i128_i128(a: i128, b: i128) -> Result<i128, anchor_lang::prelude::Error> { a.checked_mul(b).ok_or(PerpError::Overflow.into()) }
+pub fn div_u128(a: u128, b: u128) -> Result<u128, anchor_lang::prelude::Error> {
+    require!(b != 0, PerpError::DivisionByZero);
+    Ok(a / b)
+}
+pub fn u128_to_u64(v: u128) -> Result<u64, anchor_lang::prelude::Error> { u64::try_from(v).map_err(|_| PerpError::Overflow.into()) }
+pub fn i128_to_i64(v: i128) -> Result<i64, anchor_lang::prelude::Error> { i64::try_from(v).map_err(|_| PerpError::Overflow.into()) }
+pub fn i128_to_u64(v: i128) -> Result<u64, anchor_lang::prelude::Error> {
+    use crate::errors::PerpError;
+    if v < 0 { return Err(PerpError::Underflow.into()); }
+    u64::try_from(v).map_err(|_| PerpError::Overflow.into())
+}
+// oracle ± spread, rounded against the taker
+pub fn calc_execution_price(taker: Side, oracle_price: u64, spread_bps: u16) -> Result<u64, anchor_lang::prelude::Error> {
+    use crate::constants::BPS_DENOM;
+
+    let denom = BPS_DENOM as u128;
+    let price = match taker {
+        Side::Long => div_u128(add_u128(mul_u128(oracle_price as u128, denom + spread_bps as u128)?, denom - 1)?, denom)?,
+        Side::Short => div_u128(mul_u128(oracle_price as u128, sub_u128(denom, spread_bps as u128)?)?, denom)?,
+    };
+    u128_to_u64(price)
+}
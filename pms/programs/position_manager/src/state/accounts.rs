@@ -0,0 +1,444 @@
+use anchor_lang::prelude::*;
+use crate::constants::{BPS_DENOM, FUNDING_PERIOD_SECS, MAX_MARK_AGE_SECS, MAX_PRICE_DEVIATION_BPS, RATE_SCALE};
+use crate::errors::PerpError;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Side {
+    Long,
+    Short,
+}
+
+impl Side {
+    pub fn opposite(self) -> Side {
+        match self {
+            Side::Long => Side::Short,
+            Side::Short => Side::Long,
+        }
+    }
+}
+
+impl From<Side> for perp_math::Side {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Long => perp_math::Side::Long,
+            Side::Short => perp_math::Side::Short,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum MarginMode {
+    Isolated, // payouts leave the sub-account on close
+    Cross,    // payouts stay as free collateral and cover shortfalls
+}
+
+#[account]
+pub struct Position {
+    pub owner: Pubkey,
+    pub sub_id: u16,         // owning sub-account
+    pub symbol: String,      // <= 16 chars
+    pub side: Side,          // Long or Short
+    pub size: u64,           // base units
+    pub entry_price: u64,    // quote per base
+    pub margin: u64,         // locked collateral (quote)
+    pub leverage: u16,       // 1..=1000
+    pub unrealized_pnl: i64, // snapshot
+    pub realized_pnl: i64,   // quote
+    pub funding_accrued: i64,// quote received (negative: paid), settled on close
+    pub liquidation_price: u64,
+    pub last_update: i64,
+    pub bump: u8,
+    pub funding_index: i128, // PoolMarket::funding_index funding_accrued is booked up to
+}
+
+impl Position {
+    pub fn space(max_symbol: usize) -> usize {
+        8  // discriminator
+        + 32 // owner
+        + 2  // sub_id
+        + 4 + max_symbol // symbol string
+        + 1  // side
+        + 8  // size
+        + 8  // entry_price
+        + 8  // margin
+        + 2  // leverage
+        + 8  // unrealized_pnl
+        + 8  // realized_pnl
+        + 8  // funding_accrued
+        + 8  // liquidation_price
+        + 8  // last_update
+        + 1  // bump
+        + 16 // funding_index
+        + 32 // extra padding room
+    }
+
+    // Books the funding the position paid or received since its last accrual; `market`
+    // must have accrued up to now first.
+    pub fn accrue_funding(&mut self, market: &PoolMarket) -> Result<()> {
+        let delta = market.funding_index.checked_sub(self.funding_index).ok_or(PerpError::Overflow)?;
+        let paid = (self.size as i128).checked_mul(delta).ok_or(PerpError::Overflow)?;
+        let received = match self.side {
+            Side::Long => -paid,
+            Side::Short => paid,
+        };
+        // rounded down, so the trader never receives more than the index implies
+        let received = i64::try_from(received.div_euclid(RATE_SCALE as i128)).map_err(|_| PerpError::Overflow)?;
+        self.funding_accrued = self.funding_accrued.checked_add(received).ok_or(PerpError::Overflow)?;
+        self.funding_index = market.funding_index;
+        Ok(())
+    }
+}
+
+#[account]
+pub struct UserAccount {
+    pub owner: Pubkey,
+    pub sub_id: u16,
+    pub margin_mode: MarginMode,
+    pub total_collateral: u64,
+    pub locked_collateral: u64,
+    pub total_pnl: i64,
+    pub position_count: u32,
+    pub bump: u8,
+}
+
+impl UserAccount {
+    pub const SPACE: usize = 8  // disc
+        + 32 + 2 + 1 + 8 + 8 + 8 + 4 + 1
+        + 16; // padding
+
+    // Accounts created through init_if_needed start zeroed.
+    pub fn init_if_empty(&mut self, owner: Pubkey, sub_id: u16, bump: u8) {
+        if self.owner == Pubkey::default() {
+            self.owner = owner;
+            self.sub_id = sub_id;
+            self.margin_mode = MarginMode::Isolated;
+            self.total_collateral = 0;
+            self.locked_collateral = 0;
+            self.total_pnl = 0;
+            self.position_count = 0;
+            self.bump = bump;
+        }
+    }
+
+    pub fn free_collateral(&self) -> u64 {
+        self.total_collateral.saturating_sub(self.locked_collateral)
+    }
+
+    // Books a fully closed position: unlocks its margin and records the PnL.
+    // Returns the amount to transfer out of the vault; in cross mode the proceeds
+    // stay as free collateral and a loss past the margin is covered from it.
+    pub fn settle_close(&mut self, margin: u64, net_pnl: i64) -> Result<u64> {
+        let equity = margin as i128 + net_pnl as i128;
+        let payout = u64::try_from(equity.max(0)).map_err(|_| PerpError::Overflow)?;
+
+        self.locked_collateral = self.locked_collateral.checked_sub(margin).ok_or(PerpError::Overflow)?;
+        self.total_collateral = self.total_collateral.checked_sub(margin).ok_or(PerpError::Overflow)?;
+        self.total_pnl = self.total_pnl.checked_add(net_pnl).ok_or(PerpError::Overflow)?;
+        self.position_count = self.position_count.saturating_sub(1);
+
+        if self.margin_mode == MarginMode::Isolated {
+            return Ok(payout);
+        }
+        if equity >= 0 {
+            self.total_collateral = self.total_collateral.checked_add(payout).ok_or(PerpError::Overflow)?;
+        } else {
+            let shortfall = u64::try_from(-equity).map_err(|_| PerpError::Overflow)?.min(self.free_collateral());
+            self.total_collateral = self.total_collateral.checked_sub(shortfall).ok_or(PerpError::Overflow)?;
+        }
+        Ok(0)
+    }
+}
+
+#[account]
+pub struct VaultAuthority {
+    pub bump: u8,
+}
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct PoolParams {
+    pub base_spread_bps: u16,      // charged on every fill
+    pub skew_spread_bps: u16,      // extra spread when a fill leaves skew == liquidity
+    pub max_spread_bps: u16,       // cap on base + skew spread
+    pub max_utilization_bps: u32,  // open interest cap, bps of liquidity (may exceed 10000)
+    pub max_skew_bps: u32,         // per-market |long - short| cap, bps of liquidity
+    pub funding_rate_bps: u16,     // hourly funding at one-sided open interest, bps of the mark
+}
+
+impl PoolParams {
+    pub fn validate(&self) -> Result<()> {
+        require!(self.max_spread_bps <= crate::constants::MAX_SPREAD_BPS, PerpError::InvalidPoolParams);
+        require!(self.base_spread_bps <= self.max_spread_bps, PerpError::InvalidPoolParams);
+        require!(self.max_utilization_bps > 0 && self.max_skew_bps > 0, PerpError::InvalidPoolParams);
+        require!(self.funding_rate_bps <= crate::constants::MAX_FUNDING_RATE_BPS, PerpError::InvalidPoolParams);
+        Ok(())
+    }
+}
+
+// Counterparty to every trade on one quote mint. LP funds sit in the same vault as
+// trader margin; `liquidity` is the LP-owned part of the vault balance.
+#[account]
+pub struct LiquidityPool {
+    pub authority: Pubkey,
+    pub quote_mint: Pubkey,
+    pub lp_mint: Pubkey,
+    pub liquidity: u64,     // LP deposits + trader losses − trader profits
+    pub open_interest: u64, // entry notional of all open positions, both sides
+    pub params: PoolParams,
+    pub bump: u8,
+    pub market_count: u16,  // PoolMarkets created for this pool
+}
+
+impl LiquidityPool {
+    pub const SPACE: usize = 8 // disc
+        + 32 + 32 + 32 + 8 + 8
+        + 2 + 2 + 2 + 4 + 4 + 2
+        + 1 + 2
+        + 32; // padding
+
+    // What LP shares are priced against: liquidity less the traders' unrealized PnL at
+    // each market's mark. `markets` must be every PoolMarket of the pool (pool_key);
+    // a market carrying open interest needs a fresh mark.
+    pub fn share_value<'info>(&self, pool_key: &Pubkey, markets: &'info [AccountInfo<'info>], now: i64) -> Result<u64> {
+        require!(markets.len() == self.market_count as usize, PerpError::PoolMarketMismatch);
+        let mut value = self.liquidity as i128;
+        for (i, info) in markets.iter().enumerate() {
+            require!(markets[..i].iter().all(|m| m.key != info.key), PerpError::PoolMarketMismatch);
+            let market: Account<'info, PoolMarket> = Account::try_from(info)?;
+            require_keys_eq!(market.pool, *pool_key, PerpError::PoolMarketMismatch);
+            if market.long_size == 0 && market.short_size == 0 {
+                continue;
+            }
+            value = value.checked_sub(market.trader_pnl(market.fresh_mark(now)?)?).ok_or(PerpError::Overflow)?;
+        }
+        // traders up by more than the pool holds: shares are worth nothing until they close
+        Ok(u64::try_from(value.max(0)).map_err(|_| PerpError::Overflow)?)
+    }
+
+    // Spread for a fill that moves the market's net skew from `skew` to `skew_after`
+    // (long − short notional). Fills that reduce |skew| only pay the base spread.
+    pub fn spread_bps(&self, skew: i128, skew_after: i128) -> Result<u16> {
+        let p = &self.params;
+        if skew_after.unsigned_abs() <= skew.unsigned_abs() {
+            return Ok(p.base_spread_bps);
+        }
+        if self.liquidity == 0 {
+            return Ok(p.max_spread_bps);
+        }
+        let impact = skew_after.unsigned_abs()
+            .checked_mul(p.skew_spread_bps as u128).ok_or(PerpError::Overflow)?
+            / self.liquidity as u128;
+        let spread = (p.base_spread_bps as u128).saturating_add(impact).min(p.max_spread_bps as u128);
+        Ok(spread as u16)
+    }
+
+    // Price the pool fills a taker at: buys (`Long`) above the oracle, sells below.
+    pub fn execution_price(&self, market: &PoolMarket, taker: Side, notional: u64, oracle_price: u64) -> Result<(u64, u16)> {
+        let skew = market.skew();
+        let skew_after = match taker {
+            Side::Long => skew + notional as i128,
+            Side::Short => skew - notional as i128,
+        };
+        let spread = self.spread_bps(skew, skew_after)?;
+        let price = crate::math::calc_execution_price(taker, oracle_price, spread)?;
+        Ok((price, spread))
+    }
+
+    // Checked after any fill that adds exposure; reducing fills are always allowed.
+    pub fn check_caps(&self, market: &PoolMarket) -> Result<()> {
+        let liq = self.liquidity as u128;
+        let oi = (self.open_interest as u128).checked_mul(BPS_DENOM as u128).ok_or(PerpError::Overflow)?;
+        require!(oi <= liq * self.params.max_utilization_bps as u128, PerpError::PoolUtilizationExceeded);
+        let skew = market.skew().unsigned_abs().checked_mul(BPS_DENOM as u128).ok_or(PerpError::Overflow)?;
+        require!(skew <= liq * self.params.max_skew_bps as u128, PerpError::PoolSkewExceeded);
+        Ok(())
+    }
+
+    // `size` in base units, `notional` at the fill price
+    pub fn add_open_interest(&mut self, market: &mut PoolMarket, side: Side, size: u64, notional: u64) -> Result<()> {
+        self.open_interest = self.open_interest.checked_add(notional).ok_or(PerpError::Overflow)?;
+        let (base, oi) = match side {
+            Side::Long => (&mut market.long_size, &mut market.long_open_interest),
+            Side::Short => (&mut market.short_size, &mut market.short_open_interest),
+        };
+        *base = base.checked_add(size).ok_or(PerpError::Overflow)?;
+        *oi = oi.checked_add(notional).ok_or(PerpError::Overflow)?;
+        Ok(())
+    }
+
+    // `notional` at the position's entry price. Saturating: entry-price averaging can
+    // leave a few units of rounding dust.
+    pub fn remove_open_interest(&mut self, market: &mut PoolMarket, side: Side, size: u64, notional: u64) {
+        self.open_interest = self.open_interest.saturating_sub(notional);
+        let (base, oi) = match side {
+            Side::Long => (&mut market.long_size, &mut market.long_open_interest),
+            Side::Short => (&mut market.short_size, &mut market.short_open_interest),
+        };
+        *base = base.saturating_sub(size);
+        *oi = oi.saturating_sub(notional);
+    }
+
+    // Trader profit is paid out of pool liquidity, so it can never exceed it.
+    pub fn cap_trader_pnl(&self, net_pnl: i64) -> i64 {
+        net_pnl.min(self.liquidity.min(i64::MAX as u64) as i64)
+    }
+
+    // Books the pool side of a settled close from the user's collateral before and
+    // after `UserAccount::settle_close` and the payout it returned: whatever the
+    // trader lost stays in the vault as liquidity, whatever they won leaves it.
+    pub fn settle_trader_pnl(&mut self, collateral_before: u64, collateral_after: u64, payout: u64) -> Result<i64> {
+        let pool_delta = collateral_before as i128 - collateral_after as i128 - payout as i128;
+        let liquidity = (self.liquidity as i128).checked_add(pool_delta).ok_or(PerpError::Overflow)?;
+        self.liquidity = u64::try_from(liquidity).map_err(|_| PerpError::InsufficientPoolLiquidity)?;
+        i64::try_from(pool_delta).map_err(|_| PerpError::Overflow.into())
+    }
+
+    // Settles the PnL a partial reduce realized right away: a profit (capped at the
+    // liquidity) leaves the pool as free collateral of the sub-account, a loss comes out
+    // of the position's margin (at most all of it) and stays in the vault as liquidity.
+    // Returns the amount settled from the trader's side.
+    pub fn settle_partial_pnl(&mut self, user: &mut UserAccount, margin: &mut u64, pnl: i64) -> Result<i64> {
+        if pnl >= 0 {
+            let profit = self.cap_trader_pnl(pnl) as u64;
+            self.liquidity = self.liquidity.checked_sub(profit).ok_or(PerpError::Overflow)?;
+            user.total_collateral = user.total_collateral.checked_add(profit).ok_or(PerpError::Overflow)?;
+            Ok(profit as i64)
+        } else {
+            let loss = pnl.unsigned_abs().min(*margin);
+            *margin -= loss;
+            user.locked_collateral = user.locked_collateral.checked_sub(loss).ok_or(PerpError::Overflow)?;
+            user.total_collateral = user.total_collateral.checked_sub(loss).ok_or(PerpError::Overflow)?;
+            self.liquidity = self.liquidity.checked_add(loss).ok_or(PerpError::Overflow)?;
+            Ok(-(loss as i64))
+        }
+    }
+}
+
+// Per-symbol open interest the pool is carrying; drives the skew spread. The mark is
+// pushed by the pool authority (set_mark_price) and prices what the pool owes traders.
+#[account]
+pub struct PoolMarket {
+    pub pool: Pubkey,
+    pub symbol: String,
+    pub long_open_interest: u64,  // entry notional
+    pub short_open_interest: u64, // entry notional
+    pub bump: u8,
+    pub long_size: u64,           // base units
+    pub short_size: u64,          // base units
+    pub mark_price: u64,
+    pub mark_updated_at: i64,     // 0 = never marked
+    pub funding_index: i128,      // funding a long has paid per base unit, quote × RATE_SCALE
+    pub funding_updated_at: i64,
+}
+
+impl PoolMarket {
+    pub fn space(max_symbol: usize) -> usize {
+        8 + 32 + 4 + max_symbol + 8 + 8 + 1 + 8 + 8 + 8 + 8 + 16 + 8 + 16
+    }
+
+    pub fn fresh_mark(&self, now: i64) -> Result<u64> {
+        require!(
+            self.mark_updated_at > 0 && now.saturating_sub(self.mark_updated_at) <= MAX_MARK_AGE_SECS,
+            PerpError::StaleMarkPrice
+        );
+        Ok(self.mark_price)
+    }
+
+    // What trades fill against: the fresh mark, as long as the caller's expected price
+    // is within MAX_PRICE_DEVIATION_BPS of it.
+    pub fn checked_mark(&self, expected_price: u64, now: i64) -> Result<u64> {
+        let mark = self.fresh_mark(now)?;
+        let band = mark as u128 * MAX_PRICE_DEVIATION_BPS as u128 / BPS_DENOM as u128;
+        require!((expected_price as i128 - mark as i128).unsigned_abs() <= band, PerpError::PriceOutsideMarkBand);
+        Ok(mark)
+    }
+
+    // Accrues funding up to `now` at `mark`: the heavier side pays funding_rate_bps per
+    // FUNDING_PERIOD_SECS, scaled by skew / open interest in base units. The pool holds
+    // the skew's opposite, so it collects the difference. Call before sizes change.
+    pub fn accrue_funding(&mut self, funding_rate_bps: u16, mark: u64, now: i64) -> Result<()> {
+        let elapsed = now.saturating_sub(self.funding_updated_at).max(0) as i128;
+        self.funding_updated_at = now;
+        let total = self.long_size as i128 + self.short_size as i128;
+        if total == 0 || elapsed == 0 {
+            return Ok(());
+        }
+        // hourly rate × RATE_SCALE, signed by skew: at most funding_rate_bps × 100
+        let rate = (funding_rate_bps as i128 * RATE_SCALE as i128)
+            .checked_mul(self.long_size as i128 - self.short_size as i128)
+            .ok_or(PerpError::Overflow)?
+            / (total * BPS_DENOM as i128);
+        let delta = (mark as i128)
+            .checked_mul(rate)
+            .and_then(|v| v.checked_mul(elapsed))
+            .ok_or(PerpError::Overflow)?
+            / FUNDING_PERIOD_SECS as i128;
+        self.funding_index = self.funding_index.checked_add(delta).ok_or(PerpError::Overflow)?;
+        Ok(())
+    }
+
+    // Traders' unrealized PnL across both sides at `mark`; the pool holds the opposite.
+    pub fn trader_pnl(&self, mark: u64) -> Result<i128> {
+        let long_value = (self.long_size as i128).checked_mul(mark as i128).ok_or(PerpError::Overflow)?;
+        let short_value = (self.short_size as i128).checked_mul(mark as i128).ok_or(PerpError::Overflow)?;
+        Ok(long_value - self.long_open_interest as i128 + self.short_open_interest as i128 - short_value)
+    }
+
+    // Traders' net position; the pool holds the opposite.
+    pub fn skew(&self) -> i128 {
+        self.long_open_interest as i128 - self.short_open_interest as i128
+    }
+}
+
+// Standing consent from a recipient to take over one position, so the sender can
+// transfer it without a second signature. Consumed by transfer_position.
+#[account]
+pub struct TransferApproval {
+    pub recipient: Pubkey,
+    pub to_sub_id: u16,
+    pub from_owner: Pubkey,
+    pub from_sub_id: u16,
+    pub symbol: String,
+    pub max_size: u64,   // the recipient takes on at most this much
+    pub min_margin: u64, // with at least this much margin
+    pub bump: u8,
+}
+
+impl TransferApproval {
+    pub fn space(max_symbol: usize) -> usize {
+        8 + 32 + 2 + 32 + 2 + 4 + max_symbol + 8 + 8 + 1
+    }
+}
+
+// Per-position opt-in for auto_top_up (PDA: ["top_up", position]): keep the margin
+// ratio at or above target_mr by moving up to max_total of free collateral in.
+#[account]
+pub struct TopUpSettings {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub target_mr: u64, // scaled by 1e6 (RATE_SCALE)
+    pub max_total: u64, // lifetime budget, quote
+    pub used: u64,      // already moved into the position
+    pub bump: u8,
+}
+
+impl TopUpSettings {
+    pub const SPACE: usize = 8 + 32 + 32 + 8 + 8 + 8 + 1;
+
+    pub fn remaining(&self) -> u64 {
+        self.max_total.saturating_sub(self.used)
+    }
+
+    // Closes the settings at `info` into `receiver` if they exist. Instructions that end
+    // a position take its ["top_up", position] address unconditionally and call this,
+    // so settings never outlive the position they were made for.
+    pub fn close_if_open<'info>(info: &AccountInfo<'info>, receiver: &AccountInfo<'info>) -> Result<()> {
+        if info.owner != &crate::ID || info.lamports() == 0 {
+            return Ok(());
+        }
+        let lamports = info.lamports();
+        **receiver.try_borrow_mut_lamports()? = receiver.lamports().checked_add(lamports).ok_or(PerpError::Overflow)?;
+        **info.try_borrow_mut_lamports()? = 0;
+        info.assign(&anchor_lang::system_program::ID);
+        info.realloc(0, false)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,8 @@
+use crate::errors::PerpError;
+
+// The tier table lives in perp-math, so the backend checks opens against the same one.
+pub use perp_math::{LeverageTier, LEVERAGE_TIERS};
+
+pub fn get_leverage_tier(leverage: u16, pos_size_quote: u64) -> Result<LeverageTier, anchor_lang::prelude::Error> {
+    perp_math::leverage_tier(leverage, pos_size_quote).ok_or_else(|| PerpError::LeverageExceeded.into())
+}
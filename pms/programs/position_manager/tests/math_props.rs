@@ -0,0 +1,161 @@
+// Property tests for the on-chain fixed-point math and tier lookup. Inputs are drawn
+// wide enough to hit overflow paths; those must come back as errors, never panics.
+
+use proptest::prelude::*;
+
+use position_manager::constants::{MAX_LEVERAGE, MIN_LEVERAGE, RATE_SCALE};
+use position_manager::math::*;
+use position_manager::tiers::{get_leverage_tier, LeverageTier, LEVERAGE_TIERS};
+use position_manager::Side;
+
+fn side() -> impl Strategy<Value = Side> {
+    prop_oneof![Just(Side::Long), Just(Side::Short)]
+}
+
+// Mixes small values in so notionals land in the high-leverage tiers, not only tier 0.
+fn amount(max: u64) -> impl Strategy<Value = u64> {
+    prop_oneof![1u64..=1_000, 1u64..=max]
+}
+
+fn leverage() -> impl Strategy<Value = u16> {
+    MIN_LEVERAGE..=MAX_LEVERAGE
+}
+
+// Mirrors open_position: margin = notional / leverage on the tier for that notional,
+// and only positions whose IM exceeds MM are accepted.
+fn open(size: u64, entry: u64, leverage: u16) -> Option<(u64, LeverageTier)> {
+    let notional = u128_to_u64(mul_u128(size as u128, entry as u128).ok()?).ok()?;
+    let tier = get_leverage_tier(leverage, notional).ok()?;
+    let margin = notional / leverage as u64;
+    let accepted = margin as u128 * RATE_SCALE > notional as u128 * tier.maintenance_margin_rate as u128;
+    accepted.then_some((margin, tier))
+}
+
+proptest! {
+    #[test]
+    fn checked_helpers_match_std(a in any::<u128>(), b in any::<u128>(), c in any::<u64>(), x in any::<i128>(), y in any::<i128>()) {
+        prop_assert_eq!(add_u128(a, b).ok(), a.checked_add(b));
+        prop_assert_eq!(sub_u128(a, b).ok(), a.checked_sub(b));
+        prop_assert_eq!(mul_u128(a, b).ok(), a.checked_mul(b));
+        prop_assert_eq!(mul_u128_u64(a, c).ok(), a.checked_mul(c as u128));
+        prop_assert_eq!(mul_i128_i128(x, y).ok(), x.checked_mul(y));
+        prop_assert_eq!(div_u128(a, b).ok(), a.checked_div(b));
+        prop_assert_eq!(u128_to_u64(a).ok(), u64::try_from(a).ok());
+        prop_assert_eq!(i128_to_i64(x).ok(), i64::try_from(x).ok());
+        prop_assert_eq!(i128_to_u64(x).ok(), if x < 0 { None } else { u64::try_from(x).ok() });
+    }
+
+    #[test]
+    fn pnl_never_panics_and_sides_mirror(size in any::<u64>(), entry in any::<u64>(), price in any::<u64>()) {
+        let long = calc_unrealized_pnl(Side::Long, size, entry, price);
+        let short = calc_unrealized_pnl(Side::Short, size, entry, price);
+        if let (Ok(l), Ok(s)) = (&long, &short) {
+            prop_assert_eq!(*l, -*s);
+            prop_assert_eq!(calc_realized_pnl_full(Side::Long, size, entry, price).unwrap(), *l);
+        }
+        prop_assert_eq!(long.is_ok(), short.is_ok());
+    }
+
+    #[test]
+    fn partial_then_full_equals_one_step(
+        side in side(),
+        size in 1u64..=1_000_000_000,
+        cut in any::<u64>(),
+        entry in 1u64..=1_000_000_000_000,
+        price in 0u64..=1_000_000_000_000,
+    ) {
+        let reduce = cut % (size + 1);
+        let partial = calc_realized_pnl_partial(side, reduce, entry, price).unwrap();
+        let rest = calc_realized_pnl_full(side, size - reduce, entry, price).unwrap();
+        prop_assert_eq!(partial + rest, calc_realized_pnl_full(side, size, entry, price).unwrap());
+    }
+
+    #[test]
+    fn liquidation_price_never_panics(
+        side in side(),
+        size in any::<u64>(),
+        entry in any::<u64>(),
+        margin in any::<u64>(),
+        mmr in 0u64..RATE_SCALE as u64,
+    ) {
+        let _ = calc_liquidation_price(side, size, entry, margin, mmr);
+    }
+
+    #[test]
+    fn overcollateralized_long_liquidates_at_zero(
+        size in 1u64..=1_000_000_000,
+        entry in 0u64..=1_000_000_000,
+        extra in 0u64..=1_000_000_000,
+        mmr in 0u64..RATE_SCALE as u64,
+    ) {
+        let margin = size * entry + extra;
+        prop_assert_eq!(calc_liquidation_price(Side::Long, size, entry, margin, mmr).unwrap(), 0);
+    }
+
+    #[test]
+    fn liquidation_price_on_the_losing_side_of_entry(
+        side in side(),
+        size in amount(1_000_000_000),
+        entry in amount(1_000_000_000),
+        leverage in leverage(),
+    ) {
+        if let Some((margin, tier)) = open(size, entry, leverage) {
+            let liq = calc_liquidation_price(side, size, entry, margin, tier.maintenance_margin_rate).unwrap();
+            match side {
+                Side::Long => prop_assert!(liq < entry, "long liq {} >= entry {}", liq, entry),
+                Side::Short => {
+                    // liq is floored, so it only clears entry once the exact price is a full unit above it
+                    let mmr = tier.maintenance_margin_rate as u128;
+                    let exact_clears = (size as u128 * entry as u128 + margin as u128) * RATE_SCALE
+                        >= (entry as u128 + 1) * size as u128 * (RATE_SCALE + mmr);
+                    prop_assert!(liq >= entry, "short liq {} < entry {}", liq, entry);
+                    prop_assert!(!exact_clears || liq > entry, "short liq {} <= entry {}", liq, entry);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn margin_ratio_at_liquidation_price_is_mmr(
+        side in side(),
+        size in amount(1_000_000_000),
+        entry in amount(1_000_000_000),
+        leverage in leverage(),
+    ) {
+        if let Some((margin, tier)) = open(size, entry, leverage) {
+            let mmr = tier.maintenance_margin_rate as i128;
+            let liq = calc_liquidation_price(side, size, entry, margin, tier.maintenance_margin_rate).unwrap();
+            let equity = margin as i128 + calc_unrealized_pnl(side, size, entry, liq).unwrap();
+            let notional = size as i128 * liq as i128;
+            // liq is floored, i.e. off by < 1 price unit; each unit moves
+            // equity * RATE_SCALE - notional * mmr by size * (RATE_SCALE ± mmr)
+            let gap = equity * RATE_SCALE as i128 - notional * mmr;
+            let tolerance = size as i128 * (RATE_SCALE as i128 + mmr);
+            prop_assert!(gap.abs() <= tolerance, "MR off by {} (tolerance {})", gap, tolerance);
+        }
+    }
+
+    #[test]
+    fn tier_lookup_picks_first_eligible_tier(leverage in any::<u16>(), notional in any::<u64>()) {
+        let expected = LEVERAGE_TIERS
+            .iter()
+            .find(|t| leverage <= t.max_leverage && notional <= t.max_position_size);
+        match (get_leverage_tier(leverage, notional), expected) {
+            (Ok(t), Some(e)) => {
+                prop_assert_eq!(t.max_leverage, e.max_leverage);
+                prop_assert!(leverage <= t.max_leverage && notional <= t.max_position_size);
+            }
+            (Err(_), None) => {}
+            (got, want) => prop_assert!(false, "got {:?}, want {:?}", got.is_ok(), want.is_some()),
+        }
+    }
+}
+
+#[test]
+fn tiers_are_ordered_by_leverage() {
+    for w in LEVERAGE_TIERS.windows(2) {
+        assert!(w[0].max_leverage < w[1].max_leverage);
+        assert!(w[0].maintenance_margin_rate > w[1].maintenance_margin_rate);
+    }
+    assert_eq!(LEVERAGE_TIERS.last().unwrap().max_leverage, MAX_LEVERAGE);
+}
@@ -0,0 +1,832 @@
+// In-process tests: the Anchor handlers run natively inside solana-program-test's
+// BanksClient, with the real SPL token and ATA programs. No validator needed.
+
+use anchor_lang::{AccountDeserialize, AnchorDeserialize, Discriminator, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::spl_token;
+use base64::Engine;
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestBanksClientExt, ProgramTestContext};
+use std::collections::HashSet;
+use std::sync::Once;
+use solana_sdk::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    system_instruction, system_program, sysvar,
+    transaction::{Transaction, TransactionError},
+};
+
+use position_manager::events::{MarginToppedUp, PoolFill, PoolPnlSettled, PositionClosed, PositionModified, PositionOpened};
+use position_manager::constants::MAX_MARK_AGE_SECS;
+use position_manager::instructions::ModifyKind;
+use position_manager::{LiquidityPool, PerpError, PoolMarket, PoolParams, Position, Side, TopUpSettings, UserAccount};
+
+const SYMBOL: &str = "SOL-PERP";
+const DECIMALS: u8 = 6;
+const TRADER_FUNDS: u64 = 1_000_000_000; // 1,000 quote
+const POOL_FUNDS: u64 = 1_000_000_000_000; // 1,000,000 quote
+
+// Anchor's entry ties every AccountInfo to one lifetime; processor! doesn't.
+fn process_instruction<'a, 'b, 'c, 'info>(
+    program_id: &'a Pubkey,
+    accounts: &'b [AccountInfo<'info>],
+    data: &'c [u8],
+) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    position_manager::entry(program_id, accounts, data)
+}
+
+// Natively run programs log `emit!` data with the stub's println instead of into the
+// transaction logs. Forward it through sol_log, so events show up in the logs as
+// "Program log: Program data: <base64>".
+struct EventLogStubs(Box<dyn SyscallStubs>);
+
+struct NoStubs;
+impl SyscallStubs for NoStubs {}
+
+impl SyscallStubs for EventLogStubs {
+    fn sol_log(&self, message: &str) {
+        self.0.sol_log(message)
+    }
+    fn sol_log_compute_units(&self) {
+        self.0.sol_log_compute_units()
+    }
+    fn sol_remaining_compute_units(&self) -> u64 {
+        self.0.sol_remaining_compute_units()
+    }
+    fn sol_invoke_signed(&self, instruction: &Instruction, account_infos: &[AccountInfo], signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+        self.0.sol_invoke_signed(instruction, account_infos, signers_seeds)
+    }
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_clock_sysvar(var_addr)
+    }
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_schedule_sysvar(var_addr)
+    }
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_fees_sysvar(var_addr)
+    }
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_rent_sysvar(var_addr)
+    }
+    fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_rewards_sysvar(var_addr)
+    }
+    fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_last_restart_slot(var_addr)
+    }
+    unsafe fn sol_memcpy(&self, dst: *mut u8, src: *const u8, n: usize) {
+        self.0.sol_memcpy(dst, src, n)
+    }
+    unsafe fn sol_memmove(&self, dst: *mut u8, src: *const u8, n: usize) {
+        self.0.sol_memmove(dst, src, n)
+    }
+    unsafe fn sol_memcmp(&self, s1: *const u8, s2: *const u8, n: usize, result: *mut i32) {
+        self.0.sol_memcmp(s1, s2, n, result)
+    }
+    unsafe fn sol_memset(&self, s: *mut u8, c: u8, n: usize) {
+        self.0.sol_memset(s, c, n)
+    }
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.0.sol_get_return_data()
+    }
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.0.sol_set_return_data(data)
+    }
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let encoded: Vec<String> = fields.iter().map(|f| base64::engine::general_purpose::STANDARD.encode(f)).collect();
+        self.0.sol_log(&format!("Program data: {}", encoded.join(" ")))
+    }
+    fn sol_get_processed_sibling_instruction(&self, index: usize) -> Option<Instruction> {
+        self.0.sol_get_processed_sibling_instruction(index)
+    }
+    fn sol_get_stack_height(&self) -> u64 {
+        self.0.sol_get_stack_height()
+    }
+}
+
+fn pool_params() -> PoolParams {
+    PoolParams {
+        base_spread_bps: 10,
+        skew_spread_bps: 100,
+        max_spread_bps: 100,
+        max_utilization_bps: 50_000,
+        max_skew_bps: 10_000,
+        funding_rate_bps: 0,
+    }
+}
+
+struct Env {
+    ctx: ProgramTestContext,
+    mint: Pubkey,
+    trader: Keypair,
+    trader_ata: Pubkey,
+    lp: Keypair,
+    sent: HashSet<Signature>,
+}
+
+impl Env {
+    async fn new() -> Self {
+        let pt = ProgramTest::new("position_manager", position_manager::ID, processor!(process_instruction));
+        let mut ctx = pt.start_with_context().await;
+        // program-test installs its own stubs on first start; wrap them once after that
+        static WRAP_STUBS: Once = Once::new();
+        WRAP_STUBS.call_once(|| {
+            let inner = set_syscall_stubs(Box::new(NoStubs));
+            set_syscall_stubs(Box::new(EventLogStubs(inner)));
+        });
+
+        let mint = Keypair::new();
+        let trader = Keypair::new();
+        let lp = Keypair::new();
+        let payer = ctx.payer.pubkey();
+        let rent = ctx.banks_client.get_rent().await.unwrap();
+
+        let mut ixs = vec![
+            system_instruction::create_account(
+                &payer,
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint(&spl_token::ID, &mint.pubkey(), &payer, None, DECIMALS).unwrap(),
+        ];
+        for (wallet, amount) in [(trader.pubkey(), TRADER_FUNDS), (lp.pubkey(), POOL_FUNDS)] {
+            ixs.push(system_instruction::transfer(&payer, &wallet, 1_000_000_000));
+            ixs.push(spl_associated_token_account::instruction::create_associated_token_account(
+                &payer, &wallet, &mint.pubkey(), &spl_token::ID,
+            ));
+            let ata = get_associated_token_address(&wallet, &mint.pubkey());
+            ixs.push(spl_token::instruction::mint_to(&spl_token::ID, &mint.pubkey(), &ata, &payer, &[], amount).unwrap());
+        }
+        let mut env = Env {
+            trader_ata: get_associated_token_address(&trader.pubkey(), &mint.pubkey()),
+            mint: mint.pubkey(),
+            trader,
+            lp,
+            ctx,
+            sent: HashSet::new(),
+        };
+        env.send(ixs, &[&mint]).await.unwrap();
+        env
+    }
+
+    async fn send(&mut self, ixs: Vec<Instruction>, signers: &[&Keypair]) -> Result<Vec<String>, BanksClientError> {
+        let mut all: Vec<&Keypair> = vec![&self.ctx.payer];
+        all.extend_from_slice(signers);
+        let blockhash = self.ctx.banks_client.get_latest_blockhash().await?;
+        let mut tx = Transaction::new_signed_with_payer(&ixs, Some(&self.ctx.payer.pubkey()), &all, blockhash);
+        // a retry of a failed transaction signs identically until the blockhash moves on
+        if !self.sent.insert(tx.signatures[0]) {
+            let blockhash = self.ctx.banks_client.get_new_latest_blockhash(&blockhash).await?;
+            tx = Transaction::new_signed_with_payer(&ixs, Some(&self.ctx.payer.pubkey()), &all, blockhash);
+            self.sent.insert(tx.signatures[0]);
+        }
+        let res = self.ctx.banks_client.process_transaction_with_metadata(tx).await?;
+        res.result.map_err(BanksClientError::TransactionError)?;
+        Ok(res.metadata.map(|m| m.log_messages).unwrap_or_default())
+    }
+
+    async fn token_balance(&mut self, account: Pubkey) -> u64 {
+        let acc = self.ctx.banks_client.get_account(account).await.unwrap().expect("token account");
+        spl_token::state::Account::unpack(&acc.data).unwrap().amount
+    }
+
+    async fn anchor_account<T: AccountDeserialize>(&mut self, address: Pubkey) -> Option<T> {
+        let acc = self.ctx.banks_client.get_account(address).await.unwrap()?;
+        Some(T::try_deserialize(&mut acc.data.as_slice()).unwrap())
+    }
+
+    fn pool(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"pool", self.mint.as_ref()], &position_manager::ID).0
+    }
+    fn lp_mint(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"lp_mint", self.pool().as_ref()], &position_manager::ID).0
+    }
+    fn pool_market(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"pool_market", self.pool().as_ref(), SYMBOL.as_bytes()], &position_manager::ID).0
+    }
+    fn vault(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"vault", self.mint.as_ref()], &position_manager::ID).0
+    }
+    fn vault_authority(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"vault_authority"], &position_manager::ID).0
+    }
+    fn user(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"user", self.trader.pubkey().as_ref(), &0u16.to_le_bytes()], &position_manager::ID).0
+    }
+    fn position(&self) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"position", self.trader.pubkey().as_ref(), &0u16.to_le_bytes(), SYMBOL.as_bytes()],
+            &position_manager::ID,
+        )
+        .0
+    }
+
+    fn top_up(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"top_up", self.position().as_ref()], &position_manager::ID).0
+    }
+    fn approval(&self, recipient: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                b"transfer_approval",
+                recipient.as_ref(),
+                &0u16.to_le_bytes(),
+                self.trader.pubkey().as_ref(),
+                &0u16.to_le_bytes(),
+                SYMBOL.as_bytes(),
+            ],
+            &position_manager::ID,
+        )
+        .0
+    }
+
+    // Pool, market (marked at 100) and LP liquidity; every position instruction needs them.
+    async fn setup_pool(&mut self, params: PoolParams, liquidity: u64) {
+        let authority = self.ctx.payer.pubkey();
+        let init_pool = Instruction {
+            program_id: position_manager::ID,
+            accounts: position_manager::accounts::InitializePool {
+                authority,
+                pool: self.pool(),
+                quote_mint: self.mint,
+                lp_mint: self.lp_mint(),
+                vault: self.vault(),
+                vault_authority: self.vault_authority(),
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+            data: position_manager::instruction::InitializePool { params }.data(),
+        };
+        let init_market = Instruction {
+            program_id: position_manager::ID,
+            accounts: position_manager::accounts::InitializePoolMarket {
+                authority,
+                pool: self.pool(),
+                pool_market: self.pool_market(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: position_manager::instruction::InitializePoolMarket { symbol: SYMBOL.to_string() }.data(),
+        };
+        self.send(vec![init_pool, init_market], &[]).await.unwrap();
+
+        let lp = self.lp.pubkey();
+        let lp_quote = get_associated_token_address(&lp, &self.mint);
+        let lp_shares = get_associated_token_address(&lp, &self.lp_mint());
+        let create_lp_ata = spl_associated_token_account::instruction::create_associated_token_account(
+            &authority, &lp, &self.lp_mint(), &spl_token::ID,
+        );
+        let mut accounts = position_manager::accounts::AddLiquidity {
+            provider: lp,
+            pool: self.pool(),
+            quote_mint: self.mint,
+            lp_mint: self.lp_mint(),
+            provider_quote_ata: lp_quote,
+            provider_lp_ata: lp_shares,
+            vault: self.vault(),
+            token_program: spl_token::ID,
+        }
+        .to_account_metas(None);
+        accounts.push(AccountMeta::new_readonly(self.pool_market(), false));
+        let add = Instruction {
+            program_id: position_manager::ID,
+            accounts,
+            data: position_manager::instruction::AddLiquidity { amount: liquidity }.data(),
+        };
+        let lp_kp = self.lp.insecure_clone();
+        self.send(vec![create_lp_ata, add], &[&lp_kp]).await.unwrap();
+        self.set_mark(100_000_000).await.unwrap();
+    }
+
+    async fn advance_clock(&mut self, secs: i64) {
+        let mut clock: Clock = self.ctx.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += secs;
+        self.ctx.set_sysvar(&clock);
+    }
+
+    async fn set_mark(&mut self, price: u64) -> Result<Vec<String>, BanksClientError> {
+        let ix = Instruction {
+            program_id: position_manager::ID,
+            accounts: position_manager::accounts::SetMarkPrice {
+                authority: self.ctx.payer.pubkey(),
+                pool: self.pool(),
+                pool_market: self.pool_market(),
+            }
+            .to_account_metas(None),
+            data: position_manager::instruction::SetMarkPrice { price }.data(),
+        };
+        self.send(vec![ix], &[]).await
+    }
+
+    // The pool's only market goes along as a remaining account, so shares are priced
+    // against its open positions.
+    async fn remove_liquidity(&mut self, shares: u64) -> Result<Vec<String>, BanksClientError> {
+        let lp = self.lp.pubkey();
+        let mut accounts = position_manager::accounts::RemoveLiquidity {
+            provider: lp,
+            pool: self.pool(),
+            quote_mint: self.mint,
+            lp_mint: self.lp_mint(),
+            provider_quote_ata: get_associated_token_address(&lp, &self.mint),
+            provider_lp_ata: get_associated_token_address(&lp, &self.lp_mint()),
+            vault: self.vault(),
+            vault_authority: self.vault_authority(),
+            token_program: spl_token::ID,
+        }
+        .to_account_metas(None);
+        accounts.push(AccountMeta::new_readonly(self.pool_market(), false));
+        let ix = Instruction {
+            program_id: position_manager::ID,
+            accounts,
+            data: position_manager::instruction::RemoveLiquidity { shares }.data(),
+        };
+        let lp_kp = self.lp.insecure_clone();
+        self.send(vec![ix], &[&lp_kp]).await
+    }
+
+    async fn deposit(&mut self, amount: u64) -> Result<Vec<String>, BanksClientError> {
+        let ix = Instruction {
+            program_id: position_manager::ID,
+            accounts: position_manager::accounts::DepositCollateral {
+                owner: self.trader.pubkey(),
+                user: self.user(),
+                quote_mint: self.mint,
+                user_quote_ata: self.trader_ata,
+                vault: self.vault(),
+                vault_authority: self.vault_authority(),
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+            data: position_manager::instruction::DepositCollateral { sub_id: 0, amount }.data(),
+        };
+        let trader = self.trader.insecure_clone();
+        self.send(vec![ix], &[&trader]).await
+    }
+
+    async fn open(&mut self, side: Side, size: u64, leverage: u16, price: u64) -> Result<Vec<String>, BanksClientError> {
+        let ix = Instruction {
+            program_id: position_manager::ID,
+            accounts: position_manager::accounts::OpenPosition {
+                owner: self.trader.pubkey(),
+                user: self.user(),
+                position: self.position(),
+                quote_mint: self.mint,
+                pool: self.pool(),
+                pool_market: self.pool_market(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: position_manager::instruction::OpenPosition {
+                sub_id: 0,
+                symbol: SYMBOL.to_string(),
+                side,
+                size,
+                leverage,
+                entry_price: price,
+            }
+            .data(),
+        };
+        let trader = self.trader.insecure_clone();
+        self.send(vec![ix], &[&trader]).await
+    }
+
+    async fn modify(&mut self, action: ModifyKind) -> Result<Vec<String>, BanksClientError> {
+        let ix = Instruction {
+            program_id: position_manager::ID,
+            accounts: position_manager::accounts::ModifyPosition {
+                owner: self.trader.pubkey(),
+                user: self.user(),
+                position: self.position(),
+                quote_mint: self.mint,
+                pool: self.pool(),
+                pool_market: self.pool_market(),
+                user_quote_ata: self.trader_ata,
+                vault: self.vault(),
+                vault_authority: self.vault_authority(),
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: position_manager::instruction::ModifyPosition { action }.data(),
+        };
+        let trader = self.trader.insecure_clone();
+        self.send(vec![ix], &[&trader]).await
+    }
+
+    async fn set_auto_top_up(&mut self, target_mr: u64, max_total: u64) -> Result<Vec<String>, BanksClientError> {
+        let ix = Instruction {
+            program_id: position_manager::ID,
+            accounts: position_manager::accounts::SetAutoTopUp {
+                owner: self.trader.pubkey(),
+                position: self.position(),
+                settings: self.top_up(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: position_manager::instruction::SetAutoTopUp { target_mr, max_total }.data(),
+        };
+        let trader = self.trader.insecure_clone();
+        self.send(vec![ix], &[&trader]).await
+    }
+
+    // The test payer signs as keeper.
+    async fn auto_top_up(&mut self) -> Result<Vec<String>, BanksClientError> {
+        let ix = Instruction {
+            program_id: position_manager::ID,
+            accounts: position_manager::accounts::AutoTopUp {
+                keeper: self.ctx.payer.pubkey(),
+                user: self.user(),
+                position: self.position(),
+                pool: self.pool(),
+                pool_market: self.pool_market(),
+                settings: self.top_up(),
+            }
+            .to_account_metas(None),
+            data: position_manager::instruction::AutoTopUp {}.data(),
+        };
+        self.send(vec![ix], &[]).await
+    }
+
+    // `recipient` pre-approves taking over the position into its sub-account 0.
+    async fn approve_transfer(&mut self, recipient: &Keypair, max_size: u64, min_margin: u64) -> Result<Vec<String>, BanksClientError> {
+        let ix = Instruction {
+            program_id: position_manager::ID,
+            accounts: position_manager::accounts::ApprovePositionTransfer {
+                recipient: recipient.pubkey(),
+                approval: self.approval(&recipient.pubkey()),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: position_manager::instruction::ApprovePositionTransfer {
+                from_owner: self.trader.pubkey(),
+                from_sub_id: 0,
+                to_sub_id: 0,
+                symbol: SYMBOL.to_string(),
+                max_size,
+                min_margin,
+            }
+            .data(),
+        };
+        self.send(vec![ix], &[recipient]).await
+    }
+
+    async fn transfer(&mut self, recipient: Pubkey) -> Result<Vec<String>, BanksClientError> {
+        let ix = Instruction {
+            program_id: position_manager::ID,
+            accounts: position_manager::accounts::TransferPosition {
+                owner: self.trader.pubkey(),
+                recipient,
+                from_user: self.user(),
+                position: self.position(),
+                to_user: Pubkey::find_program_address(&[b"user", recipient.as_ref(), &0u16.to_le_bytes()], &position_manager::ID).0,
+                new_position: Pubkey::find_program_address(
+                    &[b"position", recipient.as_ref(), &0u16.to_le_bytes(), SYMBOL.as_bytes()],
+                    &position_manager::ID,
+                )
+                .0,
+                approval: Some(self.approval(&recipient)),
+                top_up: self.top_up(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: position_manager::instruction::TransferPosition { to_sub_id: 0 }.data(),
+        };
+        let trader = self.trader.insecure_clone();
+        self.send(vec![ix], &[&trader]).await
+    }
+
+    async fn close(&mut self, exit_price: u64) -> Result<Vec<String>, BanksClientError> {
+        let ix = Instruction {
+            program_id: position_manager::ID,
+            accounts: position_manager::accounts::ClosePosition {
+                owner: self.trader.pubkey(),
+                user: self.user(),
+                position: self.position(),
+                quote_mint: self.mint,
+                pool: self.pool(),
+                pool_market: self.pool_market(),
+                user_quote_ata: self.trader_ata,
+                vault: self.vault(),
+                vault_authority: self.vault_authority(),
+                top_up: self.top_up(),
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: position_manager::instruction::ClosePosition { exit_price }.data(),
+        };
+        let trader = self.trader.insecure_clone();
+        self.send(vec![ix], &[&trader]).await
+    }
+}
+
+// Anchor emits events as "Program data: <base64(discriminator ++ borsh)>" log lines.
+fn events<T: AnchorDeserialize + Discriminator>(logs: &[String]) -> Vec<T> {
+    logs.iter()
+        .filter_map(|l| l.strip_prefix("Program log: ").unwrap_or(l).strip_prefix("Program data: "))
+        .filter_map(|b64| base64::engine::general_purpose::STANDARD.decode(b64).ok())
+        .filter(|data| data.len() >= 8 && data[..8] == T::discriminator())
+        .map(|data| T::deserialize(&mut &data[8..]).unwrap())
+        .collect()
+}
+
+fn assert_program_error(err: BanksClientError, expected: PerpError) {
+    match err {
+        BanksClientError::TransactionError(TransactionError::InstructionError(_, InstructionError::Custom(code))) => {
+            assert_eq!(code, u32::from(expected), "unexpected error code");
+        }
+        other => panic!("expected {:?}, got {:?}", expected, other),
+    }
+}
+
+#[tokio::test]
+async fn test_position_lifecycle() {
+    let mut env = Env::new().await;
+    env.setup_pool(pool_params(), POOL_FUNDS).await;
+    assert_eq!(env.token_balance(env.vault()).await, POOL_FUNDS);
+
+    // open: 10 @ oracle 100, 10x; the pool sells at oracle + 10 bps, and the margin is
+    // locked from the collateral deposited first
+    env.deposit(100_100_000).await.unwrap();
+    let logs = env.open(Side::Long, 10, 10, 100_000_000).await.unwrap();
+    let opened = events::<PositionOpened>(&logs);
+    assert_eq!(opened.len(), 1);
+    assert_eq!(opened[0].entry_price, 100_100_000);
+    assert_eq!(opened[0].initial_margin, 100_100_000);
+    let fills = events::<PoolFill>(&logs);
+    assert_eq!(fills.len(), 1);
+    assert_eq!(fills[0].spread_bps, 10);
+    assert_eq!(fills[0].oracle_price, 100_000_000);
+    assert_eq!(fills[0].long_open_interest, 1_001_000_000);
+
+    assert_eq!(env.token_balance(env.trader_ata).await, TRADER_FUNDS - 100_100_000);
+    assert_eq!(env.token_balance(env.vault()).await, POOL_FUNDS + 100_100_000);
+    let pos: Position = env.anchor_account(env.position()).await.unwrap();
+    assert_eq!((pos.size, pos.entry_price, pos.margin, pos.leverage), (10, 100_100_000, 100_100_000, 10));
+    assert!(pos.side == Side::Long);
+    let user: UserAccount = env.anchor_account(env.user()).await.unwrap();
+    assert_eq!((user.total_collateral, user.locked_collateral, user.position_count), (100_100_000, 100_100_000, 1));
+
+    // increase: +5 with 50 extra margin
+    let logs = env
+        .modify(ModifyKind::IncreaseSize { add_size: 5, price: 100_000_000, add_margin: 50_000_000 })
+        .await
+        .unwrap();
+    assert_eq!(events::<PositionModified>(&logs)[0].size, 15);
+    let pos: Position = env.anchor_account(env.position()).await.unwrap();
+    assert_eq!((pos.size, pos.entry_price, pos.margin), (15, 100_100_000, 150_100_000));
+    assert_eq!(env.token_balance(env.trader_ata).await, TRADER_FUNDS - 150_100_000);
+
+    // decrease: -5 @ mark 110; the pool buys at mark - 10 bps and pays the profit
+    // into the sub-account as free collateral
+    env.set_mark(110_000_000).await.unwrap();
+    let logs = env.modify(ModifyKind::DecreaseSize { reduce_size: 5, price: 110_000_000 }).await.unwrap();
+    assert_eq!(events::<PoolFill>(&logs)[0].execution_price, 109_890_000);
+    let partial = 5 * (109_890_000 - 100_100_000);
+    assert_eq!(events::<PoolPnlSettled>(&logs)[0].pool_pnl, -partial);
+    let pos: Position = env.anchor_account(env.position()).await.unwrap();
+    assert_eq!((pos.size, pos.margin), (10, 150_100_000));
+    assert_eq!(pos.realized_pnl, partial);
+    let user: UserAccount = env.anchor_account(env.user()).await.unwrap();
+    assert_eq!((user.total_collateral, user.locked_collateral), (150_100_000 + partial as u64, 150_100_000));
+    let pool: LiquidityPool = env.anchor_account(env.pool()).await.unwrap();
+    assert_eq!(pool.liquidity, POOL_FUNDS - partial as u64);
+    let market: PoolMarket = env.anchor_account(env.pool_market()).await.unwrap();
+    assert_eq!((market.long_open_interest, market.short_open_interest), (1_001_000_000, 0));
+
+    // add / remove margin
+    env.modify(ModifyKind::AddMargin { amount: 20_000_000 }).await.unwrap();
+    env.modify(ModifyKind::RemoveMargin { amount: 10_000_000, price: 110_000_000 }).await.unwrap();
+    let pos: Position = env.anchor_account(env.position()).await.unwrap();
+    assert_eq!(pos.margin, 160_100_000);
+    let user: UserAccount = env.anchor_account(env.user()).await.unwrap();
+    assert_eq!((user.total_collateral, user.locked_collateral), (160_100_000 + partial as u64, 160_100_000));
+    assert_eq!(env.token_balance(env.trader_ata).await, TRADER_FUNDS - 160_100_000);
+
+    // close @ mark 120; the pool pays the profit
+    env.set_mark(120_000_000).await.unwrap();
+    let logs = env.close(120_000_000).await.unwrap();
+    let closed = events::<PositionClosed>(&logs);
+    let net_pnl = 10 * (119_880_000 - 100_100_000);
+    assert_eq!(closed[0].exit_price, 119_880_000);
+    assert_eq!(closed[0].realized_pnl, net_pnl);
+    assert_eq!(closed[0].payout, 160_100_000 + net_pnl as u64);
+
+    assert!(env.ctx.banks_client.get_account(env.position()).await.unwrap().is_none());
+    let user: UserAccount = env.anchor_account(env.user()).await.unwrap();
+    assert_eq!((user.total_collateral, user.locked_collateral, user.position_count), (partial as u64, 0, 0));
+    assert_eq!(user.total_pnl, net_pnl);
+    assert_eq!(env.token_balance(env.trader_ata).await, TRADER_FUNDS + net_pnl as u64);
+
+    let pool: LiquidityPool = env.anchor_account(env.pool()).await.unwrap();
+    assert_eq!(pool.liquidity, POOL_FUNDS - net_pnl as u64 - partial as u64);
+    assert_eq!(pool.open_interest, 0);
+    assert_eq!(env.token_balance(env.vault()).await, pool.liquidity + partial as u64);
+}
+
+#[tokio::test]
+async fn test_partial_reduce_loss_paid_into_pool() {
+    let mut env = Env::new().await;
+    env.setup_pool(pool_params(), POOL_FUNDS).await;
+    env.deposit(100_100_000).await.unwrap();
+    env.open(Side::Long, 10, 10, 100_000_000).await.unwrap();
+
+    // -5 @ mark 98, filled at 97.902: the loss leaves the margin for the pool
+    env.set_mark(98_000_000).await.unwrap();
+    let logs = env.modify(ModifyKind::DecreaseSize { reduce_size: 5, price: 98_000_000 }).await.unwrap();
+    let loss = 5 * (100_100_000 - 97_902_000);
+    assert_eq!(events::<PoolPnlSettled>(&logs)[0].pool_pnl, loss);
+    let pos: Position = env.anchor_account(env.position()).await.unwrap();
+    assert_eq!((pos.margin, pos.realized_pnl), (100_100_000 - loss as u64, -loss));
+    let user: UserAccount = env.anchor_account(env.user()).await.unwrap();
+    assert_eq!((user.total_collateral, user.locked_collateral), (100_100_000 - loss as u64, 100_100_000 - loss as u64));
+    let pool: LiquidityPool = env.anchor_account(env.pool()).await.unwrap();
+    assert_eq!(pool.liquidity, POOL_FUNDS + loss as u64);
+    assert_eq!(env.token_balance(env.vault()).await, pool.liquidity + user.total_collateral);
+}
+
+#[tokio::test]
+async fn test_lp_shares_priced_at_mark() {
+    let mut env = Env::new().await;
+    env.setup_pool(pool_params(), POOL_FUNDS).await;
+    env.deposit(100_100_000).await.unwrap();
+    env.open(Side::Long, 10, 10, 100_000_000).await.unwrap();
+
+    // open interest without a fresh mark can't be priced
+    env.advance_clock(MAX_MARK_AGE_SECS + 1).await;
+    let err = env.remove_liquidity(POOL_FUNDS / 100).await.unwrap_err();
+    assert_program_error(err, PerpError::StaleMarkPrice);
+
+    // at a mark of 120 the long is up 10 × 19.9, which the pool owes
+    env.set_mark(120_000_000).await.unwrap();
+    env.remove_liquidity(POOL_FUNDS / 100).await.unwrap();
+    let value = POOL_FUNDS - 10 * (120_000_000 - 100_100_000);
+    let lp_quote = get_associated_token_address(&env.lp.pubkey(), &env.mint);
+    assert_eq!(env.token_balance(lp_quote).await, value / 100);
+    let pool: LiquidityPool = env.anchor_account(env.pool()).await.unwrap();
+    assert_eq!(pool.liquidity, POOL_FUNDS - value / 100);
+}
+
+#[tokio::test]
+async fn test_remove_margin_below_maintenance_rejected() {
+    let mut env = Env::new().await;
+    env.setup_pool(pool_params(), POOL_FUNDS).await;
+    env.deposit(100_100_000).await.unwrap();
+    env.open(Side::Long, 10, 10, 100_000_000).await.unwrap();
+
+    // 100 of margin against ~1000 notional; leaving 1 breaches the 2.5% MMR
+    let err = env
+        .modify(ModifyKind::RemoveMargin { amount: 99_100_000, price: 100_000_000 })
+        .await
+        .unwrap_err();
+    assert_program_error(err, PerpError::MaintenanceBreach);
+
+    let pos: Position = env.anchor_account(env.position()).await.unwrap();
+    assert_eq!(pos.margin, 100_100_000);
+}
+
+#[tokio::test]
+async fn test_open_over_utilization_cap_rejected() {
+    let mut env = Env::new().await;
+    // 500 of liquidity with open interest capped at 1x
+    env.setup_pool(PoolParams { max_utilization_bps: 10_000, ..pool_params() }, 500_000_000).await;
+
+    env.deposit(100_000_000).await.unwrap();
+
+    let err = env.open(Side::Short, 10, 10, 100_000_000).await.unwrap_err();
+    assert_program_error(err, PerpError::PoolUtilizationExceeded);
+    let user: UserAccount = env.anchor_account(env.user()).await.unwrap();
+    assert_eq!((user.total_collateral, user.locked_collateral), (100_000_000, 0));
+    assert!(env.ctx.banks_client.get_account(env.position()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_open_needs_free_collateral() {
+    let mut env = Env::new().await;
+    env.setup_pool(pool_params(), POOL_FUNDS).await;
+
+    // the initial margin is 100.1 at the pool's price; the wallet is never debited
+    env.deposit(100_000_000).await.unwrap();
+    let err = env.open(Side::Long, 10, 10, 100_000_000).await.unwrap_err();
+    assert_program_error(err, PerpError::InsufficientFreeCollateral);
+    assert_eq!(env.token_balance(env.trader_ata).await, TRADER_FUNDS - 100_000_000);
+
+    env.deposit(100_000).await.unwrap();
+    env.open(Side::Long, 10, 10, 100_000_000).await.unwrap();
+    let user: UserAccount = env.anchor_account(env.user()).await.unwrap();
+    assert_eq!((user.total_collateral, user.locked_collateral, user.free_collateral()), (100_100_000, 100_100_000, 0));
+    assert_eq!(env.token_balance(env.trader_ata).await, TRADER_FUNDS - 100_100_000);
+}
+
+#[tokio::test]
+async fn test_transfer_checks_approval_terms_and_closes_top_up() {
+    let mut env = Env::new().await;
+    env.setup_pool(pool_params(), POOL_FUNDS).await;
+    env.deposit(100_100_000).await.unwrap();
+    env.open(Side::Long, 10, 10, 100_000_000).await.unwrap();
+    env.set_auto_top_up(100_000, 5_000_000).await.unwrap();
+
+    let recipient = Keypair::new();
+    let payer = env.ctx.payer.pubkey();
+    env.send(vec![system_instruction::transfer(&payer, &recipient.pubkey(), 1_000_000_000)], &[]).await.unwrap();
+    env.approve_transfer(&recipient, 10, 100_100_000).await.unwrap();
+
+    // the sender pulled margin after the approval; the recipient didn't agree to that
+    env.modify(ModifyKind::RemoveMargin { amount: 10_000_000, price: 100_000_000 }).await.unwrap();
+    let err = env.transfer(recipient.pubkey()).await.unwrap_err();
+    assert_program_error(err, PerpError::TransferApprovalMismatch);
+
+    env.modify(ModifyKind::AddMargin { amount: 10_000_000 }).await.unwrap();
+    env.transfer(recipient.pubkey()).await.unwrap();
+    assert!(env.ctx.banks_client.get_account(env.position()).await.unwrap().is_none());
+    assert!(env.ctx.banks_client.get_account(env.top_up()).await.unwrap().is_none());
+    assert!(env.ctx.banks_client.get_account(env.approval(&recipient.pubkey())).await.unwrap().is_none());
+    let new_position = Pubkey::find_program_address(
+        &[b"position", recipient.pubkey().as_ref(), &0u16.to_le_bytes(), SYMBOL.as_bytes()],
+        &position_manager::ID,
+    )
+    .0;
+    let pos: Position = env.anchor_account(new_position).await.unwrap();
+    assert_eq!((pos.owner, pos.size, pos.margin), (recipient.pubkey(), 10, 100_100_000));
+}
+
+#[tokio::test]
+async fn test_auto_top_up_at_pool_mark_and_settings_closed_with_position() {
+    let mut env = Env::new().await;
+    env.setup_pool(pool_params(), POOL_FUNDS).await;
+    env.deposit(200_100_000).await.unwrap();
+    env.open(Side::Long, 10, 10, 100_000_000).await.unwrap();
+    env.set_auto_top_up(150_000, 100_000_000).await.unwrap();
+
+    // the keeper can't bring its own price; without a fresh pool mark there's nothing to check
+    env.advance_clock(MAX_MARK_AGE_SECS + 1).await;
+    let err = env.auto_top_up().await.unwrap_err();
+    assert_program_error(err, PerpError::StaleMarkPrice);
+
+    // at a mark of 100 equity is 99.1 against 1000 notional; 15% needs 50.9 more
+    env.set_mark(100_000_000).await.unwrap();
+    let logs = env.auto_top_up().await.unwrap();
+    let topped = events::<MarginToppedUp>(&logs);
+    assert_eq!(topped.len(), 1);
+    assert_eq!((topped[0].mark_price, topped[0].margin_ratio_before, topped[0].amount), (100_000_000, 99_100, 50_900_000));
+    let settings: TopUpSettings = env.anchor_account(env.top_up()).await.unwrap();
+    assert_eq!(settings.used, 50_900_000);
+
+    env.close(100_000_000).await.unwrap();
+    assert!(env.ctx.banks_client.get_account(env.position()).await.unwrap().is_none());
+    assert!(env.ctx.banks_client.get_account(env.top_up()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_fills_at_pool_mark_within_price_band() {
+    let mut env = Env::new().await;
+    env.setup_pool(pool_params(), POOL_FUNDS).await;
+    env.deposit(200_000_000).await.unwrap();
+
+    // 2% off the mark of 100 is refused outright
+    let err = env.open(Side::Long, 10, 10, 102_000_000).await.unwrap_err();
+    assert_program_error(err, PerpError::PriceOutsideMarkBand);
+
+    // within the band the caller's price only guards against a moved mark: the fill is
+    // still the mark + 10 bps
+    let logs = env.open(Side::Long, 10, 10, 100_900_000).await.unwrap();
+    assert_eq!(events::<PoolFill>(&logs)[0].oracle_price, 100_000_000);
+    assert_eq!(events::<PositionOpened>(&logs)[0].entry_price, 100_100_000);
+
+    // closing at a price the caller picks is refused too
+    let err = env.close(120_000_000).await.unwrap_err();
+    assert_program_error(err, PerpError::PriceOutsideMarkBand);
+    let err = env.modify(ModifyKind::DecreaseSize { reduce_size: 5, price: 90_000_000 }).await.unwrap_err();
+    assert_program_error(err, PerpError::PriceOutsideMarkBand);
+}
+
+#[tokio::test]
+async fn test_funding_accrues_on_chain_and_settles_on_close() {
+    let mut env = Env::new().await;
+    env.setup_pool(PoolParams { funding_rate_bps: 10, ..pool_params() }, POOL_FUNDS).await;
+    env.deposit(100_100_000).await.unwrap();
+    env.open(Side::Long, 10, 10, 100_000_000).await.unwrap();
+
+    // all open interest is long, so the long pays the full 10 bps of its 1000 notional
+    // for the hour
+    env.advance_clock(3_600).await;
+    env.set_mark(100_000_000).await.unwrap();
+    let logs = env.close(100_000_000).await.unwrap();
+    let funding = 1_000_000;
+    let net_pnl = 10 * (99_900_000 - 100_100_000) - funding;
+    let closed = events::<PositionClosed>(&logs);
+    assert_eq!((closed[0].realized_pnl, closed[0].payout), (net_pnl, (100_100_000 + net_pnl) as u64));
+    let market: PoolMarket = env.anchor_account(env.pool_market()).await.unwrap();
+    assert_eq!(market.funding_index, 100_000_000 * 1_000);
+    let pool: LiquidityPool = env.anchor_account(env.pool()).await.unwrap();
+    assert_eq!(pool.liquidity, POOL_FUNDS + net_pnl.unsigned_abs());
+}
@@ -0,0 +1,501 @@
+// Checks perp-sdk against the program's own Anchor client types: every builder must
+// produce the same data and account metas as `instruction::X` / `accounts::X`, the
+// account decoders must read what the program writes, and error codes must line up.
+// perp-events is held to the program's #[event] structs the same way.
+
+use anchor_lang::{AccountSerialize, AnchorSerialize, Event, InstructionData, ToAccountMetas};
+use anchor_spl::token::ID as TOKEN_PROGRAM_ID;
+use perp_sdk::{types as sdk, InstructionBuilder, ProgramAccount};
+use position_manager::instructions::{BatchLeg, ModifyKind};
+use position_manager::{accounts, instruction, PerpError};
+use solana_sdk::{instruction::{AccountMeta, Instruction}, pubkey::Pubkey, system_program, sysvar};
+
+const SYMBOL: &str = "SOL-PERP";
+
+struct Keys {
+    b: InstructionBuilder,
+    owner: Pubkey,
+    mint: Pubkey,
+}
+
+impl Keys {
+    fn new() -> Self {
+        let mint = Pubkey::new_unique();
+        Self { b: InstructionBuilder::new(position_manager::ID, mint), owner: Pubkey::new_unique(), mint }
+    }
+
+    fn pda(&self, seeds: &[&[u8]]) -> Pubkey {
+        Pubkey::find_program_address(seeds, &position_manager::ID).0
+    }
+
+    fn user(&self, owner: &Pubkey, sub_id: u16) -> Pubkey {
+        self.pda(&[b"user", owner.as_ref(), &sub_id.to_le_bytes()])
+    }
+
+    fn position(&self, owner: &Pubkey, sub_id: u16) -> Pubkey {
+        self.pda(&[b"position", owner.as_ref(), &sub_id.to_le_bytes(), SYMBOL.as_bytes()])
+    }
+
+    fn pool(&self) -> Pubkey {
+        self.pda(&[b"pool", self.mint.as_ref()])
+    }
+
+    fn pool_market(&self) -> Pubkey {
+        self.pda(&[b"pool_market", self.pool().as_ref(), SYMBOL.as_bytes()])
+    }
+
+    fn vault(&self) -> Pubkey {
+        self.pda(&[b"vault", self.mint.as_ref()])
+    }
+
+    fn vault_authority(&self) -> Pubkey {
+        self.pda(&[b"vault_authority"])
+    }
+
+    fn lp_mint(&self) -> Pubkey {
+        self.pda(&[b"lp_mint", self.pool().as_ref()])
+    }
+
+    fn ata(&self, owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        spl_associated_token_account::get_associated_token_address(owner, mint)
+    }
+
+    fn top_up(&self, position: &Pubkey) -> Pubkey {
+        self.pda(&[b"top_up", position.as_ref()])
+    }
+
+    fn approval(&self, recipient: &Pubkey, to_sub_id: u16, from_sub_id: u16) -> Pubkey {
+        self.pda(&[
+            b"transfer_approval",
+            recipient.as_ref(),
+            &to_sub_id.to_le_bytes(),
+            self.owner.as_ref(),
+            &from_sub_id.to_le_bytes(),
+            SYMBOL.as_bytes(),
+        ])
+    }
+}
+
+fn assert_ix(got: Instruction, accounts: impl ToAccountMetas, data: impl InstructionData) {
+    assert_eq!(got.program_id, position_manager::ID);
+    assert_eq!(got.accounts, accounts.to_account_metas(None));
+    assert_eq!(got.data, data.data());
+}
+
+fn params() -> (sdk::PoolParams, position_manager::PoolParams) {
+    let (b, s, m, u, k, f) = (5, 50, 100, 50_000, 20_000, 10);
+    (
+        sdk::PoolParams { base_spread_bps: b, skew_spread_bps: s, max_spread_bps: m, max_utilization_bps: u, max_skew_bps: k, funding_rate_bps: f },
+        position_manager::PoolParams { base_spread_bps: b, skew_spread_bps: s, max_spread_bps: m, max_utilization_bps: u, max_skew_bps: k, funding_rate_bps: f },
+    )
+}
+
+#[test]
+fn position_instructions_match_program() {
+    let k = Keys::new();
+    let o = k.owner;
+    assert_ix(
+        k.b.open_position(&o, 2, SYMBOL, sdk::Side::Short, 1_000, 25, 180_000_000),
+        accounts::OpenPosition {
+            owner: o,
+            user: k.user(&o, 2),
+            position: k.position(&o, 2),
+            quote_mint: k.mint,
+            pool: k.pool(),
+            pool_market: k.pool_market(),
+            system_program: system_program::ID,
+        },
+        instruction::OpenPosition {
+            sub_id: 2,
+            symbol: SYMBOL.to_string(),
+            side: position_manager::Side::Short,
+            size: 1_000,
+            leverage: 25,
+            entry_price: 180_000_000,
+        },
+    );
+
+    let modify = || accounts::ModifyPosition {
+        owner: o,
+        user: k.user(&o, 0),
+        position: k.position(&o, 0),
+        quote_mint: k.mint,
+        pool: k.pool(),
+        pool_market: k.pool_market(),
+        user_quote_ata: k.ata(&o, &k.mint),
+        vault: k.vault(),
+        vault_authority: k.vault_authority(),
+        token_program: TOKEN_PROGRAM_ID,
+    };
+    let actions = [
+        (sdk::ModifyKind::IncreaseSize { add_size: 1, price: 2, add_margin: 3 }, ModifyKind::IncreaseSize { add_size: 1, price: 2, add_margin: 3 }),
+        (sdk::ModifyKind::DecreaseSize { reduce_size: 4, price: 5 }, ModifyKind::DecreaseSize { reduce_size: 4, price: 5 }),
+        (sdk::ModifyKind::AddMargin { amount: 6 }, ModifyKind::AddMargin { amount: 6 }),
+        (sdk::ModifyKind::RemoveMargin { amount: 7, price: 8 }, ModifyKind::RemoveMargin { amount: 7, price: 8 }),
+    ];
+    for (ours, theirs) in actions {
+        assert_ix(k.b.modify_position(&o, 0, SYMBOL, ours), modify(), instruction::ModifyPosition { action: theirs });
+    }
+
+    let position = k.position(&o, 0);
+    assert_ix(
+        k.b.close_position(&o, 0, SYMBOL, 190_000_000),
+        accounts::ClosePosition {
+            owner: o,
+            user: k.user(&o, 0),
+            position,
+            quote_mint: k.mint,
+            pool: k.pool(),
+            pool_market: k.pool_market(),
+            user_quote_ata: k.ata(&o, &k.mint),
+            vault: k.vault(),
+            vault_authority: k.vault_authority(),
+            top_up: k.top_up(&position),
+            token_program: TOKEN_PROGRAM_ID,
+        },
+        instruction::ClosePosition { exit_price: 190_000_000 },
+    );
+
+    let leg = sdk::BatchLeg { price: 9 };
+    let mut got = k.b.close_all_positions(&o, 1, &[(SYMBOL, leg)], 5_000);
+    let remaining = got.accounts.split_off(8);
+    assert_eq!(remaining.iter().map(|m| (m.pubkey, m.is_writable)).collect::<Vec<_>>(), vec![(k.position(&o, 1), true), (k.pool_market(), true), (k.top_up(&k.position(&o, 1)), true)]);
+    assert_ix(
+        got,
+        accounts::CloseAllPositions {
+            owner: o,
+            user: k.user(&o, 1),
+            quote_mint: k.mint,
+            pool: k.pool(),
+            user_quote_ata: k.ata(&o, &k.mint),
+            vault: k.vault(),
+            vault_authority: k.vault_authority(),
+            token_program: TOKEN_PROGRAM_ID,
+        },
+        instruction::CloseAllPositions { legs: vec![BatchLeg { price: 9 }], reduce_bps: 5_000 },
+    );
+}
+
+#[test]
+fn collateral_instructions_match_program() {
+    let k = Keys::new();
+    let o = k.owner;
+    assert_ix(
+        k.b.deposit_collateral(&o, 3, 500),
+        accounts::DepositCollateral {
+            owner: o,
+            user: k.user(&o, 3),
+            quote_mint: k.mint,
+            user_quote_ata: k.ata(&o, &k.mint),
+            vault: k.vault(),
+            vault_authority: k.vault_authority(),
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        instruction::DepositCollateral { sub_id: 3, amount: 500 },
+    );
+    assert_ix(
+        k.b.withdraw_collateral(&o, 3, 400),
+        accounts::WithdrawCollateral {
+            owner: o,
+            user: k.user(&o, 3),
+            quote_mint: k.mint,
+            user_quote_ata: k.ata(&o, &k.mint),
+            vault: k.vault(),
+            vault_authority: k.vault_authority(),
+            token_program: TOKEN_PROGRAM_ID,
+        },
+        instruction::WithdrawCollateral { amount: 400 },
+    );
+    assert_ix(
+        k.b.transfer_collateral(&o, 0, 1, 300),
+        accounts::TransferCollateral { owner: o, from_user: k.user(&o, 0), to_user: k.user(&o, 1), system_program: system_program::ID },
+        instruction::TransferCollateral { from_sub_id: 0, to_sub_id: 1, amount: 300 },
+    );
+    assert_ix(
+        k.b.set_margin_mode(&o, 1, sdk::MarginMode::Cross),
+        accounts::SetMarginMode { owner: o, user: k.user(&o, 1) },
+        instruction::SetMarginMode { margin_mode: position_manager::MarginMode::Cross },
+    );
+}
+
+#[test]
+fn pool_instructions_match_program() {
+    let k = Keys::new();
+    let a = k.owner;
+    let (ours, theirs) = params();
+    assert_ix(
+        k.b.initialize_pool(&a, ours),
+        accounts::InitializePool {
+            authority: a,
+            pool: k.pool(),
+            quote_mint: k.mint,
+            lp_mint: k.lp_mint(),
+            vault: k.vault(),
+            vault_authority: k.vault_authority(),
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        instruction::InitializePool { params: theirs },
+    );
+    assert_ix(k.b.update_pool_params(&a, ours), accounts::UpdatePoolParams { authority: a, pool: k.pool() }, instruction::UpdatePoolParams { params: theirs });
+    assert_ix(
+        k.b.initialize_pool_market(&a, SYMBOL),
+        accounts::InitializePoolMarket { authority: a, pool: k.pool(), pool_market: k.pool_market(), system_program: system_program::ID },
+        instruction::InitializePoolMarket { symbol: SYMBOL.to_string() },
+    );
+    assert_ix(
+        k.b.set_mark_price(&a, SYMBOL, 181_000_000),
+        accounts::SetMarkPrice { authority: a, pool: k.pool(), pool_market: k.pool_market() },
+        instruction::SetMarkPrice { price: 181_000_000 },
+    );
+    // every market of the pool follows as a read-only remaining account
+    let mut add = k.b.add_liquidity(&a, 1_000, &[SYMBOL]);
+    assert_eq!(add.accounts.split_off(8), vec![AccountMeta::new_readonly(k.pool_market(), false)]);
+    assert_ix(
+        add,
+        accounts::AddLiquidity {
+            provider: a,
+            pool: k.pool(),
+            quote_mint: k.mint,
+            lp_mint: k.lp_mint(),
+            provider_quote_ata: k.ata(&a, &k.mint),
+            provider_lp_ata: k.ata(&a, &k.lp_mint()),
+            vault: k.vault(),
+            token_program: TOKEN_PROGRAM_ID,
+        },
+        instruction::AddLiquidity { amount: 1_000 },
+    );
+    let mut remove = k.b.remove_liquidity(&a, 10, &[SYMBOL]);
+    assert_eq!(remove.accounts.split_off(9), vec![AccountMeta::new_readonly(k.pool_market(), false)]);
+    assert_ix(
+        remove,
+        accounts::RemoveLiquidity {
+            provider: a,
+            pool: k.pool(),
+            quote_mint: k.mint,
+            lp_mint: k.lp_mint(),
+            provider_quote_ata: k.ata(&a, &k.mint),
+            provider_lp_ata: k.ata(&a, &k.lp_mint()),
+            vault: k.vault(),
+            vault_authority: k.vault_authority(),
+            token_program: TOKEN_PROGRAM_ID,
+        },
+        instruction::RemoveLiquidity { shares: 10 },
+    );
+}
+
+#[test]
+fn transfer_and_top_up_instructions_match_program() {
+    let k = Keys::new();
+    let o = k.owner;
+    let r = Pubkey::new_unique();
+    let transfer = |approval: Option<Pubkey>| accounts::TransferPosition {
+        owner: o,
+        recipient: r,
+        from_user: k.user(&o, 0),
+        position: k.position(&o, 0),
+        to_user: k.user(&r, 4),
+        new_position: k.position(&r, 4),
+        approval,
+        top_up: k.top_up(&k.position(&o, 0)),
+        system_program: system_program::ID,
+    };
+    assert_ix(k.b.transfer_position(&o, 0, SYMBOL, &r, 4, false), transfer(Some(k.approval(&r, 4, 0))), instruction::TransferPosition { to_sub_id: 4 });
+    // co-signed: no approval, and the recipient is flagged as a signer
+    let mut cosigned = k.b.transfer_position(&o, 0, SYMBOL, &r, 4, true);
+    assert!(cosigned.accounts[1].is_signer);
+    cosigned.accounts[1].is_signer = false;
+    assert_ix(cosigned, transfer(None), instruction::TransferPosition { to_sub_id: 4 });
+
+    let approve = instruction::ApprovePositionTransfer {
+        from_owner: o,
+        from_sub_id: 0,
+        to_sub_id: 4,
+        symbol: SYMBOL.to_string(),
+        max_size: 10,
+        min_margin: 1_000,
+    };
+    assert_ix(
+        k.b.approve_position_transfer(&r, &o, 0, 4, SYMBOL, 10, 1_000),
+        accounts::ApprovePositionTransfer { recipient: r, approval: k.approval(&r, 4, 0), system_program: system_program::ID },
+        approve,
+    );
+    assert_ix(
+        k.b.revoke_position_transfer(&r, &o, 0, 4, SYMBOL),
+        accounts::RevokePositionTransfer { recipient: r, approval: k.approval(&r, 4, 0) },
+        instruction::RevokePositionTransfer {},
+    );
+
+    let position = k.position(&o, 0);
+    assert_ix(
+        k.b.set_auto_top_up(&o, 0, SYMBOL, 100_000, 5_000),
+        accounts::SetAutoTopUp { owner: o, position, settings: k.top_up(&position), system_program: system_program::ID },
+        instruction::SetAutoTopUp { target_mr: 100_000, max_total: 5_000 },
+    );
+    assert_ix(
+        k.b.disable_auto_top_up(&o, 0, SYMBOL),
+        accounts::DisableAutoTopUp { owner: o, settings: k.top_up(&position) },
+        instruction::DisableAutoTopUp {},
+    );
+    let keeper = Pubkey::new_unique();
+    assert_ix(
+        k.b.auto_top_up(&keeper, &o, 0, SYMBOL),
+        accounts::AutoTopUp { keeper, user: k.user(&o, 0), position, pool: k.pool(), pool_market: k.pool_market(), settings: k.top_up(&position) },
+        instruction::AutoTopUp {},
+    );
+    assert_ix(
+        k.b.get_position_health(&o, 0, SYMBOL, 150),
+        accounts::GetPositionHealth { position },
+        instruction::GetPositionHealth { mark_price: 150 },
+    );
+}
+
+#[test]
+fn account_decoders_read_program_layouts() {
+    let owner = Pubkey::new_unique();
+    let position = position_manager::Position {
+        owner,
+        sub_id: 1,
+        symbol: SYMBOL.to_string(),
+        side: position_manager::Side::Long,
+        size: 10,
+        entry_price: 100,
+        margin: 50,
+        leverage: 20,
+        unrealized_pnl: -3,
+        realized_pnl: 7,
+        funding_accrued: -1,
+        liquidation_price: 95,
+        last_update: 1_700_000_000,
+        bump: 254,
+        funding_index: -12_345,
+    };
+    let mut data = Vec::new();
+    position.try_serialize(&mut data).unwrap();
+    data.resize(position_manager::Position::space(position_manager::constants::MAX_SYMBOL_LEN), 0);
+    let decoded = perp_sdk::accounts::Position::try_deserialize(&data).unwrap();
+    assert_eq!((decoded.owner, decoded.sub_id, decoded.symbol.as_str(), decoded.side), (owner, 1, SYMBOL, sdk::Side::Long));
+    assert_eq!((decoded.size, decoded.entry_price, decoded.margin, decoded.leverage), (10, 100, 50, 20));
+    assert_eq!((decoded.unrealized_pnl, decoded.realized_pnl, decoded.funding_accrued), (-3, 7, -1));
+    assert_eq!((decoded.liquidation_price, decoded.last_update, decoded.bump, decoded.funding_index), (95, 1_700_000_000, 254, -12_345));
+    assert!(perp_sdk::accounts::UserAccount::try_deserialize(&data).is_err());
+
+    let user = position_manager::UserAccount {
+        owner,
+        sub_id: 1,
+        margin_mode: position_manager::MarginMode::Cross,
+        total_collateral: 1_000,
+        locked_collateral: 400,
+        total_pnl: -20,
+        position_count: 2,
+        bump: 253,
+    };
+    let mut data = Vec::new();
+    user.try_serialize(&mut data).unwrap();
+    let decoded = perp_sdk::accounts::UserAccount::try_deserialize(&data).unwrap();
+    assert_eq!(decoded.margin_mode, sdk::MarginMode::Cross);
+    assert_eq!((decoded.total_collateral, decoded.locked_collateral, decoded.free_collateral()), (1_000, 400, 600));
+    assert_eq!((decoded.total_pnl, decoded.position_count, decoded.bump), (-20, 2, 253));
+}
+
+#[test]
+fn constants_match_program() {
+    assert_eq!(perp_sdk::constants::MAX_MARK_AGE_SECS, position_manager::constants::MAX_MARK_AGE_SECS);
+    assert_eq!(perp_sdk::constants::MAX_BATCH_POSITIONS, position_manager::instructions::close_all_positions::MAX_BATCH_POSITIONS);
+}
+
+#[test]
+fn error_codes_match_program() {
+    use PerpError::*;
+    let program = [
+        Overflow, Underflow, DivisionByZero, InvalidLeverage, LeverageExceeded, InvalidSize, InvalidAmount, SymbolTooLong,
+        InsufficientMarginForIncrease, MaintenanceBreach, InvalidState, InvalidSubAccount, InsufficientFreeCollateral,
+        PositionsOpen, InvalidPoolParams, PoolUtilizationExceeded, PoolSkewExceeded, InsufficientPoolLiquidity,
+        PoolMarketMismatch, MissingTransferApproval, TopUpNotNeeded, TopUpUnavailable, InitialMarginTooLow, StaleMarkPrice,
+        TransferApprovalMismatch, PriceOutsideMarkBand,
+    ];
+    assert_eq!(program.len(), perp_sdk::PerpError::ALL.len());
+    for (theirs, ours) in program.into_iter().zip(perp_sdk::PerpError::ALL) {
+        assert_eq!(format!("{theirs:?}"), format!("{ours:?}"));
+        assert_eq!(theirs.to_string(), ours.message());
+        let code = u32::from(theirs);
+        assert_eq!(ours.code(), code);
+        assert_eq!(perp_sdk::PerpError::from_code(code), Some(ours));
+        assert_eq!(perp_sdk::PerpError::from_instruction_error(&solana_sdk::instruction::InstructionError::Custom(code)), Some(ours));
+    }
+    assert_eq!(perp_sdk::PerpError::from_code(5999), None);
+    assert_eq!(perp_sdk::PerpError::from_code(6000 + program.len() as u32), None);
+}
+
+// Decodes what emit! logs and checks the decoded struct re-encodes to the same bytes.
+macro_rules! assert_event {
+    ($event:expr, $variant:ident) => {{
+        let data = $event.data();
+        match perp_events::PerpEvent::decode(&data).unwrap() {
+            perp_events::PerpEvent::$variant(decoded) => assert_eq!(decoded.try_to_vec().unwrap(), data[8..]),
+            other => panic!("{} decoded as {}", stringify!($variant), other.name()),
+        }
+    }};
+}
+
+#[test]
+fn event_decoder_reads_program_events() {
+    use position_manager::events::*;
+    let (owner, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let symbol = SYMBOL.to_string();
+    let side = position_manager::Side::Short;
+
+    assert_event!(
+        PositionOpened { owner, symbol: symbol.clone(), side, size: 1, leverage: 2, entry_price: 3, initial_margin: 4, liquidation_price: 5, sub_id: 6 },
+        PositionOpened
+    );
+    assert_event!(
+        PositionModified { owner, symbol: symbol.clone(), size: 1, margin: 2, leverage: 3, price: 4, unrealized_pnl: -5, liquidation_price: 6, sub_id: 7 },
+        PositionModified
+    );
+    assert_event!(
+        PositionClosed { owner, symbol: symbol.clone(), size_closed: 1, exit_price: 2, realized_pnl: -3, payout: 4, sub_id: 5 },
+        PositionClosed
+    );
+    assert_event!(CollateralDeposited { owner, sub_id: 1, amount: 2, total_collateral: 3 }, CollateralDeposited);
+    assert_event!(CollateralWithdrawn { owner, sub_id: 1, amount: 2, total_collateral: 3 }, CollateralWithdrawn);
+    assert_event!(CollateralTransferred { owner, from_sub_id: 1, to_sub_id: 2, amount: 3 }, CollateralTransferred);
+    assert_event!(
+        PoolFill {
+            owner,
+            symbol: symbol.clone(),
+            taker_side: side,
+            notional: 1,
+            oracle_price: 2,
+            execution_price: 3,
+            spread_bps: 4,
+            long_open_interest: 5,
+            short_open_interest: 6,
+        },
+        PoolFill
+    );
+    assert_event!(PoolPnlSettled { owner, symbol: symbol.clone(), pool_pnl: -1, liquidity: 2 }, PoolPnlSettled);
+    assert_event!(LiquidityAdded { provider: owner, amount: 1, shares: 2, liquidity: 3 }, LiquidityAdded);
+    assert_event!(LiquidityRemoved { provider: owner, amount: 1, shares: 2, liquidity: 3 }, LiquidityRemoved);
+    assert_event!(
+        PositionTransferred { from_owner: owner, from_sub_id: 1, to_owner: other, to_sub_id: 2, symbol, size: 3, margin: 4, position: other },
+        PositionTransferred
+    );
+    assert_event!(AutoTopUpConfigured { owner, position: other, target_mr: 1, max_total: 2 }, AutoTopUpConfigured);
+    assert_event!(
+        MarginToppedUp {
+            owner,
+            position: other,
+            keeper: other,
+            amount: 1,
+            mark_price: 2,
+            margin_ratio_before: -3,
+            margin: 4,
+            liquidation_price: 5,
+        },
+        MarginToppedUp
+    );
+}
@@ -0,0 +1,9 @@
+pub const BPS_DENOM: u128 = 10_000;
+pub const SHARE_PRICE_SCALE: u128 = 1_000_000; // NAV per share, 1e6
+pub const SECONDS_PER_YEAR: u128 = 365 * 24 * 60 * 60;
+pub const MAX_MANAGEMENT_FEE_BPS: u16 = 500;   // 5% / year
+pub const MAX_PERFORMANCE_FEE_BPS: u16 = 5_000; // 50% of gains
+pub const MAX_VAULT_POSITIONS: usize = 8;
+pub const MAX_NAV_AGE_SECS: i64 = 60;
+pub const POSITION_SUB_ID: u16 = 0; // vault trades from sub-account 0 of its trader PDA
+pub const MIN_SEED_AMOUNT: u64 = 1_000; // manager's deposit at init; its shares are never redeemable
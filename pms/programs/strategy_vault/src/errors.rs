@@ -0,0 +1,15 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum VaultError {
+    #[msg("Math overflow")] Overflow,
+    #[msg("Invalid amount")] InvalidAmount,
+    #[msg("Fee above maximum")] FeeTooHigh,
+    #[msg("Only the vault manager can do this")] Unauthorized,
+    #[msg("NAV is stale; mark_nav first")] StaleNav,
+    #[msg("Position list does not match the vault's open positions")] PositionMismatch,
+    #[msg("Vault already holds the maximum number of positions")] TooManyPositions,
+    #[msg("Not enough idle collateral to redeem")] InsufficientLiquidity,
+    #[msg("Vault NAV is zero")] ZeroNav,
+    #[msg("Pool market does not match the position")] MarketMismatch,
+}
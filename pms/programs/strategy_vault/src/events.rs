@@ -0,0 +1,34 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct VaultDeposited {
+    pub vault: Pubkey,
+    pub depositor: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub nav: u64,
+}
+
+#[event]
+pub struct VaultRedeemed {
+    pub vault: Pubkey,
+    pub depositor: Pubkey,
+    pub shares: u64,
+    pub amount: u64,
+    pub nav: u64,
+}
+
+#[event]
+pub struct NavMarked {
+    pub vault: Pubkey,
+    pub idle: u64,
+    pub position_equity: u64,
+    pub nav: u64,
+}
+
+#[event]
+pub struct FeesCollected {
+    pub vault: Pubkey,
+    pub manager: Pubkey,
+    pub shares: u64,
+}
@@ -0,0 +1,65 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount};
+
+use crate::errors::VaultError;
+use crate::events::FeesCollected;
+use crate::state::accounts::*;
+
+// Permissionless: accrues fees up to now and mints the owed shares to the manager.
+pub(crate) fn handler(ctx: Context<CollectFees>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(ctx.accounts.strategy_vault.nav_fresh(now), VaultError::StaleNav);
+
+    let minted = ctx.accounts.share_mint.supply;
+    let nav = ctx.accounts.strategy_vault.nav(ctx.accounts.quote_account.amount)?;
+    ctx.accounts.strategy_vault.accrue_fees(minted, nav, now)?;
+
+    let shares = ctx.accounts.strategy_vault.pending_fee_shares;
+    if shares > 0 {
+        let vault = &ctx.accounts.strategy_vault;
+        let id_bytes = vault.vault_id.to_le_bytes();
+        let signer_seeds: &[&[u8]] = &[b"strategy_vault", vault.manager.as_ref(), &id_bytes, &[vault.bump]];
+        token::mint_to(ctx.accounts.mint_fee_shares_ctx().with_signer(&[signer_seeds]), shares)?;
+        ctx.accounts.strategy_vault.pending_fee_shares = 0;
+    }
+
+    emit!(FeesCollected {
+        vault: ctx.accounts.strategy_vault.key(),
+        manager: ctx.accounts.strategy_vault.manager,
+        shares,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    #[account(
+        mut,
+        seeds = [b"strategy_vault", strategy_vault.manager.as_ref(), &strategy_vault.vault_id.to_le_bytes()],
+        bump = strategy_vault.bump,
+        has_one = share_mint,
+        has_one = quote_account
+    )]
+    pub strategy_vault: Account<'info, StrategyVault>,
+
+    #[account(mut)]
+    pub share_mint: Account<'info, Mint>,
+    pub quote_account: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = share_mint, token::authority = strategy_vault.manager)]
+    pub manager_shares: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> CollectFees<'info> {
+    pub fn mint_fee_shares_ctx(&self) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
+        let cpi_accounts = MintTo {
+            mint: self.share_mint.to_account_info(),
+            to: self.manager_shares.to_account_info(),
+            authority: self.strategy_vault.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+}
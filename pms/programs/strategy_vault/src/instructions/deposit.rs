@@ -0,0 +1,85 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount, Transfer};
+
+use crate::errors::VaultError;
+use crate::events::VaultDeposited;
+use crate::state::accounts::*;
+
+pub(crate) fn handler(ctx: Context<Deposit>, amount: u64) -> Result<()> {
+    require!(amount > 0, VaultError::InvalidAmount);
+    let now = Clock::get()?.unix_timestamp;
+    require!(ctx.accounts.strategy_vault.nav_fresh(now), VaultError::StaleNav);
+
+    let minted = ctx.accounts.share_mint.supply;
+    let nav = ctx.accounts.strategy_vault.nav(ctx.accounts.quote_account.amount)?;
+    ctx.accounts.strategy_vault.accrue_fees(minted, nav, now)?;
+    let supply = ctx.accounts.strategy_vault.effective_supply(minted)?;
+
+    // the seed shares keep the supply above zero (initialize_vault)
+    require!(nav > 0, VaultError::ZeroNav);
+    let shares = u64::try_from((amount as u128) * (supply as u128) / (nav as u128)).map_err(|_| VaultError::Overflow)?;
+    require!(shares > 0, VaultError::InvalidAmount);
+
+    token::transfer(ctx.accounts.transfer_to_vault_ctx(), amount)?;
+
+    let vault = &ctx.accounts.strategy_vault;
+    let id_bytes = vault.vault_id.to_le_bytes();
+    let signer_seeds: &[&[u8]] = &[b"strategy_vault", vault.manager.as_ref(), &id_bytes, &[vault.bump]];
+    token::mint_to(ctx.accounts.mint_shares_ctx().with_signer(&[signer_seeds]), shares)?;
+
+    emit!(VaultDeposited {
+        vault: ctx.accounts.strategy_vault.key(),
+        depositor: ctx.accounts.depositor.key(),
+        amount,
+        shares,
+        nav,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    pub depositor: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy_vault", strategy_vault.manager.as_ref(), &strategy_vault.vault_id.to_le_bytes()],
+        bump = strategy_vault.bump,
+        has_one = share_mint,
+        has_one = quote_account
+    )]
+    pub strategy_vault: Account<'info, StrategyVault>,
+
+    #[account(mut)]
+    pub share_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub quote_account: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = strategy_vault.quote_mint)]
+    pub depositor_quote: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = share_mint)]
+    pub depositor_shares: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> Deposit<'info> {
+    pub fn transfer_to_vault_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.depositor_quote.to_account_info(),
+            to: self.quote_account.to_account_info(),
+            authority: self.depositor.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+
+    pub fn mint_shares_ctx(&self) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
+        let cpi_accounts = MintTo {
+            mint: self.share_mint.to_account_info(),
+            to: self.depositor_shares.to_account_info(),
+            authority: self.strategy_vault.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+}
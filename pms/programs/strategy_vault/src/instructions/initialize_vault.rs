@@ -0,0 +1,146 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{self, Transfer as SystemTransfer};
+use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount, Transfer};
+
+use crate::constants::*;
+use crate::errors::VaultError;
+use crate::state::accounts::*;
+
+pub(crate) fn handler(
+    ctx: Context<InitializeVault>,
+    vault_id: u64,
+    management_fee_bps: u16,
+    performance_fee_bps: u16,
+    trader_lamports: u64,
+    seed_amount: u64,
+) -> Result<()> {
+    require!(management_fee_bps <= MAX_MANAGEMENT_FEE_BPS, VaultError::FeeTooHigh);
+    require!(performance_fee_bps <= MAX_PERFORMANCE_FEE_BPS, VaultError::FeeTooHigh);
+    require!(seed_amount >= MIN_SEED_AMOUNT, VaultError::InvalidAmount);
+
+    // The trader PDA pays rent for the position_manager accounts it opens.
+    if trader_lamports > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                SystemTransfer {
+                    from: ctx.accounts.manager.to_account_info(),
+                    to: ctx.accounts.trader.to_account_info(),
+                },
+            ),
+            trader_lamports,
+        )?;
+    }
+
+    let vault = &mut ctx.accounts.strategy_vault;
+    vault.manager = ctx.accounts.manager.key();
+    vault.vault_id = vault_id;
+    vault.quote_mint = ctx.accounts.quote_mint.key();
+    vault.share_mint = ctx.accounts.share_mint.key();
+    vault.quote_account = ctx.accounts.quote_account.key();
+    vault.trader = ctx.accounts.trader.key();
+    vault.management_fee_bps = management_fee_bps;
+    vault.performance_fee_bps = performance_fee_bps;
+    vault.high_water_mark = SHARE_PRICE_SCALE as u64;
+    vault.last_fee_ts = Clock::get()?.unix_timestamp;
+    vault.pending_fee_shares = 0;
+    vault.position_equity = 0;
+    vault.marked_at = 0;
+    vault.positions = Vec::new();
+    vault.bump = ctx.bumps.strategy_vault;
+    vault.trader_bump = ctx.bumps.trader;
+
+    // The manager seeds the vault 1:1 into seed_shares, which only the vault could
+    // move and nothing ever does: the supply never drops back to zero, so no later
+    // depositor can mint against collateral already sitting in quote_account.
+    token::transfer(ctx.accounts.transfer_seed_ctx(), seed_amount)?;
+    let vault = &ctx.accounts.strategy_vault;
+    let id_bytes = vault.vault_id.to_le_bytes();
+    let signer_seeds: &[&[u8]] = &[b"strategy_vault", vault.manager.as_ref(), &id_bytes, &[vault.bump]];
+    token::mint_to(ctx.accounts.mint_seed_shares_ctx().with_signer(&[signer_seeds]), seed_amount)?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct InitializeVault<'info> {
+    #[account(mut)]
+    pub manager: Signer<'info>,
+
+    #[account(
+        init,
+        payer = manager,
+        seeds = [b"strategy_vault", manager.key().as_ref(), &vault_id.to_le_bytes()],
+        bump,
+        space = StrategyVault::SPACE
+    )]
+    pub strategy_vault: Account<'info, StrategyVault>,
+
+    /// CHECK: system-owned PDA, only ever used as a signer via seeds
+    #[account(
+        mut,
+        seeds = [b"trader", strategy_vault.key().as_ref()],
+        bump
+    )]
+    pub trader: SystemAccount<'info>,
+
+    pub quote_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = manager,
+        seeds = [b"share_mint", strategy_vault.key().as_ref()],
+        bump,
+        mint::decimals = quote_mint.decimals,
+        mint::authority = strategy_vault
+    )]
+    pub share_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = manager,
+        seeds = [b"vault_quote", strategy_vault.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = trader
+    )]
+    pub quote_account: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = manager,
+        seeds = [b"seed_shares", strategy_vault.key().as_ref()],
+        bump,
+        token::mint = share_mint,
+        token::authority = strategy_vault
+    )]
+    pub seed_shares: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = quote_mint, token::authority = manager)]
+    pub manager_quote: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+impl<'info> InitializeVault<'info> {
+    pub fn transfer_seed_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.manager_quote.to_account_info(),
+            to: self.quote_account.to_account_info(),
+            authority: self.manager.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+
+    pub fn mint_seed_shares_ctx(&self) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
+        let cpi_accounts = MintTo {
+            mint: self.share_mint.to_account_info(),
+            to: self.seed_shares.to_account_info(),
+            authority: self.strategy_vault.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+}
@@ -0,0 +1,116 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+use position_manager::cpi::accounts::{ClosePosition, WithdrawCollateral};
+use position_manager::program::PositionManager;
+
+use crate::errors::VaultError;
+use crate::instructions::manager_open_position::{free_collateral, pool_mark};
+use crate::state::accounts::*;
+
+// Closes a vault position at the pool market's mark; the payout lands back in the
+// vault's idle collateral.
+pub(crate) fn handler(ctx: Context<ManagerClosePosition>) -> Result<()> {
+    let position_key = ctx.accounts.position.key();
+    require!(ctx.accounts.strategy_vault.positions.contains(&position_key), VaultError::PositionMismatch);
+    let mark = pool_mark(&ctx.accounts.pool_market)?;
+
+    let vault_key = ctx.accounts.strategy_vault.key();
+    let signer_seeds: &[&[u8]] = &[b"trader", vault_key.as_ref(), &[ctx.accounts.strategy_vault.trader_bump]];
+    position_manager::cpi::close_position(
+        ctx.accounts.close_position_ctx().with_signer(&[signer_seeds]),
+        mark,
+    )?;
+
+    // profits of earlier partial reduces are still in the sub-account
+    let unused = free_collateral(&ctx.accounts.user)?;
+    if unused > 0 {
+        position_manager::cpi::withdraw_collateral(ctx.accounts.withdraw_collateral_ctx().with_signer(&[signer_seeds]), unused)?;
+    }
+
+    let vault = &mut ctx.accounts.strategy_vault;
+    vault.positions.retain(|p| *p != position_key);
+    vault.marked_at = 0;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ManagerClosePosition<'info> {
+    pub manager: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy_vault", strategy_vault.manager.as_ref(), &strategy_vault.vault_id.to_le_bytes()],
+        bump = strategy_vault.bump,
+        has_one = manager,
+        has_one = trader,
+        has_one = quote_account,
+        has_one = quote_mint
+    )]
+    pub strategy_vault: Account<'info, StrategyVault>,
+
+    #[account(mut)]
+    pub trader: SystemAccount<'info>,
+
+    /// CHECK: position_manager UserAccount, validated by position_manager
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+    /// CHECK: position_manager Position, validated by position_manager
+    #[account(mut)]
+    pub position: UncheckedAccount<'info>,
+
+    pub quote_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub quote_account: Account<'info, TokenAccount>,
+
+    /// CHECK: position_manager LiquidityPool, validated by position_manager
+    #[account(mut)]
+    pub pool: UncheckedAccount<'info>,
+    /// CHECK: position_manager PoolMarket for the position's symbol, validated by position_manager
+    #[account(mut)]
+    pub pool_market: UncheckedAccount<'info>,
+
+    /// CHECK: position_manager vault, validated by position_manager
+    #[account(mut)]
+    pub pm_vault: UncheckedAccount<'info>,
+    /// CHECK: position_manager vault authority, validated by position_manager
+    #[account(mut)]
+    pub pm_vault_authority: UncheckedAccount<'info>,
+    /// CHECK: position_manager TopUpSettings address of the position, validated by position_manager
+    #[account(mut)]
+    pub pm_top_up: UncheckedAccount<'info>,
+
+    pub position_manager_program: Program<'info, PositionManager>,
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> ManagerClosePosition<'info> {
+    pub fn close_position_ctx(&self) -> CpiContext<'_, '_, '_, 'info, ClosePosition<'info>> {
+        let cpi_accounts = ClosePosition {
+            owner: self.trader.to_account_info(),
+            user: self.user.to_account_info(),
+            position: self.position.to_account_info(),
+            quote_mint: self.quote_mint.to_account_info(),
+            pool: self.pool.to_account_info(),
+            pool_market: self.pool_market.to_account_info(),
+            user_quote_ata: self.quote_account.to_account_info(),
+            vault: self.pm_vault.to_account_info(),
+            vault_authority: self.pm_vault_authority.to_account_info(),
+            top_up: self.pm_top_up.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+        };
+        CpiContext::new(self.position_manager_program.to_account_info(), cpi_accounts)
+    }
+
+    pub fn withdraw_collateral_ctx(&self) -> CpiContext<'_, '_, '_, 'info, WithdrawCollateral<'info>> {
+        let cpi_accounts = WithdrawCollateral {
+            owner: self.trader.to_account_info(),
+            user: self.user.to_account_info(),
+            quote_mint: self.quote_mint.to_account_info(),
+            user_quote_ata: self.quote_account.to_account_info(),
+            vault: self.pm_vault.to_account_info(),
+            vault_authority: self.pm_vault_authority.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+        };
+        CpiContext::new(self.position_manager_program.to_account_info(), cpi_accounts)
+    }
+}
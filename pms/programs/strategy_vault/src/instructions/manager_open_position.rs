@@ -0,0 +1,156 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+use position_manager::cpi::accounts::{DepositCollateral, OpenPosition, WithdrawCollateral};
+use position_manager::program::PositionManager;
+use position_manager::{PoolMarket, Side, UserAccount};
+
+use crate::constants::*;
+use crate::errors::VaultError;
+use crate::state::accounts::*;
+
+// Opens a perp position for the vault through position_manager; the trader PDA
+// is the position owner. Up to `margin` of idle collateral is deposited into the
+// trader's sub-account for the initial margin, and whatever the open doesn't lock
+// is withdrawn again, so the sub-account never holds free collateral between calls.
+// The leg fills at the pool market's mark, never at a price the manager picks.
+pub(crate) fn handler(ctx: Context<ManagerOpenPosition>, symbol: String, side: Side, size: u64, leverage: u16, margin: u64) -> Result<()> {
+    require!(ctx.accounts.strategy_vault.positions.len() < MAX_VAULT_POSITIONS, VaultError::TooManyPositions);
+    require!(margin > 0, VaultError::InvalidAmount);
+
+    let mark = pool_mark(&ctx.accounts.pool_market)?;
+
+    let vault_key = ctx.accounts.strategy_vault.key();
+    let signer_seeds: &[&[u8]] = &[b"trader", vault_key.as_ref(), &[ctx.accounts.strategy_vault.trader_bump]];
+    position_manager::cpi::deposit_collateral(
+        ctx.accounts.deposit_collateral_ctx().with_signer(&[signer_seeds]),
+        POSITION_SUB_ID,
+        margin,
+    )?;
+    position_manager::cpi::open_position(
+        ctx.accounts.open_position_ctx().with_signer(&[signer_seeds]),
+        POSITION_SUB_ID,
+        symbol,
+        side,
+        size,
+        leverage,
+        mark,
+    )?;
+
+    let unused = free_collateral(&ctx.accounts.user)?;
+    if unused > 0 {
+        position_manager::cpi::withdraw_collateral(ctx.accounts.withdraw_collateral_ctx().with_signer(&[signer_seeds]), unused)?;
+    }
+
+    let vault = &mut ctx.accounts.strategy_vault;
+    vault.positions.push(ctx.accounts.position.key());
+    vault.marked_at = 0;
+
+    Ok(())
+}
+
+// The fresh mark of the pool market the leg trades on, read the same way mark_nav
+// does. position_manager checks the account is the position's market during the CPI.
+pub(crate) fn pool_mark(pool_market: &AccountInfo) -> Result<u64> {
+    require_keys_eq!(*pool_market.owner, position_manager::ID, VaultError::MarketMismatch);
+    let data = pool_market.try_borrow_data()?;
+    PoolMarket::try_deserialize(&mut &data[..])?.fresh_mark(Clock::get()?.unix_timestamp)
+}
+
+// Collateral in the trader's sub-account that no position has locked. NAV only counts
+// idle collateral and position equity, so every manager instruction withdraws this.
+pub(crate) fn free_collateral(user: &AccountInfo) -> Result<u64> {
+    let data = user.try_borrow_data()?;
+    Ok(UserAccount::try_deserialize(&mut &data[..])?.free_collateral())
+}
+
+#[derive(Accounts)]
+pub struct ManagerOpenPosition<'info> {
+    pub manager: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy_vault", strategy_vault.manager.as_ref(), &strategy_vault.vault_id.to_le_bytes()],
+        bump = strategy_vault.bump,
+        has_one = manager,
+        has_one = trader,
+        has_one = quote_account,
+        has_one = quote_mint
+    )]
+    pub strategy_vault: Account<'info, StrategyVault>,
+
+    #[account(mut)]
+    pub trader: SystemAccount<'info>,
+
+    /// CHECK: position_manager UserAccount, validated by position_manager
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+    /// CHECK: position_manager Position, validated by position_manager
+    #[account(mut)]
+    pub position: UncheckedAccount<'info>,
+
+    pub quote_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub quote_account: Account<'info, TokenAccount>,
+
+    /// CHECK: position_manager LiquidityPool, validated by position_manager
+    #[account(mut)]
+    pub pool: UncheckedAccount<'info>,
+    /// CHECK: position_manager PoolMarket for the position's symbol, validated by position_manager
+    #[account(mut)]
+    pub pool_market: UncheckedAccount<'info>,
+
+    /// CHECK: position_manager vault, validated by position_manager
+    #[account(mut)]
+    pub pm_vault: UncheckedAccount<'info>,
+    /// CHECK: position_manager vault authority, validated by position_manager
+    #[account(mut)]
+    pub pm_vault_authority: UncheckedAccount<'info>,
+
+    pub position_manager_program: Program<'info, PositionManager>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+impl<'info> ManagerOpenPosition<'info> {
+    pub fn deposit_collateral_ctx(&self) -> CpiContext<'_, '_, '_, 'info, DepositCollateral<'info>> {
+        let cpi_accounts = DepositCollateral {
+            owner: self.trader.to_account_info(),
+            user: self.user.to_account_info(),
+            quote_mint: self.quote_mint.to_account_info(),
+            user_quote_ata: self.quote_account.to_account_info(),
+            vault: self.pm_vault.to_account_info(),
+            vault_authority: self.pm_vault_authority.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            rent: self.rent.to_account_info(),
+        };
+        CpiContext::new(self.position_manager_program.to_account_info(), cpi_accounts)
+    }
+
+    pub fn open_position_ctx(&self) -> CpiContext<'_, '_, '_, 'info, OpenPosition<'info>> {
+        let cpi_accounts = OpenPosition {
+            owner: self.trader.to_account_info(),
+            user: self.user.to_account_info(),
+            position: self.position.to_account_info(),
+            quote_mint: self.quote_mint.to_account_info(),
+            pool: self.pool.to_account_info(),
+            pool_market: self.pool_market.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+        };
+        CpiContext::new(self.position_manager_program.to_account_info(), cpi_accounts)
+    }
+
+    pub fn withdraw_collateral_ctx(&self) -> CpiContext<'_, '_, '_, 'info, WithdrawCollateral<'info>> {
+        let cpi_accounts = WithdrawCollateral {
+            owner: self.trader.to_account_info(),
+            user: self.user.to_account_info(),
+            quote_mint: self.quote_mint.to_account_info(),
+            user_quote_ata: self.quote_account.to_account_info(),
+            vault: self.pm_vault.to_account_info(),
+            vault_authority: self.pm_vault_authority.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+        };
+        CpiContext::new(self.position_manager_program.to_account_info(), cpi_accounts)
+    }
+}
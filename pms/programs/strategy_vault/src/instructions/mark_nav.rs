@@ -0,0 +1,56 @@
+use anchor_lang::prelude::*;
+use position_manager::instructions::position_health;
+use position_manager::{PoolMarket, Position};
+
+use crate::errors::VaultError;
+use crate::events::NavMarked;
+use crate::state::accounts::*;
+
+// Permissionless: marks every open position at its pool market's mark price, so the
+// NAV never depends on prices the manager picks. remaining_accounts holds one
+// (Position, PoolMarket) pair per entry in strategy_vault.positions, in order.
+// Deposits, redemptions and fees price off this snapshot until it goes stale.
+pub(crate) fn handler<'info>(ctx: Context<'_, '_, 'info, 'info, MarkNav<'info>>) -> Result<()> {
+    let vault = &mut ctx.accounts.strategy_vault;
+    let remaining = ctx.remaining_accounts;
+    require!(remaining.len() == 2 * vault.positions.len(), VaultError::PositionMismatch);
+
+    let now = Clock::get()?.unix_timestamp;
+    let pool = Pubkey::find_program_address(&[b"pool", vault.quote_mint.as_ref()], &position_manager::ID).0;
+    let mut equity: u64 = 0;
+    for (pair, expected) in remaining.chunks(2).zip(vault.positions.iter()) {
+        require_keys_eq!(pair[0].key(), *expected, VaultError::PositionMismatch);
+        let pos: Account<'info, Position> = Account::try_from(&pair[0])?;
+        let market: Account<'info, PoolMarket> = Account::try_from(&pair[1])?;
+        require!(market.pool == pool && market.symbol == pos.symbol, VaultError::MarketMismatch);
+        // isolated margin: a position can lose at most its margin
+        let health = position_health(&pos, market.fresh_mark(now)?)?;
+        equity = equity.checked_add(health.equity.max(0) as u64).ok_or(VaultError::Overflow)?;
+    }
+
+    vault.position_equity = equity;
+    vault.marked_at = now;
+
+    let idle = ctx.accounts.quote_account.amount;
+    emit!(NavMarked {
+        vault: vault.key(),
+        idle,
+        position_equity: equity,
+        nav: vault.nav(idle)?,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MarkNav<'info> {
+    #[account(
+        mut,
+        seeds = [b"strategy_vault", strategy_vault.manager.as_ref(), &strategy_vault.vault_id.to_le_bytes()],
+        bump = strategy_vault.bump,
+        has_one = quote_account
+    )]
+    pub strategy_vault: Account<'info, StrategyVault>,
+
+    pub quote_account: Account<'info, anchor_spl::token::TokenAccount>,
+}
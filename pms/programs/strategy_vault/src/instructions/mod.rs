@@ -0,0 +1,17 @@
+pub mod initialize_vault;
+pub mod deposit;
+pub mod redeem;
+pub mod mark_nav;
+pub mod collect_fees;
+pub mod manager_open_position;
+pub mod manager_modify_position;
+pub mod manager_close_position;
+
+pub use initialize_vault::*;
+pub use deposit::*;
+pub use redeem::*;
+pub use mark_nav::*;
+pub use collect_fees::*;
+pub use manager_open_position::*;
+pub use manager_modify_position::*;
+pub use manager_close_position::*;
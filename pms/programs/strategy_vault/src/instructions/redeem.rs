@@ -0,0 +1,88 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Burn, Mint, Token, TokenAccount, Transfer};
+
+use crate::errors::VaultError;
+use crate::events::VaultRedeemed;
+use crate::state::accounts::*;
+
+pub(crate) fn handler(ctx: Context<Redeem>, shares: u64) -> Result<()> {
+    require!(shares > 0, VaultError::InvalidAmount);
+    let now = Clock::get()?.unix_timestamp;
+    require!(ctx.accounts.strategy_vault.nav_fresh(now), VaultError::StaleNav);
+
+    let idle = ctx.accounts.quote_account.amount;
+    let minted = ctx.accounts.share_mint.supply;
+    let nav = ctx.accounts.strategy_vault.nav(idle)?;
+    ctx.accounts.strategy_vault.accrue_fees(minted, nav, now)?;
+    let supply = ctx.accounts.strategy_vault.effective_supply(minted)?;
+    require!(supply > 0, VaultError::InvalidAmount);
+
+    let amount = u64::try_from((shares as u128) * (nav as u128) / (supply as u128)).map_err(|_| VaultError::Overflow)?;
+    require!(amount <= idle, VaultError::InsufficientLiquidity);
+
+    token::burn(ctx.accounts.burn_shares_ctx(), shares)?;
+
+    let vault_key = ctx.accounts.strategy_vault.key();
+    let signer_seeds: &[&[u8]] = &[b"trader", vault_key.as_ref(), &[ctx.accounts.strategy_vault.trader_bump]];
+    token::transfer(ctx.accounts.transfer_from_vault_ctx().with_signer(&[signer_seeds]), amount)?;
+
+    emit!(VaultRedeemed {
+        vault: vault_key,
+        depositor: ctx.accounts.depositor.key(),
+        shares,
+        amount,
+        nav,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct Redeem<'info> {
+    pub depositor: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy_vault", strategy_vault.manager.as_ref(), &strategy_vault.vault_id.to_le_bytes()],
+        bump = strategy_vault.bump,
+        has_one = share_mint,
+        has_one = quote_account,
+        has_one = trader
+    )]
+    pub strategy_vault: Account<'info, StrategyVault>,
+
+    /// CHECK: signer PDA for quote_account, checked through has_one
+    pub trader: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub share_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub quote_account: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = strategy_vault.quote_mint)]
+    pub depositor_quote: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = share_mint)]
+    pub depositor_shares: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> Redeem<'info> {
+    pub fn burn_shares_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
+        let cpi_accounts = Burn {
+            mint: self.share_mint.to_account_info(),
+            from: self.depositor_shares.to_account_info(),
+            authority: self.depositor.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+
+    pub fn transfer_from_vault_ctx(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.quote_account.to_account_info(),
+            to: self.depositor_quote.to_account_info(),
+            authority: self.trader.to_account_info(),
+        };
+        CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+    }
+}
@@ -0,0 +1,68 @@
+use anchor_lang::prelude::*;
+
+pub mod constants;
+pub mod errors;
+pub mod events;
+pub mod instructions;
+pub mod state;
+
+use instructions::*;
+use position_manager::Side;
+
+pub use errors::VaultError;
+pub use state::*;
+
+declare_id!("StratVau1t111111111111111111111111111111111");
+
+#[program]
+pub mod strategy_vault {
+    use super::*;
+
+    pub fn initialize_vault(
+        ctx: Context<InitializeVault>,
+        vault_id: u64,
+        management_fee_bps: u16,
+        performance_fee_bps: u16,
+        trader_lamports: u64,
+        seed_amount: u64,
+    ) -> Result<()> {
+        instructions::initialize_vault::handler(ctx, vault_id, management_fee_bps, performance_fee_bps, trader_lamports, seed_amount)
+    }
+
+    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
+        instructions::deposit::handler(ctx, amount)
+    }
+
+    pub fn redeem(ctx: Context<Redeem>, shares: u64) -> Result<()> {
+        instructions::redeem::handler(ctx, shares)
+    }
+
+    // (Position, PoolMarket) pairs go in remaining_accounts, in strategy_vault.positions order.
+    pub fn mark_nav<'info>(ctx: Context<'_, '_, 'info, 'info, MarkNav<'info>>) -> Result<()> {
+        instructions::mark_nav::handler(ctx)
+    }
+
+    pub fn collect_fees(ctx: Context<CollectFees>) -> Result<()> {
+        instructions::collect_fees::handler(ctx)
+    }
+
+    // Manager legs trade at the pool market's mark.
+    pub fn manager_open_position(
+        ctx: Context<ManagerOpenPosition>,
+        symbol: String,
+        side: Side,
+        size: u64,
+        leverage: u16,
+        margin: u64,
+    ) -> Result<()> {
+        instructions::manager_open_position::handler(ctx, symbol, side, size, leverage, margin)
+    }
+
+    pub fn manager_modify_position(ctx: Context<ManagerModifyPosition>, action: ManagerModifyKind) -> Result<()> {
+        instructions::manager_modify_position::handler(ctx, action)
+    }
+
+    pub fn manager_close_position(ctx: Context<ManagerClosePosition>) -> Result<()> {
+        instructions::manager_close_position::handler(ctx)
+    }
+}
@@ -0,0 +1,84 @@
+use anchor_lang::prelude::*;
+use crate::constants::*;
+use crate::errors::VaultError;
+
+#[account]
+pub struct StrategyVault {
+    pub manager: Pubkey,
+    pub vault_id: u64,
+    pub quote_mint: Pubkey,
+    pub share_mint: Pubkey,
+    pub quote_account: Pubkey,     // idle collateral, authority = trader
+    pub trader: Pubkey,            // system-owned PDA that owns the perp positions
+    pub management_fee_bps: u16,   // per year, charged by diluting shares
+    pub performance_fee_bps: u16,  // on NAV per share above the high-water mark
+    pub high_water_mark: u64,      // NAV per share, scaled by 1e6
+    pub last_fee_ts: i64,
+    pub pending_fee_shares: u64,   // accrued to the manager, minted by collect_fees
+    pub position_equity: u64,      // sum of position equity at the last mark
+    pub marked_at: i64,            // 0 = stale (a trade happened since the last mark)
+    pub positions: Vec<Pubkey>,    // open Position PDAs owned by `trader`
+    pub bump: u8,
+    pub trader_bump: u8,
+}
+
+impl StrategyVault {
+    pub const SPACE: usize = 8 // disc
+        + 32 + 8 + 32 + 32 + 32 + 32
+        + 2 + 2 + 8 + 8 + 8 + 8 + 8
+        + 4 + 32 * MAX_VAULT_POSITIONS
+        + 1 + 1
+        + 32; // padding
+
+    pub fn nav_fresh(&self, now: i64) -> bool {
+        self.positions.is_empty() || (self.marked_at > 0 && now - self.marked_at <= MAX_NAV_AGE_SECS)
+    }
+
+    // NAV = idle collateral + position equity at the last mark
+    pub fn nav(&self, idle: u64) -> Result<u64> {
+        let equity = if self.positions.is_empty() { 0 } else { self.position_equity };
+        Ok(idle.checked_add(equity).ok_or(VaultError::Overflow)?)
+    }
+
+    // Minted supply plus fee shares owed to the manager but not minted yet
+    pub fn effective_supply(&self, minted: u64) -> Result<u64> {
+        Ok(minted.checked_add(self.pending_fee_shares).ok_or(VaultError::Overflow)?)
+    }
+
+    // Accrues management fees for the elapsed time and performance fees on NAV per
+    // share above the high-water mark. Both are paid by minting new shares, so
+    // depositors are diluted instead of collateral leaving the vault.
+    pub fn accrue_fees(&mut self, minted: u64, nav: u64, now: i64) -> Result<()> {
+        let supply = self.effective_supply(minted)? as u128;
+        let dt = now.saturating_sub(self.last_fee_ts).max(0) as u128;
+        self.last_fee_ts = now;
+        if supply == 0 || nav == 0 {
+            return Ok(());
+        }
+
+        let mgmt = supply
+            .checked_mul(self.management_fee_bps as u128).ok_or(VaultError::Overflow)?
+            .checked_mul(dt).ok_or(VaultError::Overflow)?
+            / (BPS_DENOM * SECONDS_PER_YEAR);
+        let supply = supply + mgmt;
+
+        let nav_u = nav as u128;
+        let per_share = nav_u.checked_mul(SHARE_PRICE_SCALE).ok_or(VaultError::Overflow)? / supply;
+        let mut perf = 0u128;
+        if per_share > self.high_water_mark as u128 && self.performance_fee_bps > 0 {
+            let gain = (per_share - self.high_water_mark as u128)
+                .checked_mul(supply).ok_or(VaultError::Overflow)? / SHARE_PRICE_SCALE;
+            let fee_value = gain * self.performance_fee_bps as u128 / BPS_DENOM;
+            // shares worth fee_value after they are added to the supply
+            perf = fee_value.checked_mul(supply).ok_or(VaultError::Overflow)? / (nav_u - fee_value).max(1);
+        }
+
+        let added = u64::try_from(mgmt + perf).map_err(|_| VaultError::Overflow)?;
+        self.pending_fee_shares = self.pending_fee_shares.checked_add(added).ok_or(VaultError::Overflow)?;
+        let new_per_share = nav_u.checked_mul(SHARE_PRICE_SCALE).ok_or(VaultError::Overflow)? / (supply + perf);
+        if new_per_share > self.high_water_mark as u128 {
+            self.high_water_mark = u64::try_from(new_per_share).map_err(|_| VaultError::Overflow)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,588 @@
+// In-process tests: strategy_vault and position_manager run natively inside
+// solana-program-test's BanksClient, CPIs included, with the real SPL token program.
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use anchor_spl::token::spl_token;
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestBanksClientExt, ProgramTestContext};
+use std::collections::HashSet;
+use solana_sdk::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    system_instruction, system_program, sysvar,
+    transaction::{Transaction, TransactionError},
+};
+
+use position_manager::constants::MAX_MARK_AGE_SECS;
+use position_manager::{PerpError, PoolParams, Side, UserAccount};
+use strategy_vault::constants::{MIN_SEED_AMOUNT, SECONDS_PER_YEAR, SHARE_PRICE_SCALE};
+use strategy_vault::instructions::ManagerModifyKind;
+use strategy_vault::{StrategyVault, VaultError};
+
+const SYMBOL: &str = "SOL-PERP";
+const OTHER_SYMBOL: &str = "BTC-PERP";
+const DECIMALS: u8 = 6;
+const FUNDS: u64 = 1_000_000_000_000; // 1,000,000 quote per wallet
+const SEED: u64 = 1_000_000;
+
+// Anchor's entry ties every AccountInfo to one lifetime; processor! doesn't.
+fn process_vault<'a, 'b, 'c, 'info>(program_id: &'a Pubkey, accounts: &'b [AccountInfo<'info>], data: &'c [u8]) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    strategy_vault::entry(program_id, accounts, data)
+}
+
+fn process_position_manager<'a, 'b, 'c, 'info>(program_id: &'a Pubkey, accounts: &'b [AccountInfo<'info>], data: &'c [u8]) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    position_manager::entry(program_id, accounts, data)
+}
+
+fn pm_pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &position_manager::ID).0
+}
+
+fn vault_pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &strategy_vault::ID).0
+}
+
+struct Env {
+    ctx: ProgramTestContext,
+    mint: Pubkey,
+    manager: Keypair,
+    depositor: Keypair,
+    manager_quote: Pubkey,
+    manager_shares: Pubkey,
+    depositor_quote: Pubkey,
+    depositor_shares: Pubkey,
+    sent: HashSet<Signature>,
+}
+
+impl Env {
+    // A funded position_manager pool with two markets; the vault comes from initialize_vault.
+    async fn new() -> Self {
+        let mut pt = ProgramTest::new("strategy_vault", strategy_vault::ID, processor!(process_vault));
+        pt.add_program("position_manager", position_manager::ID, processor!(process_position_manager));
+        let mut ctx = pt.start_with_context().await;
+
+        let mint = Keypair::new();
+        let payer = ctx.payer.pubkey();
+        let rent = ctx.banks_client.get_rent().await.unwrap();
+        let mut env = Env {
+            ctx,
+            mint: mint.pubkey(),
+            manager: Keypair::new(),
+            depositor: Keypair::new(),
+            manager_quote: Pubkey::default(),
+            manager_shares: Pubkey::default(),
+            depositor_quote: Pubkey::default(),
+            depositor_shares: Pubkey::default(),
+            sent: HashSet::new(),
+        };
+        let ixs = vec![
+            system_instruction::create_account(
+                &payer,
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint(&spl_token::ID, &mint.pubkey(), &payer, None, DECIMALS).unwrap(),
+            system_instruction::transfer(&payer, &env.manager.pubkey(), 10_000_000_000),
+        ];
+        env.send(ixs, &[&mint]).await.unwrap();
+
+        env.manager_quote = env.token_account(env.manager.pubkey(), env.mint, FUNDS).await;
+        env.depositor_quote = env.token_account(env.depositor.pubkey(), env.mint, FUNDS).await;
+        env.setup_pool().await;
+        env
+    }
+
+    async fn send(&mut self, ixs: Vec<Instruction>, signers: &[&Keypair]) -> Result<(), BanksClientError> {
+        let mut all: Vec<&Keypair> = vec![&self.ctx.payer];
+        all.extend_from_slice(signers);
+        let blockhash = self.ctx.banks_client.get_latest_blockhash().await?;
+        let mut tx = Transaction::new_signed_with_payer(&ixs, Some(&self.ctx.payer.pubkey()), &all, blockhash);
+        // a retry of a failed transaction signs identically until the blockhash moves on
+        if !self.sent.insert(tx.signatures[0]) {
+            let blockhash = self.ctx.banks_client.get_new_latest_blockhash(&blockhash).await?;
+            tx = Transaction::new_signed_with_payer(&ixs, Some(&self.ctx.payer.pubkey()), &all, blockhash);
+            self.sent.insert(tx.signatures[0]);
+        }
+        self.ctx.banks_client.process_transaction(tx).await
+    }
+
+    // A fresh token account for `owner`, with `amount` minted into it.
+    async fn token_account(&mut self, owner: Pubkey, mint: Pubkey, amount: u64) -> Pubkey {
+        let account = Keypair::new();
+        let payer = self.ctx.payer.pubkey();
+        let rent = self.ctx.banks_client.get_rent().await.unwrap();
+        let mut ixs = vec![
+            system_instruction::create_account(
+                &payer,
+                &account.pubkey(),
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_account3(&spl_token::ID, &account.pubkey(), &mint, &owner).unwrap(),
+        ];
+        if amount > 0 {
+            ixs.push(spl_token::instruction::mint_to(&spl_token::ID, &mint, &account.pubkey(), &payer, &[], amount).unwrap());
+        }
+        self.send(ixs, &[&account]).await.unwrap();
+        account.pubkey()
+    }
+
+    async fn token_balance(&mut self, account: Pubkey) -> u64 {
+        let acc = self.ctx.banks_client.get_account(account).await.unwrap().expect("token account");
+        spl_token::state::Account::unpack(&acc.data).unwrap().amount
+    }
+
+    async fn share_supply(&mut self) -> u64 {
+        let acc = self.ctx.banks_client.get_account(self.share_mint()).await.unwrap().expect("share mint");
+        spl_token::state::Mint::unpack(&acc.data).unwrap().supply
+    }
+
+    async fn vault(&mut self) -> StrategyVault {
+        let acc = self.ctx.banks_client.get_account(self.strategy_vault()).await.unwrap().expect("strategy vault");
+        StrategyVault::try_deserialize(&mut acc.data.as_slice()).unwrap()
+    }
+
+    fn pool(&self) -> Pubkey {
+        pm_pda(&[b"pool", self.mint.as_ref()])
+    }
+    fn pool_market(&self, symbol: &str) -> Pubkey {
+        pm_pda(&[b"pool_market", self.pool().as_ref(), symbol.as_bytes()])
+    }
+    fn strategy_vault(&self) -> Pubkey {
+        vault_pda(&[b"strategy_vault", self.manager.pubkey().as_ref(), &0u64.to_le_bytes()])
+    }
+    fn trader(&self) -> Pubkey {
+        vault_pda(&[b"trader", self.strategy_vault().as_ref()])
+    }
+    fn share_mint(&self) -> Pubkey {
+        vault_pda(&[b"share_mint", self.strategy_vault().as_ref()])
+    }
+    fn quote_account(&self) -> Pubkey {
+        vault_pda(&[b"vault_quote", self.strategy_vault().as_ref()])
+    }
+    fn user(&self) -> Pubkey {
+        pm_pda(&[b"user", self.trader().as_ref(), &0u16.to_le_bytes()])
+    }
+    fn position(&self) -> Pubkey {
+        pm_pda(&[b"position", self.trader().as_ref(), &0u16.to_le_bytes(), SYMBOL.as_bytes()])
+    }
+
+    async fn setup_pool(&mut self) {
+        let authority = self.ctx.payer.pubkey();
+        let params = PoolParams {
+            base_spread_bps: 10,
+            skew_spread_bps: 100,
+            max_spread_bps: 100,
+            max_utilization_bps: 50_000,
+            max_skew_bps: 10_000,
+            funding_rate_bps: 0,
+        };
+        let mut ixs = vec![Instruction {
+            program_id: position_manager::ID,
+            accounts: position_manager::accounts::InitializePool {
+                authority,
+                pool: self.pool(),
+                quote_mint: self.mint,
+                lp_mint: pm_pda(&[b"lp_mint", self.pool().as_ref()]),
+                vault: pm_pda(&[b"vault", self.mint.as_ref()]),
+                vault_authority: pm_pda(&[b"vault_authority"]),
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+            data: position_manager::instruction::InitializePool { params }.data(),
+        }];
+        for symbol in [SYMBOL, OTHER_SYMBOL] {
+            ixs.push(Instruction {
+                program_id: position_manager::ID,
+                accounts: position_manager::accounts::InitializePoolMarket {
+                    authority,
+                    pool: self.pool(),
+                    pool_market: self.pool_market(symbol),
+                    system_program: system_program::ID,
+                }
+                .to_account_metas(None),
+                data: position_manager::instruction::InitializePoolMarket { symbol: symbol.to_string() }.data(),
+            });
+        }
+        self.send(ixs, &[]).await.unwrap();
+
+        let lp = Keypair::new();
+        let lp_quote = self.token_account(lp.pubkey(), self.mint, FUNDS).await;
+        let lp_shares = self.token_account(lp.pubkey(), pm_pda(&[b"lp_mint", self.pool().as_ref()]), 0).await;
+        let mut accounts = position_manager::accounts::AddLiquidity {
+            provider: lp.pubkey(),
+            pool: self.pool(),
+            quote_mint: self.mint,
+            lp_mint: pm_pda(&[b"lp_mint", self.pool().as_ref()]),
+            provider_quote_ata: lp_quote,
+            provider_lp_ata: lp_shares,
+            vault: pm_pda(&[b"vault", self.mint.as_ref()]),
+            token_program: spl_token::ID,
+        }
+        .to_account_metas(None);
+        for symbol in [SYMBOL, OTHER_SYMBOL] {
+            accounts.push(AccountMeta::new_readonly(self.pool_market(symbol), false));
+        }
+        let add = Instruction {
+            program_id: position_manager::ID,
+            accounts,
+            data: position_manager::instruction::AddLiquidity { amount: FUNDS }.data(),
+        };
+        self.send(vec![add], &[&lp]).await.unwrap();
+    }
+
+    // The pool authority is the test payer, not the vault manager.
+    async fn set_mark(&mut self, symbol: &str, price: u64) {
+        let ix = Instruction {
+            program_id: position_manager::ID,
+            accounts: position_manager::accounts::SetMarkPrice {
+                authority: self.ctx.payer.pubkey(),
+                pool: self.pool(),
+                pool_market: self.pool_market(symbol),
+            }
+            .to_account_metas(None),
+            data: position_manager::instruction::SetMarkPrice { price }.data(),
+        };
+        self.send(vec![ix], &[]).await.unwrap();
+    }
+
+    async fn advance_clock(&mut self, secs: i64) {
+        let mut clock: Clock = self.ctx.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += secs;
+        self.ctx.set_sysvar(&clock);
+    }
+
+    async fn initialize_vault(&mut self, management_fee_bps: u16, performance_fee_bps: u16, seed_amount: u64) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: strategy_vault::ID,
+            accounts: strategy_vault::accounts::InitializeVault {
+                manager: self.manager.pubkey(),
+                strategy_vault: self.strategy_vault(),
+                trader: self.trader(),
+                quote_mint: self.mint,
+                share_mint: self.share_mint(),
+                quote_account: self.quote_account(),
+                seed_shares: vault_pda(&[b"seed_shares", self.strategy_vault().as_ref()]),
+                manager_quote: self.manager_quote,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+            data: strategy_vault::instruction::InitializeVault {
+                vault_id: 0,
+                management_fee_bps,
+                performance_fee_bps,
+                trader_lamports: 1_000_000_000,
+                seed_amount,
+            }
+            .data(),
+        };
+        let manager = self.manager.insecure_clone();
+        self.send(vec![ix], &[&manager]).await?;
+        self.manager_shares = self.token_account(self.manager.pubkey(), self.share_mint(), 0).await;
+        self.depositor_shares = self.token_account(self.depositor.pubkey(), self.share_mint(), 0).await;
+        Ok(())
+    }
+
+    async fn deposit(&mut self, amount: u64) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: strategy_vault::ID,
+            accounts: strategy_vault::accounts::Deposit {
+                depositor: self.depositor.pubkey(),
+                strategy_vault: self.strategy_vault(),
+                share_mint: self.share_mint(),
+                quote_account: self.quote_account(),
+                depositor_quote: self.depositor_quote,
+                depositor_shares: self.depositor_shares,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: strategy_vault::instruction::Deposit { amount }.data(),
+        };
+        let depositor = self.depositor.insecure_clone();
+        self.send(vec![ix], &[&depositor]).await
+    }
+
+    async fn redeem(&mut self, shares: u64) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: strategy_vault::ID,
+            accounts: strategy_vault::accounts::Redeem {
+                depositor: self.depositor.pubkey(),
+                strategy_vault: self.strategy_vault(),
+                trader: self.trader(),
+                share_mint: self.share_mint(),
+                quote_account: self.quote_account(),
+                depositor_quote: self.depositor_quote,
+                depositor_shares: self.depositor_shares,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: strategy_vault::instruction::Redeem { shares }.data(),
+        };
+        let depositor = self.depositor.insecure_clone();
+        self.send(vec![ix], &[&depositor]).await
+    }
+
+    // Anyone can mark; the payer here is neither the manager nor a depositor.
+    async fn mark_nav(&mut self, market_symbol: &str) -> Result<(), BanksClientError> {
+        let mut accounts = strategy_vault::accounts::MarkNav {
+            strategy_vault: self.strategy_vault(),
+            quote_account: self.quote_account(),
+        }
+        .to_account_metas(None);
+        accounts.push(AccountMeta::new_readonly(self.position(), false));
+        accounts.push(AccountMeta::new_readonly(self.pool_market(market_symbol), false));
+        let ix = Instruction { program_id: strategy_vault::ID, accounts, data: strategy_vault::instruction::MarkNav {}.data() };
+        self.send(vec![ix], &[]).await
+    }
+
+    async fn collect_fees(&mut self) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: strategy_vault::ID,
+            accounts: strategy_vault::accounts::CollectFees {
+                strategy_vault: self.strategy_vault(),
+                share_mint: self.share_mint(),
+                quote_account: self.quote_account(),
+                manager_shares: self.manager_shares,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: strategy_vault::instruction::CollectFees {}.data(),
+        };
+        self.send(vec![ix], &[]).await
+    }
+
+    async fn open(&mut self, size: u64, margin: u64) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: strategy_vault::ID,
+            accounts: strategy_vault::accounts::ManagerOpenPosition {
+                manager: self.manager.pubkey(),
+                strategy_vault: self.strategy_vault(),
+                trader: self.trader(),
+                user: self.user(),
+                position: self.position(),
+                quote_mint: self.mint,
+                quote_account: self.quote_account(),
+                pool: self.pool(),
+                pool_market: self.pool_market(SYMBOL),
+                pm_vault: pm_pda(&[b"vault", self.mint.as_ref()]),
+                pm_vault_authority: pm_pda(&[b"vault_authority"]),
+                position_manager_program: position_manager::ID,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+            data: strategy_vault::instruction::ManagerOpenPosition {
+                symbol: SYMBOL.to_string(),
+                side: Side::Long,
+                size,
+                leverage: 10,
+                margin,
+            }
+            .data(),
+        };
+        let manager = self.manager.insecure_clone();
+        self.send(vec![ix], &[&manager]).await
+    }
+
+    async fn modify(&mut self, action: ManagerModifyKind) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: strategy_vault::ID,
+            accounts: strategy_vault::accounts::ManagerModifyPosition {
+                manager: self.manager.pubkey(),
+                strategy_vault: self.strategy_vault(),
+                trader: self.trader(),
+                user: self.user(),
+                position: self.position(),
+                quote_mint: self.mint,
+                quote_account: self.quote_account(),
+                pool: self.pool(),
+                pool_market: self.pool_market(SYMBOL),
+                pm_vault: pm_pda(&[b"vault", self.mint.as_ref()]),
+                pm_vault_authority: pm_pda(&[b"vault_authority"]),
+                position_manager_program: position_manager::ID,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: strategy_vault::instruction::ManagerModifyPosition { action }.data(),
+        };
+        let manager = self.manager.insecure_clone();
+        self.send(vec![ix], &[&manager]).await
+    }
+}
+
+fn assert_error(err: BanksClientError, code: u32) {
+    match err {
+        BanksClientError::TransactionError(TransactionError::InstructionError(_, InstructionError::Custom(got))) => {
+            assert_eq!(got, code, "unexpected error code");
+        }
+        other => panic!("expected error {}, got {:?}", code, other),
+    }
+}
+
+#[tokio::test]
+async fn test_deposit_and_redeem_priced_at_pool_marks() {
+    let mut env = Env::new().await;
+    env.initialize_vault(0, 0, SEED).await.unwrap();
+    env.deposit(100_000_000).await.unwrap();
+    assert_eq!(env.token_balance(env.depositor_shares).await, 100_000_000);
+
+    // long 10 @ 100.1 locks 100.1 of margin; the unused budget comes back to idle
+    env.set_mark(SYMBOL, 100_000_000).await;
+    env.open(10, 101_000_000).await.unwrap();
+    assert_eq!(env.token_balance(env.quote_account()).await, 900_000);
+    let err = env.deposit(30_000_000).await.unwrap_err();
+    assert_error(err, u32::from(VaultError::StaleNav));
+
+    // NAV = 0.9 idle + 100.1 margin + 10 × (120 − 100.1) = 300 over 101 shares
+    env.set_mark(SYMBOL, 120_000_000).await;
+    env.mark_nav(SYMBOL).await.unwrap();
+    assert_eq!(env.vault().await.position_equity, 299_100_000);
+    env.deposit(30_000_000).await.unwrap();
+    assert_eq!(env.token_balance(env.depositor_shares).await, 100_000_000 + 10_100_000);
+
+    let before = env.token_balance(env.depositor_quote).await;
+    env.redeem(10_100_000).await.unwrap();
+    assert_eq!(env.token_balance(env.depositor_quote).await - before, 30_000_000);
+
+    // the remaining shares are worth far more than the idle collateral
+    let err = env.redeem(100_000_000).await.unwrap_err();
+    assert_error(err, u32::from(VaultError::InsufficientLiquidity));
+}
+
+#[tokio::test]
+async fn test_mark_nav_only_takes_the_positions_pool_market() {
+    let mut env = Env::new().await;
+    env.initialize_vault(0, 2_000, SEED).await.unwrap();
+    env.deposit(100_000_000).await.unwrap();
+    env.set_mark(SYMBOL, 100_000_000).await;
+    env.open(10, 101_000_000).await.unwrap();
+
+    // the position's market mark went stale, for NAV and for the manager's legs alike
+    env.advance_clock(MAX_MARK_AGE_SECS + 1).await;
+    let err = env.mark_nav(SYMBOL).await.unwrap_err();
+    assert_error(err, u32::from(PerpError::StaleMarkPrice));
+    let err = env.modify(ManagerModifyKind::DecreaseSize { reduce_size: 5 }).await.unwrap_err();
+    assert_error(err, u32::from(PerpError::StaleMarkPrice));
+
+    // a fresh but unrelated market can't stand in for it
+    env.set_mark(OTHER_SYMBOL, 1_000_000_000).await;
+    let err = env.mark_nav(OTHER_SYMBOL).await.unwrap_err();
+    assert_error(err, u32::from(VaultError::MarketMismatch));
+    assert_eq!(env.vault().await.marked_at, 0);
+
+    // at the pool's mark there is no gain, so no performance fee
+    env.set_mark(SYMBOL, 100_100_000).await;
+    env.mark_nav(SYMBOL).await.unwrap();
+    assert_eq!(env.vault().await.position_equity, 100_100_000);
+    env.collect_fees().await.unwrap();
+    assert_eq!(env.token_balance(env.manager_shares).await, 0);
+}
+
+#[tokio::test]
+async fn test_performance_fee_on_pool_marked_gain() {
+    let mut env = Env::new().await;
+    env.initialize_vault(0, 2_000, SEED).await.unwrap();
+    env.deposit(100_000_000).await.unwrap();
+    env.set_mark(SYMBOL, 100_000_000).await;
+    env.open(10, 101_000_000).await.unwrap();
+    env.set_mark(SYMBOL, 120_000_000).await;
+    env.mark_nav(SYMBOL).await.unwrap();
+
+    // NAV went from 101 to 300 over 101 shares; the fee shares are worth 20% of the gain
+    env.collect_fees().await.unwrap();
+    let fee_shares = env.token_balance(env.manager_shares).await;
+    let supply = env.share_supply().await;
+    let fee_value = fee_shares as u128 * 300_000_000 / supply as u128;
+    assert!(fee_value.abs_diff(199_000_000 * 2_000 / 10_000) <= 10, "fee worth {}", fee_value);
+
+    // the high-water mark moved up, so collecting again at the same NAV adds nothing
+    env.collect_fees().await.unwrap();
+    assert_eq!(env.token_balance(env.manager_shares).await, fee_shares);
+}
+
+#[tokio::test]
+async fn test_partial_reduce_profit_returns_to_idle() {
+    let mut env = Env::new().await;
+    env.initialize_vault(0, 0, SEED).await.unwrap();
+    env.deposit(100_000_000).await.unwrap();
+    env.set_mark(SYMBOL, 100_000_000).await;
+    env.open(10, 101_000_000).await.unwrap();
+
+    // -5 filled at 109.89: the realized 48.95 leaves the sub-account for idle collateral
+    env.set_mark(SYMBOL, 110_000_000).await;
+    env.modify(ManagerModifyKind::DecreaseSize { reduce_size: 5 }).await.unwrap();
+    assert_eq!(env.token_balance(env.quote_account()).await, 900_000 + 5 * (109_890_000 - 100_100_000));
+    let acc = env.ctx.banks_client.get_account(env.user()).await.unwrap().expect("user account");
+    let user = UserAccount::try_deserialize(&mut acc.data.as_slice()).unwrap();
+    assert_eq!((user.total_collateral, user.free_collateral()), (100_100_000, 0));
+}
+
+#[tokio::test]
+async fn test_first_depositor_does_not_take_idle_funds() {
+    let mut env = Env::new().await;
+    let err = env.initialize_vault(0, 0, MIN_SEED_AMOUNT - 1).await.unwrap_err();
+    assert_error(err, u32::from(VaultError::InvalidAmount));
+    env.initialize_vault(0, 0, SEED).await.unwrap();
+
+    // collateral already sitting in the vault belongs to the seed shares
+    let payer = env.ctx.payer.pubkey();
+    let donation = spl_token::instruction::mint_to(&spl_token::ID, &env.mint, &env.quote_account(), &payer, &[], 50_000_000).unwrap();
+    env.send(vec![donation], &[]).await.unwrap();
+
+    env.deposit(100_000_000).await.unwrap();
+    let shares = env.token_balance(env.depositor_shares).await;
+    assert_eq!(shares, 100_000_000 * SEED / (SEED + 50_000_000));
+    let before = env.token_balance(env.depositor_quote).await;
+    env.redeem(shares).await.unwrap();
+    assert!(env.token_balance(env.depositor_quote).await - before <= 100_000_000);
+}
+
+#[test]
+fn test_accrue_fees_management_and_high_water_mark() {
+    let mut vault = StrategyVault {
+        manager: Pubkey::default(),
+        vault_id: 0,
+        quote_mint: Pubkey::default(),
+        share_mint: Pubkey::default(),
+        quote_account: Pubkey::default(),
+        trader: Pubkey::default(),
+        management_fee_bps: 200,
+        performance_fee_bps: 0,
+        high_water_mark: SHARE_PRICE_SCALE as u64,
+        last_fee_ts: 0,
+        pending_fee_shares: 0,
+        position_equity: 0,
+        marked_at: 0,
+        positions: Vec::new(),
+        bump: 0,
+        trader_bump: 0,
+    };
+
+    // 2% a year of 1,000,000 shares, pro rata per second
+    vault.accrue_fees(1_000_000, 1_000_000, SECONDS_PER_YEAR as i64).unwrap();
+    assert_eq!((vault.pending_fee_shares, vault.last_fee_ts), (20_000, SECONDS_PER_YEAR as i64));
+    vault.accrue_fees(1_000_000, 1_000_000, SECONDS_PER_YEAR as i64 * 3 / 2).unwrap();
+    assert_eq!(vault.pending_fee_shares, 30_200);
+    assert_eq!(vault.high_water_mark, SHARE_PRICE_SCALE as u64);
+
+    // with a performance fee, a NAV at the high-water mark accrues nothing
+    vault.management_fee_bps = 0;
+    vault.performance_fee_bps = 2_000;
+    let supply = 1_000_000 + vault.pending_fee_shares;
+    vault.accrue_fees(1_000_000, supply, SECONDS_PER_YEAR as i64 * 2).unwrap();
+    assert_eq!(vault.pending_fee_shares, 30_200);
+}
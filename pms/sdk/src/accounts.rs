@@ -0,0 +1,139 @@
+// Program account layouts. Data is an 8-byte Anchor discriminator followed by the
+// borsh fields; accounts are allocated for the longest symbol, so trailing bytes are ignored.
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::constants::MAX_MARK_AGE_SECS;
+use crate::types::{MarginMode, PoolParams, Side};
+use crate::{discriminator, SdkError};
+
+pub trait ProgramAccount: BorshDeserialize {
+    const NAME: &'static str;
+
+    fn discriminator() -> [u8; 8] {
+        discriminator("account", Self::NAME)
+    }
+
+    fn try_deserialize(data: &[u8]) -> Result<Self, SdkError> {
+        if data.len() < 8 || data[..8] != Self::discriminator() {
+            return Err(SdkError::AccountDiscriminator(Self::NAME));
+        }
+        let mut body = &data[8..];
+        Self::deserialize(&mut body).map_err(|e| SdkError::AccountData(Self::NAME, e.to_string()))
+    }
+}
+
+macro_rules! program_account {
+    ($name:ident) => {
+        impl ProgramAccount for $name {
+            const NAME: &'static str = stringify!($name);
+        }
+    };
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub owner: Pubkey,
+    pub sub_id: u16,
+    pub symbol: String,
+    pub side: Side,
+    pub size: u64,
+    pub entry_price: u64,
+    pub margin: u64,
+    pub leverage: u16,
+    pub unrealized_pnl: i64,
+    pub realized_pnl: i64,
+    pub funding_accrued: i64,
+    pub liquidation_price: u64,
+    pub last_update: i64,
+    pub bump: u8,
+    pub funding_index: i128,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UserAccount {
+    pub owner: Pubkey,
+    pub sub_id: u16,
+    pub margin_mode: MarginMode,
+    pub total_collateral: u64,
+    pub locked_collateral: u64,
+    pub total_pnl: i64,
+    pub position_count: u32,
+    pub bump: u8,
+}
+
+impl UserAccount {
+    pub fn free_collateral(&self) -> u64 {
+        self.total_collateral.saturating_sub(self.locked_collateral)
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VaultAuthority {
+    pub bump: u8,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LiquidityPool {
+    pub authority: Pubkey,
+    pub quote_mint: Pubkey,
+    pub lp_mint: Pubkey,
+    pub liquidity: u64,
+    pub open_interest: u64,
+    pub params: PoolParams,
+    pub bump: u8,
+    pub market_count: u16,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PoolMarket {
+    pub pool: Pubkey,
+    pub symbol: String,
+    pub long_open_interest: u64,
+    pub short_open_interest: u64,
+    pub bump: u8,
+    pub long_size: u64,
+    pub short_size: u64,
+    pub mark_price: u64,
+    pub mark_updated_at: i64,
+    pub funding_index: i128,
+    pub funding_updated_at: i64,
+}
+
+impl PoolMarket {
+    // The mark the program prices against at `now`, None once it is older than MAX_MARK_AGE_SECS
+    pub fn fresh_mark(&self, now: i64) -> Option<u64> {
+        let fresh = self.mark_updated_at > 0 && now.saturating_sub(self.mark_updated_at) <= MAX_MARK_AGE_SECS;
+        fresh.then_some(self.mark_price)
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TransferApproval {
+    pub recipient: Pubkey,
+    pub to_sub_id: u16,
+    pub from_owner: Pubkey,
+    pub from_sub_id: u16,
+    pub symbol: String,
+    pub max_size: u64,
+    pub min_margin: u64,
+    pub bump: u8,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TopUpSettings {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub target_mr: u64,
+    pub max_total: u64,
+    pub used: u64,
+    pub bump: u8,
+}
+
+program_account!(Position);
+program_account!(UserAccount);
+program_account!(VaultAuthority);
+program_account!(LiquidityPool);
+program_account!(PoolMarket);
+program_account!(TransferApproval);
+program_account!(TopUpSettings);
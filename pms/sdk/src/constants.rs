@@ -0,0 +1,4 @@
+// Program limits clients check before sending; sdk_parity keeps them equal to the program's.
+
+pub const MAX_MARK_AGE_SECS: i64 = 60; // older pool marks can't price fills, LP shares or top-ups
+pub const MAX_BATCH_POSITIONS: usize = 16; // legs one close_all_positions instruction takes
@@ -0,0 +1,123 @@
+use solana_program::instruction::InstructionError;
+
+/// Anchor offsets custom program errors by 6000.
+pub const ERROR_CODE_OFFSET: u32 = 6000;
+
+// Mirrors the program's PerpError; variants are only ever appended there, so the
+// index here is the on-chain code minus ERROR_CODE_OFFSET.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PerpError {
+    Overflow,
+    Underflow,
+    DivisionByZero,
+    InvalidLeverage,
+    LeverageExceeded,
+    InvalidSize,
+    InvalidAmount,
+    SymbolTooLong,
+    InsufficientMarginForIncrease,
+    MaintenanceBreach,
+    InvalidState,
+    InvalidSubAccount,
+    InsufficientFreeCollateral,
+    PositionsOpen,
+    InvalidPoolParams,
+    PoolUtilizationExceeded,
+    PoolSkewExceeded,
+    InsufficientPoolLiquidity,
+    PoolMarketMismatch,
+    MissingTransferApproval,
+    TopUpNotNeeded,
+    TopUpUnavailable,
+    InitialMarginTooLow,
+    StaleMarkPrice,
+    TransferApprovalMismatch,
+    PriceOutsideMarkBand,
+}
+
+impl PerpError {
+    pub const ALL: [PerpError; 26] = [
+        PerpError::Overflow,
+        PerpError::Underflow,
+        PerpError::DivisionByZero,
+        PerpError::InvalidLeverage,
+        PerpError::LeverageExceeded,
+        PerpError::InvalidSize,
+        PerpError::InvalidAmount,
+        PerpError::SymbolTooLong,
+        PerpError::InsufficientMarginForIncrease,
+        PerpError::MaintenanceBreach,
+        PerpError::InvalidState,
+        PerpError::InvalidSubAccount,
+        PerpError::InsufficientFreeCollateral,
+        PerpError::PositionsOpen,
+        PerpError::InvalidPoolParams,
+        PerpError::PoolUtilizationExceeded,
+        PerpError::PoolSkewExceeded,
+        PerpError::InsufficientPoolLiquidity,
+        PerpError::PoolMarketMismatch,
+        PerpError::MissingTransferApproval,
+        PerpError::TopUpNotNeeded,
+        PerpError::TopUpUnavailable,
+        PerpError::InitialMarginTooLow,
+        PerpError::StaleMarkPrice,
+        PerpError::TransferApprovalMismatch,
+        PerpError::PriceOutsideMarkBand,
+    ];
+
+    pub fn code(self) -> u32 {
+        ERROR_CODE_OFFSET + self as u32
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        let index = code.checked_sub(ERROR_CODE_OFFSET)?;
+        Self::ALL.get(index as usize).copied()
+    }
+
+    // Custom errors come back as InstructionError::Custom inside TransactionError::InstructionError
+    pub fn from_instruction_error(err: &InstructionError) -> Option<Self> {
+        match err {
+            InstructionError::Custom(code) => Self::from_code(*code),
+            _ => None,
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            PerpError::Overflow => "Math overflow",
+            PerpError::Underflow => "Math underflow",
+            PerpError::DivisionByZero => "Division by zero",
+            PerpError::InvalidLeverage => "Invalid leverage",
+            PerpError::LeverageExceeded => "Leverage exceeded for tier",
+            PerpError::InvalidSize => "Invalid position size",
+            PerpError::InvalidAmount => "Invalid amount",
+            PerpError::SymbolTooLong => "Symbol too long",
+            PerpError::InsufficientMarginForIncrease => "Insufficient margin for increase",
+            PerpError::MaintenanceBreach => "Post-removal margin would breach maintenance",
+            PerpError::InvalidState => "Invalid state",
+            PerpError::InvalidSubAccount => "Invalid sub-account id",
+            PerpError::InsufficientFreeCollateral => "Insufficient free collateral",
+            PerpError::PositionsOpen => "Sub-account still has open positions",
+            PerpError::InvalidPoolParams => "Invalid pool parameters",
+            PerpError::PoolUtilizationExceeded => "Pool utilization cap exceeded",
+            PerpError::PoolSkewExceeded => "Pool skew cap exceeded",
+            PerpError::InsufficientPoolLiquidity => "Insufficient pool liquidity",
+            PerpError::PoolMarketMismatch => "Pool market does not match position",
+            PerpError::MissingTransferApproval => "Recipient must sign or pre-approve the transfer",
+            PerpError::TopUpNotNeeded => "Margin ratio is above the top-up target",
+            PerpError::TopUpUnavailable => "Nothing left to top up from budget or free collateral",
+            PerpError::InitialMarginTooLow => "Initial margin must exceed maintenance margin",
+            PerpError::StaleMarkPrice => "Pool market mark price is missing or stale",
+            PerpError::TransferApprovalMismatch => "Position no longer matches the transfer approval",
+            PerpError::PriceOutsideMarkBand => "Price is too far from the pool market mark",
+        }
+    }
+}
+
+impl std::fmt::Display for PerpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} ({}): {}", self, self.code(), self.message())
+    }
+}
+
+impl std::error::Error for PerpError {}
@@ -0,0 +1,361 @@
+// One builder per program instruction. Account order and writable/signer flags
+// follow the program's #[derive(Accounts)] structs; every PDA comes from crate::pda,
+// and token accounts default to the owner's ATA for the quote (or LP) mint.
+// Trade prices are what the caller expects: the program fills at the pool market's
+// mark and rejects prices more than MAX_PRICE_DEVIATION_BPS (1%) away from it.
+use borsh::BorshSerialize;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::pubkey::Pubkey;
+use solana_program::{system_program, sysvar};
+
+use crate::discriminator;
+use crate::pda::{self, associated_token_address, TOKEN_PROGRAM_ID};
+use crate::types::{BatchLeg, MarginMode, ModifyKind, PoolParams, Side};
+
+#[derive(Clone, Debug)]
+pub struct InstructionBuilder {
+    pub program_id: Pubkey,
+    pub quote_mint: Pubkey,
+}
+
+impl InstructionBuilder {
+    pub fn new(program_id: Pubkey, quote_mint: Pubkey) -> Self {
+        Self { program_id, quote_mint }
+    }
+
+    // The initial margin is locked from the sub-account's free collateral, so
+    // deposit_collateral (or transfer_collateral) has to fund it first.
+    #[allow(clippy::too_many_arguments)] // mirrors the instruction's arguments
+    pub fn open_position(&self, owner: &Pubkey, sub_id: u16, symbol: &str, side: Side, size: u64, leverage: u16, entry_price: u64) -> Instruction {
+        let pool = self.pool();
+        let accounts = vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(self.user(owner, sub_id), false),
+            AccountMeta::new(self.position(owner, sub_id, symbol), false),
+            AccountMeta::new_readonly(self.quote_mint, false),
+            AccountMeta::new(pool, false),
+            AccountMeta::new(pda::pool_market_pda(&self.program_id, &pool, symbol).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        self.build("open_position", accounts, (sub_id, symbol.to_string(), side, size, leverage, entry_price))
+    }
+
+    pub fn modify_position(&self, owner: &Pubkey, sub_id: u16, symbol: &str, action: ModifyKind) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(self.user(owner, sub_id), false),
+            AccountMeta::new(self.position(owner, sub_id, symbol), false),
+        ];
+        accounts.extend(self.market_accounts(owner, symbol));
+        accounts.push(AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false));
+        self.build("modify_position", accounts, action)
+    }
+
+    // The position's TopUpSettings address is always passed; the program closes the
+    // settings along with the position if they exist.
+    pub fn close_position(&self, owner: &Pubkey, sub_id: u16, symbol: &str, exit_price: u64) -> Instruction {
+        let position = self.position(owner, sub_id, symbol);
+        let mut accounts = vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(self.user(owner, sub_id), false),
+            AccountMeta::new(position, false),
+        ];
+        accounts.extend(self.market_accounts(owner, symbol));
+        accounts.extend([
+            AccountMeta::new(pda::top_up_pda(&self.program_id, &position).0, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        ]);
+        self.build("close_position", accounts, exit_price)
+    }
+
+    // Each leg is (symbol, expected price); the (position, pool_market, top_up) triples
+    // go in remaining accounts in the same order.
+    pub fn close_all_positions(&self, owner: &Pubkey, sub_id: u16, legs: &[(&str, BatchLeg)], reduce_bps: u16) -> Instruction {
+        let pool = self.pool();
+        let mut accounts = vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(self.user(owner, sub_id), false),
+            AccountMeta::new_readonly(self.quote_mint, false),
+            AccountMeta::new(pool, false),
+            AccountMeta::new(associated_token_address(owner, &self.quote_mint), false),
+            AccountMeta::new(self.vault(), false),
+            AccountMeta::new(self.vault_authority(), false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        ];
+        for (symbol, _) in legs {
+            let position = self.position(owner, sub_id, symbol);
+            accounts.push(AccountMeta::new(position, false));
+            accounts.push(AccountMeta::new(pda::pool_market_pda(&self.program_id, &pool, symbol).0, false));
+            accounts.push(AccountMeta::new(pda::top_up_pda(&self.program_id, &position).0, false));
+        }
+        let legs: Vec<BatchLeg> = legs.iter().map(|(_, leg)| *leg).collect();
+        self.build("close_all_positions", accounts, (legs, reduce_bps))
+    }
+
+    pub fn deposit_collateral(&self, owner: &Pubkey, sub_id: u16, amount: u64) -> Instruction {
+        let accounts = vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(self.user(owner, sub_id), false),
+            AccountMeta::new_readonly(self.quote_mint, false),
+            AccountMeta::new(associated_token_address(owner, &self.quote_mint), false),
+            AccountMeta::new(self.vault(), false),
+            AccountMeta::new(self.vault_authority(), false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+        ];
+        self.build("deposit_collateral", accounts, (sub_id, amount))
+    }
+
+    pub fn withdraw_collateral(&self, owner: &Pubkey, sub_id: u16, amount: u64) -> Instruction {
+        let accounts = vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(self.user(owner, sub_id), false),
+            AccountMeta::new_readonly(self.quote_mint, false),
+            AccountMeta::new(associated_token_address(owner, &self.quote_mint), false),
+            AccountMeta::new(self.vault(), false),
+            AccountMeta::new(self.vault_authority(), false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        ];
+        self.build("withdraw_collateral", accounts, amount)
+    }
+
+    pub fn transfer_collateral(&self, owner: &Pubkey, from_sub_id: u16, to_sub_id: u16, amount: u64) -> Instruction {
+        let accounts = vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(self.user(owner, from_sub_id), false),
+            AccountMeta::new(self.user(owner, to_sub_id), false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        self.build("transfer_collateral", accounts, (from_sub_id, to_sub_id, amount))
+    }
+
+    pub fn set_margin_mode(&self, owner: &Pubkey, sub_id: u16, margin_mode: MarginMode) -> Instruction {
+        let accounts = vec![
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(self.user(owner, sub_id), false),
+        ];
+        self.build("set_margin_mode", accounts, margin_mode)
+    }
+
+    pub fn initialize_pool(&self, authority: &Pubkey, params: PoolParams) -> Instruction {
+        let pool = self.pool();
+        let accounts = vec![
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(pool, false),
+            AccountMeta::new_readonly(self.quote_mint, false),
+            AccountMeta::new(pda::lp_mint_pda(&self.program_id, &pool).0, false),
+            AccountMeta::new(self.vault(), false),
+            AccountMeta::new(self.vault_authority(), false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+        ];
+        self.build("initialize_pool", accounts, params)
+    }
+
+    pub fn update_pool_params(&self, authority: &Pubkey, params: PoolParams) -> Instruction {
+        let accounts = vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(self.pool(), false),
+        ];
+        self.build("update_pool_params", accounts, params)
+    }
+
+    pub fn initialize_pool_market(&self, authority: &Pubkey, symbol: &str) -> Instruction {
+        let pool = self.pool();
+        let accounts = vec![
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(pool, false),
+            AccountMeta::new(pda::pool_market_pda(&self.program_id, &pool, symbol).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        self.build("initialize_pool_market", accounts, symbol.to_string())
+    }
+
+    pub fn set_mark_price(&self, authority: &Pubkey, symbol: &str, price: u64) -> Instruction {
+        let pool = self.pool();
+        let accounts = vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new_readonly(pool, false),
+            AccountMeta::new(pda::pool_market_pda(&self.program_id, &pool, symbol).0, false),
+        ];
+        self.build("set_mark_price", accounts, price)
+    }
+
+    // `symbols` must name every market of the pool; their PoolMarkets price the shares.
+    pub fn add_liquidity(&self, provider: &Pubkey, amount: u64, symbols: &[&str]) -> Instruction {
+        let mut accounts = self.liquidity_accounts(provider, false);
+        accounts.extend(self.pool_markets(symbols));
+        self.build("add_liquidity", accounts, amount)
+    }
+
+    pub fn remove_liquidity(&self, provider: &Pubkey, shares: u64, symbols: &[&str]) -> Instruction {
+        let mut accounts = self.liquidity_accounts(provider, true);
+        accounts.extend(self.pool_markets(symbols));
+        self.build("remove_liquidity", accounts, shares)
+    }
+
+    // recipient_signs: the recipient co-signs this transaction; otherwise their
+    // approve_position_transfer approval is passed (and closed back to them).
+    // The position's top-up settings, if any, are closed back to the owner.
+    pub fn transfer_position(&self, owner: &Pubkey, sub_id: u16, symbol: &str, recipient: &Pubkey, to_sub_id: u16, recipient_signs: bool) -> Instruction {
+        let approval = (!recipient_signs)
+            .then(|| pda::transfer_approval_pda(&self.program_id, recipient, to_sub_id, owner, sub_id, symbol).0);
+        let position = self.position(owner, sub_id, symbol);
+        let accounts = vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(*recipient, recipient_signs),
+            AccountMeta::new(self.user(owner, sub_id), false),
+            AccountMeta::new(position, false),
+            AccountMeta::new(self.user(recipient, to_sub_id), false),
+            AccountMeta::new(self.position(recipient, to_sub_id, symbol), false),
+            self.optional(approval),
+            AccountMeta::new(pda::top_up_pda(&self.program_id, &position).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        self.build("transfer_position", accounts, to_sub_id)
+    }
+
+    // The approval only covers the position while its size is at most max_size and its
+    // margin at least min_margin.
+    #[allow(clippy::too_many_arguments)] // mirrors the instruction's arguments
+    pub fn approve_position_transfer(
+        &self,
+        recipient: &Pubkey,
+        from_owner: &Pubkey,
+        from_sub_id: u16,
+        to_sub_id: u16,
+        symbol: &str,
+        max_size: u64,
+        min_margin: u64,
+    ) -> Instruction {
+        let accounts = vec![
+            AccountMeta::new(*recipient, true),
+            AccountMeta::new(pda::transfer_approval_pda(&self.program_id, recipient, to_sub_id, from_owner, from_sub_id, symbol).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        self.build("approve_position_transfer", accounts, (*from_owner, from_sub_id, to_sub_id, symbol.to_string(), max_size, min_margin))
+    }
+
+    pub fn revoke_position_transfer(&self, recipient: &Pubkey, from_owner: &Pubkey, from_sub_id: u16, to_sub_id: u16, symbol: &str) -> Instruction {
+        let accounts = vec![
+            AccountMeta::new(*recipient, true),
+            AccountMeta::new(pda::transfer_approval_pda(&self.program_id, recipient, to_sub_id, from_owner, from_sub_id, symbol).0, false),
+        ];
+        self.build("revoke_position_transfer", accounts, ())
+    }
+
+    pub fn set_auto_top_up(&self, owner: &Pubkey, sub_id: u16, symbol: &str, target_mr: u64, max_total: u64) -> Instruction {
+        let position = self.position(owner, sub_id, symbol);
+        let accounts = vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(position, false),
+            AccountMeta::new(pda::top_up_pda(&self.program_id, &position).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        self.build("set_auto_top_up", accounts, (target_mr, max_total))
+    }
+
+    pub fn disable_auto_top_up(&self, owner: &Pubkey, sub_id: u16, symbol: &str) -> Instruction {
+        let position = self.position(owner, sub_id, symbol);
+        let accounts = vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(pda::top_up_pda(&self.program_id, &position).0, false),
+        ];
+        self.build("disable_auto_top_up", accounts, ())
+    }
+
+    // Permissionless; `keeper` only signs and pays fees. The program checks the margin
+    // ratio at the pool market's mark.
+    pub fn auto_top_up(&self, keeper: &Pubkey, owner: &Pubkey, sub_id: u16, symbol: &str) -> Instruction {
+        let position = self.position(owner, sub_id, symbol);
+        let pool = self.pool();
+        let accounts = vec![
+            AccountMeta::new_readonly(*keeper, true),
+            AccountMeta::new(self.user(owner, sub_id), false),
+            AccountMeta::new(position, false),
+            AccountMeta::new_readonly(pool, false),
+            AccountMeta::new_readonly(pda::pool_market_pda(&self.program_id, &pool, symbol).0, false),
+            AccountMeta::new(pda::top_up_pda(&self.program_id, &position).0, false),
+        ];
+        self.build("auto_top_up", accounts, ())
+    }
+
+    // Simulate and decode the return data as types::PositionHealth.
+    pub fn get_position_health(&self, owner: &Pubkey, sub_id: u16, symbol: &str, mark_price: u64) -> Instruction {
+        let accounts = vec![AccountMeta::new_readonly(self.position(owner, sub_id, symbol), false)];
+        self.build("get_position_health", accounts, mark_price)
+    }
+
+    fn build(&self, name: &str, accounts: Vec<AccountMeta>, args: impl BorshSerialize) -> Instruction {
+        let mut data = discriminator("global", name).to_vec();
+        args.serialize(&mut data).expect("borsh into a Vec cannot fail");
+        Instruction { program_id: self.program_id, accounts, data }
+    }
+
+    // Anchor reads an absent Option<Account> as the program id in that slot.
+    fn optional(&self, key: Option<Pubkey>) -> AccountMeta {
+        match key {
+            Some(key) => AccountMeta::new(key, false),
+            None => AccountMeta::new_readonly(self.program_id, false),
+        }
+    }
+
+    // quote_mint, pool, pool_market, user_quote_ata, vault, vault_authority
+    fn market_accounts(&self, owner: &Pubkey, symbol: &str) -> [AccountMeta; 6] {
+        let pool = self.pool();
+        [
+            AccountMeta::new_readonly(self.quote_mint, false),
+            AccountMeta::new(pool, false),
+            AccountMeta::new(pda::pool_market_pda(&self.program_id, &pool, symbol).0, false),
+            AccountMeta::new(associated_token_address(owner, &self.quote_mint), false),
+            AccountMeta::new(self.vault(), false),
+            AccountMeta::new(self.vault_authority(), false),
+        ]
+    }
+
+    fn pool_markets<'a>(&'a self, symbols: &'a [&str]) -> impl Iterator<Item = AccountMeta> + 'a {
+        let pool = self.pool();
+        symbols.iter().map(move |s| AccountMeta::new_readonly(pda::pool_market_pda(&self.program_id, &pool, s).0, false))
+    }
+
+    fn liquidity_accounts(&self, provider: &Pubkey, with_vault_authority: bool) -> Vec<AccountMeta> {
+        let pool = self.pool();
+        let lp_mint = pda::lp_mint_pda(&self.program_id, &pool).0;
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*provider, true),
+            AccountMeta::new(pool, false),
+            AccountMeta::new_readonly(self.quote_mint, false),
+            AccountMeta::new(lp_mint, false),
+            AccountMeta::new(associated_token_address(provider, &self.quote_mint), false),
+            AccountMeta::new(associated_token_address(provider, &lp_mint), false),
+            AccountMeta::new(self.vault(), false),
+        ];
+        if with_vault_authority {
+            accounts.push(AccountMeta::new_readonly(self.vault_authority(), false));
+        }
+        accounts.push(AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false));
+        accounts
+    }
+
+    fn user(&self, owner: &Pubkey, sub_id: u16) -> Pubkey {
+        pda::user_pda(&self.program_id, owner, sub_id).0
+    }
+
+    fn position(&self, owner: &Pubkey, sub_id: u16, symbol: &str) -> Pubkey {
+        pda::position_pda(&self.program_id, owner, sub_id, symbol).0
+    }
+
+    fn pool(&self) -> Pubkey {
+        pda::pool_pda(&self.program_id, &self.quote_mint).0
+    }
+
+    fn vault(&self) -> Pubkey {
+        pda::vault_pda(&self.program_id, &self.quote_mint).0
+    }
+
+    fn vault_authority(&self) -> Pubkey {
+        pda::vault_authority_pda(&self.program_id).0
+    }
+}
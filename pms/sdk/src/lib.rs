@@ -0,0 +1,33 @@
+//! Client SDK for the position_manager program, kept in step with its IDL:
+//! instruction builders, PDA derivation, account decoders and error codes.
+
+pub mod accounts;
+pub mod constants;
+pub mod errors;
+pub mod instructions;
+pub mod pda;
+pub mod types;
+
+use solana_program::{hash::hash, pubkey, pubkey::Pubkey};
+
+pub use accounts::ProgramAccount;
+pub use errors::PerpError;
+pub use instructions::InstructionBuilder;
+
+pub const PROGRAM_ID: Pubkey = pubkey!("PosMgr1111111111111111111111111111111111111");
+
+#[derive(Debug, thiserror::Error)]
+pub enum SdkError {
+    #[error("account is not a {0}")]
+    AccountDiscriminator(&'static str),
+    #[error("invalid {0} data: {1}")]
+    AccountData(&'static str, String),
+}
+
+/// Anchor discriminator: first 8 bytes of sha256("<namespace>:<name>"), with
+/// namespace "global" for instructions, "account" for accounts and "event" for events.
+pub fn discriminator(namespace: &str, name: &str) -> [u8; 8] {
+    let mut out = [0u8; 8];
+    out.copy_from_slice(&hash(format!("{namespace}:{name}").as_bytes()).to_bytes()[..8]);
+    out
+}
@@ -0,0 +1,42 @@
+use solana_program::{pubkey, pubkey::Pubkey};
+
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+// Sub-accounts are numbered per owner; sub_id 0 is the default account.
+pub fn user_pda(program: &Pubkey, owner: &Pubkey, sub_id: u16) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"user", owner.as_ref(), &sub_id.to_le_bytes()], program)
+}
+pub fn position_pda(program: &Pubkey, owner: &Pubkey, sub_id: u16, symbol: &str) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"position", owner.as_ref(), &sub_id.to_le_bytes(), symbol.as_bytes()], program)
+}
+pub fn vault_pda(program: &Pubkey, quote_mint: &Pubkey) -> (Pubkey, u8) {
+    // In the on-chain code, vault PDA seed was ["vault", quote_mint]; authority = ["vault_authority"]
+    Pubkey::find_program_address(&[b"vault", quote_mint.as_ref()], program)
+}
+pub fn vault_authority_pda(program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"vault_authority"], program)
+}
+// Liquidity pool (counterparty) for a quote mint, its LP share mint and per-symbol markets
+pub fn pool_pda(program: &Pubkey, quote_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"pool", quote_mint.as_ref()], program)
+}
+pub fn lp_mint_pda(program: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"lp_mint", pool.as_ref()], program)
+}
+pub fn pool_market_pda(program: &Pubkey, pool: &Pubkey, symbol: &str) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"pool_market", pool.as_ref(), symbol.as_bytes()], program)
+}
+pub fn transfer_approval_pda(program: &Pubkey, recipient: &Pubkey, to_sub_id: u16, from_owner: &Pubkey, from_sub_id: u16, symbol: &str) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"transfer_approval", recipient.as_ref(), &to_sub_id.to_le_bytes(), from_owner.as_ref(), &from_sub_id.to_le_bytes(), symbol.as_bytes()],
+        program,
+    )
+}
+pub fn top_up_pda(program: &Pubkey, position: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"top_up", position.as_ref()], program)
+}
+// Owner's associated token account for `mint`; builders use it for user_quote_ata / provider ATAs.
+pub fn associated_token_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[owner.as_ref(), TOKEN_PROGRAM_ID.as_ref(), mint.as_ref()], &ASSOCIATED_TOKEN_PROGRAM_ID).0
+}
@@ -0,0 +1,51 @@
+// Instruction argument types, borsh-identical to the program's (declaration order matters).
+use borsh::{BorshDeserialize, BorshSerialize};
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Long,
+    Short,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarginMode {
+    Isolated,
+    Cross,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModifyKind {
+    IncreaseSize { add_size: u64, price: u64, add_margin: u64 },
+    DecreaseSize { reduce_size: u64, price: u64 },
+    AddMargin { amount: u64 },
+    RemoveMargin { amount: u64, price: u64 },
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchLeg {
+    pub price: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolParams {
+    pub base_spread_bps: u16,
+    pub skew_spread_bps: u16,
+    pub max_spread_bps: u16,
+    pub max_utilization_bps: u32,
+    pub max_skew_bps: u32,
+    pub funding_rate_bps: u16,
+}
+
+// Return data of get_position_health
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PositionHealth {
+    pub mark_price: u64,
+    pub notional: u64,
+    pub unrealized_pnl: i64,
+    pub equity: i64,
+    pub maintenance_margin: u64,
+    pub maintenance_margin_rate: u64,
+    pub margin_ratio: i64,
+    pub liquidation_price: u64,
+    pub liquidatable: bool,
+}
@@ -1,4 +0,0 @@
-pub const RATE_SCALE: u128 = 1_000_000; // 1e6
-pub const MAX_SYMBOL_LEN: usize = 16;
-pub const MAX_LEVERAGE: u16 = 1000;
-pub const MIN_LEVERAGE: u16 = 1;
\ No newline at end of file
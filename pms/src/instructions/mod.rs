@@ -1,7 +0,0 @@
-pub mod open_positions;
-pub mod modify_positions;
-pub mod close_positions;
-
-pub use open_positions::*;
-pub use modify_positions::*;
-pub use close_positions::*;
\ No newline at end of file
@@ -32,7 +32,7 @@ pub trait LiquidationEngine: Send + Sync {
 
 #[async_trait::async_trait]
 pub trait SettlementRelayer: Send + Sync {
-    async fn close_position(&self, owner: solana_sdk::pubkey::Pubkey, symbol: &str, exit_price: u64, funding_payment: i64) -> Result<String>;
+    async fn close_position(&self, owner: solana_sdk::pubkey::Pubkey, symbol: &str, exit_price: u64) -> Result<String>;
     async fn modify_position(&self, owner: solana_sdk::pubkey::Pubkey, symbol: &str, action: models::ModifyAction) -> Result<String>;
     async fn liquidate_position(&self, owner: solana_sdk::pubkey::Pubkey, symbol: &str, close_base: u64, mark_price: u64) -> Result<String>;
 }
@@ -17,40 +17,43 @@ pub fn calculate_mark_price(oracle_price: u64, funding_impact: i64) -> u64 {
     ((oracle_price as i128 + funding_impact as i128) as u64).max(1)
 }
 
-pub fn calculate_initial_margin(notional: u64, im_rate: u64) -> u64 {
-    notional * im_rate / 10000
+// Margin, PnL and liquidation use perp-math: rates are scaled by 1e6 (perp_math::RATE_SCALE)
+// and results match the on-chain program unit for unit.
+pub fn calculate_initial_margin(notional: u64, leverage: u16) -> perp_math::Result<u64> {
+    to_u64(perp_math::initial_margin(notional as u128, leverage)?)
 }
 
-pub fn calculate_maintenance_margin(notional: u64, mm_rate: u64) -> u64 {
-    notional * mm_rate / 10000
+pub fn calculate_maintenance_margin(notional: u64, mm_rate: u64) -> perp_math::Result<u64> {
+    to_u64(perp_math::maintenance_margin(notional as u128, mm_rate)?)
 }
 
-pub fn calculate_unrealized_pnl(entry_price: u64, mark_price: u64, size: u64, is_long: bool) -> i64 {
-    let price_diff = mark_price as i64 - entry_price as i64;
-    let pnl = price_diff * size as i64 / entry_price as i64;
-    if is_long { pnl } else { -pnl }
+pub fn calculate_unrealized_pnl(entry_price: u64, mark_price: u64, size: u64, is_long: bool) -> perp_math::Result<i128> {
+    perp_math::unrealized_pnl(side(is_long), size, entry_price, mark_price)
 }
 
-pub fn calculate_liquidation_price(entry_price: u64, margin: u64, size: u64, mm_rate: u64, is_long: bool) -> u64 {
-    let maintenance_margin = calculate_maintenance_margin(size * entry_price / 1000000, mm_rate);
-    let max_loss = margin.saturating_sub(maintenance_margin);
-    
-    if is_long {
-        entry_price.saturating_sub(max_loss * entry_price / (size * entry_price / 1000000))
-    } else {
-        entry_price + (max_loss * entry_price / (size * entry_price / 1000000))
-    }
+pub fn calculate_liquidation_price(entry_price: u64, margin: u64, size: u64, mm_rate: u64, is_long: bool) -> perp_math::Result<u64> {
+    perp_math::liquidation_price(side(is_long), size, entry_price, margin, mm_rate)
 }
 
-pub fn derive_position_pda(program_id: &Pubkey, owner: &Pubkey, symbol: &str) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[b"position", owner.as_ref(), symbol.as_bytes()],
-        program_id
-    )
+pub fn calculate_bankruptcy_price(entry_price: u64, margin: u64, size: u64, is_long: bool) -> perp_math::Result<u64> {
+    perp_math::bankruptcy_price(side(is_long), size, entry_price, margin)
 }
 
-pub fn derive_user_pda(program_id: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
-    Pubkey::find_program_address(&[b"user", owner.as_ref()], program_id)
+fn side(is_long: bool) -> perp_math::Side {
+    if is_long { perp_math::Side::Long } else { perp_math::Side::Short }
+}
+
+fn to_u64(v: u128) -> perp_math::Result<u64> {
+    u64::try_from(v).map_err(|_| perp_math::MathError::Overflow)
+}
+
+// Seeds come from perp_sdk::pda so they match the program (positions are per sub-account)
+pub fn derive_position_pda(program_id: &Pubkey, owner: &Pubkey, sub_id: u16, symbol: &str) -> (Pubkey, u8) {
+    perp_sdk::pda::position_pda(program_id, owner, sub_id, symbol)
+}
+
+pub fn derive_user_pda(program_id: &Pubkey, owner: &Pubkey, sub_id: u16) -> (Pubkey, u8) {
+    perp_sdk::pda::user_pda(program_id, owner, sub_id)
 }
 
 pub fn calculate_funding_payment(position_size: u64, funding_rate: i64, hours: u64) -> i64 {
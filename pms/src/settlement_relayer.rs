@@ -17,7 +17,7 @@ impl DefaultSettlementRelayer {
 
 #[async_trait::async_trait]
 impl SettlementRelayer for DefaultSettlementRelayer {
-    async fn close_position(&self, owner: Pubkey, symbol: &str, exit_price: u64, funding_payment: i64) -> Result<String> {
+    async fn close_position(&self, owner: Pubkey, symbol: &str, exit_price: u64) -> Result<String> {
         let tx_sig = format!("close_{}_{}", symbol, uuid::Uuid::new_v4().to_string()[..8].to_string());
         Ok(tx_sig)
     }
@@ -2,47 +2,44 @@ use solana_sdk::{
     pubkey::Pubkey,
     signature::{Keypair, Signer},
     transaction::Transaction,
-    instruction::{Instruction, AccountMeta},
 };
 use solana_client::rpc_client::RpcClient;
 use anyhow::Result;
+use perp_sdk::{types::Side, InstructionBuilder};
+
+#[derive(Debug, Clone)]
+pub struct MarketTrade {
+    pub symbol: String,
+    pub size: u64, // 1 qty = 1000000 (6 decimals)
+    pub leverage: u16, // 20x
+    pub is_long: bool,
+}
 
 pub async fn execute_market_trade(
     client: &RpcClient,
     trader: &Keypair,
     program_id: &Pubkey,
-    symbol: &str,
-    size: u64, // 1 qty = 1000000 (6 decimals)
-    leverage: u8, // 20x
-    is_long: bool,
+    quote_mint: &Pubkey,
+    trade: &MarketTrade,
 ) -> Result<String> {
-    let (market_pda, _) = crate::solana_markets::derive_market_pda(program_id, symbol);
-    let (position_pda, _) = crate::perpetual_mechanics::derive_position_pda(program_id, &trader.pubkey(), symbol);
-    let (user_pda, _) = crate::perpetual_mechanics::derive_user_pda(program_id, &trader.pubkey());
-    
     // Get current market price (mock for now)
-    let market_price = match symbol {
+    let market_price = match trade.symbol.as_str() {
         "SOL-PERP" => 180_000000u64, // $180
         _ => return Err(anyhow::anyhow!("Unsupported symbol")),
     };
     
-    let margin_required = (size * market_price / 1000000) / leverage as u64;
+    let margin_required = (trade.size * market_price / 1000000) / trade.leverage as u64;
     
-    let trade_ix = Instruction {
-        program_id: *program_id,
-        accounts: vec![
-            AccountMeta::new(trader.pubkey(), true),
-            AccountMeta::new(position_pda, false),
-            AccountMeta::new(user_pda, false),
-            AccountMeta::new_readonly(market_pda, false),
-            AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
-        ],
-        data: create_trade_data(size, leverage, is_long, market_price),
-    };
+    let side = if trade.is_long { Side::Long } else { Side::Short };
+    let builder = InstructionBuilder::new(*program_id, *quote_mint);
+    // The margin is locked from sub-account 0, so fund it first; 1% on top covers the
+    // pool's spread, and what isn't locked stays there as free collateral
+    let deposit_ix = builder.deposit_collateral(&trader.pubkey(), 0, margin_required + margin_required / 100);
+    let trade_ix = builder.open_position(&trader.pubkey(), 0, &trade.symbol, side, trade.size, trade.leverage, market_price);
     
     let recent_blockhash = client.get_latest_blockhash()?;
     let tx = Transaction::new_signed_with_payer(
-        &[trade_ix],
+        &[deposit_ix, trade_ix],
         Some(&trader.pubkey()),
         &[trader],
         recent_blockhash,
@@ -51,10 +48,10 @@ pub async fn execute_market_trade(
     let signature = client.send_and_confirm_transaction(&tx)?;
     
     println!("🚀 Trade executed:");
-    println!("  Symbol: {}", symbol);
-    println!("  Size: {} qty", size as f64 / 1000000.0);
-    println!("  Leverage: {}x", leverage);
-    println!("  Side: {}", if is_long { "LONG" } else { "SHORT" });
+    println!("  Symbol: {}", trade.symbol);
+    println!("  Size: {} qty", trade.size as f64 / 1000000.0);
+    println!("  Leverage: {}x", trade.leverage);
+    println!("  Side: {}", if trade.is_long { "LONG" } else { "SHORT" });
     println!("  Price: ${}", market_price as f64 / 1000000.0);
     println!("  Margin: ${}", margin_required as f64 / 1000000.0);
     println!("  Signature: {}", signature);
@@ -62,16 +59,7 @@ pub async fn execute_market_trade(
     Ok(signature.to_string())
 }
 
-fn create_trade_data(size: u64, leverage: u8, is_long: bool, price: u64) -> Vec<u8> {
-    let mut data = Vec::new();
-    data.push(0); // Open position instruction
-    data.extend_from_slice(&size.to_le_bytes());
-    data.push(leverage);
-    data.push(if is_long { 1 } else { 0 });
-    data.extend_from_slice(&price.to_le_bytes());
-    data
+pub async fn sol_long_20x(client: &RpcClient, trader: &Keypair, program_id: &Pubkey, quote_mint: &Pubkey) -> Result<String> {
+    let trade = MarketTrade { symbol: "SOL-PERP".to_string(), size: 1000000, leverage: 20, is_long: true };
+    execute_market_trade(client, trader, program_id, quote_mint, &trade).await
 }
-
-pub async fn sol_long_20x(client: &RpcClient, trader: &Keypair, program_id: &Pubkey) -> Result<String> {
-    execute_market_trade(client, trader, program_id, "SOL-PERP", 1000000, 20, true).await
-}
\ No newline at end of file